@@ -0,0 +1,120 @@
+//! Tauri IPC commands for autocorrect dictionaries.
+
+use tauri::State;
+
+use crate::managers::import_manager::ImportManager;
+use crate::models::autocorrect::AutocorrectDictionarySummary;
+
+use super::combo_commands::{lock_combo_manager, parse_uuid, reload_engine_combos};
+use super::engine_commands::EngineState;
+use super::error::CommandError;
+use super::AppState;
+
+/// Maximum size of a typo list accepted for import.
+const MAX_AUTOCORRECT_IMPORT_SIZE: usize = 10 * 1024 * 1024; // 10 MB
+
+/// Summary of an autocorrect list import, returned to the frontend.
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AutocorrectImportResponse {
+    pub dictionary: AutocorrectDictionarySummary,
+    pub imported_count: usize,
+    pub skipped_count: usize,
+    pub errors: Vec<String>,
+}
+
+/// Returns all autocorrect dictionaries (without their entries).
+#[tauri::command]
+pub fn get_autocorrect_dictionaries(
+    state: State<AppState>,
+) -> Result<Vec<AutocorrectDictionarySummary>, CommandError> {
+    let manager = lock_combo_manager(&state)?;
+    Ok(manager.get_dictionary_summaries())
+}
+
+/// Imports a TSV or Wikipedia-style typo list as a new autocorrect dictionary.
+#[tauri::command]
+pub fn import_autocorrect_list(
+    combo_state: State<AppState>,
+    engine_state: State<EngineState>,
+    content: String,
+    name: String,
+) -> Result<AutocorrectImportResponse, CommandError> {
+    if content.len() > MAX_AUTOCORRECT_IMPORT_SIZE {
+        return Err(CommandError {
+            code: "VALIDATION_ERROR".to_string(),
+            message: "Import content exceeds 10 MB limit".to_string(),
+        });
+    }
+
+    let result = ImportManager::import_autocorrect_list(&content, &name)?;
+    let mut manager = lock_combo_manager(&combo_state)?;
+    let dictionary = manager
+        .add_dictionary(result.dictionary)
+        .map_err(CommandError::from)?;
+    drop(manager);
+
+    reload_engine_combos(&combo_state, &engine_state)?;
+
+    Ok(AutocorrectImportResponse {
+        dictionary,
+        imported_count: result.imported_count,
+        skipped_count: result.skipped_count,
+        errors: result.errors,
+    })
+}
+
+/// Toggles a dictionary's enabled state and returns the new state.
+#[tauri::command]
+pub fn toggle_autocorrect_dictionary(
+    combo_state: State<AppState>,
+    engine_state: State<EngineState>,
+    id: String,
+) -> Result<bool, CommandError> {
+    let uuid = parse_uuid("id", &id)?;
+    let mut manager = lock_combo_manager(&combo_state)?;
+    let enabled = manager.toggle_dictionary(uuid).map_err(CommandError::from)?;
+    drop(manager);
+
+    reload_engine_combos(&combo_state, &engine_state)?;
+
+    Ok(enabled)
+}
+
+/// Deletes an autocorrect dictionary by ID.
+#[tauri::command]
+pub fn delete_autocorrect_dictionary(
+    combo_state: State<AppState>,
+    engine_state: State<EngineState>,
+    id: String,
+) -> Result<(), CommandError> {
+    let uuid = parse_uuid("id", &id)?;
+    let mut manager = lock_combo_manager(&combo_state)?;
+    manager.delete_dictionary(uuid).map_err(CommandError::from)?;
+    drop(manager);
+
+    reload_engine_combos(&combo_state, &engine_state)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_import_response_serializes_camel_case() {
+        use crate::models::autocorrect::{AutocorrectDictionary, AutocorrectEntry};
+
+        let dict = AutocorrectDictionary::new("Typos", vec![AutocorrectEntry::new("teh", "the")]);
+        let response = AutocorrectImportResponse {
+            dictionary: AutocorrectDictionarySummary::from(&dict),
+            imported_count: 1,
+            skipped_count: 0,
+            errors: Vec::new(),
+        };
+        let json = serde_json::to_string(&response).expect("serialize");
+        assert!(json.contains("importedCount"));
+        assert!(json.contains("entryCount"));
+    }
+}
@@ -1,26 +1,28 @@
 //! Tauri IPC commands for combo CRUD operations.
 
+use std::sync::MutexGuard;
+
 use tauri::State;
 use uuid::Uuid;
 
-use crate::models::combo::Combo;
+use crate::managers::combo_manager::ComboManager;
+use crate::models::combo::{Combo, ComboBuilder, ComboPatch, SnippetFormat};
 use crate::models::matching::MatchingMode;
 
 use super::error::CommandError;
 use super::AppState;
 use super::engine_commands::EngineState;
 
-/// Helper to reload combos into the expansion engine after changes.
-fn reload_engine_combos(
+/// Helper to reload combos and autocorrect dictionaries into the expansion
+/// engine after changes.
+pub(super) fn reload_engine_combos(
     combo_state: &State<AppState>,
     engine_state: &State<EngineState>,
 ) -> Result<(), CommandError> {
-    let manager = combo_state.combo_manager.lock().map_err(|_| CommandError {
-        code: "LOCK_ERROR".to_string(),
-        message: "Failed to acquire combo manager lock".to_string(),
-    })?;
+    let manager = lock_combo_manager(combo_state)?;
 
     let combos = manager.get_all_combos();
+    let dictionaries = manager.get_all_dictionaries();
     drop(manager); // Release lock
 
     let engine = engine_state.engine.lock().map_err(|_| CommandError {
@@ -32,12 +34,26 @@ fn reload_engine_combos(
         code: "ENGINE_ERROR".to_string(),
         message: format!("Failed to reload combos into engine: {}", e),
     })?;
+    engine.load_dictionaries(&dictionaries).map_err(|e| CommandError {
+        code: "ENGINE_ERROR".to_string(),
+        message: format!("Failed to reload autocorrect dictionaries into engine: {}", e),
+    })?;
 
     Ok(())
 }
 
+/// Locks the combo manager, returning a `CommandError` on failure.
+pub(super) fn lock_combo_manager<'a>(
+    state: &'a State<'_, AppState>,
+) -> Result<MutexGuard<'a, ComboManager>, CommandError> {
+    state.combo_manager.lock().map_err(|_| CommandError {
+        code: "LOCK_ERROR".to_string(),
+        message: "Failed to acquire combo manager lock".to_string(),
+    })
+}
+
 /// Parses a UUID string, returning a `CommandError` on failure.
-fn parse_uuid(field: &str, value: &str) -> Result<Uuid, CommandError> {
+pub(super) fn parse_uuid(field: &str, value: &str) -> Result<Uuid, CommandError> {
     Uuid::parse_str(value).map_err(|_| CommandError::invalid_uuid(field, value))
 }
 
@@ -50,16 +66,20 @@ fn parse_matching_mode(value: &str) -> Result<MatchingMode, CommandError> {
     }
 }
 
+fn parse_snippet_format(value: &str) -> Result<SnippetFormat, CommandError> {
+    match value.to_lowercase().as_str() {
+        "plain" => Ok(SnippetFormat::Plain),
+        "html" => Ok(SnippetFormat::Html),
+        "markdown" => Ok(SnippetFormat::Markdown),
+        "image" => Ok(SnippetFormat::Image),
+        _ => Err(CommandError::invalid_snippet_format(value)),
+    }
+}
+
 /// Returns all combos.
 #[tauri::command]
 pub fn get_all_combos(state: State<AppState>) -> Result<Vec<Combo>, CommandError> {
-    let manager = state
-        .combo_manager
-        .lock()
-        .map_err(|_| CommandError {
-            code: "LOCK_ERROR".to_string(),
-            message: "Failed to acquire combo manager lock".to_string(),
-        })?;
+    let manager = lock_combo_manager(&state)?;
     Ok(manager.get_all_combos())
 }
 
@@ -67,13 +87,7 @@ pub fn get_all_combos(state: State<AppState>) -> Result<Vec<Combo>, CommandError
 #[tauri::command]
 pub fn get_combo(state: State<AppState>, id: String) -> Result<Option<Combo>, CommandError> {
     let uuid = parse_uuid("id", &id)?;
-    let manager = state
-        .combo_manager
-        .lock()
-        .map_err(|_| CommandError {
-            code: "LOCK_ERROR".to_string(),
-            message: "Failed to acquire combo manager lock".to_string(),
-        })?;
+    let manager = lock_combo_manager(&state)?;
     Ok(manager.get_combo(uuid))
 }
 
@@ -88,18 +102,31 @@ pub fn create_combo(
     group_id: String,
     matching_mode: String,
     case_sensitive: bool,
+    propagate_case: Option<bool>,
+    snippet_format: Option<String>,
+    exclude_from_history: Option<bool>,
 ) -> Result<Combo, CommandError> {
     let gid = parse_uuid("group_id", &group_id)?;
     let mode = parse_matching_mode(&matching_mode)?;
-    let mut manager = combo_state
-        .combo_manager
-        .lock()
-        .map_err(|_| CommandError {
-            code: "LOCK_ERROR".to_string(),
-            message: "Failed to acquire combo manager lock".to_string(),
-        })?;
+    let format = snippet_format
+        .map(|f| parse_snippet_format(&f))
+        .transpose()?;
+    let mut manager = lock_combo_manager(&combo_state)?;
+    let builder = ComboBuilder::new()
+        .name(name)
+        .keyword(keyword)
+        .snippet(snippet)
+        .group_id(gid)
+        .matching_mode(mode)
+        .case_sensitive(case_sensitive)
+        .propagate_case(propagate_case.unwrap_or(false))
+        .snippet_format(format.unwrap_or_default());
+    let builder = match exclude_from_history {
+        Some(exclude) => builder.exclude_from_history(exclude),
+        None => builder,
+    };
     let combo = manager
-        .create_combo(name, keyword, snippet, gid, mode, case_sensitive)
+        .create_combo_from(builder)
         .map_err(CommandError::from)?;
     drop(manager);
 
@@ -109,34 +136,18 @@ pub fn create_combo(
     Ok(combo)
 }
 
-/// Updates an existing combo. Only provided fields are changed.
+/// Updates an existing combo. Only the fields set in `patch` are changed.
 #[tauri::command]
 pub fn update_combo(
     combo_state: State<AppState>,
     engine_state: State<EngineState>,
     id: String,
-    name: Option<String>,
-    keyword: Option<String>,
-    snippet: Option<String>,
-    group_id: Option<String>,
-    matching_mode: Option<String>,
-    case_sensitive: Option<bool>,
-    enabled: Option<bool>,
+    patch: ComboPatch,
 ) -> Result<Combo, CommandError> {
     let uuid = parse_uuid("id", &id)?;
-    let gid = group_id.map(|g| parse_uuid("group_id", &g)).transpose()?;
-    let mode = matching_mode
-        .map(|m| parse_matching_mode(&m))
-        .transpose()?;
-    let mut manager = combo_state
-        .combo_manager
-        .lock()
-        .map_err(|_| CommandError {
-            code: "LOCK_ERROR".to_string(),
-            message: "Failed to acquire combo manager lock".to_string(),
-        })?;
+    let mut manager = lock_combo_manager(&combo_state)?;
     let combo = manager
-        .update_combo(uuid, name, keyword, snippet, gid, mode, case_sensitive, enabled)
+        .update_combo(uuid, patch)
         .map_err(CommandError::from)?;
     drop(manager);
 
@@ -154,13 +165,7 @@ pub fn delete_combo(
     id: String,
 ) -> Result<(), CommandError> {
     let uuid = parse_uuid("id", &id)?;
-    let mut manager = combo_state
-        .combo_manager
-        .lock()
-        .map_err(|_| CommandError {
-            code: "LOCK_ERROR".to_string(),
-            message: "Failed to acquire combo manager lock".to_string(),
-        })?;
+    let mut manager = lock_combo_manager(&combo_state)?;
     manager.delete_combo(uuid).map_err(CommandError::from)?;
     drop(manager);
 
@@ -174,13 +179,7 @@ pub fn delete_combo(
 #[tauri::command]
 pub fn duplicate_combo(state: State<AppState>, id: String) -> Result<Combo, CommandError> {
     let uuid = parse_uuid("id", &id)?;
-    let mut manager = state
-        .combo_manager
-        .lock()
-        .map_err(|_| CommandError {
-            code: "LOCK_ERROR".to_string(),
-            message: "Failed to acquire combo manager lock".to_string(),
-        })?;
+    let mut manager = lock_combo_manager(&state)?;
     manager.duplicate_combo(uuid).map_err(CommandError::from)
 }
 
@@ -193,13 +192,7 @@ pub fn move_combo_to_group(
 ) -> Result<(), CommandError> {
     let cid = parse_uuid("combo_id", &combo_id)?;
     let gid = parse_uuid("group_id", &group_id)?;
-    let mut manager = state
-        .combo_manager
-        .lock()
-        .map_err(|_| CommandError {
-            code: "LOCK_ERROR".to_string(),
-            message: "Failed to acquire combo manager lock".to_string(),
-        })?;
+    let mut manager = lock_combo_manager(&state)?;
     manager
         .move_combo_to_group(cid, gid)
         .map_err(CommandError::from)
@@ -213,13 +206,7 @@ pub fn toggle_combo(
     id: String,
 ) -> Result<bool, CommandError> {
     let uuid = parse_uuid("id", &id)?;
-    let mut manager = combo_state
-        .combo_manager
-        .lock()
-        .map_err(|_| CommandError {
-            code: "LOCK_ERROR".to_string(),
-            message: "Failed to acquire combo manager lock".to_string(),
-        })?;
+    let mut manager = lock_combo_manager(&combo_state)?;
     let enabled = manager.toggle_combo(uuid).map_err(CommandError::from)?;
     drop(manager);
 
@@ -264,4 +251,12 @@ mod tests {
         let result = parse_matching_mode("invalid");
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_parse_snippet_format() {
+        assert_eq!(parse_snippet_format("plain").unwrap(), SnippetFormat::Plain);
+        assert_eq!(parse_snippet_format("markdown").unwrap(), SnippetFormat::Markdown);
+        assert_eq!(parse_snippet_format("image").unwrap(), SnippetFormat::Image);
+        assert!(parse_snippet_format("rtf").is_err());
+    }
 }
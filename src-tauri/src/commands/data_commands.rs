@@ -3,9 +3,13 @@
 use std::sync::Mutex;
 
 use crate::commands::error::CommandError;
+use crate::managers::asset_manager::{AssetManager, EmbeddedAsset, MAX_EMBEDDED_ASSETS};
 use crate::managers::backup_manager::{BackupInfo, BackupManager};
 use crate::managers::export_manager::{ExportFormat, ExportManager};
-use crate::managers::import_manager::{ConflictResolution, ImportFormat, ImportManager, ImportPreview, ImportResult};
+use crate::managers::import_manager::{
+    ConflictResolution, ImportFormat, ImportManager, ImportPreview, ImportResult,
+    MAX_NATIVE_IMPORT_BYTES,
+};
 use crate::managers::update_manager::{UpdateManager, VersionInfo};
 
 /// State for backup manager, managed by Tauri.
@@ -18,6 +22,11 @@ pub struct UpdateState {
     pub update_manager: Mutex<UpdateManager>,
 }
 
+/// State for the snippet asset store, managed by Tauri.
+pub struct AssetState {
+    pub asset_manager: Mutex<AssetManager>,
+}
+
 impl From<crate::managers::import_manager::ImportError> for CommandError {
     fn from(err: crate::managers::import_manager::ImportError) -> Self {
         CommandError {
@@ -76,18 +85,24 @@ fn parse_export_format(format: &str) -> Result<ExportFormat, CommandError> {
     }
 }
 
-/// Import combos from the given content string.
+/// Import combos from the given content string. Images embedded in a
+/// native export are written to the asset store.
 #[tauri::command]
 pub fn import_combos(
+    asset_state: tauri::State<'_, AssetState>,
     content: String,
     format: String,
     conflict_resolution: String,
 ) -> Result<ImportResult, CommandError> {
-    const MAX_IMPORT_SIZE: usize = 10 * 1024 * 1024; // 10 MB
-    if content.len() > MAX_IMPORT_SIZE {
+    // Bound the work format detection does; the limit for the detected
+    // format is checked below.
+    if content.len() > MAX_NATIVE_IMPORT_BYTES {
         return Err(CommandError {
             code: "VALIDATION_ERROR".to_string(),
-            message: "Import content exceeds 10 MB limit".to_string(),
+            message: format!(
+                "Import content exceeds {} MB limit",
+                MAX_NATIVE_IMPORT_BYTES / (1024 * 1024)
+            ),
         });
     }
 
@@ -97,6 +112,7 @@ pub fn import_combos(
     } else {
         parse_import_format(&format)?
     };
+    ImportManager::check_size(&content, fmt)?;
 
     match fmt {
         ImportFormat::BeeftextJson => {
@@ -108,29 +124,46 @@ pub fn import_combos(
         ImportFormat::TextExpanderCsv => {
             Ok(ImportManager::import_textexpander_csv(&content, conflict)?)
         }
-        ImportFormat::MuttonTextJson => Ok(ImportManager::import_muttontext_json(&content)?),
+        ImportFormat::MuttonTextJson => {
+            let mut result = ImportManager::import_muttontext_json(&content)?;
+            let assets = asset_state.asset_manager.lock().map_err(|_| CommandError {
+                code: "INTERNAL_ERROR".to_string(),
+                message: "Lock poisoned".to_string(),
+            })?;
+            let (_, errors) = assets.unpack(&result.assets);
+            result.errors.extend(errors);
+            Ok(result)
+        }
     }
 }
 
 /// Preview what an import would produce.
 #[tauri::command]
 pub fn preview_import(content: String) -> Result<ImportPreview, CommandError> {
-    const MAX_IMPORT_SIZE: usize = 10 * 1024 * 1024; // 10 MB
-    if content.len() > MAX_IMPORT_SIZE {
+    // Bound the work format detection does; the limit for the detected
+    // format is checked below.
+    if content.len() > MAX_NATIVE_IMPORT_BYTES {
         return Err(CommandError {
             code: "VALIDATION_ERROR".to_string(),
-            message: "Import content exceeds 10 MB limit".to_string(),
+            message: format!(
+                "Import content exceeds {} MB limit",
+                MAX_NATIVE_IMPORT_BYTES / (1024 * 1024)
+            ),
         });
     }
 
-    Ok(ImportManager::preview_import(&content)?)
+    let preview = ImportManager::preview_import(&content)?;
+    ImportManager::check_size(&content, preview.format)?;
+    Ok(preview)
 }
 
-/// Export combos to the given format.
+/// Export combos to the given format. Native exports embed the images
+/// that image combos use.
 #[tauri::command]
 pub fn export_combos(
     format: String,
     state: tauri::State<'_, super::AppState>,
+    asset_state: tauri::State<'_, AssetState>,
 ) -> Result<String, CommandError> {
     let fmt = parse_export_format(&format)?;
     let manager = state.combo_manager.lock().map_err(|_| CommandError {
@@ -140,9 +173,62 @@ pub fn export_combos(
     let combos = manager.get_all_combos();
     let groups = manager.get_all_groups();
     drop(manager);
+    if fmt == ExportFormat::MuttonTextJson {
+        let assets = asset_state.asset_manager.lock().map_err(|_| CommandError {
+            code: "INTERNAL_ERROR".to_string(),
+            message: "Lock poisoned".to_string(),
+        })?;
+        let embedded = assets.embed_for(&combos);
+        drop(assets);
+        // A file with more images than an import accepts would be useless.
+        if embedded.len() > MAX_EMBEDDED_ASSETS {
+            return Err(CommandError {
+                code: "EXPORT_ERROR".to_string(),
+                message: format!(
+                    "Export embeds {} images, exceeding the limit of {}",
+                    embedded.len(),
+                    MAX_EMBEDDED_ASSETS
+                ),
+            });
+        }
+        return Ok(ExportManager::export_muttontext_json_with_assets(
+            &combos, &groups, &embedded,
+        )?);
+    }
     Ok(ExportManager::export_to_format(&combos, &groups, fmt)?)
 }
 
+/// Store an image for an image combo, returning the asset name to use as
+/// the combo's snippet. Only PNG files within the size limits are accepted.
+#[tauri::command]
+pub fn store_image_asset(
+    asset_state: tauri::State<'_, AssetState>,
+    data: Vec<u8>,
+) -> Result<String, CommandError> {
+    let assets = asset_state.asset_manager.lock().map_err(|_| CommandError {
+        code: "INTERNAL_ERROR".to_string(),
+        message: "Lock poisoned".to_string(),
+    })?;
+    Ok(assets.store(&data)?)
+}
+
+/// Get an image asset as a `data:` URL, for previews.
+#[tauri::command]
+pub fn get_image_asset(
+    asset_state: tauri::State<'_, AssetState>,
+    name: String,
+) -> Result<String, CommandError> {
+    let assets = asset_state.asset_manager.lock().map_err(|_| CommandError {
+        code: "INTERNAL_ERROR".to_string(),
+        message: "Lock poisoned".to_string(),
+    })?;
+    let bytes = assets.read(&name)?;
+    Ok(format!(
+        "data:image/png;base64,{}",
+        EmbeddedAsset::new(name, &bytes).data
+    ))
+}
+
 /// Create a new backup.
 #[tauri::command]
 pub fn create_backup(
@@ -286,6 +372,7 @@ mod tests {
             errors: vec!["test error".to_string()],
             combos: Vec::new(),
             groups: Vec::new(),
+            assets: Vec::new(),
         };
         let json = serde_json::to_string(&result).unwrap();
         assert!(json.contains("importedCount"));
@@ -6,6 +6,7 @@
 
 use serde::{Deserialize, Serialize};
 
+use crate::managers::asset_manager::AssetError;
 use crate::managers::combo_manager::ComboManagerError;
 use crate::managers::backup_manager::BackupError;
 
@@ -96,6 +97,10 @@ impl From<ComboManagerError> for CommandError {
                 code: "GROUP_NOT_FOUND".to_string(),
                 message: err.to_string(),
             },
+            ComboManagerError::DictionaryNotFound(_) => CommandError {
+                code: "DICTIONARY_NOT_FOUND".to_string(),
+                message: err.to_string(),
+            },
             ComboManagerError::Validation(_) => CommandError {
                 code: "VALIDATION_ERROR".to_string(),
                 message: err.to_string(),
@@ -108,6 +113,10 @@ impl From<ComboManagerError> for CommandError {
                 code: "VALIDATION_ERROR".to_string(),
                 message: err.to_string(),
             },
+            ComboManagerError::Asset(asset_err) => CommandError {
+                code: asset_error_code(asset_err).to_string(),
+                message: err.to_string(),
+            },
         }
     }
 }
@@ -139,6 +148,27 @@ impl From<BackupError> for CommandError {
     }
 }
 
+/// The error code for an asset error.
+fn asset_error_code(err: &AssetError) -> &'static str {
+    match err {
+        AssetError::Io(_) => "ASSET_IO_ERROR",
+        AssetError::InvalidName(_) => "ASSET_INVALID_NAME",
+        AssetError::NotFound(_) => "ASSET_NOT_FOUND",
+        AssetError::TooLarge(_, _) | AssetError::DimensionsTooLarge(_, _, _) => "ASSET_TOO_LARGE",
+        AssetError::UnsupportedType(_) => "ASSET_UNSUPPORTED_TYPE",
+        AssetError::InvalidImage(_) | AssetError::InvalidEncoding(_) => "ASSET_INVALID",
+    }
+}
+
+impl From<AssetError> for CommandError {
+    fn from(err: AssetError) -> Self {
+        CommandError {
+            code: asset_error_code(&err).to_string(),
+            message: err.to_string(),
+        }
+    }
+}
+
 impl CommandError {
     /// Creates a CommandError for invalid UUID parsing.
     pub fn invalid_uuid(field: &str, value: &str) -> Self {
@@ -157,6 +187,16 @@ impl CommandError {
             ),
         }
     }
+
+    /// Creates a CommandError for invalid snippet format.
+    pub fn invalid_snippet_format(value: &str) -> Self {
+        CommandError {
+            code: "INVALID_SNIPPET_FORMAT".to_string(),
+            message: format!(
+                "Invalid snippet format: '{value}'. Expected 'plain', 'html', 'markdown' or 'image'."
+            ),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -198,6 +238,21 @@ mod tests {
         assert_eq!(err.code, "GROUP_NOT_FOUND");
     }
 
+    #[test]
+    fn test_from_dictionary_not_found() {
+        let id = Uuid::new_v4();
+        let err: CommandError = ComboManagerError::DictionaryNotFound(id).into();
+        assert_eq!(err.code, "DICTIONARY_NOT_FOUND");
+    }
+
+    #[test]
+    fn test_from_combo_asset_error() {
+        let err: CommandError =
+            ComboManagerError::Asset(AssetError::InvalidName("../x.png".into())).into();
+        assert_eq!(err.code, "ASSET_INVALID_NAME");
+        assert!(err.message.contains("../x.png"));
+    }
+
     #[test]
     fn test_invalid_uuid_error() {
         let err = CommandError::invalid_uuid("id", "not-a-uuid");
@@ -212,6 +267,22 @@ mod tests {
         assert!(err.message.contains("bad"));
     }
 
+    #[test]
+    fn test_invalid_snippet_format_error() {
+        let err = CommandError::invalid_snippet_format("rtf");
+        assert_eq!(err.code, "INVALID_SNIPPET_FORMAT");
+        assert!(err.message.contains("rtf"));
+    }
+
+    #[test]
+    fn test_from_asset_error() {
+        let err: CommandError = AssetError::TooLarge(10, 5).into();
+        assert_eq!(err.code, "ASSET_TOO_LARGE");
+        let err: CommandError = AssetError::UnsupportedType("Gif".into()).into();
+        assert_eq!(err.code, "ASSET_UNSUPPORTED_TYPE");
+        assert!(err.message.contains("Gif"));
+    }
+
     // ── MT-1106: ErrorResponse tests ─────────────────────────────
 
     #[test]
@@ -3,6 +3,7 @@
 use std::sync::Mutex;
 use crate::managers::combo_manager::ComboManager;
 
+pub mod autocorrect_commands;
 pub mod combo_commands;
 pub mod error;
 pub mod group_commands;
@@ -1,6 +1,6 @@
 //! Tauri IPC commands for system tray operations.
 
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
 
 use tauri::State;
 
@@ -8,9 +8,14 @@ use crate::managers::tray_manager::{TrayManager, TrayMenuItem, TrayState};
 
 use super::error::CommandError;
 
-/// Tauri-managed state wrapper for TrayManager.
+/// Event emitted with the new [`TrayState`] whenever the engine changes it,
+/// e.g. when a password field gains or loses focus.
+pub const TRAY_STATE_CHANGED_EVENT: &str = "tray-state-changed";
+
+/// Tauri-managed state wrapper for TrayManager. Shared with the engine's
+/// password-field callback, which updates it directly.
 pub struct TrayMgrState {
-    pub tray_manager: Mutex<TrayManager>,
+    pub tray_manager: Arc<Mutex<TrayManager>>,
 }
 
 #[tauri::command]
@@ -67,6 +72,10 @@ mod tests {
         let state = TrayState::ExcludedApp;
         let json = serde_json::to_string(&state).unwrap();
         assert_eq!(json, "\"excludedApp\"");
+
+        let state = TrayState::PasswordField;
+        let json = serde_json::to_string(&state).unwrap();
+        assert_eq!(json, "\"passwordField\"");
     }
 
     #[test]
@@ -108,7 +117,7 @@ mod tests {
     #[test]
     fn test_tray_mgr_state_struct() {
         let state = TrayMgrState {
-            tray_manager: Mutex::new(TrayManager::new()),
+            tray_manager: Arc::new(Mutex::new(TrayManager::new())),
         };
         let mgr = state.tray_manager.lock().unwrap();
         assert_eq!(mgr.state(), TrayState::Active);
@@ -4,15 +4,16 @@ pub mod models;
 pub mod platform;
 pub mod utils;
 
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex, OnceLock};
 
+use tauri::{Emitter, Manager};
 use tracing_subscriber::EnvFilter;
 
 use commands::AppState;
 use commands::shortcut_commands::ShortcutState;
-use commands::tray_commands::TrayMgrState;
+use commands::tray_commands::{TrayMgrState, TRAY_STATE_CHANGED_EVENT};
 use commands::preferences_commands::PreferencesState;
-use commands::data_commands::{BackupState, UpdateState};
+use commands::data_commands::{AssetState, BackupState, UpdateState};
 use commands::engine_commands::EngineState;
 use managers::combo_manager::ComboManager;
 use managers::combo_storage::ComboStorage;
@@ -20,9 +21,10 @@ use managers::shortcut_manager::ShortcutManager;
 use managers::tray_manager::TrayManager;
 use managers::preferences_manager::PreferencesManager;
 use managers::backup_manager::BackupManager;
+use managers::asset_manager::AssetManager;
 use managers::update_manager::UpdateManager;
 use managers::engine_manager::EngineManager;
-use managers::storage::{get_combos_path, get_preferences_path, get_backups_dir};
+use managers::storage::{get_combos_path, get_preferences_path, get_backups_dir, get_assets_dir};
 
 /// Initialize the tracing subscriber for structured logging.
 fn init_tracing() {
@@ -39,13 +41,17 @@ pub fn run() {
 
     let combos_path = get_combos_path().expect("Failed to resolve combos.json path");
     let storage = ComboStorage::new(combos_path);
-    let manager = ComboManager::new(storage).expect("Failed to initialize ComboManager");
     let shortcut_manager = ShortcutManager::new();
-    let tray_manager = TrayManager::new();
+    let tray_manager = Arc::new(Mutex::new(TrayManager::new()));
     let preferences_path = get_preferences_path().expect("Failed to resolve preferences.json path");
     let preferences_manager = PreferencesManager::new(preferences_path).expect("Failed to initialize PreferencesManager");
+    let assets_dir = get_assets_dir().expect("Failed to resolve assets directory");
+    let asset_manager = AssetManager::new(assets_dir);
+    let manager = ComboManager::new(storage)
+        .expect("Failed to initialize ComboManager")
+        .with_assets(asset_manager.clone());
     let backups_dir = get_backups_dir().expect("Failed to resolve backups directory");
-    let backup_manager = BackupManager::new(backups_dir, 10);
+    let backup_manager = BackupManager::new(backups_dir, 10).with_assets(asset_manager.clone());
     let update_manager = UpdateManager::new(env!("CARGO_PKG_VERSION").to_string());
 
     // Check accessibility permissions on macOS (required for keyboard monitoring)
@@ -67,11 +73,59 @@ pub fn run() {
     }
 
     // Initialize expansion engine
-    let engine_manager = EngineManager::new();
+    let mut engine_manager = EngineManager::new();
+
+    // Save the timing profiles the engine learns. The app handle is only
+    // known once the app is set up, and saving happens on its own thread so
+    // the expansion worker never waits for the preferences lock.
+    let app_handle: Arc<OnceLock<tauri::AppHandle>> = Arc::new(OnceLock::new());
+    let handle_for_engine = app_handle.clone();
+    engine_manager.on_timing_learned(move |profiles| {
+        let handle = match handle_for_engine.get() {
+            Some(handle) => handle.clone(),
+            None => return,
+        };
+        std::thread::spawn(move || {
+            let state = handle.state::<PreferencesState>();
+            let result = match state.preferences_manager.lock() {
+                Ok(mut mgr) => mgr.set_timing_profiles(profiles),
+                Err(_) => return,
+            };
+            if let Err(e) = result {
+                tracing::warn!("Failed to save learned timing profiles: {}", e);
+            }
+        });
+    });
+
+    // Show a focused password field in the tray as soon as the engine
+    // notices it, and tell the frontend.
+    let tray_for_engine = tray_manager.clone();
+    let handle_for_tray = app_handle.clone();
+    engine_manager.on_password_field(move |focused| {
+        let state = match tray_for_engine.lock() {
+            Ok(mut mgr) => {
+                mgr.set_password_field(focused);
+                mgr.state()
+            }
+            Err(_) => return,
+        };
+        if let Some(handle) = handle_for_tray.get() {
+            if let Err(e) = handle.emit(TRAY_STATE_CHANGED_EVENT, state) {
+                tracing::warn!("Failed to emit tray state: {}", e);
+            }
+        }
+    });
 
     // Load initial combos and preferences into engine
     let combos = manager.get_all_combos();
     engine_manager.load_combos(&combos).expect("Failed to load combos into engine");
+    engine_manager
+        .load_dictionaries(&manager.get_all_dictionaries())
+        .expect("Failed to load autocorrect dictionaries into engine");
+
+    engine_manager
+        .set_assets(asset_manager.clone())
+        .expect("Failed to set engine asset store");
 
     let preferences = preferences_manager.get();
     engine_manager.apply_preferences(&preferences).expect("Failed to apply preferences to engine");
@@ -92,6 +146,7 @@ pub fn run() {
         ))
         .setup(move |app| {
             use tauri_plugin_autostart::ManagerExt;
+            let _ = app_handle.set(app.handle().clone());
             let autolaunch = app.autolaunch();
             if start_at_login {
                 let _ = autolaunch.enable();
@@ -108,7 +163,7 @@ pub fn run() {
             shortcut_manager: Mutex::new(shortcut_manager),
         })
         .manage(TrayMgrState {
-            tray_manager: Mutex::new(tray_manager),
+            tray_manager,
         })
         .manage(PreferencesState {
             preferences_manager: Mutex::new(preferences_manager),
@@ -119,6 +174,9 @@ pub fn run() {
         .manage(UpdateState {
             update_manager: Mutex::new(update_manager),
         })
+        .manage(AssetState {
+            asset_manager: Mutex::new(asset_manager),
+        })
         .manage(EngineState {
             engine: Mutex::new(engine_manager),
         })
@@ -132,6 +190,11 @@ pub fn run() {
             commands::combo_commands::duplicate_combo,
             commands::combo_commands::move_combo_to_group,
             commands::combo_commands::toggle_combo,
+            // Autocorrect commands
+            commands::autocorrect_commands::get_autocorrect_dictionaries,
+            commands::autocorrect_commands::import_autocorrect_list,
+            commands::autocorrect_commands::toggle_autocorrect_dictionary,
+            commands::autocorrect_commands::delete_autocorrect_dictionary,
             // Group commands
             commands::group_commands::get_all_groups,
             commands::group_commands::get_group,
@@ -165,6 +228,8 @@ pub fn run() {
             commands::data_commands::import_combos,
             commands::data_commands::preview_import,
             commands::data_commands::export_combos,
+            commands::data_commands::store_image_asset,
+            commands::data_commands::get_image_asset,
             commands::data_commands::create_backup,
             commands::data_commands::restore_backup,
             commands::data_commands::list_backups,
@@ -0,0 +1,442 @@
+//! Storage for files that snippets refer to, such as the images pasted by
+//! image combos.
+//!
+//! Assets live in a flat directory under the config directory and are named
+//! by a generated UUID, so a combo only ever stores the file name. Backups
+//! and native exports carry the assets their combos use, base64-encoded.
+
+use std::collections::HashSet;
+use std::io::Cursor;
+use std::path::PathBuf;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine as _;
+use image::{ImageFormat, ImageReader};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::managers::clipboard_manager::ClipboardFormat;
+use crate::models::combo::{Combo, SnippetFormat};
+
+/// Maximum size of an image asset file (5 MiB).
+pub const MAX_ASSET_BYTES: usize = 5 * 1024 * 1024;
+
+/// Maximum number of assets a native export may embed.
+pub const MAX_EMBEDDED_ASSETS: usize = 16;
+
+/// Maximum width or height of an image asset, in pixels. Keeps the decoded
+/// RGBA data that goes on the clipboard within reason.
+pub const MAX_IMAGE_DIMENSION: u32 = 4096;
+
+/// Errors that can occur while storing or loading assets.
+#[derive(Debug, Error)]
+pub enum AssetError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid asset name: {0}")]
+    InvalidName(String),
+    #[error("Asset not found: {0}")]
+    NotFound(String),
+    #[error("Asset is {0} bytes, exceeding the limit of {1}")]
+    TooLarge(usize, usize),
+    #[error("Unsupported image type: {0}")]
+    UnsupportedType(String),
+    #[error("Image is {0}x{1} pixels, exceeding the limit of {2}x{2}")]
+    DimensionsTooLarge(u32, u32, u32),
+    #[error("Invalid image data: {0}")]
+    InvalidImage(String),
+    #[error("Invalid asset encoding: {0}")]
+    InvalidEncoding(String),
+}
+
+/// An asset carried inside a backup or export file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EmbeddedAsset {
+    /// File name in the assets directory.
+    pub name: String,
+    /// File contents, base64-encoded.
+    pub data: String,
+}
+
+impl EmbeddedAsset {
+    /// Wraps `bytes` for embedding under `name`.
+    pub fn new(name: impl Into<String>, bytes: &[u8]) -> Self {
+        Self {
+            name: name.into(),
+            data: BASE64.encode(bytes),
+        }
+    }
+
+    /// Decodes and validates the asset, returning its bytes.
+    ///
+    /// Applies the same name, size and type checks as [`AssetManager::store`].
+    pub fn decode(&self) -> Result<Vec<u8>, AssetError> {
+        validate_name(&self.name)?;
+        // Reject oversized payloads before decoding them.
+        if self.data.len() / 4 * 3 > MAX_ASSET_BYTES + 3 {
+            return Err(AssetError::TooLarge(self.data.len() / 4 * 3, MAX_ASSET_BYTES));
+        }
+        let bytes = BASE64
+            .decode(self.data.as_bytes())
+            .map_err(|e| AssetError::InvalidEncoding(e.to_string()))?;
+        validate_image(&bytes)?;
+        Ok(bytes)
+    }
+}
+
+/// Length of `bytes` bytes once base64-encoded, including padding.
+pub const fn encoded_len(bytes: usize) -> usize {
+    (bytes + 2) / 3 * 4
+}
+
+/// Checks that `name` is a plain file name of the form `<stem>.png`.
+///
+/// Rejects separators and leading dots so a name from an imported file
+/// can't point outside the assets directory.
+pub fn validate_name(name: &str) -> Result<(), AssetError> {
+    let valid = !name.is_empty()
+        && name.len() <= 128
+        && !name.starts_with('.')
+        && name.ends_with(".png")
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.');
+    if valid {
+        Ok(())
+    } else {
+        Err(AssetError::InvalidName(name.to_string()))
+    }
+}
+
+/// Checks that `bytes` is a PNG image within the size and dimension limits,
+/// returning its width and height.
+pub fn validate_image(bytes: &[u8]) -> Result<(u32, u32), AssetError> {
+    if bytes.len() > MAX_ASSET_BYTES {
+        return Err(AssetError::TooLarge(bytes.len(), MAX_ASSET_BYTES));
+    }
+    match image::guess_format(bytes) {
+        Ok(ImageFormat::Png) => {}
+        Ok(other) => return Err(AssetError::UnsupportedType(format!("{:?}", other))),
+        Err(_) => return Err(AssetError::UnsupportedType("unknown".to_string())),
+    }
+    let (width, height) = ImageReader::with_format(Cursor::new(bytes), ImageFormat::Png)
+        .into_dimensions()
+        .map_err(|e| AssetError::InvalidImage(e.to_string()))?;
+    if width == 0 || height == 0 {
+        return Err(AssetError::InvalidImage("image has no pixels".to_string()));
+    }
+    if width > MAX_IMAGE_DIMENSION || height > MAX_IMAGE_DIMENSION {
+        return Err(AssetError::DimensionsTooLarge(width, height, MAX_IMAGE_DIMENSION));
+    }
+    Ok((width, height))
+}
+
+/// Names of the assets used by `combos`, in first-use order and without
+/// duplicates.
+pub fn referenced_assets(combos: &[Combo]) -> Vec<&str> {
+    let mut seen = HashSet::new();
+    combos
+        .iter()
+        .filter(|c| c.snippet_format == SnippetFormat::Image)
+        .map(|c| c.snippet.as_str())
+        .filter(|name| seen.insert(*name))
+        .collect()
+}
+
+/// Stores and loads snippet assets in a single directory.
+#[derive(Debug, Clone)]
+pub struct AssetManager {
+    dir: PathBuf,
+}
+
+impl AssetManager {
+    /// Creates a manager for the assets in `dir`. The directory is created
+    /// on first write.
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// Returns the assets directory.
+    pub fn dir(&self) -> &PathBuf {
+        &self.dir
+    }
+
+    /// Returns the path of the asset called `name`.
+    pub fn path_for(&self, name: &str) -> Result<PathBuf, AssetError> {
+        validate_name(name)?;
+        Ok(self.dir.join(name))
+    }
+
+    /// Validates `bytes` as an image and stores it under a new name, which
+    /// is returned.
+    pub fn store(&self, bytes: &[u8]) -> Result<String, AssetError> {
+        validate_image(bytes)?;
+        let name = format!("{}.png", Uuid::new_v4());
+        self.write(&name, bytes)?;
+        Ok(name)
+    }
+
+    /// Reads the asset called `name`.
+    pub fn read(&self, name: &str) -> Result<Vec<u8>, AssetError> {
+        let path = self.path_for(name)?;
+        if !path.exists() {
+            return Err(AssetError::NotFound(name.to_string()));
+        }
+        Ok(std::fs::read(path)?)
+    }
+
+    /// Deletes the asset called `name`.
+    pub fn delete(&self, name: &str) -> Result<(), AssetError> {
+        let path = self.path_for(name)?;
+        if !path.exists() {
+            return Err(AssetError::NotFound(name.to_string()));
+        }
+        std::fs::remove_file(path)?;
+        Ok(())
+    }
+
+    /// Reads and decodes the image asset `name` into RGBA pixels for the
+    /// clipboard.
+    pub fn load_image(&self, name: &str) -> Result<ClipboardFormat, AssetError> {
+        let bytes = self.read(name)?;
+        validate_image(&bytes)?;
+        let image = image::load_from_memory_with_format(&bytes, ImageFormat::Png)
+            .map_err(|e| AssetError::InvalidImage(e.to_string()))?
+            .into_rgba8();
+        Ok(ClipboardFormat::Image {
+            width: image.width() as usize,
+            height: image.height() as usize,
+            bytes: image.into_raw(),
+        })
+    }
+
+    /// Embeds the assets used by `combos`. Missing assets are left out with
+    /// a warning, so a lost file doesn't block a backup.
+    pub fn embed_for(&self, combos: &[Combo]) -> Vec<EmbeddedAsset> {
+        referenced_assets(combos)
+            .into_iter()
+            .filter_map(|name| match self.read(name) {
+                Ok(bytes) => Some(EmbeddedAsset::new(name, &bytes)),
+                Err(e) => {
+                    tracing::warn!("Not embedding asset '{}': {}", name, e);
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Validates and writes embedded assets into the assets directory,
+    /// replacing files of the same name. Returns the names written, and an
+    /// error message for each asset that was rejected.
+    pub fn unpack(&self, assets: &[EmbeddedAsset]) -> (Vec<String>, Vec<String>) {
+        let mut written = Vec::new();
+        let mut errors = Vec::new();
+        for asset in assets {
+            let result = asset
+                .decode()
+                .and_then(|bytes| self.write(&asset.name, &bytes));
+            match result {
+                Ok(()) => written.push(asset.name.clone()),
+                Err(e) => errors.push(format!("Asset '{}': {}", asset.name, e)),
+            }
+        }
+        (written, errors)
+    }
+
+    fn write(&self, name: &str, bytes: &[u8]) -> Result<(), AssetError> {
+        let path = self.path_for(name)?;
+        std::fs::create_dir_all(&self.dir)?;
+        std::fs::write(path, bytes)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::combo::ComboBuilder;
+    use tempfile::TempDir;
+
+    /// Encodes a `width`x`height` opaque red PNG.
+    fn png_bytes(width: u32, height: u32) -> Vec<u8> {
+        let image = image::RgbaImage::from_pixel(width, height, image::Rgba([255, 0, 0, 255]));
+        let mut out = Cursor::new(Vec::new());
+        image.write_to(&mut out, ImageFormat::Png).unwrap();
+        out.into_inner()
+    }
+
+    fn image_combo(keyword: &str, asset: &str) -> Combo {
+        ComboBuilder::new()
+            .keyword(keyword)
+            .snippet(asset)
+            .snippet_format(SnippetFormat::Image)
+            .build()
+            .unwrap()
+    }
+
+    // ── Validation ───────────────────────────────────────────────
+
+    #[test]
+    fn test_validate_name() {
+        assert!(validate_name("3f2a.png").is_ok());
+        assert!(validate_name("logo_v2-final.png").is_ok());
+        assert!(validate_name("../etc/passwd.png").is_err());
+        assert!(validate_name("dir/logo.png").is_err());
+        assert!(validate_name(".png").is_err());
+        assert!(validate_name("logo.jpg").is_err());
+        assert!(validate_name("").is_err());
+    }
+
+    #[test]
+    fn test_validate_image_accepts_png() {
+        assert_eq!(validate_image(&png_bytes(3, 2)).unwrap(), (3, 2));
+    }
+
+    #[test]
+    fn test_validate_image_rejects_other_types() {
+        let gif = b"GIF89a\x01\x00\x01\x00\x00\x00\x00;";
+        assert!(matches!(validate_image(gif), Err(AssetError::UnsupportedType(_))));
+        assert!(matches!(
+            validate_image(b"not an image"),
+            Err(AssetError::UnsupportedType(_))
+        ));
+    }
+
+    #[test]
+    fn test_validate_image_rejects_oversized() {
+        let mut bytes = png_bytes(1, 1);
+        bytes.resize(MAX_ASSET_BYTES + 1, 0);
+        assert!(matches!(validate_image(&bytes), Err(AssetError::TooLarge(_, _))));
+    }
+
+    #[test]
+    fn test_validate_image_rejects_huge_dimensions() {
+        let bytes = png_bytes(MAX_IMAGE_DIMENSION + 1, 1);
+        assert!(matches!(
+            validate_image(&bytes),
+            Err(AssetError::DimensionsTooLarge(_, _, _))
+        ));
+    }
+
+    #[test]
+    fn test_validate_image_rejects_truncated_png() {
+        let bytes = png_bytes(2, 2);
+        assert!(validate_image(&bytes[..12]).is_err());
+    }
+
+    // ── Store & Load ─────────────────────────────────────────────
+
+    #[test]
+    fn test_store_and_load_image() {
+        let dir = TempDir::new().unwrap();
+        let mgr = AssetManager::new(dir.path().join("assets"));
+
+        let name = mgr.store(&png_bytes(2, 3)).unwrap();
+        assert!(name.ends_with(".png"));
+        assert!(mgr.path_for(&name).unwrap().exists());
+
+        match mgr.load_image(&name).unwrap() {
+            ClipboardFormat::Image {
+                width,
+                height,
+                bytes,
+            } => {
+                assert_eq!((width, height), (2, 3));
+                assert_eq!(bytes.len(), 2 * 3 * 4);
+                assert_eq!(&bytes[..4], &[255, 0, 0, 255]);
+            }
+            other => panic!("Expected an image, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_store_rejects_invalid_image() {
+        let dir = TempDir::new().unwrap();
+        let mgr = AssetManager::new(dir.path().to_path_buf());
+        assert!(mgr.store(b"plain text").is_err());
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_read_missing_asset() {
+        let dir = TempDir::new().unwrap();
+        let mgr = AssetManager::new(dir.path().to_path_buf());
+        assert!(matches!(mgr.read("missing.png"), Err(AssetError::NotFound(_))));
+        assert!(matches!(
+            mgr.read("../secret.png"),
+            Err(AssetError::InvalidName(_))
+        ));
+    }
+
+    #[test]
+    fn test_delete_asset() {
+        let dir = TempDir::new().unwrap();
+        let mgr = AssetManager::new(dir.path().to_path_buf());
+        let name = mgr.store(&png_bytes(1, 1)).unwrap();
+        mgr.delete(&name).unwrap();
+        assert!(matches!(mgr.read(&name), Err(AssetError::NotFound(_))));
+    }
+
+    // ── Embedding ────────────────────────────────────────────────
+
+    #[test]
+    fn test_referenced_assets_dedups_and_skips_text() {
+        let text = ComboBuilder::new().keyword("tx").snippet("a.png").build().unwrap();
+        let combos = vec![
+            image_combo("ia", "a.png"),
+            text,
+            image_combo("ib", "b.png"),
+            image_combo("ic", "a.png"),
+        ];
+        assert_eq!(referenced_assets(&combos), vec!["a.png", "b.png"]);
+    }
+
+    #[test]
+    fn test_embed_and_unpack_roundtrip() {
+        let src = TempDir::new().unwrap();
+        let dst = TempDir::new().unwrap();
+        let from = AssetManager::new(src.path().to_path_buf());
+        let to = AssetManager::new(dst.path().to_path_buf());
+
+        let name = from.store(&png_bytes(2, 2)).unwrap();
+        let combos = vec![image_combo("logo", &name), image_combo("gone", "missing.png")];
+        let embedded = from.embed_for(&combos);
+        assert_eq!(embedded.len(), 1);
+
+        let (written, errors) = to.unpack(&embedded);
+        assert_eq!(written, vec![name.clone()]);
+        assert!(errors.is_empty());
+        assert_eq!(to.read(&name).unwrap(), from.read(&name).unwrap());
+    }
+
+    #[test]
+    fn test_unpack_rejects_invalid_assets() {
+        let dir = TempDir::new().unwrap();
+        let mgr = AssetManager::new(dir.path().to_path_buf());
+        let assets = vec![
+            EmbeddedAsset::new("../escape.png", &png_bytes(1, 1)),
+            EmbeddedAsset::new("text.png", b"hello"),
+            EmbeddedAsset {
+                name: "bad.png".to_string(),
+                data: "!!not base64!!".to_string(),
+            },
+            EmbeddedAsset::new("ok.png", &png_bytes(1, 1)),
+        ];
+
+        let (written, errors) = mgr.unpack(&assets);
+        assert_eq!(written, vec!["ok.png".to_string()]);
+        assert_eq!(errors.len(), 3);
+        assert!(errors[0].contains("Invalid asset name"));
+        assert!(errors[1].contains("Unsupported image type"));
+        assert!(errors[2].contains("Invalid asset encoding"));
+    }
+
+    #[test]
+    fn test_embedded_asset_serialization() {
+        let asset = EmbeddedAsset::new("a.png", b"\x89PNG");
+        let json = serde_json::to_string(&asset).unwrap();
+        assert_eq!(json, r#"{"name":"a.png","data":"iVBORw=="}"#);
+    }
+}
@@ -1,10 +1,12 @@
-//! Backup and restore functionality for combos, groups, and preferences.
+//! Backup and restore functionality for combos, groups, preferences, and
+//! the assets that combos use.
 
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 use thiserror::Error;
 
+use crate::managers::asset_manager::{AssetManager, EmbeddedAsset};
 use crate::models::combo::Combo;
 use crate::models::group::Group;
 
@@ -51,6 +53,10 @@ pub struct BackupData {
     pub combos: Vec<Combo>,
     pub groups: Vec<Group>,
     pub preferences: serde_json::Value,
+    /// Images used by image combos. Absent from backups made before image
+    /// snippets existed.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub assets: Vec<EmbeddedAsset>,
 }
 
 /// Manages backup creation, restoration, and retention.
@@ -58,6 +64,9 @@ pub struct BackupManager {
     pub backup_dir: PathBuf,
     pub max_backups: u32,
     pub auto_interval_hours: u32,
+    /// Asset store that image combos' files are saved from and restored to.
+    /// Backups leave assets out when unset.
+    pub assets: Option<AssetManager>,
 }
 
 impl BackupManager {
@@ -66,10 +75,19 @@ impl BackupManager {
             backup_dir,
             max_backups,
             auto_interval_hours: 24,
+            assets: None,
         }
     }
 
-    /// Create a backup file containing combos, groups, and preferences.
+    /// Includes the assets used by backed-up combos, read from and restored
+    /// to `assets`.
+    pub fn with_assets(mut self, assets: AssetManager) -> Self {
+        self.assets = Some(assets);
+        self
+    }
+
+    /// Create a backup file containing combos, groups, preferences, and the
+    /// assets the combos use.
     pub fn create_backup(
         &self,
         combos: &[Combo],
@@ -92,6 +110,11 @@ impl BackupManager {
             combos: combos.to_vec(),
             groups: groups.to_vec(),
             preferences: preferences.clone(),
+            assets: self
+                .assets
+                .as_ref()
+                .map(|assets| assets.embed_for(combos))
+                .unwrap_or_default(),
         };
 
         let json = serde_json::to_string_pretty(&data)
@@ -109,7 +132,10 @@ impl BackupManager {
         })
     }
 
-    /// Restore a backup by its ID.
+    /// Restore a backup by its ID, writing its assets back to the asset store.
+    ///
+    /// Fails with [`BackupError::InvalidBackup`] if an embedded asset is not
+    /// a valid image.
     pub fn restore_backup(&self, backup_id: &str) -> Result<BackupData, BackupError> {
         // Validate backup_id to prevent path traversal
         if !backup_id.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-') {
@@ -127,6 +153,13 @@ impl BackupManager {
         let data: BackupData = serde_json::from_str(&content)
             .map_err(|e| BackupError::InvalidBackup(e.to_string()))?;
 
+        if let Some(assets) = &self.assets {
+            let (_, errors) = assets.unpack(&data.assets);
+            if !errors.is_empty() {
+                return Err(BackupError::InvalidBackup(errors.join("; ")));
+            }
+        }
+
         Ok(data)
     }
 
@@ -263,6 +296,74 @@ mod tests {
         assert_eq!(data.metadata.version, "1.0");
     }
 
+    // ── Assets ───────────────────────────────────────────────────
+
+    fn png_bytes() -> Vec<u8> {
+        let mut out = std::io::Cursor::new(Vec::new());
+        image::RgbaImage::new(1, 1)
+            .write_to(&mut out, image::ImageFormat::Png)
+            .unwrap();
+        out.into_inner()
+    }
+
+    fn image_combo(asset: &str) -> Combo {
+        use crate::models::combo::{ComboBuilder, SnippetFormat};
+        ComboBuilder::new()
+            .keyword("logo")
+            .snippet(asset)
+            .snippet_format(SnippetFormat::Image)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_backup_roundtrips_assets() {
+        let dir = TempDir::new().unwrap();
+        let source = AssetManager::new(dir.path().join("assets"));
+        let name = source.store(&png_bytes()).unwrap();
+        let mgr = make_manager(&dir).with_assets(source.clone());
+        let info = mgr
+            .create_backup(&[image_combo(&name)], &[], &serde_json::json!({}))
+            .unwrap();
+
+        // Restore onto a machine without the asset.
+        let target = AssetManager::new(dir.path().join("restored"));
+        let restorer = make_manager(&dir).with_assets(target.clone());
+        let data = restorer.restore_backup(&info.id).unwrap();
+        assert_eq!(data.assets.len(), 1);
+        assert_eq!(target.read(&name).unwrap(), png_bytes());
+    }
+
+    #[test]
+    fn test_backup_without_asset_store_omits_assets() {
+        let dir = TempDir::new().unwrap();
+        let mgr = make_manager(&dir);
+        let info = mgr
+            .create_backup(&[image_combo("a.png")], &[], &serde_json::json!({}))
+            .unwrap();
+        let content = std::fs::read_to_string(&info.path).unwrap();
+        assert!(!content.contains("\"assets\""));
+        assert!(mgr.restore_backup(&info.id).unwrap().assets.is_empty());
+    }
+
+    #[test]
+    fn test_restore_rejects_invalid_assets() {
+        let dir = TempDir::new().unwrap();
+        let mgr = make_manager(&dir);
+        let info = mgr.create_backup(&[], &[], &serde_json::json!({})).unwrap();
+
+        let mut data: BackupData =
+            serde_json::from_str(&std::fs::read_to_string(&info.path).unwrap()).unwrap();
+        data.assets.push(EmbeddedAsset::new("fake.png", b"not an image"));
+        std::fs::write(&info.path, serde_json::to_string(&data).unwrap()).unwrap();
+
+        let restorer = make_manager(&dir).with_assets(AssetManager::new(dir.path().join("assets")));
+        match restorer.restore_backup(&info.id) {
+            Err(BackupError::InvalidBackup(msg)) => assert!(msg.contains("fake.png")),
+            other => panic!("Expected InvalidBackup, got {:?}", other.map(|_| ())),
+        }
+    }
+
     // ── List Backups ─────────────────────────────────────────────
 
     #[test]
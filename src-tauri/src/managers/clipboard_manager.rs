@@ -2,7 +2,22 @@
 //!
 //! Provides clipboard read/write with preserve/restore semantics so that
 //! the user's clipboard content is not destroyed during snippet expansion.
+//! Preserving takes a [`ClipboardSnapshot`] of the formats the provider
+//! can read — files, images, HTML and text — not just the text. Restoring
+//! puts back the richest of them the provider accepts: arboard owns one
+//! format at a time, HTML with its plain-text alternative being the only
+//! pair, so a copied file comes back without its text path and a copied
+//! browser image without its HTML. Other MIME targets an application
+//! offered are not saved at all.
+//!
+//! Snippets can be written *concealed*: marked with the hints clipboard
+//! history tools honour (`x-kde-passwordManagerHint` on Linux, which also
+//! skips the `CLIPBOARD_MANAGER` handoff, `org.nspasteboard.ConcealedType`
+//! on macOS, and the history/cloud exclusion formats on Windows), so they
+//! don't end up in the user's clipboard history.
 
+use std::borrow::Cow;
+use std::path::PathBuf;
 use std::thread;
 use std::time::Duration;
 
@@ -19,6 +34,134 @@ pub enum ClipboardError {
     WriteFailed(String),
     #[error("No preserved clipboard content to restore")]
     NothingToRestore,
+    #[error("Clipboard format not supported: {0}")]
+    UnsupportedFormat(&'static str),
+}
+
+/// One representation of the clipboard contents.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClipboardFormat {
+    /// Copied files or folders.
+    FileList(Vec<PathBuf>),
+    /// Decoded RGBA8 pixels, `width * height * 4` bytes.
+    Image {
+        width: usize,
+        height: usize,
+        bytes: Vec<u8>,
+    },
+    /// HTML with its plain-text alternative, if the clipboard had one.
+    Html {
+        html: String,
+        alt_text: Option<String>,
+    },
+    Text(String),
+}
+
+impl ClipboardFormat {
+    /// The MIME type this format is offered as on Linux.
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            Self::FileList(_) => "text/uri-list",
+            Self::Image { .. } => "image/png",
+            Self::Html { .. } => "text/html",
+            Self::Text(_) => "text/plain",
+        }
+    }
+
+    /// Approximate memory held by this format, in bytes.
+    pub fn size(&self) -> usize {
+        match self {
+            Self::FileList(paths) => paths.iter().map(|p| p.as_os_str().len()).sum(),
+            Self::Image { bytes, .. } => bytes.len(),
+            Self::Html { html, alt_text } => {
+                html.len() + alt_text.as_ref().map_or(0, String::len)
+            }
+            Self::Text(text) => text.len(),
+        }
+    }
+
+    /// Restore priority: richer formats first, plain text last.
+    fn rank(&self) -> u8 {
+        match self {
+            Self::FileList(_) => 0,
+            Self::Image { .. } => 1,
+            Self::Html { .. } => 2,
+            Self::Text(_) => 3,
+        }
+    }
+}
+
+/// Default cap on the size of a [`ClipboardSnapshot`]: 32 MiB, enough for
+/// a full-screen 4K screenshot.
+pub const DEFAULT_MAX_SNAPSHOT_BYTES: usize = 32 * 1024 * 1024;
+
+/// The clipboard contents saved before an expansion, ordered from the
+/// richest format to plain text.
+///
+/// Restoring hands the formats to [`ClipboardProvider::write_formats`],
+/// which puts back as many as the provider can offer together; for arboard
+/// that is the richest one. Formats that would take the snapshot over its
+/// size cap are dropped when it is taken, leaving the smaller ones.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ClipboardSnapshot {
+    formats: Vec<ClipboardFormat>,
+}
+
+impl ClipboardSnapshot {
+    /// Builds a snapshot from `formats`, keeping as many as fit in
+    /// `max_bytes`, richest first.
+    pub fn new(mut formats: Vec<ClipboardFormat>, max_bytes: usize) -> Self {
+        formats.sort_by_key(ClipboardFormat::rank);
+        let mut total = 0;
+        formats.retain(|format| {
+            let size = format.size();
+            if total + size > max_bytes {
+                tracing::debug!(
+                    "Not preserving {} clipboard content: {} bytes exceeds the {} byte cap",
+                    format.mime_type(),
+                    size,
+                    max_bytes
+                );
+                return false;
+            }
+            total += size;
+            true
+        });
+        Self { formats }
+    }
+
+    /// A snapshot holding only `text`.
+    pub fn from_text(text: impl Into<String>) -> Self {
+        Self {
+            formats: vec![ClipboardFormat::Text(text.into())],
+        }
+    }
+
+    /// The saved formats, richest first.
+    pub fn formats(&self) -> &[ClipboardFormat] {
+        &self.formats
+    }
+
+    /// The plain text, from the text format or the HTML's alternative.
+    pub fn text(&self) -> Option<&str> {
+        self.formats.iter().find_map(|format| match format {
+            ClipboardFormat::Text(text) => Some(text.as_str()),
+            ClipboardFormat::Html {
+                alt_text: Some(text),
+                ..
+            } => Some(text.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Total size of the saved formats, in bytes.
+    pub fn size(&self) -> usize {
+        self.formats.iter().map(ClipboardFormat::size).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.formats.is_empty()
+    }
 }
 
 /// Trait abstracting clipboard operations for testability.
@@ -27,9 +170,97 @@ pub trait ClipboardProvider: Send {
     fn read_text(&mut self) -> Result<String, ClipboardError>;
     /// Writes text to the clipboard.
     fn write_text(&mut self, text: &str) -> Result<(), ClipboardError>;
+
+    /// Reads every format on the clipboard that the provider understands.
+    /// A provider that can tell a format's size before copying it should
+    /// skip formats that don't fit in `max_bytes`. The default reads only
+    /// text.
+    fn read_formats(&mut self, _max_bytes: usize) -> Result<Vec<ClipboardFormat>, ClipboardError> {
+        Ok(vec![ClipboardFormat::Text(self.read_text()?)])
+    }
+
+    /// Replaces the clipboard contents with `format`. The default only
+    /// writes text.
+    fn write_format(&mut self, format: &ClipboardFormat) -> Result<(), ClipboardError> {
+        match format {
+            ClipboardFormat::Text(text) => self.write_text(text),
+            other => Err(ClipboardError::UnsupportedFormat(other.mime_type())),
+        }
+    }
+
+    /// Like [`Self::write_format`], but asks clipboard managers not to
+    /// record the content. The default can't set the hints and writes the
+    /// format as is.
+    fn write_concealed(&mut self, format: &ClipboardFormat) -> Result<(), ClipboardError> {
+        self.write_format(format)
+    }
+
+    /// Replaces the clipboard contents with as many of `formats` as the
+    /// provider can offer together. `formats` are ordered richest first.
+    ///
+    /// The default can only hold one format at a time: it writes the first
+    /// of `formats` that succeeds and the others are lost.
+    fn write_formats(&mut self, formats: &[ClipboardFormat]) -> Result<(), ClipboardError> {
+        write_first_format(self, formats)
+    }
+}
+
+/// Writes the first of `formats` that `provider` accepts, for providers
+/// that hold one format at a time.
+fn write_first_format<P: ClipboardProvider + ?Sized>(
+    provider: &mut P,
+    formats: &[ClipboardFormat],
+) -> Result<(), ClipboardError> {
+    let mut last_err = ClipboardError::NothingToRestore;
+    for format in formats {
+        match provider.write_format(format) {
+            Ok(()) => return Ok(()),
+            Err(e) => {
+                tracing::debug!(
+                    "Could not write {} clipboard content: {}",
+                    format.mime_type(),
+                    e
+                );
+                last_err = e;
+            }
+        }
+    }
+    Err(last_err)
+}
+
+impl<P: ClipboardProvider + ?Sized> ClipboardProvider for Box<P> {
+    fn read_text(&mut self) -> Result<String, ClipboardError> {
+        (**self).read_text()
+    }
+
+    fn write_text(&mut self, text: &str) -> Result<(), ClipboardError> {
+        (**self).write_text(text)
+    }
+
+    fn read_formats(&mut self, max_bytes: usize) -> Result<Vec<ClipboardFormat>, ClipboardError> {
+        (**self).read_formats(max_bytes)
+    }
+
+    fn write_format(&mut self, format: &ClipboardFormat) -> Result<(), ClipboardError> {
+        (**self).write_format(format)
+    }
+
+    fn write_concealed(&mut self, format: &ClipboardFormat) -> Result<(), ClipboardError> {
+        (**self).write_concealed(format)
+    }
+
+    fn write_formats(&mut self, formats: &[ClipboardFormat]) -> Result<(), ClipboardError> {
+        (**self).write_formats(formats)
+    }
 }
 
 /// Real clipboard provider using arboard.
+///
+/// arboard reads and writes only files, images, HTML and text, so other
+/// targets an application offered (its private formats, for one) are not
+/// preserved. It also writes one format per call, HTML together with its
+/// plain-text alternative being the only pair, so a clipboard holding
+/// several of the others gets back only the richest of them.
 pub struct ArboardProvider {
     clipboard: arboard::Clipboard,
 }
@@ -55,21 +286,146 @@ impl ClipboardProvider for ArboardProvider {
             .set_text(text)
             .map_err(|e| ClipboardError::WriteFailed(e.to_string()))
     }
+
+    /// Reads files, image, HTML and text. A format that is absent or can't
+    /// be converted is skipped, so an empty clipboard gives no formats. An
+    /// image larger than what is left of `max_bytes` is dropped as soon as
+    /// arboard hands it over, before it is copied.
+    fn read_formats(&mut self, max_bytes: usize) -> Result<Vec<ClipboardFormat>, ClipboardError> {
+        let mut formats = Vec::new();
+        if let Ok(paths) = self.clipboard.get().file_list() {
+            if !paths.is_empty() {
+                formats.push(ClipboardFormat::FileList(paths));
+            }
+        }
+        if let Ok(image) = self.clipboard.get_image() {
+            let budget = max_bytes.saturating_sub(formats.iter().map(ClipboardFormat::size).sum());
+            formats.extend(image_within(image, budget));
+        }
+        let text = self.clipboard.get_text().ok();
+        if let Ok(html) = self.clipboard.get().html() {
+            formats.push(ClipboardFormat::Html {
+                html,
+                alt_text: text.clone(),
+            });
+        }
+        if let Some(text) = text {
+            formats.push(ClipboardFormat::Text(text));
+        }
+        Ok(formats)
+    }
+
+    fn write_format(&mut self, format: &ClipboardFormat) -> Result<(), ClipboardError> {
+        let result = match format {
+            ClipboardFormat::FileList(paths) => self.clipboard.set().file_list(paths),
+            ClipboardFormat::Image {
+                width,
+                height,
+                bytes,
+            } => self.clipboard.set_image(arboard::ImageData {
+                width: *width,
+                height: *height,
+                bytes: Cow::Borrowed(bytes),
+            }),
+            ClipboardFormat::Html { html, alt_text } => {
+                self.clipboard.set_html(html.as_str(), alt_text.as_deref())
+            }
+            ClipboardFormat::Text(text) => self.clipboard.set_text(text.as_str()),
+        };
+        result.map_err(|e| ClipboardError::WriteFailed(e.to_string()))
+    }
+
+    fn write_concealed(&mut self, format: &ClipboardFormat) -> Result<(), ClipboardError> {
+        let set = self.clipboard.set();
+        #[cfg(all(unix, not(target_os = "macos")))]
+        let set = {
+            use arboard::SetExtLinux;
+            set.exclude_from_history()
+        };
+        #[cfg(target_os = "macos")]
+        let set = {
+            use arboard::SetExtApple;
+            set.exclude_from_history()
+        };
+        #[cfg(windows)]
+        let set = {
+            use arboard::SetExtWindows;
+            set.exclude_from_history().exclude_from_cloud()
+        };
+        let result = match format {
+            ClipboardFormat::FileList(paths) => set.file_list(paths),
+            ClipboardFormat::Image {
+                width,
+                height,
+                bytes,
+            } => set.image(arboard::ImageData {
+                width: *width,
+                height: *height,
+                bytes: Cow::Borrowed(bytes),
+            }),
+            ClipboardFormat::Html { html, alt_text } => {
+                set.html(html.as_str(), alt_text.as_deref())
+            }
+            ClipboardFormat::Text(text) => set.text(text.as_str()),
+        };
+        result.map_err(|e| ClipboardError::WriteFailed(e.to_string()))
+    }
+
+    /// Writes the richest of `formats` arboard accepts; the others are
+    /// lost. HTML goes with the saved text as its alternative when it had
+    /// none of its own.
+    fn write_formats(&mut self, formats: &[ClipboardFormat]) -> Result<(), ClipboardError> {
+        write_first_format(self, &with_text_alternative(formats))
+    }
+}
+
+/// `formats` with the plain text attached to HTML that has no alternative.
+fn with_text_alternative(formats: &[ClipboardFormat]) -> Vec<ClipboardFormat> {
+    let text = formats.iter().find_map(|format| match format {
+        ClipboardFormat::Text(text) => Some(text),
+        _ => None,
+    });
+    formats
+        .iter()
+        .map(|format| match format {
+            ClipboardFormat::Html { html, alt_text: None } => ClipboardFormat::Html {
+                html: html.clone(),
+                alt_text: text.cloned(),
+            },
+            _ => format.clone(),
+        })
+        .collect()
+}
+
+/// Converts an image read by arboard, or drops it without copying its
+/// pixels if they take more than `max_bytes`.
+fn image_within(image: arboard::ImageData<'_>, max_bytes: usize) -> Option<ClipboardFormat> {
+    if image.bytes.len() > max_bytes {
+        tracing::debug!(
+            "Not preserving image/png clipboard content: {} bytes exceeds the {} byte cap",
+            image.bytes.len(),
+            max_bytes
+        );
+        return None;
+    }
+    Some(ClipboardFormat::Image {
+        width: image.width,
+        height: image.height,
+        bytes: image.bytes.into_owned(),
+    })
 }
 
 /// Manages clipboard operations with preserve/restore capability.
 pub struct ClipboardManager<P: ClipboardProvider> {
     provider: P,
-    preserved: Option<String>,
+    preserved: Option<ClipboardSnapshot>,
+    max_snapshot_bytes: usize,
 }
 
 impl ClipboardManager<ArboardProvider> {
     /// Creates a new `ClipboardManager` backed by the system clipboard.
     pub fn new_system() -> Result<Self, ClipboardError> {
-        Ok(Self {
-            provider: ArboardProvider::new()?,
-            preserved: None,
-        })
+        Ok(Self::new(ArboardProvider::new()?))
     }
 }
 
@@ -79,9 +435,16 @@ impl<P: ClipboardProvider> ClipboardManager<P> {
         Self {
             provider,
             preserved: None,
+            max_snapshot_bytes: DEFAULT_MAX_SNAPSHOT_BYTES,
         }
     }
 
+    /// Sets the size cap for snapshots taken by [`Self::preserve`] and
+    /// [`ClipboardGuard`].
+    pub fn set_max_snapshot_bytes(&mut self, max_bytes: usize) {
+        self.max_snapshot_bytes = max_bytes;
+    }
+
     /// Reads current clipboard text.
     pub fn read(&mut self) -> Result<String, ClipboardError> {
         tracing::debug!("Reading clipboard");
@@ -94,20 +457,86 @@ impl<P: ClipboardProvider> ClipboardManager<P> {
         self.provider.write_text(text)
     }
 
+    /// Writes `html` with `text` as its plain-text alternative, so rich-text
+    /// editors paste the HTML and everything else the text. Writes only the
+    /// text if the provider can't write HTML.
+    pub fn write_html(&mut self, html: &str, text: &str) -> Result<(), ClipboardError> {
+        let format = ClipboardFormat::Html {
+            html: html.to_string(),
+            alt_text: Some(text.to_string()),
+        };
+        self.put(&format, false)
+    }
+
+    /// Writes a single format, such as an image, replacing the clipboard
+    /// contents. HTML falls back to its alternative text like
+    /// [`Self::write_html`].
+    pub fn write_format(&mut self, format: &ClipboardFormat) -> Result<(), ClipboardError> {
+        self.put(format, false)
+    }
+
+    /// [`Self::write_format`], marking the content so clipboard history
+    /// tools don't record it.
+    pub fn write_concealed(&mut self, format: &ClipboardFormat) -> Result<(), ClipboardError> {
+        self.put(format, true)
+    }
+
+    fn put(&mut self, format: &ClipboardFormat, conceal: bool) -> Result<(), ClipboardError> {
+        tracing::debug!(
+            "Writing {} to clipboard{}: {} bytes",
+            format.mime_type(),
+            if conceal { " (concealed)" } else { "" },
+            format.size()
+        );
+        let write = |provider: &mut P, format: &ClipboardFormat| {
+            if conceal {
+                provider.write_concealed(format)
+            } else {
+                provider.write_format(format)
+            }
+        };
+        match (write(&mut self.provider, format), format) {
+            (Err(e), ClipboardFormat::Html { alt_text: Some(text), .. }) => {
+                tracing::debug!("Could not write HTML ({}); writing plain text", e);
+                write(&mut self.provider, &ClipboardFormat::Text(text.clone()))
+            }
+            (result, _) => result,
+        }
+    }
+
+    /// Takes a snapshot of every readable clipboard format.
+    ///
+    /// Falls back to plain text if the provider can't list formats, and to
+    /// an empty snapshot if it can't read at all.
+    pub fn snapshot(&mut self) -> ClipboardSnapshot {
+        self.snapshot_with_retry(0, Duration::ZERO)
+    }
+
+    /// Makes the clipboard hold `snapshot` again, as far as the provider
+    /// can (see [`ClipboardProvider::write_formats`]). An empty snapshot
+    /// empties the clipboard.
+    pub fn restore_snapshot(&mut self, snapshot: &ClipboardSnapshot) -> Result<(), ClipboardError> {
+        self.restore_snapshot_with_retry(snapshot, 0, Duration::ZERO)
+    }
+
     /// Saves the current clipboard content for later restoration.
     pub fn preserve(&mut self) -> Result<(), ClipboardError> {
-        let content = self.provider.read_text().unwrap_or_default();
-        tracing::debug!("Preserving clipboard: {} chars", content.len());
-        self.preserved = Some(content);
+        let snapshot = self.snapshot();
+        tracing::debug!(
+            "Preserving clipboard: {} formats, {} bytes",
+            snapshot.formats().len(),
+            snapshot.size()
+        );
+        self.preserved = Some(snapshot);
         Ok(())
     }
 
     /// Restores previously preserved clipboard content.
     pub fn restore(&mut self) -> Result<(), ClipboardError> {
         match self.preserved.take() {
-            Some(content) => {
-                tracing::debug!("Restoring clipboard: {} chars", content.len());
-                self.provider.write_text(&content)
+            Some(snapshot) => {
+                tracing::debug!("Restoring clipboard: {} bytes", snapshot.size());
+                self.restore_snapshot(&snapshot)
             }
             None => Err(ClipboardError::NothingToRestore),
         }
@@ -118,37 +547,76 @@ impl<P: ClipboardProvider> ClipboardManager<P> {
         self.preserved.is_some()
     }
 
-    /// Reads clipboard text with retry logic.
-    ///
-    /// Retries up to `retries` times with `delay` between attempts.
-    /// This helps on Windows where clipboard access can transiently fail
-    /// if another application has it open.
-    pub fn read_with_retry(
+    /// [`Self::snapshot`], retrying each read like [`Self::read_with_retry`].
+    pub fn snapshot_with_retry(&mut self, retries: u32, delay: Duration) -> ClipboardSnapshot {
+        let max_bytes = self.max_snapshot_bytes;
+        let formats = match self.retry(retries, delay, "read", |p| p.read_formats(max_bytes)) {
+            Ok(formats) => formats,
+            Err(e) => {
+                tracing::debug!("Could not list clipboard formats ({}); saving text only", e);
+                match self.retry(retries, delay, "read", |p| p.read_text()) {
+                    Ok(text) => vec![ClipboardFormat::Text(text)],
+                    Err(_) => Vec::new(),
+                }
+            }
+        };
+        ClipboardSnapshot::new(formats, self.max_snapshot_bytes)
+    }
+
+    /// [`Self::restore_snapshot`], retrying each write like
+    /// [`Self::write_with_retry`].
+    pub fn restore_snapshot_with_retry(
         &mut self,
+        snapshot: &ClipboardSnapshot,
         retries: u32,
         delay: Duration,
-    ) -> Result<String, ClipboardError> {
-        let mut last_err = None;
-        for attempt in 0..=retries {
-            match self.provider.read_text() {
-                Ok(text) => return Ok(text),
-                Err(e) => {
+    ) -> Result<(), ClipboardError> {
+        if snapshot.is_empty() {
+            return self.retry(retries, delay, "write", |p| p.write_text(""));
+        }
+        self.retry(retries, delay, "write", |p| p.write_formats(snapshot.formats()))
+    }
+
+    /// Runs `op` on the provider up to `retries + 1` times, sleeping
+    /// `delay` between failed attempts.
+    fn retry<T>(
+        &mut self,
+        retries: u32,
+        delay: Duration,
+        what: &str,
+        mut op: impl FnMut(&mut P) -> Result<T, ClipboardError>,
+    ) -> Result<T, ClipboardError> {
+        let mut attempt = 0;
+        loop {
+            match op(&mut self.provider) {
+                Ok(value) => return Ok(value),
+                Err(e) if attempt < retries => {
                     tracing::debug!(
-                        "Clipboard read attempt {}/{} failed: {}",
+                        "Clipboard {} attempt {}/{} failed: {}",
+                        what,
                         attempt + 1,
                         retries + 1,
                         e
                     );
-                    last_err = Some(e);
-                    if attempt < retries {
-                        thread::sleep(delay);
-                    }
+                    attempt += 1;
+                    thread::sleep(delay);
                 }
+                Err(e) => return Err(e),
             }
         }
-        Err(last_err.unwrap_or(ClipboardError::ReadFailed(
-            "All retries exhausted".to_string(),
-        )))
+    }
+
+    /// Reads clipboard text with retry logic.
+    ///
+    /// Retries up to `retries` times with `delay` between attempts.
+    /// This helps on Windows where clipboard access can transiently fail
+    /// if another application has it open.
+    pub fn read_with_retry(
+        &mut self,
+        retries: u32,
+        delay: Duration,
+    ) -> Result<String, ClipboardError> {
+        self.retry(retries, delay, "read", |p| p.read_text())
     }
 
     /// Writes text to clipboard with retry logic.
@@ -158,27 +626,7 @@ impl<P: ClipboardProvider> ClipboardManager<P> {
         retries: u32,
         delay: Duration,
     ) -> Result<(), ClipboardError> {
-        let mut last_err = None;
-        for attempt in 0..=retries {
-            match self.provider.write_text(text) {
-                Ok(()) => return Ok(()),
-                Err(e) => {
-                    tracing::debug!(
-                        "Clipboard write attempt {}/{} failed: {}",
-                        attempt + 1,
-                        retries + 1,
-                        e
-                    );
-                    last_err = Some(e);
-                    if attempt < retries {
-                        thread::sleep(delay);
-                    }
-                }
-            }
-        }
-        Err(last_err.unwrap_or(ClipboardError::WriteFailed(
-            "All retries exhausted".to_string(),
-        )))
+        self.retry(retries, delay, "write", |p| p.write_text(text))
     }
 }
 
@@ -194,18 +642,21 @@ pub const CLIPBOARD_RETRY_DELAY: Duration = Duration::from_millis(50);
 /// while guaranteeing the user's original content is restored afterward.
 pub struct ClipboardGuard<'a, P: ClipboardProvider> {
     manager: &'a mut ClipboardManager<P>,
-    saved: Option<String>,
+    saved: Option<ClipboardSnapshot>,
     restored: bool,
 }
 
 impl<'a, P: ClipboardProvider> ClipboardGuard<'a, P> {
-    /// Creates a new guard, saving the current clipboard content.
-    /// If reading fails, saves an empty string.
+    /// Creates a new guard, saving a snapshot of the current clipboard.
+    /// If reading fails, saves an empty snapshot.
     pub fn new(manager: &'a mut ClipboardManager<P>) -> Self {
-        let saved = manager
-            .read_with_retry(CLIPBOARD_RETRY_COUNT, CLIPBOARD_RETRY_DELAY)
-            .unwrap_or_default();
-        tracing::debug!("ClipboardGuard: saved {} chars", saved.len());
+        let saved =
+            manager.snapshot_with_retry(CLIPBOARD_RETRY_COUNT, CLIPBOARD_RETRY_DELAY);
+        tracing::debug!(
+            "ClipboardGuard: saved {} formats, {} bytes",
+            saved.formats().len(),
+            saved.size()
+        );
         Self {
             manager,
             saved: Some(saved),
@@ -225,9 +676,12 @@ impl<'a, P: ClipboardProvider> ClipboardGuard<'a, P> {
             return Ok(());
         }
         self.restored = true;
-        if let Some(content) = self.saved.take() {
-            self.manager
-                .write_with_retry(&content, CLIPBOARD_RETRY_COUNT, CLIPBOARD_RETRY_DELAY)
+        if let Some(snapshot) = self.saved.take() {
+            self.manager.restore_snapshot_with_retry(
+                &snapshot,
+                CLIPBOARD_RETRY_COUNT,
+                CLIPBOARD_RETRY_DELAY,
+            )
         } else {
             Ok(())
         }
@@ -502,4 +956,314 @@ mod tests {
         // Double restore should be no-op
         guard.restore().unwrap();
     }
+
+    // ── Snapshot tests ───────────────────────────────────────────
+
+    /// Provider holding one format at a time, like arboard.
+    struct RichProvider {
+        current: Vec<ClipboardFormat>,
+        reject_images: bool,
+        /// Number of `write_formats` calls.
+        format_writes: usize,
+        /// Whether the current content carries the history-exclusion hints.
+        concealed: bool,
+    }
+
+    impl RichProvider {
+        fn new(current: Vec<ClipboardFormat>) -> Self {
+            Self {
+                current,
+                reject_images: false,
+                format_writes: 0,
+                concealed: false,
+            }
+        }
+    }
+
+    impl ClipboardProvider for RichProvider {
+        fn read_text(&mut self) -> Result<String, ClipboardError> {
+            ClipboardSnapshot::new(self.current.clone(), usize::MAX)
+                .text()
+                .map(str::to_string)
+                .ok_or_else(|| ClipboardError::ReadFailed("no text".into()))
+        }
+
+        fn write_text(&mut self, text: &str) -> Result<(), ClipboardError> {
+            self.current = vec![ClipboardFormat::Text(text.to_string())];
+            self.concealed = false;
+            Ok(())
+        }
+
+        fn read_formats(
+            &mut self,
+            _max_bytes: usize,
+        ) -> Result<Vec<ClipboardFormat>, ClipboardError> {
+            Ok(self.current.clone())
+        }
+
+        fn write_format(&mut self, format: &ClipboardFormat) -> Result<(), ClipboardError> {
+            if self.reject_images && matches!(format, ClipboardFormat::Image { .. }) {
+                return Err(ClipboardError::WriteFailed("mock image failure".into()));
+            }
+            self.current = vec![format.clone()];
+            self.concealed = false;
+            Ok(())
+        }
+
+        fn write_concealed(&mut self, format: &ClipboardFormat) -> Result<(), ClipboardError> {
+            self.write_format(format)?;
+            self.concealed = true;
+            Ok(())
+        }
+
+        fn write_formats(&mut self, formats: &[ClipboardFormat]) -> Result<(), ClipboardError> {
+            self.format_writes += 1;
+            write_first_format(self, &with_text_alternative(formats))
+        }
+    }
+
+    fn image(side: usize) -> ClipboardFormat {
+        ClipboardFormat::Image {
+            width: side,
+            height: side,
+            bytes: vec![0xff; side * side * 4],
+        }
+    }
+
+    #[test]
+    fn test_image_within_cap_is_kept() {
+        let bytes = vec![0xff; 2 * 2 * 4];
+        let data = arboard::ImageData {
+            width: 2,
+            height: 2,
+            bytes: Cow::Borrowed(&bytes),
+        };
+        assert_eq!(image_within(data, 16), Some(image(2)));
+    }
+
+    #[test]
+    fn test_image_over_cap_is_dropped() {
+        let bytes = vec![0xff; 2 * 2 * 4];
+        let data = arboard::ImageData {
+            width: 2,
+            height: 2,
+            bytes: Cow::Borrowed(&bytes),
+        };
+        assert_eq!(image_within(data, 15), None);
+    }
+
+    #[test]
+    fn test_snapshot_orders_richest_first() {
+        let snapshot = ClipboardSnapshot::new(
+            vec![
+                ClipboardFormat::Text("a.png".into()),
+                image(2),
+                ClipboardFormat::FileList(vec![PathBuf::from("/tmp/a.png")]),
+            ],
+            usize::MAX,
+        );
+        let mimes: Vec<_> = snapshot.formats().iter().map(ClipboardFormat::mime_type).collect();
+        assert_eq!(mimes, vec!["text/uri-list", "image/png", "text/plain"]);
+    }
+
+    #[test]
+    fn test_snapshot_cap_drops_large_formats() {
+        let snapshot =
+            ClipboardSnapshot::new(vec![image(64), ClipboardFormat::Text("caption".into())], 1024);
+        assert_eq!(snapshot.formats(), &[ClipboardFormat::Text("caption".into())]);
+        assert_eq!(snapshot.size(), 7);
+    }
+
+    #[test]
+    fn test_snapshot_text_uses_html_alternative() {
+        let snapshot = ClipboardSnapshot::new(
+            vec![ClipboardFormat::Html {
+                html: "<b>hi</b>".into(),
+                alt_text: Some("hi".into()),
+            }],
+            usize::MAX,
+        );
+        assert_eq!(snapshot.text(), Some("hi"));
+        assert_eq!(ClipboardSnapshot::default().text(), None);
+    }
+
+    #[test]
+    fn test_default_provider_snapshot_is_text() {
+        let mut mgr = ClipboardManager::new(MockProvider::new("plain"));
+        assert_eq!(mgr.snapshot(), ClipboardSnapshot::from_text("plain"));
+    }
+
+    #[test]
+    fn test_default_provider_rejects_rich_formats() {
+        let mut provider = MockProvider::new("");
+        let result = provider.write_format(&image(1));
+        assert!(matches!(result, Err(ClipboardError::UnsupportedFormat("image/png"))));
+    }
+
+    #[test]
+    fn test_preserve_and_restore_image() {
+        let mut mgr = ClipboardManager::new(RichProvider::new(vec![image(4)]));
+        mgr.preserve().unwrap();
+        mgr.write("snippet").unwrap();
+        mgr.restore().unwrap();
+        assert_eq!(mgr.provider.current, vec![image(4)]);
+    }
+
+    #[test]
+    fn test_restore_falls_back_to_text() {
+        let mut provider = RichProvider::new(vec![image(4), ClipboardFormat::Text("caption".into())]);
+        provider.reject_images = true;
+        let mut mgr = ClipboardManager::new(provider);
+        mgr.preserve().unwrap();
+        mgr.write("snippet").unwrap();
+        mgr.restore().unwrap();
+        assert_eq!(mgr.read().unwrap(), "caption");
+    }
+
+    #[test]
+    fn test_copied_file_restores_without_its_text() {
+        let files = ClipboardFormat::FileList(vec![PathBuf::from("/tmp/a.png")]);
+        let mut mgr = ClipboardManager::new(RichProvider::new(vec![
+            ClipboardFormat::Text("/tmp/a.png".into()),
+            files.clone(),
+        ]));
+        mgr.preserve().unwrap();
+        mgr.write("snippet").unwrap();
+        mgr.restore().unwrap();
+        assert_eq!(mgr.provider.current, vec![files]);
+        assert_eq!(mgr.provider.format_writes, 1);
+    }
+
+    #[test]
+    fn test_restored_html_keeps_saved_text() {
+        let html = ClipboardFormat::Html {
+            html: "<b>Jane</b>".into(),
+            alt_text: None,
+        };
+        let mut mgr = ClipboardManager::new(RichProvider::new(vec![
+            ClipboardFormat::Text("Jane".into()),
+            html,
+        ]));
+        mgr.preserve().unwrap();
+        mgr.write("snippet").unwrap();
+        mgr.restore().unwrap();
+        assert_eq!(
+            mgr.provider.current,
+            vec![ClipboardFormat::Html {
+                html: "<b>Jane</b>".into(),
+                alt_text: Some("Jane".into()),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_single_format_provider_restores_richest_format() {
+        let mut mgr = ClipboardManager::new(RichProvider::new(vec![
+            image(4),
+            ClipboardFormat::Text("caption".into()),
+        ]));
+        mgr.preserve().unwrap();
+        mgr.write("snippet").unwrap();
+        mgr.restore().unwrap();
+        assert_eq!(mgr.provider.current, vec![image(4)]);
+        assert_eq!(mgr.provider.format_writes, 1);
+    }
+
+    #[test]
+    fn test_restore_reports_error_when_no_format_restores() {
+        let mut provider = RichProvider::new(vec![image(4)]);
+        provider.reject_images = true;
+        let mut mgr = ClipboardManager::new(provider);
+        mgr.preserve().unwrap();
+        mgr.write("snippet").unwrap();
+        assert!(matches!(mgr.restore(), Err(ClipboardError::WriteFailed(_))));
+    }
+
+    #[test]
+    fn test_preserve_respects_size_cap() {
+        let mut mgr = ClipboardManager::new(RichProvider::new(vec![
+            image(64),
+            ClipboardFormat::Text("caption".into()),
+        ]));
+        mgr.set_max_snapshot_bytes(1024);
+        mgr.preserve().unwrap();
+        mgr.write("snippet").unwrap();
+        mgr.restore().unwrap();
+        assert_eq!(mgr.provider.current, vec![ClipboardFormat::Text("caption".into())]);
+    }
+
+    #[test]
+    fn test_restore_empty_snapshot_clears_text() {
+        let mut mgr = ClipboardManager::new(RichProvider::new(Vec::new()));
+        mgr.preserve().unwrap();
+        mgr.write("snippet").unwrap();
+        mgr.restore().unwrap();
+        assert_eq!(mgr.read().unwrap(), "");
+    }
+
+    #[test]
+    fn test_write_html_publishes_text_alternative() {
+        let mut mgr = ClipboardManager::new(RichProvider::new(Vec::new()));
+        mgr.write_html("<b>Jane</b>", "Jane").unwrap();
+        assert_eq!(
+            mgr.provider.current,
+            vec![ClipboardFormat::Html {
+                html: "<b>Jane</b>".into(),
+                alt_text: Some("Jane".into()),
+            }]
+        );
+        assert_eq!(mgr.read().unwrap(), "Jane");
+    }
+
+    #[test]
+    fn test_write_html_falls_back_to_text() {
+        let mut mgr = ClipboardManager::new(MockProvider::new(""));
+        mgr.write_html("<b>Jane</b>", "Jane").unwrap();
+        assert_eq!(mgr.read().unwrap(), "Jane");
+    }
+
+    #[test]
+    fn test_clipboard_guard_restores_file_list() {
+        let files = ClipboardFormat::FileList(vec![PathBuf::from("/home/user/report.pdf")]);
+        let mut mgr = ClipboardManager::new(RichProvider::new(vec![files.clone()]));
+        {
+            let mut guard = ClipboardGuard::new(&mut mgr);
+            guard.manager_mut().write("temporary").unwrap();
+        }
+        assert_eq!(mgr.provider.current, vec![files]);
+    }
+
+    // ── Concealed write tests ────────────────────────────────────
+
+    #[test]
+    fn test_write_concealed_sets_history_hints() {
+        let mut mgr = ClipboardManager::new(RichProvider::new(Vec::new()));
+        mgr.write_concealed(&ClipboardFormat::Text("12345678".into())).unwrap();
+        assert!(mgr.provider.concealed);
+        assert_eq!(mgr.read().unwrap(), "12345678");
+
+        mgr.write("public").unwrap();
+        assert!(!mgr.provider.concealed);
+    }
+
+    #[test]
+    fn test_restore_after_concealed_write_is_not_concealed() {
+        let mut mgr = ClipboardManager::new(RichProvider::new(vec![image(2)]));
+        mgr.preserve().unwrap();
+        mgr.write_concealed(&ClipboardFormat::Text("secret".into())).unwrap();
+        mgr.restore().unwrap();
+        assert!(!mgr.provider.concealed);
+        assert_eq!(mgr.provider.current, vec![image(2)]);
+    }
+
+    #[test]
+    fn test_write_concealed_html_falls_back_to_text() {
+        let mut mgr = ClipboardManager::new(MockProvider::new(""));
+        let format = ClipboardFormat::Html {
+            html: "<b>Jane</b>".into(),
+            alt_text: Some("Jane".into()),
+        };
+        mgr.write_concealed(&format).unwrap();
+        assert_eq!(mgr.read().unwrap(), "Jane");
+    }
 }
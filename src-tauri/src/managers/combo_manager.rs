@@ -7,9 +7,11 @@ use chrono::Utc;
 use thiserror::Error;
 use uuid::Uuid;
 
+use crate::managers::asset_manager::{referenced_assets, validate_name, AssetError, AssetManager};
 use crate::managers::combo_storage::ComboStorage;
 use crate::managers::storage::StorageError;
-use crate::models::combo::{Combo, ComboBuilder, ComboValidationError};
+use crate::models::autocorrect::{AutocorrectDictionary, AutocorrectDictionarySummary};
+use crate::models::combo::{Combo, ComboBuilder, ComboPatch, ComboValidationError, SnippetFormat};
 use crate::models::group::Group;
 use crate::models::library::ComboLibrary;
 use crate::models::matching::MatchingMode;
@@ -21,10 +23,14 @@ pub enum ComboManagerError {
     ComboNotFound(Uuid),
     #[error("Group not found: {0}")]
     GroupNotFound(Uuid),
+    #[error("Autocorrect dictionary not found: {0}")]
+    DictionaryNotFound(Uuid),
     #[error("Validation error: {0}")]
     Validation(#[from] ComboValidationError),
     #[error("{0}")]
     ValidationMessage(String),
+    #[error(transparent)]
+    Asset(#[from] AssetError),
     #[error("Storage error: {0}")]
     Storage(#[from] StorageError),
 }
@@ -33,20 +39,36 @@ pub enum ComboManagerError {
 pub struct ComboManager {
     library: ComboLibrary,
     storage: ComboStorage,
+    assets: Option<AssetManager>,
 }
 
 impl ComboManager {
     /// Creates a new `ComboManager` by loading the library from the given storage.
     pub fn new(storage: ComboStorage) -> Result<Self, ComboManagerError> {
         let library = storage.load()?;
-        let mut mgr = Self { library, storage };
+        let mut mgr = Self {
+            library,
+            storage,
+            assets: None,
+        };
         mgr.ensure_default_group()?;
         Ok(mgr)
     }
 
     /// Creates a `ComboManager` with the given library and storage (useful for testing).
     pub fn with_library(library: ComboLibrary, storage: ComboStorage) -> Self {
-        Self { library, storage }
+        Self {
+            library,
+            storage,
+            assets: None,
+        }
+    }
+
+    /// Deletes the images of image combos from `assets` once no combo uses
+    /// them any more.
+    pub fn with_assets(mut self, assets: AssetManager) -> Self {
+        self.assets = Some(assets);
+        self
     }
 
     // ── Combo operations ────────────────────────────────────────────
@@ -71,38 +93,40 @@ impl ComboManager {
         matching_mode: MatchingMode,
         case_sensitive: bool,
     ) -> Result<Combo, ComboManagerError> {
-        if !self.library.groups.iter().any(|g| g.id == group_id) {
-            return Err(ComboManagerError::GroupNotFound(group_id));
-        }
+        self.create_combo_from(
+            ComboBuilder::new()
+                .name(name)
+                .keyword(keyword)
+                .snippet(snippet)
+                .group_id(group_id)
+                .matching_mode(matching_mode)
+                .case_sensitive(case_sensitive),
+        )
+    }
 
-        let combo = ComboBuilder::new()
-            .name(name)
-            .keyword(keyword)
-            .snippet(snippet)
-            .group_id(group_id)
-            .matching_mode(matching_mode)
-            .case_sensitive(case_sensitive)
-            .build()?;
+    /// Creates a combo with every field set on `builder` and persists the
+    /// library once. The builder must set an existing group.
+    pub fn create_combo_from(&mut self, builder: ComboBuilder) -> Result<Combo, ComboManagerError> {
+        let combo = builder.build()?;
+        check_asset_name(&combo)?;
+        if !self.library.groups.iter().any(|g| g.id == combo.group_id) {
+            return Err(ComboManagerError::GroupNotFound(combo.group_id));
+        }
 
         self.library.add_combo(combo.clone());
         self.persist()?;
         Ok(combo)
     }
 
-    /// Updates an existing combo. Only provided fields are changed.
+    /// Updates an existing combo with the fields set in `patch`. Nothing
+    /// changes if the updated combo is invalid.
     pub fn update_combo(
         &mut self,
         id: Uuid,
-        name: Option<String>,
-        keyword: Option<String>,
-        snippet: Option<String>,
-        group_id: Option<Uuid>,
-        matching_mode: Option<MatchingMode>,
-        case_sensitive: Option<bool>,
-        enabled: Option<bool>,
+        patch: ComboPatch,
     ) -> Result<Combo, ComboManagerError> {
         // Check group exists before mutating
-        if let Some(gid) = group_id {
+        if let Some(gid) = patch.group_id {
             if !self.library.groups.iter().any(|g| g.id == gid) {
                 return Err(ComboManagerError::GroupNotFound(gid));
             }
@@ -115,42 +139,24 @@ impl ComboManager {
             .find(|c| c.id == id)
             .ok_or(ComboManagerError::ComboNotFound(id))?;
 
-        if let Some(name) = name {
-            combo.name = name;
-        }
-        if let Some(keyword) = keyword {
-            combo.keyword = keyword;
-        }
-        if let Some(snippet) = snippet {
-            combo.snippet = snippet;
-        }
-        if let Some(gid) = group_id {
-            combo.group_id = gid;
-        }
-        if let Some(mode) = matching_mode {
-            combo.matching_mode = mode;
-        }
-        if let Some(cs) = case_sensitive {
-            combo.case_sensitive = cs;
-        }
-        if let Some(en) = enabled {
-            combo.enabled = en;
-        }
-        combo.modified_at = Utc::now();
-
-        combo.validate()?;
+        let mut updated = combo.clone();
+        patch.apply_to(&mut updated);
+        updated.modified_at = Utc::now();
+        updated.validate()?;
+        check_asset_name(&updated)?;
 
-        let updated = combo.clone();
+        let previous = std::mem::replace(combo, updated.clone());
         self.persist()?;
+        self.release_asset(&previous);
         Ok(updated)
     }
 
     /// Deletes a combo by ID.
     pub fn delete_combo(&mut self, id: Uuid) -> Result<(), ComboManagerError> {
-        if !self.library.remove_combo(id) {
-            return Err(ComboManagerError::ComboNotFound(id));
-        }
+        let removed = self.get_combo(id).ok_or(ComboManagerError::ComboNotFound(id))?;
+        self.library.remove_combo(id);
         self.persist()?;
+        self.release_asset(&removed);
         Ok(())
     }
 
@@ -321,6 +327,59 @@ impl ComboManager {
         Ok(new_state)
     }
 
+    // ── Autocorrect dictionary operations ──────────────────────────
+
+    /// Returns all autocorrect dictionaries, including their entries.
+    pub fn get_all_dictionaries(&self) -> Vec<AutocorrectDictionary> {
+        self.library.dictionaries.clone()
+    }
+
+    /// Returns dictionary metadata without entries (cheap to send to the UI).
+    pub fn get_dictionary_summaries(&self) -> Vec<AutocorrectDictionarySummary> {
+        self.library
+            .dictionaries
+            .iter()
+            .map(AutocorrectDictionarySummary::from)
+            .collect()
+    }
+
+    /// Adds an autocorrect dictionary and persists the library.
+    pub fn add_dictionary(
+        &mut self,
+        dictionary: AutocorrectDictionary,
+    ) -> Result<AutocorrectDictionarySummary, ComboManagerError> {
+        let summary = AutocorrectDictionarySummary::from(&dictionary);
+        self.library.add_dictionary(dictionary);
+        self.persist()?;
+        Ok(summary)
+    }
+
+    /// Deletes an autocorrect dictionary by ID.
+    pub fn delete_dictionary(&mut self, id: Uuid) -> Result<(), ComboManagerError> {
+        if !self.library.remove_dictionary(id) {
+            return Err(ComboManagerError::DictionaryNotFound(id));
+        }
+        self.persist()?;
+        Ok(())
+    }
+
+    /// Toggles a dictionary's enabled state and returns the new state.
+    pub fn toggle_dictionary(&mut self, id: Uuid) -> Result<bool, ComboManagerError> {
+        let dictionary = self
+            .library
+            .dictionaries
+            .iter_mut()
+            .find(|d| d.id == id)
+            .ok_or(ComboManagerError::DictionaryNotFound(id))?;
+
+        dictionary.enabled = !dictionary.enabled;
+        dictionary.modified_at = Utc::now();
+        let new_state = dictionary.enabled;
+
+        self.persist()?;
+        Ok(new_state)
+    }
+
     // ── Utility ────────────────────────────────────────────────────
 
     /// Check if a keyword is unique across all combos.
@@ -352,11 +411,31 @@ impl ComboManager {
         Ok(())
     }
 
+    /// Deletes the image `combo` used, if it was an image combo and no
+    /// remaining combo uses the same image. Backups carry their own copy,
+    /// so restoring one brings the file back.
+    fn release_asset(&self, combo: &Combo) {
+        let assets = match &self.assets {
+            Some(assets) => assets,
+            None => return,
+        };
+        if combo.snippet_format != SnippetFormat::Image
+            || referenced_assets(&self.library.combos).contains(&combo.snippet.as_str())
+        {
+            return;
+        }
+        match assets.delete(&combo.snippet) {
+            Ok(()) | Err(AssetError::NotFound(_)) => {}
+            Err(e) => tracing::warn!("Failed to delete unused asset '{}': {}", combo.snippet, e),
+        }
+    }
+
     /// Shrinks internal collections to fit their contents, releasing unused
     /// allocated memory (MT-1110).
     pub fn compact(&mut self) {
         self.library.combos.shrink_to_fit();
         self.library.groups.shrink_to_fit();
+        self.library.dictionaries.shrink_to_fit();
         tracing::debug!(
             "ComboManager compacted: {} combos, {} groups",
             self.library.combos.len(),
@@ -407,6 +486,15 @@ impl ComboManager {
     }
 }
 
+/// Image snippets hold an asset name rather than text, so the name must
+/// stay inside the assets directory.
+fn check_asset_name(combo: &Combo) -> Result<(), ComboManagerError> {
+    if combo.snippet_format == SnippetFormat::Image {
+        validate_name(&combo.snippet)?;
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -487,19 +575,141 @@ mod tests {
         let updated = mgr
             .update_combo(
                 combo.id,
-                Some("Signature".into()),
-                None,
-                None,
-                None,
-                None,
-                None,
-                None,
+                ComboPatch {
+                    name: Some("Signature".into()),
+                    ..Default::default()
+                },
             )
             .unwrap();
         assert_eq!(updated.name, "Signature");
         assert_eq!(updated.keyword, "sig");
     }
 
+    #[test]
+    fn test_update_combo_propagate_case() {
+        let mut mgr = make_manager();
+        let gid = default_group_id(&mgr);
+        let combo = mgr
+            .create_combo(
+                "Sig".into(),
+                "sig".into(),
+                "Regards".into(),
+                gid,
+                MatchingMode::Strict,
+                false,
+            )
+            .unwrap();
+        assert!(!combo.propagate_case);
+        let updated = mgr
+            .update_combo(
+                combo.id,
+                ComboPatch {
+                    propagate_case: Some(true),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert!(updated.propagate_case);
+    }
+
+    #[test]
+    fn test_update_combo_snippet_format() {
+        let mut mgr = make_manager();
+        let gid = default_group_id(&mgr);
+        let combo = mgr
+            .create_combo(
+                "Sig".into(),
+                "sig".into(),
+                "**Regards**".into(),
+                gid,
+                MatchingMode::Strict,
+                false,
+            )
+            .unwrap();
+        assert_eq!(combo.snippet_format, SnippetFormat::Plain);
+        let updated = mgr
+            .update_combo(
+                combo.id,
+                ComboPatch {
+                    snippet_format: Some(SnippetFormat::Markdown),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert_eq!(updated.snippet_format, SnippetFormat::Markdown);
+    }
+
+    #[test]
+    fn test_update_combo_to_image_checks_stored_snippet() {
+        let mut mgr = make_manager();
+        let gid = default_group_id(&mgr);
+        let combo = mgr
+            .create_combo(
+                "Logo".into(),
+                "logo".into(),
+                "../secret.png".into(),
+                gid,
+                MatchingMode::Strict,
+                false,
+            )
+            .unwrap();
+        let result = mgr.update_combo(
+            combo.id,
+            ComboPatch {
+                snippet_format: Some(SnippetFormat::Image),
+                ..Default::default()
+            },
+        );
+        assert!(matches!(
+            result,
+            Err(ComboManagerError::Asset(AssetError::InvalidName(_)))
+        ));
+        // The combo is left as it was.
+        assert_eq!(
+            mgr.get_combo(combo.id).unwrap().snippet_format,
+            SnippetFormat::Plain
+        );
+
+        let updated = mgr
+            .update_combo(
+                combo.id,
+                ComboPatch {
+                    snippet: Some("logo.png".into()),
+                    snippet_format: Some(SnippetFormat::Image),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert_eq!(updated.snippet_format, SnippetFormat::Image);
+    }
+
+    #[test]
+    fn test_update_combo_exclude_from_history() {
+        let mut mgr = make_manager();
+        let gid = default_group_id(&mgr);
+        let combo = mgr
+            .create_combo(
+                "Account".into(),
+                "acct".into(),
+                "12345678".into(),
+                gid,
+                MatchingMode::Strict,
+                false,
+            )
+            .unwrap();
+        assert!(combo.exclude_from_history);
+        let updated = mgr
+            .update_combo(
+                combo.id,
+                ComboPatch {
+                    exclude_from_history: Some(false),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert!(!updated.exclude_from_history);
+    }
+
     #[test]
     fn test_delete_combo() {
         let mut mgr = make_manager();
@@ -518,6 +728,44 @@ mod tests {
         assert!(mgr.get_all_combos().is_empty());
     }
 
+    #[test]
+    fn test_delete_image_combo_deletes_unused_asset() {
+        let dir = tempdir().unwrap();
+        let assets = AssetManager::new(dir.path().to_path_buf());
+        let mut png = std::io::Cursor::new(Vec::new());
+        image::RgbaImage::new(1, 1)
+            .write_to(&mut png, image::ImageFormat::Png)
+            .unwrap();
+        let name = assets.store(png.get_ref()).unwrap();
+        let mut mgr = make_manager().with_assets(assets.clone());
+        let gid = default_group_id(&mgr);
+        let image_combo = |keyword: &str| {
+            ComboBuilder::new()
+                .keyword(keyword)
+                .group_id(gid)
+                .snippet(name.clone())
+                .snippet_format(SnippetFormat::Image)
+        };
+        let logo = mgr.create_combo_from(image_combo("logo")).unwrap();
+        let copy = mgr.create_combo_from(image_combo("logo2")).unwrap();
+
+        // Still used by the other combo.
+        mgr.delete_combo(logo.id).unwrap();
+        assert!(assets.read(&name).is_ok());
+
+        // Switching the last user to text releases it.
+        mgr.update_combo(
+            copy.id,
+            ComboPatch {
+                snippet: Some("Logo".into()),
+                snippet_format: Some(SnippetFormat::Plain),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert!(matches!(assets.read(&name), Err(AssetError::NotFound(_))));
+    }
+
     #[test]
     fn test_delete_combo_not_found() {
         let mut mgr = make_manager();
@@ -649,6 +897,28 @@ mod tests {
         assert!(!mgr.get_all_combos()[0].enabled);
     }
 
+    #[test]
+    fn test_dictionary_lifecycle() {
+        use crate::models::autocorrect::AutocorrectEntry;
+
+        let mut mgr = make_manager();
+        let dict = AutocorrectDictionary::new("Typos", vec![AutocorrectEntry::new("teh", "the")]);
+        let summary = mgr.add_dictionary(dict).unwrap();
+        assert_eq!(summary.entry_count, 1);
+        assert_eq!(mgr.get_dictionary_summaries(), vec![summary.clone()]);
+        assert_eq!(mgr.get_all_dictionaries()[0].entries[0].correction, "the");
+
+        assert!(!mgr.toggle_dictionary(summary.id).unwrap());
+        assert!(!mgr.get_all_dictionaries()[0].enabled);
+
+        mgr.delete_dictionary(summary.id).unwrap();
+        assert!(mgr.get_all_dictionaries().is_empty());
+        assert!(matches!(
+            mgr.delete_dictionary(summary.id),
+            Err(ComboManagerError::DictionaryNotFound(_))
+        ));
+    }
+
     #[test]
     fn test_check_keyword_uniqueness() {
         let mut mgr = make_manager();
@@ -736,6 +1006,31 @@ mod tests {
         assert!(after > before);
     }
 
+    #[test]
+    fn test_create_combo_from_builder() {
+        let mut mgr = make_manager();
+        let gid = default_group_id(&mgr);
+        let combo = mgr
+            .create_combo_from(
+                ComboBuilder::new()
+                    .keyword("sig")
+                    .snippet("regards")
+                    .group_id(gid)
+                    .propagate_case(true),
+            )
+            .unwrap();
+        assert!(combo.propagate_case);
+        assert_eq!(mgr.get_combo(combo.id), Some(combo));
+    }
+
+    #[test]
+    fn test_create_combo_from_builder_invalid_group() {
+        let mut mgr = make_manager();
+        let result = mgr.create_combo_from(ComboBuilder::new().keyword("sig").snippet("regards"));
+        assert!(matches!(result, Err(ComboManagerError::GroupNotFound(_))));
+        assert!(mgr.get_all_combos().is_empty());
+    }
+
     #[test]
     fn test_create_combo_invalid_group() {
         let mut mgr = make_manager();
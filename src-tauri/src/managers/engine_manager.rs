@@ -8,21 +8,41 @@
 //! - Clipboard manager
 //!
 //! It handles the full expansion pipeline: keystrokes → buffer → match → expand.
+//! Matches are detected on the hook thread; their output is sent by a single
+//! [`ExpansionWorker`], which holds input until each job is done.
 
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use thiserror::Error;
 
 use crate::managers::{
-    clipboard_manager::{ClipboardManager, ArboardProvider},
+    asset_manager::AssetManager,
+    clipboard_manager::{ArboardProvider, ClipboardManager, ClipboardProvider},
     expansion_pipeline::ExpansionPipeline,
-    input_manager::InputManager,
+    expansion_worker::{ExpansionWorker, JobBackend, JobHandle, EXPANSION_QUEUE_CAPACITY},
+    input_manager::{InputGate, InputManager, DEFAULT_MAX_BUFFER_WORDS},
+    keystroke_trace::TraceRecorder,
+    matching::MatchResult,
+    output_backend::{KeyChord, OutputBackend},
+    storage::get_logs_dir,
+    substitution::{
+        FocusGuardedBackend, LastExpansion, Snippet, SubstitutionEngine, SubstitutionError,
+        WindowFocusChecker,
+    },
+    timing_calibrator::{Insertion, TimingCalibrator},
 };
-use crate::models::{Combo, Preferences};
-use crate::models::preferences::PasteMethod;
-use crate::platform::keyboard_hook::{FocusDetector, KeyboardHook};
+use crate::models::{AutocorrectDictionary, Combo, Preferences};
+use crate::models::preferences::{PasteMethod, PasteRule, TimingProfile};
+use crate::platform::keyboard_hook::{FocusDetector, Key, KeyboardHook, WindowInfo};
 
 #[cfg(target_os = "linux")]
-use crate::platform::linux::{LinuxKeyboardHook, LinuxFocusDetector};
+use crate::platform::atspi::PasswordFieldWatcher;
+#[cfg(target_os = "linux")]
+use crate::platform::evdev::EvdevKeyboardHook;
+#[cfg(target_os = "linux")]
+use crate::platform::linux::{
+    detect_wayland_status, LinuxFocusDetector, LinuxKeyboardHook, WaylandStatus,
+};
 
 #[cfg(target_os = "macos")]
 use crate::platform::macos::{IOHIDKeyboardHook, MacOSFocusDetector};
@@ -62,12 +82,50 @@ pub enum EngineStatus {
 struct EngineInner {
     input_manager: InputManager,
     expansion_pipeline: ExpansionPipeline,
-    clipboard: ClipboardManager<ArboardProvider>,
+    clipboard: ClipboardManager<Box<dyn ClipboardProvider>>,
+    /// When set, used instead of the paste method's platform backend.
+    output_backend: Option<Box<dyn OutputBackend>>,
     focus_detector: Box<dyn FocusDetector>,
     status: EngineStatus,
     paste_method: PasteMethod,
+    /// Per-application overrides of `paste_method`, first match wins.
+    paste_rules: Vec<PasteRule>,
+    /// Most recent successful expansion and the window it was typed into,
+    /// kept for Backspace undo.
+    last_expansion: Option<(LastExpansion, WindowInfo)>,
+    undo_with_backspace: bool,
+    undo_window: Duration,
+    /// Set while the `record_keystroke_trace` debug preference is on.
+    trace_recorder: Option<Arc<TraceRecorder>>,
+    /// Per-application timing, applied to every job.
+    timing: TimingCalibrator,
+    /// Window class and insertion style of the expansion that just ran,
+    /// for the worker to calibrate once it has released input.
+    pending_calibration: Option<(String, Insertion)>,
+}
+
+/// Work queued for the expansion worker.
+enum EngineJob {
+    /// Replace the typed keyword with the snippet, in the window that had
+    /// focus when it matched.
+    Expand {
+        match_result: MatchResult,
+        target: WindowInfo,
+    },
+    /// Revert an expansion after Backspace, in the window it was typed
+    /// into.
+    Undo {
+        last: LastExpansion,
+        target: WindowInfo,
+    },
 }
 
+/// Receives all timing profiles whenever the engine has adjusted one.
+type TimingLearnedCallback = dyn Fn(Vec<TimingProfile>) + Send + Sync;
+
+/// Receives whether a password field has focus whenever that changes.
+type PasswordFieldCallback = dyn Fn(bool) + Send + Sync;
+
 /// Manages the text expansion engine lifecycle.
 ///
 /// This is the central coordinator that:
@@ -80,102 +138,401 @@ pub struct EngineManager {
     inner: Arc<Mutex<EngineInner>>,
     /// Callback to notify when a combo is used (for updating stats in storage).
     on_combo_used: Option<Arc<dyn Fn(uuid::Uuid) + Send + Sync>>,
+    /// Callback to notify when timing profiles were learned (for saving them).
+    on_timing_learned: Option<Arc<TimingLearnedCallback>>,
+    /// Callback to notify when a password field gains or loses focus (for
+    /// the tray). Shared with the password-field watcher, which outlives
+    /// registration.
+    on_password_field: Arc<Mutex<Option<Box<PasswordFieldCallback>>>>,
+    /// Sends expansion and undo output while the engine is started.
+    worker: Mutex<Option<ExpansionWorker<EngineJob>>>,
+    /// Sets the input buffer's word limit without the engine lock.
+    set_max_buffer_words: Box<dyn Fn(usize) + Send + Sync>,
+    /// Pauses input while a password field has focus; kept alive with the engine.
+    #[cfg(target_os = "linux")]
+    _password_fields: Option<PasswordFieldWatcher>,
 }
 
 use crate::managers::expansion_pipeline::ExpansionResult;
 
 impl EngineManager {
     /// Checks if there's a match in the buffer without performing expansion.
-    /// Returns the match result if found.
-    fn check_for_match(state: &mut EngineInner, buffer: &str) -> Option<crate::managers::matching::MatchResult> {
-        // Detect the currently focused application
-        let current_app = state
-            .focus_detector
-            .get_active_window_info()
-            .ok()
-            .map(|info| info.app_name);
-
-        let current_app_ref = current_app.as_deref();
+    /// Returns the match result if found, with the window that had focus,
+    /// which the expansion must still be typing into.
+    fn check_for_match(
+        state: &mut EngineInner,
+        buffer: &str,
+    ) -> Option<(MatchResult, WindowInfo)> {
+        // Detect the currently focused application (cached by the detector)
+        let window = state.focus_detector.get_active_window_info().ok();
+        let current_app = window.as_ref().map(|info| info.app_name.as_str());
 
         // Just check for match, don't perform expansion yet
-        state.expansion_pipeline.process_buffer(buffer, current_app_ref)
+        let match_result = state.expansion_pipeline.process_buffer(buffer, current_app)?;
+        Some((match_result, window.unwrap_or_default()))
     }
 
     /// Performs the expansion substitution using the provided match result.
-    /// This should be called AFTER pausing the input manager.
+    /// This should be called AFTER suppressing the input manager. Returns
+    /// the result, if it succeeded, and the keystrokes that were sent.
+    ///
+    /// Stops with [`SubstitutionError::FocusLost`] if `target` loses focus
+    /// part way through, or [`SubstitutionError::Cancelled`] if `job` is
+    /// cancelled.
     fn perform_expansion(
         state: &mut EngineInner,
-        match_result: crate::managers::matching::MatchResult,
-    ) -> Option<ExpansionResult> {
+        match_result: MatchResult,
+        target: WindowInfo,
+        job: &JobHandle,
+    ) -> (Option<ExpansionResult>, Vec<Key>) {
+        state.pending_calibration = None;
         // Perform the actual substitution based on paste method
-        let substitution_result = match state.paste_method {
-            PasteMethod::Clipboard => {
-                state.expansion_pipeline.substitution().substitute_via_clipboard(
-                    match_result.keyword_len,
-                    &match_result.snippet,
-                    &mut state.clipboard,
-                )
-            }
-            PasteMethod::SimulateKeystrokes => {
-                state.expansion_pipeline.substitution().substitute_via_keystrokes(
-                    match_result.keyword_len,
-                    &match_result.snippet,
-                )
-            }
-            PasteMethod::XdotoolType => {
-                state.expansion_pipeline.substitution().substitute_via_xdotool(
-                    match_result.keyword_len,
-                    &match_result.snippet,
-                )
+        let (method, paste_chord) = Self::paste_settings(state, &target);
+        let image = match state.expansion_pipeline.load_image(&match_result) {
+            Ok(image) => image,
+            Err(e) => {
+                tracing::error!("Substitution failed: {}", e);
+                state.last_expansion = None;
+                return (None, Vec::new());
             }
         };
+        let echo_chord = paste_chord
+            .clone()
+            .unwrap_or_else(|| KeyChord::paste(state.expansion_pipeline.substitution().config()));
+        let substitution = Self::substitution_for(state, &target.app_name);
+        let window_class = target.app_name.clone();
+        let snippet = Snippet {
+            text: &match_result.snippet,
+            html: match_result.html.as_deref(),
+            image: image.as_ref(),
+            exclude_from_history: match_result.exclude_from_history,
+        };
+        let insertion = if SubstitutionEngine::uses_clipboard(method, &snippet) {
+            Insertion::Clipboard
+        } else {
+            Insertion::Keystrokes
+        };
+        let pasted_html = insertion == Insertion::Clipboard && snippet.html.is_some();
+        let outcome = Self::with_output_backend(state, &substitution, method, |state, backend| {
+            let mut backend = JobBackend::new(backend, job);
+            let focus = WindowFocusChecker::new(state.focus_detector.as_ref(), target.clone());
+            let result = substitution.substitute(
+                &mut FocusGuardedBackend::new(&mut backend, focus),
+                method,
+                paste_chord.as_ref(),
+                match_result.keyword_len,
+                snippet,
+                &mut state.clipboard,
+            );
+            Ok((result, backend.echo(&echo_chord)))
+        });
+        let (substitution_result, echo) = match outcome {
+            Ok(outcome) => outcome,
+            Err(e) => (Err(e), Vec::new()),
+        };
 
         match substitution_result {
-            Ok(()) => Some(ExpansionResult {
-                combo_id: match_result.combo_id,
-                keyword: match_result.keyword,
-                snippet: match_result.snippet,
-            }),
+            Ok(()) => {
+                // Snippets are inserted verbatim, so the cursor ends up after
+                // them. Pasted HTML renders to an unknown number of
+                // characters (list markers and link targets in the plain
+                // text never reach the document), so it cannot be undone.
+                state.last_expansion = if pasted_html {
+                    None
+                } else {
+                    let last = LastExpansion::new(match_result.typed, &match_result.snippet, None);
+                    Some((last, target))
+                };
+                state.pending_calibration = Some((window_class, insertion));
+                let result = ExpansionResult {
+                    combo_id: match_result.combo_id,
+                    keyword: match_result.keyword,
+                    snippet: match_result.snippet,
+                };
+                (Some(result), echo)
+            }
             Err(e) => {
                 tracing::error!("Substitution failed: {}", e);
-                None
+                state.last_expansion = None;
+                (None, echo)
             }
         }
     }
+
+    /// Reverts `last` in `target`, the window it was typed into, and returns
+    /// the keystrokes that were sent. Nothing is sent once `target` has
+    /// lost focus.
+    fn perform_undo(
+        state: &mut EngineInner,
+        last: &LastExpansion,
+        target: WindowInfo,
+        job: &JobHandle,
+    ) -> Vec<Key> {
+        tracing::info!("Undoing expansion of '{}'", last.typed);
+        let (method, paste_chord) = Self::paste_settings(state, &target);
+        let echo_chord = paste_chord
+            .unwrap_or_else(|| KeyChord::paste(state.expansion_pipeline.substitution().config()));
+        let substitution = Self::substitution_for(state, &target.app_name);
+        let outcome = Self::with_output_backend(state, &substitution, method, |state, backend| {
+            let mut backend = JobBackend::new(backend, job);
+            let focus = WindowFocusChecker::new(state.focus_detector.as_ref(), target);
+            let result =
+                substitution.undo_expansion(&mut FocusGuardedBackend::new(&mut backend, focus), last);
+            Ok((result, backend.echo(&echo_chord)))
+        });
+        match outcome {
+            Ok((Ok(()), echo)) => echo,
+            Ok((Err(e), echo)) => {
+                tracing::error!("Undo failed: {}", e);
+                echo
+            }
+            Err(e) => {
+                tracing::error!("Undo failed: {}", e);
+                Vec::new()
+            }
+        }
+    }
+
+    /// Starts the worker that sends expansion and undo output, one job at a
+    /// time. Each job waits for the hook callback that queued it to return,
+    /// runs with the engine locked, then hands input back through `gate`,
+    /// replaying whatever was typed meanwhile. How far the hook was behind
+    /// the job's output at that point calibrates the window's timing.
+    fn spawn_worker(
+        inner: Arc<Mutex<EngineInner>>,
+        gate: InputGate,
+        combo_used: Option<Arc<dyn Fn(uuid::Uuid) + Send + Sync>>,
+        timing_learned: Option<Arc<TimingLearnedCallback>>,
+    ) -> ExpansionWorker<EngineJob> {
+        ExpansionWorker::spawn(EXPANSION_QUEUE_CAPACITY, move |job, handle| {
+            // Let the callback return and unblock the IOHIDManager's CFRunLoop
+            // first: CGEventPost with modifier keys requires the event
+            // pipeline to be unblocked for proper delivery.
+            gate.wait_for_hook();
+
+            let (expanded, echo) = match inner.lock() {
+                Ok(mut state) => Self::run_job(&mut state, job, handle),
+                Err(_) => (None, Vec::new()),
+            };
+            if let (Some(combo_id), Some(cb)) = (expanded, combo_used.as_ref()) {
+                cb(combo_id);
+            }
+            if let Some(unheard) = gate.release(echo) {
+                let learned = match inner.lock() {
+                    Ok(mut state) => Self::calibrate(&mut state, unheard),
+                    Err(_) => None,
+                };
+                if let (Some(profiles), Some(cb)) = (learned, timing_learned.as_ref()) {
+                    cb(profiles);
+                }
+            }
+        })
+    }
+
+    /// Feeds the lag of the expansion that just ran to the timing
+    /// calibrator. Returns the profiles if they changed.
+    fn calibrate(state: &mut EngineInner, unheard: usize) -> Option<Vec<TimingProfile>> {
+        let (window_class, insertion) = state.pending_calibration.take()?;
+        let base = state.expansion_pipeline.substitution().config();
+        if state.timing.observe(&window_class, insertion, unheard, base) {
+            Some(state.timing.profiles().to_vec())
+        } else {
+            None
+        }
+    }
+
+    /// The substitution engine with the timing of the profile for
+    /// `window_class`, if any.
+    fn substitution_for(state: &EngineInner, window_class: &str) -> SubstitutionEngine {
+        let base = state.expansion_pipeline.substitution().config();
+        SubstitutionEngine::new(state.timing.config_for(window_class, base))
+    }
+
+    /// Runs one worker job. Returns the combo that was expanded, if any, and
+    /// the keystrokes that were sent.
+    fn run_job(
+        state: &mut EngineInner,
+        job: EngineJob,
+        handle: &JobHandle,
+    ) -> (Option<uuid::Uuid>, Vec<Key>) {
+        if handle.is_cancelled() {
+            tracing::debug!("Skipping cancelled expansion job");
+            return (None, Vec::new());
+        }
+        match job {
+            EngineJob::Expand {
+                match_result,
+                target,
+            } => match Self::perform_expansion(state, match_result, target, handle) {
+                (Some(expansion_result), echo) => {
+                    tracing::info!(
+                        "Expanded combo: '{}' → {} chars",
+                        expansion_result.keyword,
+                        expansion_result.snippet.len()
+                    );
+                    (Some(expansion_result.combo_id), echo)
+                }
+                (None, echo) => (None, echo),
+            },
+            EngineJob::Undo { last, target } => {
+                (None, Self::perform_undo(state, &last, target, handle))
+            }
+        }
+    }
+
+    /// The paste method and paste shortcut for `info`, the window a job
+    /// types into: those of the first paste rule matching its class or
+    /// title, else the global paste method with the default shortcut.
+    fn paste_settings(state: &EngineInner, info: &WindowInfo) -> (PasteMethod, Option<KeyChord>) {
+        let rule = match state
+            .paste_rules
+            .iter()
+            .find(|rule| rule.matches(&info.app_name, &info.title))
+        {
+            Some(rule) => rule,
+            None => return (state.paste_method, None),
+        };
+        tracing::debug!(
+            "Paste rule '{}' matched {}: {:?}",
+            rule.pattern,
+            info.app_name,
+            rule.paste_method
+        );
+        let chord = if rule.paste_chord.is_empty() {
+            None
+        } else {
+            match rule.paste_chord.parse::<KeyChord>() {
+                Ok(chord) => Some(chord),
+                Err(e) => {
+                    tracing::warn!("{}; using the default paste shortcut", e);
+                    None
+                }
+            }
+        };
+        (rule.paste_method, chord)
+    }
+
+    /// Runs `f` with the backend set by [`Self::set_output_backend`], or a
+    /// fresh platform backend for `method` with the timing of `substitution`.
+    fn with_output_backend<T>(
+        state: &mut EngineInner,
+        substitution: &SubstitutionEngine,
+        method: PasteMethod,
+        f: impl FnOnce(&mut EngineInner, &mut dyn OutputBackend) -> Result<T, SubstitutionError>,
+    ) -> Result<T, SubstitutionError> {
+        match state.output_backend.take() {
+            Some(mut backend) => {
+                let result = f(state, backend.as_mut());
+                state.output_backend = Some(backend);
+                result
+            }
+            None => {
+                let mut backend = substitution.backend_for(method)?;
+                f(state, backend.as_mut())
+            }
+        }
+    }
+
+    /// Takes the last expansion and its window if Backspace undo is enabled
+    /// and the expansion is still within the undo window.
+    fn take_undoable_expansion(state: &mut EngineInner) -> Option<(LastExpansion, WindowInfo)> {
+        let (last, target) = state.last_expansion.take()?;
+        if state.undo_with_backspace && last.is_within(state.undo_window) {
+            Some((last, target))
+        } else {
+            None
+        }
+    }
 }
 
 impl EngineManager {
     /// Creates a new EngineManager with default configuration.
     pub fn new() -> Self {
-        let mut input_manager = InputManager::new();
-        let expansion_pipeline = ExpansionPipeline::with_defaults();
-        let clipboard = ClipboardManager::new_system()
-            .expect("Failed to initialize clipboard manager");
+        let clipboard = ArboardProvider::new().expect("Failed to initialize clipboard manager");
 
         // Attach platform-specific keyboard hook and focus detector
-        let hook: Box<dyn KeyboardHook> = Self::create_keyboard_hook();
-        let focus_detector: Box<dyn FocusDetector> = Self::create_focus_detector();
+        #[cfg_attr(not(target_os = "linux"), allow(unused_mut))]
+        let mut engine = Self::with_platform(
+            Self::create_keyboard_hook(),
+            Self::create_focus_detector(),
+            Box::new(clipboard),
+        );
+
+        // Stop buffering while a password field has focus, in any app.
+        #[cfg(target_os = "linux")]
+        {
+            let handler = engine.password_field_handler();
+            engine._password_fields = Some(PasswordFieldWatcher::start(Box::new(handler)));
+        }
+
+        engine
+    }
+
+    /// Creates an engine on the given platform parts, e.g. the mocks used
+    /// by [`crate::managers::keystroke_trace::ReplayHarness`].
+    pub fn with_platform(
+        hook: Box<dyn KeyboardHook>,
+        mut focus_detector: Box<dyn FocusDetector>,
+        clipboard: Box<dyn ClipboardProvider>,
+    ) -> Self {
+        let mut input_manager = InputManager::new();
+        let expansion_pipeline = ExpansionPipeline::with_defaults();
         input_manager.set_keyboard_hook(hook);
 
+        // Reset the buffer when the detector reports a focus switch.
+        focus_detector.on_focus_change(Box::new(input_manager.focus_change_handler()));
+        let set_max_buffer_words = Box::new(input_manager.max_buffer_words_setter());
+
         let inner = EngineInner {
             input_manager,
             expansion_pipeline,
-            clipboard,
+            clipboard: ClipboardManager::new(clipboard),
+            output_backend: None,
             focus_detector,
             status: EngineStatus::Stopped,
             paste_method: PasteMethod::default(),
+            paste_rules: Vec::new(),
+            last_expansion: None,
+            undo_with_backspace: false,
+            undo_window: Duration::from_millis(Preferences::default().undo_window_ms),
+            trace_recorder: None,
+            timing: TimingCalibrator::default(),
+            pending_calibration: None,
         };
 
         Self {
             inner: Arc::new(Mutex::new(inner)),
             on_combo_used: None,
+            on_timing_learned: None,
+            on_password_field: Arc::new(Mutex::new(None)),
+            worker: Mutex::new(None),
+            set_max_buffer_words,
+            #[cfg(target_os = "linux")]
+            _password_fields: None,
         }
     }
 
+    /// Sends all expansion and undo output to `backend` instead of the
+    /// platform backend for the paste method, e.g. a
+    /// [`RecordingBackend`](crate::managers::output_backend::RecordingBackend)
+    /// in tests.
+    pub fn set_output_backend(&self, backend: Box<dyn OutputBackend>) -> Result<(), EngineError> {
+        let mut inner = self.inner.lock().map_err(|_| EngineError::LockError)?;
+        inner.output_backend = Some(backend);
+        Ok(())
+    }
+
     /// Creates the platform-specific keyboard hook.
+    ///
+    /// On Linux, native Wayland sessions without XWayland read keyboards
+    /// through evdev since there is no X server for rdev to listen on.
     fn create_keyboard_hook() -> Box<dyn KeyboardHook> {
         #[cfg(target_os = "linux")]
         {
+            if detect_wayland_status() == WaylandStatus::NativePortal {
+                tracing::info!("Native Wayland session without XWayland; using evdev keyboard hook");
+                return Box::new(EvdevKeyboardHook::new());
+            }
             Box::new(LinuxKeyboardHook::new())
         }
 
@@ -235,14 +592,89 @@ impl EngineManager {
         self.on_combo_used = Some(Arc::new(callback));
     }
 
+    /// Registers a callback to be invoked with all timing profiles whenever
+    /// the engine has learned or adjusted one.
+    pub fn on_timing_learned<F>(&mut self, callback: F)
+    where
+        F: Fn(Vec<TimingProfile>) + Send + Sync + 'static,
+    {
+        self.on_timing_learned = Some(Arc::new(callback));
+    }
+
+    /// Registers a callback to be invoked whenever a password field gains
+    /// or loses focus. It is called once straight away with the current
+    /// answer, so it never misses a change that happened before.
+    pub fn on_password_field<F>(&mut self, callback: F)
+    where
+        F: Fn(bool) + Send + Sync + 'static,
+    {
+        // The slot stays locked until the first call is made, so a change
+        // racing with registration is always reported after it.
+        let mut slot = self.on_password_field.lock().unwrap_or_else(|e| e.into_inner());
+        callback(self.in_password_field().unwrap_or(false));
+        *slot = Some(Box::new(callback));
+    }
+
+    /// Returns a callback that pauses input while a password field has focus
+    /// and passes each change on to the [`EngineManager::on_password_field`]
+    /// callback.
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    fn password_field_handler(&self) -> impl Fn(bool) + Send + Sync + 'static {
+        let pause_input = self
+            .inner
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .input_manager
+            .password_field_handler();
+        let listener = self.on_password_field.clone();
+        move |active| {
+            pause_input(active);
+            if let Some(ref callback) = *listener.lock().unwrap_or_else(|e| e.into_inner()) {
+                callback(active);
+            }
+        }
+    }
+
     /// Loads combos into the expansion engine.
     pub fn load_combos(&self, combos: &[Combo]) -> Result<(), EngineError> {
-        let mut inner = self.inner.lock().map_err(|_| EngineError::LockError)?;
-        inner.expansion_pipeline.load_combos(combos);
+        self.inner
+            .lock()
+            .map_err(|_| EngineError::LockError)?
+            .expansion_pipeline
+            .load_combos(combos);
+
+        // Keep enough words in the input buffer for the longest phrase
+        // keyword. The input lock is taken with the engine lock released,
+        // since the hook thread takes them in the opposite order.
+        let longest_phrase = combos
+            .iter()
+            .filter(|c| c.enabled)
+            .map(|c| c.keyword.split_whitespace().count())
+            .max()
+            .unwrap_or(0);
+        (self.set_max_buffer_words)(longest_phrase.max(DEFAULT_MAX_BUFFER_WORDS));
         tracing::info!("Loaded {} combos into expansion engine", combos.len());
         Ok(())
     }
 
+    /// Loads autocorrect dictionaries into the engine.
+    pub fn load_dictionaries(&self, dictionaries: &[AutocorrectDictionary]) -> Result<(), EngineError> {
+        let mut inner = self.inner.lock().map_err(|_| EngineError::LockError)?;
+        inner.expansion_pipeline.load_dictionaries(dictionaries);
+        tracing::info!(
+            "Loaded {} autocorrect dictionaries into expansion engine",
+            dictionaries.len()
+        );
+        Ok(())
+    }
+
+    /// Sets the asset store that image snippets are loaded from.
+    pub fn set_assets(&self, assets: AssetManager) -> Result<(), EngineError> {
+        let mut inner = self.inner.lock().map_err(|_| EngineError::LockError)?;
+        inner.expansion_pipeline.set_assets(assets);
+        Ok(())
+    }
+
     /// Applies preferences to the expansion engine.
     pub fn apply_preferences(&self, prefs: &Preferences) -> Result<(), EngineError> {
         let mut inner = self.inner.lock().map_err(|_| EngineError::LockError)?;
@@ -259,11 +691,53 @@ impl EngineManager {
         inner.expansion_pipeline.apply_preferences(&prefs_with_self_exclusion);
 
         inner.paste_method = prefs.paste_method;
+        inner.paste_rules = prefs.paste_rules.clone();
+        inner.undo_with_backspace = prefs.undo_with_backspace;
+        inner.undo_window = Duration::from_millis(prefs.undo_window_ms);
+        inner
+            .timing
+            .set_profiles(prefs.timing_profiles.clone(), prefs.adaptive_timing);
+        Self::apply_trace_recording(&mut inner, prefs.record_keystroke_trace);
         tracing::info!("Applied preferences to expansion engine (paste_method: {:?}, excluded_apps: {:?})",
             prefs.paste_method, prefs_with_self_exclusion.excluded_apps);
         Ok(())
     }
 
+    /// Starts or stops recording a keystroke trace into the logs directory.
+    fn apply_trace_recording(inner: &mut EngineInner, enabled: bool) {
+        if enabled == inner.trace_recorder.is_some() {
+            return;
+        }
+        if !enabled {
+            inner.trace_recorder = None;
+            inner.input_manager.set_trace_recorder(None);
+            tracing::info!("Stopped recording keystroke trace");
+            return;
+        }
+        let file_name = format!(
+            "keystroke-trace-{}.jsonl",
+            chrono::Local::now().format("%Y%m%d-%H%M%S")
+        );
+        let recorder = get_logs_dir()
+            .map_err(|e| e.to_string())
+            .and_then(|dir| {
+                std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+                let path = dir.join(file_name);
+                TraceRecorder::create(&path)
+                    .map(|recorder| (recorder, path))
+                    .map_err(|e| e.to_string())
+            });
+        match recorder {
+            Ok((recorder, path)) => {
+                tracing::warn!("Recording every keystroke to {}", path.display());
+                let recorder = Arc::new(recorder);
+                inner.input_manager.set_trace_recorder(Some(recorder.clone()));
+                inner.trace_recorder = Some(recorder);
+            }
+            Err(e) => tracing::error!("Failed to start keystroke trace: {}", e),
+        }
+    }
+
     /// Starts the expansion engine.
     ///
     /// This begins listening for keystrokes and will automatically expand
@@ -275,10 +749,17 @@ impl EngineManager {
             return Err(EngineError::AlreadyRunning);
         }
 
+        let worker = Self::spawn_worker(
+            self.inner.clone(),
+            inner.input_manager.gate(),
+            self.on_combo_used.clone(),
+            self.on_timing_learned.clone(),
+        );
+
         // Set up the buffer change callback to trigger expansion pipeline
         // We need to use the shared inner state for this
         let inner_clone = self.inner.clone();
-        let combo_used_cb = self.on_combo_used.clone();
+        let queue = worker.queue();
 
         inner.input_manager.on_buffer_change(move |buffer| {
             // Lock the inner state to access pipeline and clipboard.
@@ -286,14 +767,10 @@ impl EngineManager {
             // notify_change(), which means the InputManagerInner mutex is ALREADY
             // held. We MUST NOT call any InputManager method that locks that mutex
             // (pause, resume, clear_buffer) or we'll deadlock.
-            // Instead, use the lock-free suppress/unsuppress/request_buffer_clear.
+            // Instead, use the lock-free suppress and the worker queue.
             if let Ok(mut state) = inner_clone.lock() {
                 // PHASE 1: Check for match (input is NOT suppressed)
-                if let Some(match_result) = Self::check_for_match(&mut state, buffer) {
-                    // PHASE 2: Match found! Suppress input via lock-free AtomicBool.
-                    // This prevents the hook from capturing keystrokes during expansion.
-                    state.input_manager.suppress();
-
+                if let Some((match_result, target)) = Self::check_for_match(&mut state, buffer) {
                     tracing::info!(
                         "Expanding combo via {:?}: keyword='{}', snippet_len={}",
                         state.paste_method,
@@ -301,40 +778,38 @@ impl EngineManager {
                         match_result.snippet.len()
                     );
 
-                    // Drop the lock before spawning to avoid holding it across threads
-                    drop(state);
-
-                    // PHASE 3: Perform expansion on a background thread to unblock
-                    // the IOHIDManager's CFRunLoop. CGEventPost with modifier keys
-                    // requires the event pipeline to be unblocked for proper delivery.
-                    let inner_for_expansion = inner_clone.clone();
-                    let combo_used_cb_clone = combo_used_cb.clone();
-                    std::thread::spawn(move || {
-                        // Small delay to let the callback return and unblock the
-                        // CFRunLoop before posting CGEvents
-                        std::thread::sleep(std::time::Duration::from_millis(5));
-
-                        if let Ok(mut state) = inner_for_expansion.lock() {
-                            if let Some(expansion_result) = Self::perform_expansion(&mut state, match_result) {
-                                tracing::info!(
-                                    "Expanded combo: '{}' → {} chars",
-                                    expansion_result.keyword,
-                                    expansion_result.snippet.len()
-                                );
-
-                                if let Some(ref cb) = combo_used_cb_clone {
-                                    cb(expansion_result.combo_id);
-                                }
-                            }
-
-                            // PHASE 4: Unsuppress AFTER a delay on a background thread.
-                            // Queued events from the hook are discarded while suppressed,
-                            // preventing re-triggering if the snippet contains the keyword.
-                            state.input_manager.unsuppress_after(
-                                std::time::Duration::from_millis(500)
-                            );
-                        }
-                    });
+                    // PHASE 2: Queue the expansion for the worker, then hold
+                    // input until the worker releases it. The job cannot start
+                    // before this callback returns, so nothing slips through.
+                    match queue.submit(EngineJob::Expand { match_result, target }) {
+                        Ok(_) => state.input_manager.suppress(),
+                        Err(e) => tracing::warn!("Dropping expansion: {}", e),
+                    }
+                }
+            }
+        });
+
+        // The first Backspace after an expansion undoes it. Like the buffer
+        // callback above, this runs with the InputManagerInner mutex held.
+        let inner_for_undo = self.inner.clone();
+        let undo_queue = worker.queue();
+        inner.input_manager.on_undo_backspace(move || {
+            let mut state = match inner_for_undo.lock() {
+                Ok(state) => state,
+                Err(_) => return false,
+            };
+            let (last, target) = match Self::take_undoable_expansion(&mut state) {
+                Some(undoable) => undoable,
+                None => return false,
+            };
+            match undo_queue.submit(EngineJob::Undo { last, target }) {
+                Ok(_) => {
+                    state.input_manager.suppress();
+                    true
+                }
+                Err(e) => {
+                    tracing::warn!("Dropping undo: {}", e);
+                    false
                 }
             }
         });
@@ -342,6 +817,8 @@ impl EngineManager {
         // Start the keyboard hook
         inner.input_manager.start()?;
         inner.status = EngineStatus::Running;
+        drop(inner);
+        *self.worker.lock().map_err(|_| EngineError::LockError)? = Some(worker);
 
         tracing::info!("Expansion engine started");
         Ok(())
@@ -349,6 +826,9 @@ impl EngineManager {
 
     /// Stops the expansion engine.
     pub fn stop(&self) -> Result<(), EngineError> {
+        // A running job holds the engine lock until it finishes, so cancel
+        // it first for it to stop at its next output operation.
+        self.cancel_expansions()?;
         let mut inner = self.inner.lock().map_err(|_| EngineError::LockError)?;
 
         if inner.status == EngineStatus::Stopped {
@@ -357,13 +837,52 @@ impl EngineManager {
 
         inner.input_manager.stop()?;
         inner.status = EngineStatus::Stopped;
+        drop(inner);
+        self.stop_worker()?;
 
         tracing::info!("Expansion engine stopped");
         Ok(())
     }
 
+    /// Cancels the running expansion and any queued behind it.
+    pub fn cancel_expansions(&self) -> Result<(), EngineError> {
+        let worker = self.worker.lock().map_err(|_| EngineError::LockError)?;
+        if let Some(ref worker) = *worker {
+            worker.cancel_all();
+        }
+        Ok(())
+    }
+
+    /// The expansion or undo that is running, if any.
+    pub(crate) fn running_expansion(&self) -> Result<Option<JobHandle>, EngineError> {
+        let worker = self.worker.lock().map_err(|_| EngineError::LockError)?;
+        Ok(worker.as_ref().and_then(ExpansionWorker::running))
+    }
+
+    /// Blocks until no expansion or undo is running or queued, or `timeout`
+    /// elapses. Returns whether the engine went idle; a stopped engine is
+    /// always idle.
+    pub fn wait_until_idle(&self, timeout: Duration) -> Result<bool, EngineError> {
+        let queue = match *self.worker.lock().map_err(|_| EngineError::LockError)? {
+            Some(ref worker) => worker.queue(),
+            None => return Ok(true),
+        };
+        Ok(queue.wait_idle(timeout))
+    }
+
+    /// Cancels all jobs and waits for the worker thread to exit. Must be
+    /// called without the engine lock, which a running job needs to finish.
+    fn stop_worker(&self) -> Result<(), EngineError> {
+        let worker = self.worker.lock().map_err(|_| EngineError::LockError)?.take();
+        drop(worker);
+        Ok(())
+    }
+
     /// Pauses the expansion engine (hook keeps running but expansions don't fire).
     pub fn pause(&self) -> Result<(), EngineError> {
+        // Cancel before waiting for the lock a running job holds, as in
+        // `stop()`; jobs queued meanwhile are cancelled once paused.
+        self.cancel_expansions()?;
         let mut inner = self.inner.lock().map_err(|_| EngineError::LockError)?;
 
         if inner.status != EngineStatus::Running {
@@ -372,6 +891,8 @@ impl EngineManager {
 
         inner.input_manager.pause();
         inner.status = EngineStatus::Paused;
+        drop(inner);
+        self.cancel_expansions()?;
 
         tracing::info!("Expansion engine paused");
         Ok(())
@@ -398,8 +919,16 @@ impl EngineManager {
         Ok(inner.status)
     }
 
+    /// Returns `true` while expansion is paused because a password field
+    /// has focus.
+    pub fn in_password_field(&self) -> Result<bool, EngineError> {
+        let inner = self.inner.lock().map_err(|_| EngineError::LockError)?;
+        Ok(inner.input_manager.in_password_field())
+    }
+
     /// Restarts the engine (stop + start with fresh hook).
     pub fn restart(&self) -> Result<(), EngineError> {
+        self.cancel_expansions()?;
         let mut inner = self.inner.lock().map_err(|_| EngineError::LockError)?;
 
         if inner.status != EngineStatus::Stopped {
@@ -411,6 +940,7 @@ impl EngineManager {
         Self::replace_keyboard_hook(&mut inner);
 
         drop(inner); // Release lock before calling start() which also locks
+        self.stop_worker()?;
         self.start()?;
         Ok(())
     }
@@ -548,6 +1078,76 @@ mod tests {
         // 7. Buffer remains empty (xdotool output not captured because we were paused)
     }
 
+    #[test]
+    fn test_paste_settings_use_the_job_window() {
+        use crate::managers::keystroke_trace::{ReplayHarness, TraceEntry, TraceEvent};
+        use crate::models::preferences::PasteRuleField;
+
+        let prefs = Preferences {
+            paste_method: PasteMethod::Clipboard,
+            paste_rules: vec![PasteRule {
+                field: PasteRuleField::WindowTitle,
+                pattern: "ssh".into(),
+                paste_method: PasteMethod::SimulateKeystrokes,
+                paste_chord: "Ctrl+Shift+V".into(),
+            }],
+            ..Default::default()
+        };
+        let harness = ReplayHarness::new(&[], &prefs);
+        let window = |title: &str| WindowInfo {
+            title: title.into(),
+            app_name: "kitty".into(),
+            process_id: Some(7),
+        };
+        // The terminal retitles itself between the match and the job.
+        harness.replay(&[TraceEntry {
+            at_ms: 0,
+            event: TraceEvent::Focus {
+                window: window("~/src"),
+            },
+        }]);
+
+        let inner = harness.engine().inner.lock().unwrap();
+        let (method, chord) = EngineManager::paste_settings(&inner, &window("ssh build-box"));
+        assert_eq!(method, PasteMethod::SimulateKeystrokes);
+        assert_eq!(chord, Some("Ctrl+Shift+V".parse().unwrap()));
+        let (method, chord) = EngineManager::paste_settings(&inner, &window("~/src"));
+        assert_eq!(method, PasteMethod::Clipboard);
+        assert_eq!(chord, None);
+    }
+
+    #[test]
+    fn test_password_field_changes_reach_callback() {
+        use crate::managers::clipboard_manager::ClipboardError;
+        use crate::platform::mock::{MockFocusDetector, MockKeyboardHook};
+
+        struct NoClipboard;
+        impl ClipboardProvider for NoClipboard {
+            fn read_text(&mut self) -> Result<String, ClipboardError> {
+                Ok(String::new())
+            }
+            fn write_text(&mut self, _text: &str) -> Result<(), ClipboardError> {
+                Ok(())
+            }
+        }
+
+        let mut engine = EngineManager::with_platform(
+            Box::new(MockKeyboardHook::new()),
+            Box::new(MockFocusDetector::new()),
+            Box::new(NoClipboard),
+        );
+        let handler = engine.password_field_handler();
+        // A change before registration is reported when it happens.
+        handler(true);
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let recorded = seen.clone();
+        engine.on_password_field(move |focused| recorded.lock().unwrap().push(focused));
+        handler(false);
+
+        assert_eq!(*seen.lock().unwrap(), vec![true, false]);
+        assert!(!engine.in_password_field().unwrap());
+    }
+
     // Note: Full integration tests require a display server and are
     // better suited for manual testing or CI with Xvfb.
 }
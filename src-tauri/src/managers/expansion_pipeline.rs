@@ -8,10 +8,12 @@ use chrono::Utc;
 use uuid::Uuid;
 use thiserror::Error;
 
-use crate::models::{Combo, Preferences};
-use crate::managers::clipboard_manager::{ClipboardManager, ClipboardProvider};
+use crate::models::{AutocorrectDictionary, Combo, Preferences};
+use crate::managers::asset_manager::AssetManager;
+use crate::managers::clipboard_manager::{ClipboardFormat, ClipboardManager, ClipboardProvider};
 use crate::managers::matching::{MatchResult, MatcherEngine};
-use crate::managers::substitution::{SubstitutionEngine, SubstitutionError};
+use crate::managers::output_backend::OutputBackend;
+use crate::managers::substitution::{Snippet, SubstitutionEngine, SubstitutionError};
 
 /// Errors arising from the expansion pipeline.
 #[derive(Debug, Error)]
@@ -40,6 +42,8 @@ pub struct ExpansionResult {
 pub struct ExpansionPipeline {
     matcher: MatcherEngine,
     substitution: SubstitutionEngine,
+    /// Where image snippets are loaded from.
+    assets: Option<AssetManager>,
     /// Whether sound feedback is enabled (stub for future implementation).
     play_sound: bool,
 }
@@ -50,6 +54,7 @@ impl ExpansionPipeline {
         Self {
             matcher,
             substitution,
+            assets: None,
             play_sound: false,
         }
     }
@@ -59,6 +64,7 @@ impl ExpansionPipeline {
         Self {
             matcher: MatcherEngine::new(),
             substitution: SubstitutionEngine::with_defaults(),
+            assets: None,
             play_sound: false,
         }
     }
@@ -88,6 +94,11 @@ impl ExpansionPipeline {
         self.matcher.load_combos(combos);
     }
 
+    /// Loads autocorrect dictionaries into the matcher engine.
+    pub fn load_dictionaries(&mut self, dictionaries: &[AutocorrectDictionary]) {
+        self.matcher.load_dictionaries(dictionaries);
+    }
+
     /// Applies preferences to the pipeline.
     pub fn apply_preferences(&mut self, prefs: &Preferences) {
         self.matcher.set_excluded_apps(prefs.excluded_apps.clone());
@@ -105,6 +116,30 @@ impl ExpansionPipeline {
         self.play_sound = play;
     }
 
+    /// Sets the asset store that image snippets are loaded from.
+    pub fn set_assets(&mut self, assets: AssetManager) {
+        self.assets = Some(assets);
+    }
+
+    /// Loads and decodes the image of an image snippet match. Returns `None`
+    /// for text matches.
+    pub fn load_image(
+        &self,
+        match_result: &MatchResult,
+    ) -> Result<Option<ClipboardFormat>, SubstitutionError> {
+        let name = match &match_result.image {
+            Some(name) => name,
+            None => return Ok(None),
+        };
+        let assets = self.assets.as_ref().ok_or_else(|| {
+            SubstitutionError::ImageUnavailable(format!("{}: no asset store configured", name))
+        })?;
+        assets
+            .load_image(name)
+            .map(Some)
+            .map_err(|e| SubstitutionError::ImageUnavailable(e.to_string()))
+    }
+
     /// Checks the buffer for a matching combo.
     ///
     /// This is the pure matching step without performing substitution.
@@ -124,6 +159,7 @@ impl ExpansionPipeline {
     /// Returns `Some(ExpansionResult)` if a match was found and expansion succeeded.
     pub fn expand_via_clipboard<P: ClipboardProvider>(
         &self,
+        backend: &mut dyn OutputBackend,
         buffer: &str,
         current_app: Option<&str>,
         clipboard_mgr: &mut ClipboardManager<P>,
@@ -139,9 +175,17 @@ impl ExpansionPipeline {
             match_result.snippet.len()
         );
 
+        let image = self.load_image(&match_result)?;
         self.substitution.substitute_via_clipboard(
+            backend,
+            None,
             match_result.keyword_len,
-            &match_result.snippet,
+            Snippet {
+                text: &match_result.snippet,
+                html: match_result.html.as_deref(),
+                image: image.as_ref(),
+                exclude_from_history: match_result.exclude_from_history,
+            },
             clipboard_mgr,
         )?;
 
@@ -159,6 +203,7 @@ impl ExpansionPipeline {
     /// Performs the full expansion via keystroke simulation.
     pub fn expand_via_keystrokes(
         &self,
+        backend: &mut dyn OutputBackend,
         buffer: &str,
         current_app: Option<&str>,
     ) -> Result<Option<ExpansionResult>, ExpansionError> {
@@ -173,40 +218,15 @@ impl ExpansionPipeline {
             match_result.snippet.len()
         );
 
-        self.substitution.substitute_via_keystrokes(
-            match_result.keyword_len,
-            &match_result.snippet,
-        )?;
-
-        if self.play_sound {
-            play_expansion_sound();
+        if match_result.image.is_some() {
+            return Err(SubstitutionError::ImageUnavailable(
+                "images can only be pasted through the clipboard".to_string(),
+            )
+            .into());
         }
 
-        Ok(Some(ExpansionResult {
-            combo_id: match_result.combo_id,
-            keyword: match_result.keyword,
-            snippet: match_result.snippet,
-        }))
-    }
-
-    /// Performs the full expansion via xdotool type command.
-    pub fn expand_via_xdotool(
-        &self,
-        buffer: &str,
-        current_app: Option<&str>,
-    ) -> Result<Option<ExpansionResult>, ExpansionError> {
-        let match_result = match self.matcher.find_match(buffer, current_app) {
-            Some(m) => m,
-            None => return Ok(None),
-        };
-
-        tracing::info!(
-            "Expanding combo via xdotool: keyword='{}', snippet_len={}",
-            match_result.keyword,
-            match_result.snippet.len()
-        );
-
-        self.substitution.substitute_via_xdotool(
+        self.substitution.substitute_via_keystrokes(
+            backend,
             match_result.keyword_len,
             &match_result.snippet,
         )?;
@@ -283,6 +303,7 @@ fn play_beep() -> Result<(), Box<dyn std::error::Error>> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::managers::output_backend::{OutputAction, RecordingBackend};
     use crate::models::combo::ComboBuilder;
     use crate::models::matching::MatchingMode;
 
@@ -516,6 +537,66 @@ mod tests {
         assert_eq!(m.keyword_len, 3);
     }
 
+    #[test]
+    fn test_expand_via_keystrokes_with_recording_backend() {
+        let mut pipeline = ExpansionPipeline::with_defaults();
+        pipeline.load_combos(&[make_combo("sig", "Best regards")]);
+        let mut backend = RecordingBackend::new();
+
+        assert!(pipeline.expand_via_keystrokes(&mut backend, "hello", None).unwrap().is_none());
+        let result = pipeline.expand_via_keystrokes(&mut backend, "hello sig", None).unwrap();
+        assert_eq!(result.unwrap().snippet, "Best regards");
+        assert_eq!(
+            backend.actions(),
+            vec![
+                OutputAction::DeleteGraphemes(3),
+                OutputAction::TypeText("Best regards".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_load_image_for_image_snippets() {
+        use crate::models::SnippetFormat;
+        use std::io::Cursor;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let assets = AssetManager::new(dir.path().to_path_buf());
+        let mut png = Cursor::new(Vec::new());
+        image::RgbaImage::new(2, 1)
+            .write_to(&mut png, image::ImageFormat::Png)
+            .unwrap();
+        let name = assets.store(png.get_ref()).unwrap();
+
+        let mut pipeline = ExpansionPipeline::with_defaults();
+        let logo = ComboBuilder::new()
+            .keyword("logo")
+            .snippet(name)
+            .snippet_format(SnippetFormat::Image)
+            .build()
+            .unwrap();
+        pipeline.load_combos(&[logo, make_combo("sig", "Best regards")]);
+        let image_match = pipeline.process_buffer("logo", None).unwrap();
+        let text_match = pipeline.process_buffer("sig", None).unwrap();
+
+        // Without an asset store the image can't be found.
+        assert!(matches!(
+            pipeline.load_image(&image_match),
+            Err(SubstitutionError::ImageUnavailable(_))
+        ));
+
+        pipeline.set_assets(assets);
+        assert!(matches!(
+            pipeline.load_image(&image_match).unwrap(),
+            Some(ClipboardFormat::Image { width: 2, height: 1, .. })
+        ));
+        assert_eq!(pipeline.load_image(&text_match).unwrap(), None);
+
+        let mut backend = RecordingBackend::new();
+        assert!(pipeline.expand_via_keystrokes(&mut backend, "logo", None).is_err());
+        assert!(backend.actions().is_empty());
+    }
+
     #[test]
     fn test_expansion_error_display() {
         let err = ExpansionError::Matching("test".to_string());
@@ -0,0 +1,551 @@
+//! Single background worker that runs expansions one at a time.
+//!
+//! The keyboard hook callback only detects matches. The output for each
+//! match is queued here as a job and sent from the worker thread, so the
+//! hook is never blocked by simulated input and two expansions can never
+//! interleave. The queue is bounded: a burst of matches beyond its capacity
+//! is refused rather than piling up. Every job has a [`JobHandle`] that can
+//! cancel it before it starts or between two output operations.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use thiserror::Error;
+
+use crate::managers::output_backend::{
+    type_unicode_sequence, KeyChord, OutputAction, OutputBackend, UnicodeInput,
+};
+use crate::managers::substitution::SubstitutionError;
+use crate::platform::keyboard_hook::Key;
+
+/// Maximum number of jobs waiting behind the one that is running.
+pub const EXPANSION_QUEUE_CAPACITY: usize = 4;
+
+/// Errors from submitting a job.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum WorkerError {
+    #[error("Expansion queue is full ({0} jobs waiting)")]
+    QueueFull(usize),
+    #[error("Expansion worker has stopped")]
+    Stopped,
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+// ---------------------------------------------------------------------------
+// JobHandle
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Default)]
+struct JobState {
+    cancelled: AtomicBool,
+    finished: Mutex<bool>,
+    done: Condvar,
+}
+
+/// Shared handle to a queued or running job. Clones refer to the same job.
+#[derive(Debug, Clone, Default)]
+pub struct JobHandle {
+    state: Arc<JobState>,
+}
+
+impl JobHandle {
+    /// Asks the job to stop. A queued job is skipped; a running one fails
+    /// with [`SubstitutionError::Cancelled`] at its next output operation.
+    pub fn cancel(&self) {
+        self.state.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.state.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Returns whether the worker is done with the job, whether it ran to
+    /// completion, failed or was cancelled.
+    pub fn is_finished(&self) -> bool {
+        *lock(&self.state.finished)
+    }
+
+    /// Blocks until the job is finished or `timeout` elapses. Returns
+    /// whether it finished.
+    pub fn wait(&self, timeout: Duration) -> bool {
+        let finished = lock(&self.state.finished);
+        let (finished, _) = self
+            .state
+            .done
+            .wait_timeout_while(finished, timeout, |finished| !*finished)
+            .unwrap_or_else(|e| e.into_inner());
+        *finished
+    }
+
+    fn finish(&self) {
+        *lock(&self.state.finished) = true;
+        self.state.done.notify_all();
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Queue
+// ---------------------------------------------------------------------------
+
+struct QueueState<J> {
+    jobs: VecDeque<(J, JobHandle)>,
+    running: Option<JobHandle>,
+    closed: bool,
+}
+
+struct Shared<J> {
+    state: Mutex<QueueState<J>>,
+    ready: Condvar,
+    /// Notified whenever the worker finishes a job.
+    finished: Condvar,
+    capacity: usize,
+}
+
+/// Submitting side of an [`ExpansionWorker`]. Cheap to clone, so it can be
+/// moved into hook callbacks while the worker itself stays with its owner.
+pub struct JobQueue<J> {
+    shared: Arc<Shared<J>>,
+}
+
+impl<J> Clone for JobQueue<J> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<J> JobQueue<J> {
+    /// Queues `job` behind any job already waiting.
+    pub fn submit(&self, job: J) -> Result<JobHandle, WorkerError> {
+        let mut state = lock(&self.shared.state);
+        if state.closed {
+            return Err(WorkerError::Stopped);
+        }
+        if state.jobs.len() >= self.shared.capacity {
+            return Err(WorkerError::QueueFull(state.jobs.len()));
+        }
+        let handle = JobHandle::default();
+        state.jobs.push_back((job, handle.clone()));
+        self.shared.ready.notify_one();
+        Ok(handle)
+    }
+
+    /// Cancels the running job and every job still waiting.
+    pub fn cancel_all(&self) {
+        let state = lock(&self.shared.state);
+        state.running.iter().for_each(JobHandle::cancel);
+        state.jobs.iter().for_each(|(_, handle)| handle.cancel());
+    }
+
+    /// The handle of the job that is running, if any.
+    pub fn running(&self) -> Option<JobHandle> {
+        lock(&self.shared.state).running.clone()
+    }
+
+    /// Number of jobs waiting, not counting the one that is running.
+    pub fn pending(&self) -> usize {
+        lock(&self.shared.state).jobs.len()
+    }
+
+    /// Blocks until no job is running or waiting, or `timeout` elapses.
+    /// Returns whether the worker went idle. A job submitted while the
+    /// last one finishes, e.g. by input it released, is waited for too.
+    pub fn wait_idle(&self, timeout: Duration) -> bool {
+        let state = lock(&self.shared.state);
+        let (state, _) = self
+            .shared
+            .finished
+            .wait_timeout_while(state, timeout, |state| {
+                state.running.is_some() || !state.jobs.is_empty()
+            })
+            .unwrap_or_else(|e| e.into_inner());
+        state.running.is_none() && state.jobs.is_empty()
+    }
+
+    /// Cancels everything and refuses further jobs. The worker thread exits
+    /// once the cancelled jobs have been drained.
+    fn close(&self) {
+        self.cancel_all();
+        lock(&self.shared.state).closed = true;
+        self.shared.ready.notify_all();
+    }
+
+    /// Blocks until a job is available. Returns `None` once the queue is
+    /// closed and empty.
+    fn next(&self) -> Option<(J, JobHandle)> {
+        let mut state = lock(&self.shared.state);
+        loop {
+            if let Some((job, handle)) = state.jobs.pop_front() {
+                state.running = Some(handle.clone());
+                return Some((job, handle));
+            }
+            if state.closed {
+                return None;
+            }
+            state = self
+                .shared
+                .ready
+                .wait(state)
+                .unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    fn done(&self, handle: &JobHandle) {
+        lock(&self.shared.state).running = None;
+        handle.finish();
+        self.shared.finished.notify_all();
+    }
+}
+
+// ---------------------------------------------------------------------------
+// ExpansionWorker
+// ---------------------------------------------------------------------------
+
+/// Owns the worker thread. Dropping the worker cancels all jobs and waits
+/// for the thread to exit.
+pub struct ExpansionWorker<J> {
+    queue: JobQueue<J>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl<J: Send + 'static> ExpansionWorker<J> {
+    /// Starts a worker that passes each job to `run`, in submission order.
+    ///
+    /// `run` is also called for jobs cancelled while queued, so it can undo
+    /// whatever was prepared when the job was submitted; it should check
+    /// [`JobHandle::is_cancelled`] before sending output.
+    pub fn spawn<F>(capacity: usize, mut run: F) -> Self
+    where
+        F: FnMut(J, &JobHandle) + Send + 'static,
+    {
+        let queue = JobQueue {
+            shared: Arc::new(Shared {
+                state: Mutex::new(QueueState {
+                    jobs: VecDeque::with_capacity(capacity),
+                    running: None,
+                    closed: false,
+                }),
+                ready: Condvar::new(),
+                finished: Condvar::new(),
+                capacity,
+            }),
+        };
+        let worker_queue = queue.clone();
+        let thread = thread::Builder::new()
+            .name("muttontext-expansion".into())
+            .spawn(move || {
+                while let Some((job, handle)) = worker_queue.next() {
+                    run(job, &handle);
+                    worker_queue.done(&handle);
+                }
+                tracing::debug!("Expansion worker stopped");
+            })
+            .map_err(|e| tracing::error!("Failed to start expansion worker: {}", e))
+            .ok();
+        if thread.is_none() {
+            lock(&queue.shared.state).closed = true;
+        }
+        Self { queue, thread }
+    }
+}
+
+impl<J> ExpansionWorker<J> {
+    /// A submitting handle for this worker.
+    pub fn queue(&self) -> JobQueue<J> {
+        self.queue.clone()
+    }
+
+    /// See [`JobQueue::submit`].
+    pub fn submit(&self, job: J) -> Result<JobHandle, WorkerError> {
+        self.queue.submit(job)
+    }
+
+    /// See [`JobQueue::cancel_all`].
+    pub fn cancel_all(&self) {
+        self.queue.cancel_all();
+    }
+
+    /// See [`JobQueue::wait_idle`].
+    pub fn wait_idle(&self, timeout: Duration) -> bool {
+        self.queue.wait_idle(timeout)
+    }
+
+    /// See [`JobQueue::running`].
+    pub fn running(&self) -> Option<JobHandle> {
+        self.queue.running()
+    }
+}
+
+impl<J> Drop for ExpansionWorker<J> {
+    fn drop(&mut self) {
+        self.queue.close();
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                tracing::error!("Expansion worker panicked");
+            }
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// JobBackend
+// ---------------------------------------------------------------------------
+
+/// Wraps the output backend for one job: refuses further output once the
+/// job is cancelled and remembers what was sent, so the input manager knows
+/// which keystrokes to expect back from the hook.
+pub struct JobBackend<'a> {
+    inner: &'a mut dyn OutputBackend,
+    handle: &'a JobHandle,
+    sent: Vec<OutputAction>,
+}
+
+impl<'a> JobBackend<'a> {
+    pub fn new(inner: &'a mut dyn OutputBackend, handle: &'a JobHandle) -> Self {
+        Self {
+            inner,
+            handle,
+            sent: Vec::new(),
+        }
+    }
+
+    /// Operations sent so far, oldest first.
+    pub fn sent(&self) -> &[OutputAction] {
+        &self.sent
+    }
+
+    /// The key presses the sent operations produce, with `paste` standing
+    /// in for each paste.
+    pub fn echo(&self, paste: &KeyChord) -> Vec<Key> {
+        self.sent.iter().flat_map(|action| action.echo(paste)).collect()
+    }
+
+    fn send(
+        &mut self,
+        action: OutputAction,
+        op: impl FnOnce(&mut dyn OutputBackend) -> Result<(), SubstitutionError>,
+    ) -> Result<(), SubstitutionError> {
+        if self.handle.is_cancelled() {
+            return Err(SubstitutionError::Cancelled);
+        }
+        op(&mut *self.inner)?;
+        self.sent.push(action);
+        Ok(())
+    }
+}
+
+impl OutputBackend for JobBackend<'_> {
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    fn delete_graphemes(&mut self, count: usize) -> Result<(), SubstitutionError> {
+        self.send(OutputAction::DeleteGraphemes(count), |b| b.delete_graphemes(count))
+    }
+
+    fn type_text(&mut self, text: &str) -> Result<(), SubstitutionError> {
+        self.send(OutputAction::TypeText(text.to_string()), |b| b.type_text(text))
+    }
+
+    fn paste(&mut self, text: &str) -> Result<(), SubstitutionError> {
+        self.send(OutputAction::Paste(text.to_string()), |b| b.paste(text))
+    }
+
+    fn key_chord(&mut self, chord: &KeyChord) -> Result<(), SubstitutionError> {
+        self.send(OutputAction::KeyChord(chord.clone()), |b| b.key_chord(chord))
+    }
+
+    fn move_cursor(&mut self, offset: isize) -> Result<(), SubstitutionError> {
+        self.send(OutputAction::MoveCursor(offset), |b| b.move_cursor(offset))
+    }
+
+    fn can_type(&self, ch: char) -> bool {
+        self.inner.can_type(ch)
+    }
+
+    fn unicode_input(&self) -> UnicodeInput {
+        self.inner.unicode_input()
+    }
+
+    /// Sends the key sequence through `self` so each part is echoed.
+    fn type_unicode(&mut self, ch: char) -> Result<(), SubstitutionError> {
+        match self.inner.unicode_input() {
+            UnicodeInput::KeySequence => type_unicode_sequence(self, ch),
+            UnicodeInput::Direct => {
+                self.send(OutputAction::TypeText(ch.to_string()), |b| b.type_unicode(ch))
+            }
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    use crate::managers::output_backend::{type_text_per_char, RecordingBackend};
+
+    const WAIT: Duration = Duration::from_secs(2);
+
+    /// A worker whose jobs block until released through the returned sender,
+    /// and report each job they ran (and whether it was cancelled).
+    #[allow(clippy::type_complexity)]
+    fn gated_worker(
+        capacity: usize,
+    ) -> (ExpansionWorker<u32>, mpsc::Sender<()>, Arc<Mutex<Vec<(u32, bool)>>>) {
+        let (release, gate) = mpsc::channel::<()>();
+        let ran = Arc::new(Mutex::new(Vec::new()));
+        let ran_clone = ran.clone();
+        let worker = ExpansionWorker::spawn(capacity, move |job: u32, handle: &JobHandle| {
+            if !handle.is_cancelled() {
+                let _ = gate.recv_timeout(WAIT);
+            }
+            ran_clone.lock().unwrap().push((job, handle.is_cancelled()));
+        });
+        (worker, release, ran)
+    }
+
+    // ── Queue ──
+
+    #[test]
+    fn test_jobs_run_in_order() {
+        let (worker, release, ran) = gated_worker(4);
+        let handles: Vec<JobHandle> = (1..=3).map(|n| worker.submit(n).unwrap()).collect();
+        for _ in 0..3 {
+            release.send(()).unwrap();
+        }
+        assert!(handles.iter().all(|h| h.wait(WAIT)));
+        assert_eq!(*ran.lock().unwrap(), vec![(1, false), (2, false), (3, false)]);
+    }
+
+    #[test]
+    fn test_full_queue_refuses_jobs() {
+        let (worker, release, _ran) = gated_worker(1);
+        let first = worker.submit(1).unwrap();
+        // Wait for the first job to leave the queue and start running.
+        while worker.queue().pending() > 0 {
+            thread::sleep(Duration::from_millis(1));
+        }
+        worker.submit(2).unwrap();
+        assert_eq!(worker.submit(3).unwrap_err(), WorkerError::QueueFull(1));
+        release.send(()).unwrap();
+        assert!(first.wait(WAIT));
+    }
+
+    #[test]
+    fn test_cancelled_job_is_skipped_but_finished() {
+        let (worker, release, ran) = gated_worker(4);
+        let first = worker.submit(1).unwrap();
+        let second = worker.submit(2).unwrap();
+        second.cancel();
+        release.send(()).unwrap();
+        assert!(first.wait(WAIT));
+        assert!(second.wait(WAIT));
+        assert_eq!(*ran.lock().unwrap(), vec![(1, false), (2, true)]);
+    }
+
+    #[test]
+    fn test_cancel_all_reaches_running_job() {
+        let (worker, release, _ran) = gated_worker(4);
+        let running = worker.submit(1).unwrap();
+        let queued = worker.submit(2).unwrap();
+        while worker.queue().pending() > 1 {
+            thread::sleep(Duration::from_millis(1));
+        }
+        worker.cancel_all();
+        assert!(running.is_cancelled());
+        assert!(queued.is_cancelled());
+        release.send(()).unwrap();
+        assert!(queued.wait(WAIT));
+    }
+
+    #[test]
+    fn test_drop_finishes_pending_jobs() {
+        let (worker, release, ran) = gated_worker(4);
+        let queue = worker.queue();
+        let handle = worker.submit(1).unwrap();
+        release.send(()).unwrap();
+        drop(worker);
+        assert!(handle.is_finished());
+        assert_eq!(queue.submit(2).unwrap_err(), WorkerError::Stopped);
+        assert_eq!(ran.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_wait_times_out_while_running() {
+        let (worker, release, _ran) = gated_worker(4);
+        let handle = worker.submit(1).unwrap();
+        assert!(!handle.wait(Duration::from_millis(20)));
+        release.send(()).unwrap();
+        assert!(handle.wait(WAIT));
+    }
+
+    #[test]
+    fn test_wait_idle_waits_for_every_job() {
+        let (worker, release, ran) = gated_worker(4);
+        assert!(worker.wait_idle(Duration::ZERO));
+        worker.submit(1).unwrap();
+        worker.submit(2).unwrap();
+        assert!(!worker.wait_idle(Duration::from_millis(20)));
+        release.send(()).unwrap();
+        release.send(()).unwrap();
+        assert!(worker.wait_idle(WAIT));
+        assert_eq!(ran.lock().unwrap().len(), 2);
+    }
+
+    // ── JobBackend ──
+
+    #[test]
+    fn test_job_backend_records_sent_output() {
+        let mut output = RecordingBackend::new();
+        let handle = JobHandle::default();
+        let mut backend = JobBackend::new(&mut output, &handle);
+        backend.delete_graphemes(2).unwrap();
+        backend.paste("hello").unwrap();
+        assert_eq!(
+            backend.sent(),
+            &[OutputAction::DeleteGraphemes(2), OutputAction::Paste("hello".into())]
+        );
+        let paste = KeyChord::platform_paste();
+        assert_eq!(
+            backend.echo(&paste),
+            vec![Key::Backspace, Key::Backspace, paste.key.clone()]
+        );
+    }
+
+    #[test]
+    fn test_job_backend_echo_of_character_missing_from_keymap() {
+        let mut output = RecordingBackend::with_keymap(|ch| ch.is_ascii());
+        let handle = JobHandle::default();
+        let mut backend = JobBackend::new(&mut output, &handle);
+        type_text_per_char(&mut backend, "5€").unwrap();
+        let typed: Vec<Key> = "5u20ac".chars().map(Key::Char).chain([Key::Space]).collect();
+        assert_eq!(backend.echo(&KeyChord::platform_paste()), typed);
+    }
+
+    #[test]
+    fn test_job_backend_stops_once_cancelled() {
+        let mut output = RecordingBackend::new();
+        let handle = JobHandle::default();
+        {
+            let mut backend = JobBackend::new(&mut output, &handle);
+            backend.delete_graphemes(2).unwrap();
+            handle.cancel();
+            let err = backend.type_text("hello").unwrap_err();
+            assert!(matches!(err, SubstitutionError::Cancelled));
+            assert_eq!(backend.sent().len(), 1);
+        }
+        assert_eq!(output.actions(), vec![OutputAction::DeleteGraphemes(2)]);
+    }
+}
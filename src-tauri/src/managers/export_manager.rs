@@ -3,6 +3,7 @@
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
+use crate::managers::asset_manager::EmbeddedAsset;
 use crate::models::combo::Combo;
 use crate::models::group::Group;
 
@@ -27,6 +28,8 @@ pub enum ExportError {
 struct MuttonTextFile {
     combos: Vec<Combo>,
     groups: Vec<Group>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    assets: Vec<EmbeddedAsset>,
 }
 
 pub struct ExportManager;
@@ -34,9 +37,20 @@ pub struct ExportManager;
 impl ExportManager {
     /// Export to native MuttonText JSON format.
     pub fn export_muttontext_json(combos: &[Combo], groups: &[Group]) -> Result<String, ExportError> {
+        Self::export_muttontext_json_with_assets(combos, groups, &[])
+    }
+
+    /// Export to native MuttonText JSON format, embedding the assets that
+    /// image combos use.
+    pub fn export_muttontext_json_with_assets(
+        combos: &[Combo],
+        groups: &[Group],
+        assets: &[EmbeddedAsset],
+    ) -> Result<String, ExportError> {
         let file = MuttonTextFile {
             combos: combos.to_vec(),
             groups: groups.to_vec(),
+            assets: assets.to_vec(),
         };
         serde_json::to_string_pretty(&file).map_err(|e| ExportError::Serialization(e.to_string()))
     }
@@ -164,6 +178,88 @@ mod tests {
         assert_eq!(result.groups[0].name, "Test");
     }
 
+    #[test]
+    fn test_export_import_roundtrip_with_assets() {
+        use crate::managers::asset_manager::AssetManager;
+        use crate::managers::import_manager::ImportManager;
+        use crate::models::combo::SnippetFormat;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let assets = AssetManager::new(dir.path().to_path_buf());
+        let mut png = std::io::Cursor::new(Vec::new());
+        image::RgbaImage::new(1, 1)
+            .write_to(&mut png, image::ImageFormat::Png)
+            .unwrap();
+        let name = assets.store(png.get_ref()).unwrap();
+        let combo = ComboBuilder::new()
+            .keyword("logo")
+            .snippet(name.clone())
+            .snippet_format(SnippetFormat::Image)
+            .build()
+            .unwrap();
+
+        let combos = [combo];
+        let embedded = assets.embed_for(&combos);
+        let json = ExportManager::export_muttontext_json_with_assets(&combos, &[], &embedded).unwrap();
+        let result = ImportManager::import_muttontext_json(&json).unwrap();
+        assert_eq!(result.combos[0].snippet_format, SnippetFormat::Image);
+        assert_eq!(result.assets.len(), 1);
+        assert_eq!(result.assets[0].name, name);
+    }
+
+    #[test]
+    fn test_export_import_roundtrip_with_large_assets() {
+        use crate::managers::asset_manager::{AssetManager, MAX_ASSET_BYTES};
+        use crate::managers::import_manager::{ImportFormat, ImportManager, MAX_IMPORT_BYTES};
+        use crate::models::combo::SnippetFormat;
+
+        // Noise doesn't compress, so each PNG ends up just under the limit.
+        let noisy_png = |seed: u32| {
+            let mut state = seed;
+            let img = image::RgbaImage::from_fn(1100, 1150, |_, _| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                image::Rgba(state.to_le_bytes())
+            });
+            let mut png = std::io::Cursor::new(Vec::new());
+            img.write_to(&mut png, image::ImageFormat::Png).unwrap();
+            png.into_inner()
+        };
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let assets = AssetManager::new(dir.path().to_path_buf());
+        let mut combos = Vec::new();
+        for (keyword, seed) in [("logo", 1), ("scan", 2)] {
+            let png = noisy_png(seed);
+            assert!(png.len() > MAX_ASSET_BYTES * 9 / 10 && png.len() <= MAX_ASSET_BYTES);
+            let name = assets.store(&png).unwrap();
+            combos.push(
+                ComboBuilder::new()
+                    .keyword(keyword)
+                    .snippet(name)
+                    .snippet_format(SnippetFormat::Image)
+                    .build()
+                    .unwrap(),
+            );
+        }
+
+        let embedded = assets.embed_for(&combos);
+        let json = ExportManager::export_muttontext_json_with_assets(&combos, &[], &embedded).unwrap();
+        assert!(json.len() > MAX_IMPORT_BYTES);
+        ImportManager::check_size(&json, ImportFormat::MuttonTextJson).unwrap();
+        let result = ImportManager::import_muttontext_json(&json).unwrap();
+        assert_eq!(result.imported_count, 2);
+        assert_eq!(result.assets.len(), 2);
+        assert!(result.errors.is_empty());
+    }
+
+    #[test]
+    fn test_export_muttontext_json_omits_empty_assets() {
+        let json = ExportManager::export_muttontext_json(&[], &[]).unwrap();
+        assert!(!json.contains("assets"));
+    }
+
     // ── TextExpander CSV ─────────────────────────────────────────
 
     #[test]
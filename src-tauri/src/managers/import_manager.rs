@@ -4,10 +4,22 @@ use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use uuid::Uuid;
 
-use crate::models::combo::{Combo, ComboBuilder};
+use crate::managers::asset_manager::{
+    encoded_len, EmbeddedAsset, MAX_ASSET_BYTES, MAX_EMBEDDED_ASSETS,
+};
+use crate::models::autocorrect::{AutocorrectDictionary, AutocorrectEntry};
+use crate::models::combo::{Combo, ComboBuilder, SnippetFormat};
 use crate::models::group::Group;
 use crate::models::matching::MatchingMode;
 
+/// Maximum size of import content, not counting embedded assets (10 MB).
+pub const MAX_IMPORT_BYTES: usize = 10 * 1024 * 1024;
+
+/// Maximum size of a native export: the combo data plus the most and
+/// largest assets it may embed.
+pub const MAX_NATIVE_IMPORT_BYTES: usize =
+    MAX_IMPORT_BYTES + MAX_EMBEDDED_ASSETS * encoded_len(MAX_ASSET_BYTES);
+
 /// Supported import formats.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -36,6 +48,19 @@ pub struct ImportResult {
     pub errors: Vec<String>,
     pub combos: Vec<Combo>,
     pub groups: Vec<Group>,
+    /// Validated assets from a native export, for the caller to store.
+    #[serde(skip)]
+    pub assets: Vec<EmbeddedAsset>,
+}
+
+/// Result of importing a bulk typo list into an autocorrect dictionary.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AutocorrectImportResult {
+    pub imported_count: usize,
+    pub skipped_count: usize,
+    pub errors: Vec<String>,
+    pub dictionary: AutocorrectDictionary,
 }
 
 /// Preview of what an import would produce.
@@ -58,6 +83,10 @@ pub enum ImportError {
     InvalidCsv(String),
     #[error("Missing required field: {0}")]
     MissingField(String),
+    #[error("Import content exceeds {0} MB limit")]
+    TooLarge(usize),
+    #[error("Import embeds {0} images, exceeding the limit of {1}")]
+    TooManyAssets(usize, usize),
 }
 
 /// Beeftext JSON structures for deserialization.
@@ -87,6 +116,8 @@ struct BeeftextGroup {
 struct MuttonTextFile {
     combos: Vec<Combo>,
     groups: Vec<Group>,
+    #[serde(default)]
+    assets: Vec<EmbeddedAsset>,
 }
 
 pub struct ImportManager;
@@ -225,6 +256,7 @@ impl ImportManager {
             errors,
             combos,
             groups,
+            assets: Vec::new(),
         })
     }
 
@@ -313,6 +345,7 @@ impl ImportManager {
             errors,
             combos,
             groups,
+            assets: Vec::new(),
         })
     }
 
@@ -376,21 +409,149 @@ impl ImportManager {
             errors,
             combos,
             groups: Vec::new(),
+            assets: Vec::new(),
         })
     }
 
+    /// Checks `content` against the size limit for `format`. Native exports
+    /// get extra room for the images they embed.
+    pub fn check_size(content: &str, format: ImportFormat) -> Result<(), ImportError> {
+        let limit = match format {
+            ImportFormat::MuttonTextJson => MAX_NATIVE_IMPORT_BYTES,
+            _ => MAX_IMPORT_BYTES,
+        };
+        if content.len() > limit {
+            return Err(ImportError::TooLarge(limit / (1024 * 1024)));
+        }
+        Ok(())
+    }
+
     /// Import from native MuttonText JSON format.
     pub fn import_muttontext_json(content: &str) -> Result<ImportResult, ImportError> {
         let file: MuttonTextFile =
             serde_json::from_str(content).map_err(|e| ImportError::InvalidJson(e.to_string()))?;
 
-        let count = file.combos.len();
+        // The extra room a native export gets is only for its images; the
+        // rest is held to the usual limit.
+        if file.assets.len() > MAX_EMBEDDED_ASSETS {
+            return Err(ImportError::TooManyAssets(file.assets.len(), MAX_EMBEDDED_ASSETS));
+        }
+        let asset_bytes: usize = file.assets.iter().map(|a| a.data.len()).sum();
+        if content.len().saturating_sub(asset_bytes) > MAX_IMPORT_BYTES {
+            return Err(ImportError::TooLarge(MAX_IMPORT_BYTES / (1024 * 1024)));
+        }
+
+        // Each embedded image must pass the same size and type checks as an
+        // image added in the app; combos using a rejected one are skipped.
+        let mut errors: Vec<String> = Vec::new();
+        let mut assets: Vec<EmbeddedAsset> = Vec::new();
+        let mut rejected = std::collections::HashSet::new();
+        for asset in file.assets {
+            match asset.decode() {
+                Ok(_) => assets.push(asset),
+                Err(e) => {
+                    errors.push(format!("Asset '{}': {}", asset.name, e));
+                    rejected.insert(asset.name);
+                }
+            }
+        }
+
+        let mut combos: Vec<Combo> = Vec::new();
+        let mut skipped = 0usize;
+        for combo in file.combos {
+            if combo.snippet_format == SnippetFormat::Image && rejected.contains(&combo.snippet) {
+                errors.push(format!(
+                    "Combo '{}' uses a rejected image, skipped",
+                    combo.keyword
+                ));
+                skipped += 1;
+            } else {
+                combos.push(combo);
+            }
+        }
+
         Ok(ImportResult {
-            imported_count: count,
-            skipped_count: 0,
-            errors: Vec::new(),
-            combos: file.combos,
+            imported_count: combos.len(),
+            skipped_count: skipped,
+            errors,
+            combos,
             groups: file.groups,
+            assets,
+        })
+    }
+
+    /// Import a bulk typo list into a new autocorrect dictionary.
+    ///
+    /// Accepts one pair per line, either tab-separated (`teh<TAB>the`) or in
+    /// the Wikipedia "common misspellings" format (`teh->the`). Blank lines and
+    /// lines starting with `#` are ignored. Wikipedia entries listing several
+    /// candidate corrections (`acn->can, acne`) are ambiguous and skipped, as
+    /// are duplicate typos, multi-word typos, and identity pairs.
+    pub fn import_autocorrect_list(
+        content: &str,
+        name: &str,
+    ) -> Result<AutocorrectImportResult, ImportError> {
+        let mut entries: Vec<AutocorrectEntry> = Vec::new();
+        let mut seen = std::collections::HashSet::new();
+        let mut errors: Vec<String> = Vec::new();
+        let mut skipped = 0usize;
+
+        for (i, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (typo, correction) = if let Some((typo, correction)) = line.split_once('\t') {
+                (typo.trim(), correction.trim())
+            } else if let Some((typo, correction)) = line.split_once("->") {
+                let correction = correction.trim();
+                if correction.contains(',') {
+                    errors.push(format!(
+                        "Line {}: ambiguous correction '{}'",
+                        i + 1,
+                        correction
+                    ));
+                    skipped += 1;
+                    continue;
+                }
+                (typo.trim(), correction)
+            } else {
+                errors.push(format!(
+                    "Line {}: expected 'typo<TAB>correction' or 'typo->correction'",
+                    i + 1
+                ));
+                skipped += 1;
+                continue;
+            };
+
+            if typo.is_empty() || correction.is_empty() {
+                errors.push(format!("Line {}: empty typo or correction", i + 1));
+                skipped += 1;
+                continue;
+            }
+            if typo.chars().any(char::is_whitespace) {
+                errors.push(format!("Line {}: typo '{}' must be a single word", i + 1, typo));
+                skipped += 1;
+                continue;
+            }
+            if typo == correction || !seen.insert(typo.to_lowercase()) {
+                skipped += 1;
+                continue;
+            }
+
+            entries.push(AutocorrectEntry::new(typo, correction));
+        }
+
+        if entries.is_empty() && skipped == 0 {
+            return Err(ImportError::MissingField("autocorrect entries".to_string()));
+        }
+
+        Ok(AutocorrectImportResult {
+            imported_count: entries.len(),
+            skipped_count: skipped,
+            errors,
+            dictionary: AutocorrectDictionary::new(name, entries),
         })
     }
 
@@ -493,6 +654,7 @@ mod tests {
         let file = MuttonTextFile {
             combos: vec![combo],
             groups: vec![group],
+            assets: Vec::new(),
         };
         let content = serde_json::to_string(&file).unwrap();
         let fmt = ImportManager::detect_format(&content).unwrap();
@@ -621,6 +783,7 @@ mod tests {
         let file = MuttonTextFile {
             combos: vec![combo.clone()],
             groups: vec![group.clone()],
+            assets: Vec::new(),
         };
         let json = serde_json::to_string(&file).unwrap();
         let result = ImportManager::import_muttontext_json(&json).unwrap();
@@ -629,6 +792,117 @@ mod tests {
         assert_eq!(result.groups[0].name, "Test");
     }
 
+    #[test]
+    fn test_import_muttontext_json_validates_assets() {
+        let mut png = std::io::Cursor::new(Vec::new());
+        image::RgbaImage::new(1, 1)
+            .write_to(&mut png, image::ImageFormat::Png)
+            .unwrap();
+        let image_combo = |keyword: &str, asset: &str| {
+            ComboBuilder::new()
+                .keyword(keyword)
+                .snippet(asset)
+                .snippet_format(SnippetFormat::Image)
+                .build()
+                .unwrap()
+        };
+        let file = MuttonTextFile {
+            combos: vec![image_combo("logo", "good.png"), image_combo("scan", "bad.png")],
+            groups: Vec::new(),
+            assets: vec![
+                EmbeddedAsset::new("good.png", png.get_ref()),
+                EmbeddedAsset::new("bad.png", b"GIF89a"),
+            ],
+        };
+        let json = serde_json::to_string(&file).unwrap();
+        let result = ImportManager::import_muttontext_json(&json).unwrap();
+
+        assert_eq!(result.imported_count, 1);
+        assert_eq!(result.skipped_count, 1);
+        assert_eq!(result.combos[0].keyword, "logo");
+        assert_eq!(result.assets.len(), 1);
+        assert_eq!(result.assets[0].name, "good.png");
+        assert_eq!(result.errors.len(), 2);
+        assert!(result.errors[0].contains("bad.png"));
+    }
+
+    #[test]
+    fn test_import_muttontext_json_rejects_too_many_assets() {
+        let file = MuttonTextFile {
+            combos: Vec::new(),
+            groups: Vec::new(),
+            assets: (0..=MAX_EMBEDDED_ASSETS)
+                .map(|i| EmbeddedAsset::new(format!("{i}.png"), b"png"))
+                .collect(),
+        };
+        let json = serde_json::to_string(&file).unwrap();
+        assert!(matches!(
+            ImportManager::import_muttontext_json(&json),
+            Err(ImportError::TooManyAssets(_, MAX_EMBEDDED_ASSETS))
+        ));
+    }
+
+    #[test]
+    fn test_check_size_allows_assets_only_in_native_exports() {
+        let content = " ".repeat(MAX_IMPORT_BYTES + 1);
+        assert!(ImportManager::check_size(&content, ImportFormat::MuttonTextJson).is_ok());
+        assert!(matches!(
+            ImportManager::check_size(&content, ImportFormat::BeeftextJson),
+            Err(ImportError::TooLarge(10))
+        ));
+    }
+
+    #[test]
+    fn test_import_muttontext_json_without_assets_field() {
+        let json = r#"{"combos": [], "groups": []}"#;
+        let result = ImportManager::import_muttontext_json(json).unwrap();
+        assert!(result.assets.is_empty());
+    }
+
+    // ── Autocorrect Lists ────────────────────────────────────────
+
+    #[test]
+    fn test_import_autocorrect_tsv() {
+        let content = "teh\tthe\nadn\tand\n";
+        let result = ImportManager::import_autocorrect_list(content, "Typos").unwrap();
+        assert_eq!(result.imported_count, 2);
+        assert_eq!(result.skipped_count, 0);
+        assert_eq!(result.dictionary.name, "Typos");
+        assert_eq!(result.dictionary.entries[0], AutocorrectEntry::new("teh", "the"));
+    }
+
+    #[test]
+    fn test_import_autocorrect_wikipedia_format() {
+        let content = "abandonned->abandoned\naberation->aberration\nacn->can, acne\n";
+        let result = ImportManager::import_autocorrect_list(content, "Wikipedia").unwrap();
+        assert_eq!(result.imported_count, 2);
+        assert_eq!(result.skipped_count, 1);
+        assert!(result.errors[0].contains("ambiguous"));
+    }
+
+    #[test]
+    fn test_import_autocorrect_skips_comments_and_blank_lines() {
+        let content = "# common typos\n\nteh->the\n   \n";
+        let result = ImportManager::import_autocorrect_list(content, "Typos").unwrap();
+        assert_eq!(result.imported_count, 1);
+        assert_eq!(result.skipped_count, 0);
+    }
+
+    #[test]
+    fn test_import_autocorrect_skips_duplicates_and_invalid_lines() {
+        let content = "teh->the\nTeh->the\nsame->same\ntwo words->twowords\nnot a pair\n";
+        let result = ImportManager::import_autocorrect_list(content, "Typos").unwrap();
+        assert_eq!(result.imported_count, 1);
+        assert_eq!(result.skipped_count, 4);
+        assert_eq!(result.errors.len(), 2);
+    }
+
+    #[test]
+    fn test_import_autocorrect_empty_content_errors() {
+        let result = ImportManager::import_autocorrect_list("\n# nothing\n", "Typos");
+        assert!(result.is_err());
+    }
+
     // ── Preview ──────────────────────────────────────────────────
 
     #[test]
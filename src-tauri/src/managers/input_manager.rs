@@ -1,15 +1,29 @@
 //! Character buffer and input management for keyword matching.
 //!
 //! `InputManager` accumulates typed characters into a buffer and resets
-//! the buffer on word boundaries, non-printable keys, mouse clicks,
-//! and focus changes. Consumers register a callback to be notified
-//! whenever the buffer content changes.
+//! the buffer on non-printable keys, mouse clicks, and focus changes.
+//! Word boundaries (space, punctuation) are kept in the buffer so that
+//! multi-word phrase keywords can match; only the most recent words are
+//! retained. Consumers register a callback to be notified whenever the
+//! buffer content changes, and may register an undo handler that gets the
+//! first Backspace pressed after an expansion has finished.
+//!
+//! While an expansion is being typed, input is suppressed: events from the
+//! hook are held instead of processed. When the expansion worker hands
+//! input back through an [`InputGate`], the keystrokes the expansion itself
+//! produced are dropped and whatever the user typed meanwhile is replayed.
 
+use std::collections::VecDeque;
 use std::sync::{Arc, Mutex, MutexGuard};
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::managers::keystroke_trace::TraceRecorder;
+use crate::utils::graphemes::{is_grapheme_boundary, last_grapheme_start};
 
 use crate::platform::keyboard_hook::{
-    FocusDetector, Key, KeyEvent, KeyEventType, KeyboardHook, PlatformError, WindowInfo,
+    FocusDetector, Key, KeyEvent, KeyEventType, KeyboardHook, MouseEvent, PlatformError,
+    WindowInfo,
 };
 
 /// Helper to handle poisoned mutexes gracefully by recovering the inner data.
@@ -20,6 +34,17 @@ fn lock_mutex<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
 /// Default maximum buffer size in characters.
 const DEFAULT_MAX_BUFFER_SIZE: usize = 256;
 
+/// Default number of recent words retained across word boundaries.
+pub(crate) const DEFAULT_MAX_BUFFER_WORDS: usize = 8;
+
+/// Maximum number of events held while suppressed; the oldest are dropped
+/// beyond this.
+const MAX_HELD_EVENTS: usize = 256;
+
+/// How long after an expansion keystrokes matching its output are still
+/// treated as its echo rather than as typing.
+const ECHO_GRACE: Duration = Duration::from_millis(300);
+
 /// Default word boundary characters.
 const DEFAULT_WORD_BOUNDARIES: &[char] = &[
     ' ', '\t', '\n', '\r', '.', ',', ';', ':', '!', '?', '(', ')', '[', ']', '{', '}', '<', '>',
@@ -31,10 +56,15 @@ const DEFAULT_WORD_BOUNDARIES: &[char] = &[
 struct InputManagerInner {
     buffer: String,
     max_buffer_size: usize,
+    /// Number of trailing words kept when a word boundary is typed.
+    max_buffer_words: usize,
     is_paused: bool,
     word_boundary_chars: Vec<char>,
     last_window_info: Option<WindowInfo>,
     on_buffer_change: Option<Arc<dyn Fn(&str) + Send + Sync>>,
+    /// Invoked for the first Backspace after an unsuppress. Returns true if
+    /// the Backspace was consumed as an undo.
+    on_undo_backspace: Option<Arc<dyn Fn() -> bool + Send + Sync>>,
 }
 
 impl InputManagerInner {
@@ -42,10 +72,12 @@ impl InputManagerInner {
         Self {
             buffer: String::with_capacity(DEFAULT_MAX_BUFFER_SIZE),
             max_buffer_size: DEFAULT_MAX_BUFFER_SIZE,
+            max_buffer_words: DEFAULT_MAX_BUFFER_WORDS,
             is_paused: false,
             word_boundary_chars: DEFAULT_WORD_BOUNDARIES.to_vec(),
             last_window_info: None,
             on_buffer_change: None,
+            on_undo_backspace: None,
         }
     }
 
@@ -61,9 +93,10 @@ impl InputManagerInner {
             // Drop oldest half to avoid unbounded growth while keeping
             // recent context.
             let drain_to = self.buffer.len() / 2;
-            // Find a char boundary at or after drain_to.
+            // Find a grapheme boundary at or after drain_to, so no cluster
+            // is left without its start.
             let mut boundary = drain_to;
-            while boundary < self.buffer.len() && !self.buffer.is_char_boundary(boundary) {
+            while boundary < self.buffer.len() && !is_grapheme_boundary(&self.buffer, boundary) {
                 boundary += 1;
             }
             self.buffer.drain(..boundary);
@@ -72,8 +105,48 @@ impl InputManagerInner {
         self.notify_change();
     }
 
+    /// Push a word boundary character, then drop everything before the
+    /// most recent `max_buffer_words` words so that phrase keywords can
+    /// still match without the buffer accumulating stale context.
+    fn push_boundary(&mut self, c: char) {
+        self.trim_to_recent_words();
+        self.push_char(c);
+    }
+
+    fn trim_to_recent_words(&mut self) {
+        let mut words = 0;
+        let mut in_word = false;
+        let mut cut = None;
+        for (idx, c) in self.buffer.char_indices().rev() {
+            if self.is_word_boundary(c) {
+                if in_word {
+                    in_word = false;
+                    words += 1;
+                    if words >= self.max_buffer_words {
+                        cut = Some(idx + c.len_utf8());
+                        break;
+                    }
+                }
+            } else {
+                in_word = true;
+            }
+        }
+        if let Some(cut) = cut {
+            // Marks combining with the boundary go with it.
+            let mut cut = cut;
+            while cut < self.buffer.len() && !is_grapheme_boundary(&self.buffer, cut) {
+                cut += 1;
+            }
+            self.buffer.drain(..cut);
+        }
+    }
+
+    /// Removes the last grapheme cluster, as the application does: `é`
+    /// typed as `e` plus a combining accent, or a ZWJ emoji sequence, goes
+    /// with a single Backspace.
     fn handle_backspace(&mut self) {
-        if self.buffer.pop().is_some() {
+        if let Some(start) = last_grapheme_start(&self.buffer) {
+            self.buffer.truncate(start);
             self.notify_change();
         }
     }
@@ -89,16 +162,106 @@ impl InputManagerInner {
     }
 }
 
+/// An event delivered by the hook while input was suppressed.
+enum HeldEvent {
+    Key(KeyEvent),
+    Click,
+}
+
+/// Input held during an expansion, and the keystrokes the last expansion
+/// is expected to echo back through the hook.
+#[derive(Default)]
+struct HeldInput {
+    events: VecDeque<HeldEvent>,
+    echo: VecDeque<Key>,
+    echo_expires: Option<Instant>,
+}
+
+impl HeldInput {
+    fn hold(&mut self, event: HeldEvent) {
+        if self.events.len() >= MAX_HELD_EVENTS {
+            self.events.pop_front();
+        }
+        self.events.push_back(event);
+    }
+
+    fn expect_echo(&mut self, echo: Vec<Key>) {
+        self.echo = echo.into();
+        self.echo_expires = Some(Instant::now() + ECHO_GRACE);
+    }
+
+    /// Returns true, consuming it, if `event` is the next keystroke the last
+    /// expansion is expected to echo. Echoes arrive in the order they were
+    /// sent, so only the head of the queue is compared.
+    fn take_echo(&mut self, event: &KeyEvent) -> bool {
+        if event.event_type != KeyEventType::Press {
+            return false;
+        }
+        if self.echo_expires.is_some_and(|expires| Instant::now() >= expires) {
+            self.echo.clear();
+            self.echo_expires = None;
+        }
+        if self.echo.front() == Some(&event.key) {
+            self.echo.pop_front();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Number of expected echoes not among the held events yet.
+    fn unheard_echo(&self) -> usize {
+        let mut echo = self.echo.iter().peekable();
+        for event in &self.events {
+            if let (HeldEvent::Key(key), Some(next)) = (event, echo.peek()) {
+                if key.event_type == KeyEventType::Press && key.key == **next {
+                    echo.next();
+                }
+            }
+        }
+        echo.count()
+    }
+
+    /// Removes the held events, leaving out echoes.
+    fn take_typed(&mut self) -> Vec<HeldEvent> {
+        let events: Vec<HeldEvent> = self.events.drain(..).collect();
+        events
+            .into_iter()
+            .filter(|event| match event {
+                HeldEvent::Key(key) => !self.take_echo(key),
+                HeldEvent::Click => true,
+            })
+            .collect()
+    }
+}
+
 /// Manages the character buffer driven by platform keyboard events.
 pub struct InputManager {
     inner: Arc<Mutex<InputManagerInner>>,
     keyboard_hook: Option<Box<dyn KeyboardHook>>,
-    /// Lock-free flag: when true, the hook callback silently discards events.
-    /// Used during expansion to prevent xdotool keystrokes from being captured.
+    /// Lock-free flag: when true, the hook callback holds events instead of
+    /// processing them. Set while an expansion is being typed.
     is_suppressed: Arc<AtomicBool>,
+    /// Incremented by every `suppress`, so a replay can tell that a replayed
+    /// key started another expansion.
+    suppress_epoch: Arc<AtomicU64>,
+    /// Events held while suppressed. `is_suppressed` is only cleared with
+    /// this locked, so no event can be held after the last replay.
+    held: Arc<Mutex<HeldInput>>,
+    /// Whether the attached hook hears our own output, and so whether
+    /// echoes need filtering.
+    hears_output: Arc<AtomicBool>,
     /// Lock-free flag: when true, the hook callback clears the buffer on the
     /// next event before processing. Used after expansion to reset state.
     needs_buffer_clear: Arc<AtomicBool>,
+    /// Lock-free flag: set when input is unsuppressed, cleared by the next
+    /// key press. A Backspace arriving while set is offered to the undo handler.
+    undo_armed: Arc<AtomicBool>,
+    /// Lock-free flag: set while a password field has focus. Keystrokes are
+    /// discarded without ever reaching the buffer.
+    in_password_field: Arc<AtomicBool>,
+    /// Receives every event the hooks deliver while a trace is recorded.
+    trace: Arc<Mutex<Option<Arc<TraceRecorder>>>>,
 }
 
 impl InputManager {
@@ -108,7 +271,13 @@ impl InputManager {
             inner: Arc::new(Mutex::new(InputManagerInner::new())),
             keyboard_hook: None,
             is_suppressed: Arc::new(AtomicBool::new(false)),
+            suppress_epoch: Arc::new(AtomicU64::new(0)),
+            held: Arc::new(Mutex::new(HeldInput::default())),
+            hears_output: Arc::new(AtomicBool::new(true)),
             needs_buffer_clear: Arc::new(AtomicBool::new(false)),
+            undo_armed: Arc::new(AtomicBool::new(false)),
+            in_password_field: Arc::new(AtomicBool::new(false)),
+            trace: Arc::new(Mutex::new(None)),
         }
     }
 
@@ -117,6 +286,22 @@ impl InputManager {
         lock_mutex(&self.inner).max_buffer_size = size;
     }
 
+    /// Set how many recent words are kept across word boundaries.
+    /// Must be at least the word count of the longest phrase keyword.
+    pub fn set_max_buffer_words(&mut self, words: usize) {
+        lock_mutex(&self.inner).max_buffer_words = words.max(1);
+    }
+
+    /// Returns a callback that does [`Self::set_max_buffer_words`].
+    ///
+    /// It only takes the input lock, so the engine can call it after
+    /// releasing its own: the hook callback takes the input lock first and
+    /// the engine lock second, and the engine must not do the reverse.
+    pub fn max_buffer_words_setter(&self) -> impl Fn(usize) + Send + Sync + 'static {
+        let inner = self.inner.clone();
+        move |words| lock_mutex(&inner).max_buffer_words = words.max(1)
+    }
+
     /// Set the word boundary characters.
     pub fn set_word_boundary_chars(&mut self, chars: Vec<char>) {
         lock_mutex(&self.inner).word_boundary_chars = chars;
@@ -130,12 +315,38 @@ impl InputManager {
         lock_mutex(&self.inner).on_buffer_change = Some(Arc::new(callback));
     }
 
+    /// Register a handler for the first Backspace pressed after input is
+    /// unsuppressed. The handler returns true if it consumed the Backspace
+    /// (e.g. to undo the last expansion); otherwise the key is processed normally.
+    ///
+    /// The handler runs on the hook thread, or the expansion worker when held
+    /// input is replayed, with the input state locked, so it must not call
+    /// methods that lock the `InputManager`.
+    pub fn on_undo_backspace<F>(&mut self, handler: F)
+    where
+        F: Fn() -> bool + Send + Sync + 'static,
+    {
+        lock_mutex(&self.inner).on_undo_backspace = Some(Arc::new(handler));
+    }
+
+    /// Start or stop recording hook events to a keystroke trace.
+    pub fn set_trace_recorder(&self, recorder: Option<Arc<TraceRecorder>>) {
+        *lock_mutex(&self.trace) = recorder;
+    }
+
+    /// Returns whether a keystroke trace is being recorded.
+    pub fn is_recording_trace(&self) -> bool {
+        lock_mutex(&self.trace).is_some()
+    }
+
     /// Attach a keyboard hook. The hook is not started until `start` is called.
     pub fn set_keyboard_hook(&mut self, hook: Box<dyn KeyboardHook>) {
         // Stop the old hook if it's running
         if let Some(ref mut old_hook) = self.keyboard_hook {
             let _ = old_hook.stop(); // Ignore error if already stopped
         }
+        self.hears_output
+            .store(hook.hears_synthetic_input(), Ordering::SeqCst);
         self.keyboard_hook = Some(hook);
     }
 
@@ -146,15 +357,56 @@ impl InputManager {
             .as_mut()
             .ok_or_else(|| PlatformError::Internal("No keyboard hook configured".into()))?;
 
+        // A click may move the caret, so whatever was typed before it
+        // cannot be completed into a keyword afterwards.
+        let mouse_inner = self.inner.clone();
+        let mouse_suppressed = self.is_suppressed.clone();
+        let mouse_held = self.held.clone();
+        let mouse_undo_armed = self.undo_armed.clone();
+        let mouse_trace = self.trace.clone();
+        hook.set_mouse_callback(Box::new(move |event: MouseEvent| {
+            if let Some(ref recorder) = *lock_mutex(&mouse_trace) {
+                recorder.record_click(&event);
+            }
+            {
+                let mut held = lock_mutex(&mouse_held);
+                if mouse_suppressed.load(Ordering::SeqCst) {
+                    held.hold(HeldEvent::Click);
+                    return;
+                }
+            }
+            Self::record_click(&mouse_inner, &mouse_undo_armed);
+        }));
+
         let inner = self.inner.clone();
         let suppressed = self.is_suppressed.clone();
+        let held_input = self.held.clone();
         let needs_clear = self.needs_buffer_clear.clone();
+        let undo_armed = self.undo_armed.clone();
+        let in_password_field = self.in_password_field.clone();
+        let trace = self.trace.clone();
         hook.start(Box::new(move |event: KeyEvent| {
-            // Check lock-free suppression flag first (no mutex needed).
-            // During expansion, all events are silently discarded.
-            if suppressed.load(Ordering::SeqCst) {
+            // Passwords must never be buffered, recorded or expanded.
+            if in_password_field.load(Ordering::SeqCst) {
                 return;
             }
+            // Record before suppression so a replay sees the echoed output too.
+            if let Some(ref recorder) = *lock_mutex(&trace) {
+                recorder.record_key(&event);
+            }
+            // During expansion, events are held for replay without taking
+            // the input lock. Echoes of the expansion arriving late are
+            // dropped here.
+            {
+                let mut held = lock_mutex(&held_input);
+                if suppressed.load(Ordering::SeqCst) {
+                    held.hold(HeldEvent::Key(event));
+                    return;
+                }
+                if held.take_echo(&event) {
+                    return;
+                }
+            }
 
             let mut state = lock_mutex(&inner);
 
@@ -164,14 +416,7 @@ impl InputManager {
                 // Don't notify - silent clear to prevent re-triggering
             }
 
-            if state.is_paused {
-                return;
-            }
-            // Only process key presses.
-            if event.event_type != KeyEventType::Press {
-                return;
-            }
-            Self::process_key_event(&mut state, &event);
+            Self::handle_key(&mut state, &undo_armed, &event);
         }))?;
 
         tracing::info!("InputManager started");
@@ -204,18 +449,42 @@ impl InputManager {
         lock_mutex(&self.inner).is_paused
     }
 
-    /// Lock-free: suppress all input events (used during expansion).
+    /// Lock-free: hold all input events until released (used during expansion).
     /// Safe to call from within the on_buffer_change callback without deadlock.
     pub fn suppress(&self) {
+        self.suppress_epoch.fetch_add(1, Ordering::SeqCst);
         self.is_suppressed.store(true, Ordering::SeqCst);
         tracing::debug!("InputManager suppressed (lock-free)");
     }
 
-    /// Lock-free: stop suppressing input events.
+    /// Stop suppressing input events, discarding any that were held.
     /// Safe to call from within the on_buffer_change callback without deadlock.
+    /// The expansion worker uses [`InputGate::release`] instead, which
+    /// replays held input.
     pub fn unsuppress(&self) {
+        let mut held = lock_mutex(&self.held);
+        held.events.clear();
+        self.undo_armed.store(true, Ordering::SeqCst);
         self.is_suppressed.store(false, Ordering::SeqCst);
-        tracing::debug!("InputManager unsuppressed (lock-free)");
+        tracing::debug!("InputManager unsuppressed");
+    }
+
+    /// Lock-free: returns whether input is being held for an expansion.
+    pub fn is_suppressed(&self) -> bool {
+        self.is_suppressed.load(Ordering::SeqCst)
+    }
+
+    /// Returns a handle the expansion worker uses to hand input back once
+    /// an expansion has been typed.
+    pub fn gate(&self) -> InputGate {
+        InputGate {
+            inner: self.inner.clone(),
+            held: self.held.clone(),
+            suppressed: self.is_suppressed.clone(),
+            suppress_epoch: self.suppress_epoch.clone(),
+            undo_armed: self.undo_armed.clone(),
+            hears_output: self.hears_output.clone(),
+        }
     }
 
     /// Lock-free: request buffer clear on the next hook event.
@@ -224,20 +493,6 @@ impl InputManager {
         self.needs_buffer_clear.store(true, Ordering::SeqCst);
     }
 
-    /// Unsuppress input and clear buffer after a delay, on a background thread.
-    /// This ensures xdotool-generated events queued on the rdev thread are
-    /// discarded (suppressed) before we resume processing.
-    pub fn unsuppress_after(&self, delay: std::time::Duration) {
-        let suppressed = self.is_suppressed.clone();
-        let needs_clear = self.needs_buffer_clear.clone();
-        std::thread::spawn(move || {
-            std::thread::sleep(delay);
-            needs_clear.store(true, Ordering::SeqCst);
-            suppressed.store(false, Ordering::SeqCst);
-            tracing::debug!("InputManager unsuppressed after delay (lock-free)");
-        });
-    }
-
     /// Get the current buffer contents.
     pub fn buffer(&self) -> String {
         lock_mutex(&self.inner).buffer.clone()
@@ -250,26 +505,112 @@ impl InputManager {
 
     /// Notify the manager that a mouse click occurred, resetting the buffer.
     pub fn handle_mouse_click(&self) {
-        lock_mutex(&self.inner).clear_buffer();
+        Self::record_click(&self.inner, &self.undo_armed);
+    }
+
+    fn record_click(inner: &Mutex<InputManagerInner>, undo_armed: &AtomicBool) {
+        undo_armed.store(false, Ordering::SeqCst);
+        lock_mutex(inner).clear_buffer();
     }
 
     /// Notify the manager that the focused window may have changed.
     /// If it has, the buffer is cleared.
     pub fn handle_focus_change(&self, detector: &dyn FocusDetector) {
         if let Ok(info) = detector.get_active_window_info() {
-            let mut state = lock_mutex(&self.inner);
-            let changed = state
-                .last_window_info
-                .as_ref()
-                .map_or(true, |last| *last != info);
-            if changed {
-                tracing::debug!("Focus changed to: {} ({})", info.app_name, info.title);
-                state.last_window_info = Some(info);
-                state.clear_buffer();
+            Self::record_window(&self.inner, &self.undo_armed, info);
+        }
+    }
+
+    /// Returns a callback for [`FocusDetector::on_focus_change`] that clears
+    /// the buffer whenever a detector reports a switch to another window.
+    ///
+    /// The callback runs on the detector's thread. The buffer is cleared
+    /// without notifying `on_buffer_change`, so it only takes the input lock
+    /// and never waits on the engine.
+    pub fn focus_change_handler(&self) -> impl Fn(WindowInfo) + Send + Sync + 'static {
+        let inner = self.inner.clone();
+        let undo_armed = self.undo_armed.clone();
+        let trace = self.trace.clone();
+        move |info| {
+            if let Some(ref recorder) = *lock_mutex(&trace) {
+                recorder.record_focus(&info);
+            }
+            Self::record_window(&inner, &undo_armed, info)
+        }
+    }
+
+    /// Returns a callback that pauses input while a password field has
+    /// focus, for [`crate::platform::atspi::PasswordFieldWatcher`] or any
+    /// other source that can tell.
+    ///
+    /// Entering a field clears the buffer silently, so nothing typed before
+    /// it can combine with what is typed after it.
+    pub fn password_field_handler(&self) -> impl Fn(bool) + Send + Sync + 'static {
+        let inner = self.inner.clone();
+        let undo_armed = self.undo_armed.clone();
+        let in_password_field = self.in_password_field.clone();
+        move |active| {
+            in_password_field.store(active, Ordering::SeqCst);
+            if active {
+                undo_armed.store(false, Ordering::SeqCst);
+                lock_mutex(&inner).buffer.clear();
             }
         }
     }
 
+    /// Lock-free: returns whether a password field currently has focus.
+    pub fn in_password_field(&self) -> bool {
+        self.in_password_field.load(Ordering::SeqCst)
+    }
+
+    fn record_window(inner: &Mutex<InputManagerInner>, undo_armed: &AtomicBool, info: WindowInfo) {
+        let mut state = lock_mutex(inner);
+        let changed = state
+            .last_window_info
+            .as_ref()
+            .map_or(true, |last| *last != info);
+        if changed {
+            tracing::debug!("Focus changed to: {} ({})", info.app_name, info.title);
+            undo_armed.store(false, Ordering::SeqCst);
+            state.last_window_info = Some(info);
+            // Silent clear: an empty buffer can never match.
+            state.buffer.clear();
+        }
+    }
+
+    /// Disarms undo on any key press; if the press is the first unmodified
+    /// Backspace since the last unsuppress, offers it to the undo handler.
+    /// Returns true if the handler consumed it.
+    fn take_undo_backspace(
+        state: &InputManagerInner,
+        undo_armed: &AtomicBool,
+        event: &KeyEvent,
+    ) -> bool {
+        if !undo_armed.swap(false, Ordering::SeqCst) {
+            return false;
+        }
+        if event.key != Key::Backspace || event.modifiers.any() {
+            return false;
+        }
+        state.on_undo_backspace.as_ref().is_some_and(|handler| handler())
+    }
+
+    /// Feeds a key event from the hook, or replayed after suppression, to
+    /// the buffer.
+    fn handle_key(state: &mut InputManagerInner, undo_armed: &AtomicBool, event: &KeyEvent) {
+        if state.is_paused {
+            return;
+        }
+        // Only process key presses.
+        if event.event_type != KeyEventType::Press {
+            return;
+        }
+        if Self::take_undo_backspace(state, undo_armed, event) {
+            return;
+        }
+        Self::process_key_event(state, event);
+    }
+
     /// Process a single key event. Called from the hook callback.
     fn process_key_event(state: &mut InputManagerInner, event: &KeyEvent) {
         // If ctrl/alt/meta is held, reset buffer (likely a shortcut).
@@ -292,17 +633,17 @@ impl InputManager {
             Key::F(_) => {
                 state.clear_buffer();
             }
-            // Printable character or space.
+            // Printable character. Boundaries are kept so phrase keywords
+            // (e.g. "on my way") can span several words.
             Key::Char(c) => {
                 if state.is_word_boundary(*c) {
-                    state.clear_buffer();
+                    state.push_boundary(*c);
                 } else {
                     state.push_char(*c);
                 }
             }
             Key::Space => {
-                // Space is always a word boundary.
-                state.clear_buffer();
+                state.push_boundary(' ');
             }
             // Unknown keys reset the buffer.
             Key::Other(_) => {
@@ -318,6 +659,90 @@ impl Default for InputManager {
     }
 }
 
+/// Hands suppressed input back to the hook once an expansion job is done.
+///
+/// Holds only the manager's shared state, so the expansion worker can keep
+/// one without borrowing the `InputManager`.
+#[derive(Clone)]
+pub struct InputGate {
+    inner: Arc<Mutex<InputManagerInner>>,
+    held: Arc<Mutex<HeldInput>>,
+    suppressed: Arc<AtomicBool>,
+    suppress_epoch: Arc<AtomicU64>,
+    undo_armed: Arc<AtomicBool>,
+    hears_output: Arc<AtomicBool>,
+}
+
+impl InputGate {
+    /// Blocks until the hook callback that is running, if any, returns.
+    /// A job queued from the callback waits for this before sending output,
+    /// so the hook thread is free to deliver it.
+    pub fn wait_for_hook(&self) {
+        drop(lock_mutex(&self.inner));
+    }
+
+    /// Ends suppression after an expansion job. `echo` is the keystrokes the
+    /// job sent; they are dropped when the hook reports them. Everything else
+    /// held meanwhile is replayed in order, as if typed now.
+    ///
+    /// If a replayed key queues another expansion, replay stops there and
+    /// the rest stays held until that job releases input in turn.
+    ///
+    /// Returns how many of `echo` the hook had not reported yet, i.e. how
+    /// far delivery of the output lagged behind the job, or `None` if the
+    /// hook doesn't hear our output.
+    pub fn release(&self, echo: Vec<Key>) -> Option<usize> {
+        let epoch = self.suppress_epoch.load(Ordering::SeqCst);
+        let unheard = if self.hears_output.load(Ordering::SeqCst) {
+            let mut held = lock_mutex(&self.held);
+            held.expect_echo(echo);
+            Some(held.unheard_echo())
+        } else {
+            None
+        };
+        // Silent clear: nothing typed before the expansion can combine with
+        // what is typed after it.
+        lock_mutex(&self.inner).buffer.clear();
+        self.undo_armed.store(true, Ordering::SeqCst);
+
+        loop {
+            let typed = {
+                let mut held = lock_mutex(&self.held);
+                if held.events.is_empty() {
+                    if self.suppress_epoch.load(Ordering::SeqCst) == epoch {
+                        self.suppressed.store(false, Ordering::SeqCst);
+                        tracing::debug!("InputManager released");
+                    }
+                    return unheard;
+                }
+                held.take_typed()
+            };
+
+            let mut state = lock_mutex(&self.inner);
+            let mut typed = typed.into_iter();
+            while let Some(event) = typed.next() {
+                match event {
+                    HeldEvent::Key(event) => {
+                        InputManager::handle_key(&mut state, &self.undo_armed, &event)
+                    }
+                    HeldEvent::Click => {
+                        self.undo_armed.store(false, Ordering::SeqCst);
+                        state.clear_buffer();
+                    }
+                }
+                if self.suppress_epoch.load(Ordering::SeqCst) != epoch {
+                    drop(state);
+                    let mut held = lock_mutex(&self.held);
+                    for event in typed.rev() {
+                        held.events.push_front(event);
+                    }
+                    return unheard;
+                }
+            }
+        }
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Tests
 // ---------------------------------------------------------------------------
@@ -378,6 +803,32 @@ mod tests {
         assert_eq!(mgr.buffer(), "a");
     }
 
+    #[test]
+    fn test_backspace_removes_combining_accent_with_its_letter() {
+        let mgr = InputManager::new();
+        {
+            let mut state = lock_mutex(&mgr.inner);
+            for c in "cafe\u{301}".chars() {
+                InputManager::process_key_event(&mut state, &char_press(c));
+            }
+            InputManager::process_key_event(&mut state, &key_press(Key::Backspace));
+        }
+        assert_eq!(mgr.buffer(), "caf");
+    }
+
+    #[test]
+    fn test_backspace_removes_whole_zwj_emoji() {
+        let mgr = InputManager::new();
+        {
+            let mut state = lock_mutex(&mgr.inner);
+            for c in "hi\u{1F469}\u{200D}\u{1F4BB}".chars() {
+                InputManager::process_key_event(&mut state, &char_press(c));
+            }
+            InputManager::process_key_event(&mut state, &key_press(Key::Backspace));
+        }
+        assert_eq!(mgr.buffer(), "hi");
+    }
+
     #[test]
     fn test_backspace_on_empty_buffer() {
         let mgr = InputManager::new();
@@ -446,18 +897,18 @@ mod tests {
     }
 
     #[test]
-    fn test_space_clears_buffer() {
+    fn test_space_is_kept_in_buffer() {
         let mgr = InputManager::new();
         {
             let mut state = lock_mutex(&mgr.inner);
             InputManager::process_key_event(&mut state, &char_press('a'));
             InputManager::process_key_event(&mut state, &key_press(Key::Space));
         }
-        assert_eq!(mgr.buffer(), "");
+        assert_eq!(mgr.buffer(), "a ");
     }
 
     #[test]
-    fn test_word_boundary_chars_clear_buffer() {
+    fn test_word_boundary_chars_are_kept_in_buffer() {
         for c in ['.', ',', '!', '?', '(', ')', '/'] {
             let mgr = InputManager::new();
             {
@@ -467,13 +918,71 @@ mod tests {
             }
             assert_eq!(
                 mgr.buffer(),
-                "",
-                "Boundary char '{}' should clear buffer",
+                format!("x{}", c),
+                "Boundary char '{}' should be kept in buffer",
                 c
             );
         }
     }
 
+    #[test]
+    fn test_phrase_spans_multiple_words() {
+        let mgr = InputManager::new();
+        {
+            let mut state = lock_mutex(&mgr.inner);
+            for c in "on my way".chars() {
+                let event = if c == ' ' { key_press(Key::Space) } else { char_press(c) };
+                InputManager::process_key_event(&mut state, &event);
+            }
+        }
+        assert_eq!(mgr.buffer(), "on my way");
+    }
+
+    #[test]
+    fn test_buffer_keeps_only_recent_words() {
+        let mut mgr = InputManager::new();
+        mgr.set_max_buffer_words(2);
+        {
+            let mut state = lock_mutex(&mgr.inner);
+            for c in "one two three four".chars() {
+                InputManager::process_key_event(&mut state, &char_press(c));
+            }
+        }
+        // "one" is dropped once the boundary after "three" is typed.
+        assert_eq!(mgr.buffer(), "two three four");
+    }
+
+    #[test]
+    fn test_max_buffer_words_setter() {
+        let mgr = InputManager::new();
+        let set_words = mgr.max_buffer_words_setter();
+        set_words(1);
+        {
+            let mut state = lock_mutex(&mgr.inner);
+            for c in "one two three".chars() {
+                InputManager::process_key_event(&mut state, &char_press(c));
+            }
+        }
+        assert_eq!(mgr.buffer(), "two three");
+        set_words(0);
+        assert_eq!(lock_mutex(&mgr.inner).max_buffer_words, 1);
+    }
+
+    #[test]
+    fn test_backspace_over_boundary() {
+        let mgr = InputManager::new();
+        {
+            let mut state = lock_mutex(&mgr.inner);
+            for c in "on my".chars() {
+                InputManager::process_key_event(&mut state, &char_press(c));
+            }
+            InputManager::process_key_event(&mut state, &key_press(Key::Backspace));
+            InputManager::process_key_event(&mut state, &key_press(Key::Backspace));
+            InputManager::process_key_event(&mut state, &key_press(Key::Backspace));
+        }
+        assert_eq!(mgr.buffer(), "on");
+    }
+
     #[test]
     fn test_ctrl_modifier_clears_buffer() {
         let mgr = InputManager::new();
@@ -539,6 +1048,20 @@ mod tests {
         assert!(buf.ends_with('h'));
     }
 
+    #[test]
+    fn test_max_buffer_size_keeps_grapheme_clusters_whole() {
+        let mut mgr = InputManager::new();
+        mgr.set_max_buffer_size(4);
+        {
+            let mut state = lock_mutex(&mgr.inner);
+            for c in "ae\u{301}x".chars() {
+                InputManager::process_key_event(&mut state, &char_press(c));
+            }
+        }
+        // The accent is dropped with its letter rather than left dangling.
+        assert_eq!(mgr.buffer(), "x");
+    }
+
     #[test]
     fn test_clear_buffer_explicit() {
         let mgr = InputManager::new();
@@ -594,6 +1117,108 @@ mod tests {
         assert_eq!(mgr.buffer(), "");
     }
 
+    #[test]
+    fn test_focus_change_handler_clears_on_switch() {
+        let mgr = InputManager::new();
+        let mut detector = MockFocusDetector::new();
+        detector.on_focus_change(Box::new(mgr.focus_change_handler()));
+        let editor = WindowInfo {
+            title: "notes".into(),
+            app_name: "editor".into(),
+            process_id: Some(1),
+        };
+        detector.switch_focus(editor.clone());
+
+        {
+            let mut state = lock_mutex(&mgr.inner);
+            InputManager::process_key_event(&mut state, &char_press('h'));
+        }
+        mgr.undo_armed.store(true, Ordering::SeqCst);
+
+        // Repeated report of the same window keeps the buffer.
+        detector.switch_focus(editor);
+        assert_eq!(mgr.buffer(), "h");
+        assert!(mgr.undo_armed.load(Ordering::SeqCst));
+
+        // A real switch clears it and disarms undo.
+        detector.switch_focus(WindowInfo {
+            title: "shell".into(),
+            app_name: "terminal".into(),
+            process_id: Some(2),
+        });
+        assert_eq!(mgr.buffer(), "");
+        assert!(!mgr.undo_armed.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_trace_records_suppressed_keys_but_not_passwords() {
+        use crate::managers::keystroke_trace::{parse_trace, TraceEvent};
+        use std::io::Write;
+
+        #[derive(Clone, Default)]
+        struct Sink(Arc<Mutex<Vec<u8>>>);
+        impl Write for Sink {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().extend_from_slice(buf);
+                Ok(buf.len())
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut mgr = InputManager::new();
+        let hook = MockKeyboardHook::new();
+        mgr.set_keyboard_hook(Box::new(hook.clone()));
+        mgr.start().unwrap();
+        let sink = Sink::default();
+        mgr.set_trace_recorder(Some(Arc::new(TraceRecorder::new(Box::new(sink.clone())))));
+        assert!(mgr.is_recording_trace());
+
+        hook.inject_event(char_press('a'));
+        mgr.suppress();
+        hook.inject_event(char_press('b'));
+        mgr.unsuppress();
+        let password_field = mgr.password_field_handler();
+        password_field(true);
+        hook.inject_event(char_press('c'));
+        password_field(false);
+        hook.inject_mouse_event(MouseEvent::click());
+
+        mgr.set_trace_recorder(None);
+        hook.inject_event(char_press('d'));
+
+        let text = String::from_utf8(sink.0.lock().unwrap().clone()).unwrap();
+        let events: Vec<TraceEvent> = parse_trace(&text).unwrap().into_iter().map(|e| e.event).collect();
+        assert_eq!(events.len(), 3);
+        assert!(matches!(&events[0], TraceEvent::Key { key: Key::Char('a'), .. }));
+        assert!(matches!(&events[1], TraceEvent::Key { key: Key::Char('b'), .. }));
+        assert_eq!(events[2], TraceEvent::Click);
+    }
+
+    #[test]
+    fn test_password_field_discards_keys() {
+        let mut mgr = InputManager::new();
+        let hook = MockKeyboardHook::new();
+        mgr.set_keyboard_hook(Box::new(hook.clone()));
+        mgr.start().unwrap();
+        let password_field = mgr.password_field_handler();
+
+        hook.inject_event(char_press('p'));
+        password_field(true);
+        assert!(mgr.in_password_field());
+        assert_eq!(mgr.buffer(), "");
+
+        hook.inject_event(char_press('s'));
+        hook.inject_event(char_press('3'));
+        assert_eq!(mgr.buffer(), "");
+
+        password_field(false);
+        assert!(!mgr.in_password_field());
+        hook.inject_event(char_press('o'));
+        assert_eq!(mgr.buffer(), "o");
+    }
+
     #[test]
     fn test_pause_and_resume() {
         let mgr = InputManager::new();
@@ -631,22 +1256,297 @@ mod tests {
         // Only treat '.' as a word boundary.
         mgr.set_word_boundary_chars(vec!['.']);
 
+        mgr.set_max_buffer_words(1);
+
         {
             let mut state = lock_mutex(&mgr.inner);
-            // Comma should NOT clear buffer now.
+            // Comma is part of the word now.
             InputManager::process_key_event(&mut state, &char_press('a'));
             InputManager::process_key_event(&mut state, &char_press(','));
+            InputManager::process_key_event(&mut state, &char_press('b'));
+            InputManager::process_key_event(&mut state, &char_press('.'));
         }
-        assert_eq!(mgr.buffer(), "a,");
+        assert_eq!(mgr.buffer(), "a,b.");
 
         {
             let mut state = lock_mutex(&mgr.inner);
-            // Dot SHOULD clear buffer.
+            // Dot separates words, so the oldest word is trimmed.
+            InputManager::process_key_event(&mut state, &char_press('c'));
             InputManager::process_key_event(&mut state, &char_press('.'));
         }
+        assert_eq!(mgr.buffer(), "c.");
+    }
+
+    // -- Undo Backspace --
+
+    /// Helper: start a manager on a mock hook whose undo handler records
+    /// calls and returns `consume`.
+    fn undo_fixture(consume: bool) -> (InputManager, MockKeyboardHook, Arc<AtomicUsize>) {
+        let mut mgr = InputManager::new();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let calls_clone = calls.clone();
+        mgr.on_undo_backspace(move || {
+            calls_clone.fetch_add(1, Ordering::SeqCst);
+            consume
+        });
+        let hook = MockKeyboardHook::new();
+        mgr.set_keyboard_hook(Box::new(hook.clone()));
+        mgr.start().unwrap();
+        (mgr, hook, calls)
+    }
+
+    #[test]
+    fn test_backspace_not_offered_before_unsuppress() {
+        let (mgr, hook, calls) = undo_fixture(true);
+        hook.inject_event(char_press('a'));
+        hook.inject_event(key_press(Key::Backspace));
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+        assert_eq!(mgr.buffer(), "");
+    }
+
+    #[test]
+    fn test_first_backspace_after_unsuppress_offered_to_handler() {
+        let (mgr, hook, calls) = undo_fixture(true);
+        mgr.suppress();
+        mgr.unsuppress();
+        hook.inject_event(key_press(Key::Backspace));
+        hook.inject_event(key_press(Key::Backspace));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_other_key_disarms_undo() {
+        let (mgr, hook, calls) = undo_fixture(true);
+        mgr.unsuppress();
+        hook.inject_event(char_press('x'));
+        hook.inject_event(key_press(Key::Backspace));
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+        assert_eq!(mgr.buffer(), "");
+    }
+
+    #[test]
+    fn test_modified_backspace_not_offered() {
+        let (mgr, hook, calls) = undo_fixture(true);
+        mgr.unsuppress();
+        let mods = Modifiers {
+            ctrl: true,
+            ..Default::default()
+        };
+        hook.inject_event(modified_press(Key::Backspace, mods));
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_mouse_click_disarms_undo() {
+        let (mgr, hook, calls) = undo_fixture(true);
+        mgr.unsuppress();
+        mgr.handle_mouse_click();
+        hook.inject_event(key_press(Key::Backspace));
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_hook_mouse_click_clears_buffer_and_disarms_undo() {
+        let (mgr, hook, calls) = undo_fixture(true);
+        hook.inject_event(char_press('b'));
+        hook.inject_event(char_press('t'));
+        mgr.unsuppress();
+
+        hook.inject_mouse_event(MouseEvent::click());
+        assert_eq!(mgr.buffer(), "");
+        hook.inject_event(key_press(Key::Backspace));
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_hook_mouse_click_ignored_while_suppressed() {
+        let (mgr, hook, _calls) = undo_fixture(true);
+        hook.inject_event(char_press('s'));
+        mgr.suppress();
+        hook.inject_mouse_event(MouseEvent::click());
+        assert_eq!(mgr.buffer(), "s");
+    }
+
+    #[test]
+    fn test_declined_undo_processes_backspace_normally() {
+        let (mgr, hook, calls) = undo_fixture(false);
+        hook.inject_event(char_press('a'));
+        hook.inject_event(char_press('b'));
+        mgr.unsuppress();
+        hook.inject_event(key_press(Key::Backspace));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(mgr.buffer(), "a");
+    }
+
+    #[test]
+    fn test_release_arms_undo() {
+        let (mgr, hook, calls) = undo_fixture(true);
+        mgr.suppress();
+        mgr.gate().release(Vec::new());
+        assert!(!mgr.is_suppressed());
+        hook.inject_event(key_press(Key::Backspace));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    // -- Held input --
+
+    /// Helper: start a manager on a mock hook whose buffer callback
+    /// suppresses input whenever the buffer ends with `trigger`, like the
+    /// engine does on a match.
+    fn held_fixture(trigger: &'static str) -> (InputManager, MockKeyboardHook) {
+        let mut mgr = InputManager::new();
+        let suppressed = mgr.is_suppressed.clone();
+        let epoch = mgr.suppress_epoch.clone();
+        mgr.on_buffer_change(move |buffer| {
+            if buffer.ends_with(trigger) {
+                epoch.fetch_add(1, Ordering::SeqCst);
+                suppressed.store(true, Ordering::SeqCst);
+            }
+        });
+        let hook = MockKeyboardHook::new();
+        mgr.set_keyboard_hook(Box::new(hook.clone()));
+        mgr.start().unwrap();
+        (mgr, hook)
+    }
+
+    #[test]
+    fn test_keys_held_while_suppressed_are_replayed() {
+        let (mgr, hook) = held_fixture("#");
+        hook.inject_event(char_press('a'));
+        mgr.suppress();
+        hook.inject_event(char_press('b'));
+        hook.inject_event(char_press('c'));
+        assert_eq!(mgr.buffer(), "a");
+
+        mgr.gate().release(Vec::new());
+        // The buffer is cleared before the held keys are replayed.
+        assert_eq!(mgr.buffer(), "bc");
+        assert!(!mgr.is_suppressed());
+    }
+
+    #[test]
+    fn test_release_drops_echoed_output() {
+        let (mgr, hook) = held_fixture("#");
+        mgr.suppress();
+        hook.inject_event(key_press(Key::Backspace));
+        hook.inject_event(char_press('x'));
+        hook.inject_event(char_press('o'));
+        hook.inject_event(char_press('k'));
+
+        mgr.gate()
+            .release(vec![Key::Backspace, Key::Char('o'), Key::Char('k')]);
+        assert_eq!(mgr.buffer(), "x");
+    }
+
+    #[test]
+    fn test_late_echo_dropped_after_release() {
+        let (mgr, hook) = held_fixture("#");
+        mgr.suppress();
+        mgr.gate().release(vec![Key::Char('o'), Key::Char('k')]);
+        hook.inject_event(char_press('o'));
+        hook.inject_event(char_press('k'));
+        hook.inject_event(char_press('o'));
+        assert_eq!(mgr.buffer(), "o");
+    }
+
+    #[test]
+    fn test_echo_expires_after_grace_period() {
+        let (mgr, hook) = held_fixture("#");
+        mgr.suppress();
+        mgr.gate().release(vec![Key::Char('o')]);
+        std::thread::sleep(ECHO_GRACE + std::time::Duration::from_millis(20));
+        hook.inject_event(char_press('o'));
+        assert_eq!(mgr.buffer(), "o");
+    }
+
+    #[test]
+    fn test_echo_ignored_when_hook_cannot_hear_output() {
+        let (mgr, hook) = held_fixture("#");
+        mgr.hears_output.store(false, Ordering::SeqCst);
+        mgr.suppress();
+        hook.inject_event(char_press('o'));
+        assert_eq!(mgr.gate().release(vec![Key::Char('o')]), None);
+        assert_eq!(mgr.buffer(), "o");
+    }
+
+    #[test]
+    fn test_release_counts_unheard_echo() {
+        let (mgr, hook) = held_fixture("#");
+        mgr.suppress();
+        hook.inject_event(key_press(Key::Backspace));
+        hook.inject_event(char_press('x'));
+        hook.inject_event(char_press('o'));
+
+        let echo = vec![Key::Backspace, Key::Char('o'), Key::Char('k'), Key::Char('!')];
+        assert_eq!(mgr.gate().release(echo), Some(2));
+        // The late echoes are still dropped when they arrive.
+        hook.inject_event(char_press('k'));
+        hook.inject_event(char_press('!'));
+        assert_eq!(mgr.buffer(), "x");
+    }
+
+    #[test]
+    fn test_release_reports_no_lag_when_all_echo_heard() {
+        let (mgr, hook) = held_fixture("#");
+        mgr.suppress();
+        hook.inject_event(char_press('o'));
+        hook.inject_event(char_press('k'));
+        assert_eq!(mgr.gate().release(vec![Key::Char('o'), Key::Char('k')]), Some(0));
+    }
+
+    #[test]
+    fn test_replay_stops_when_suppressed_again() {
+        let (mgr, hook) = held_fixture("#");
+        mgr.suppress();
+        for c in ['a', '#', 'b', 'c'] {
+            hook.inject_event(char_press(c));
+        }
+
+        mgr.gate().release(Vec::new());
+        assert_eq!(mgr.buffer(), "a#");
+        assert!(mgr.is_suppressed());
+
+        // The next job's release replays the rest.
+        mgr.gate().release(Vec::new());
+        assert_eq!(mgr.buffer(), "bc");
+        assert!(!mgr.is_suppressed());
+    }
+
+    #[test]
+    fn test_click_held_while_suppressed_is_replayed() {
+        let (mgr, hook) = held_fixture("#");
+        mgr.suppress();
+        hook.inject_event(char_press('a'));
+        hook.inject_mouse_event(MouseEvent::click());
+        hook.inject_event(char_press('b'));
+        mgr.gate().release(Vec::new());
+        assert_eq!(mgr.buffer(), "b");
+    }
+
+    #[test]
+    fn test_unsuppress_discards_held_input() {
+        let (mgr, hook) = held_fixture("#");
+        mgr.suppress();
+        hook.inject_event(char_press('a'));
+        mgr.unsuppress();
+        mgr.suppress();
+        mgr.gate().release(Vec::new());
         assert_eq!(mgr.buffer(), "");
     }
 
+    #[test]
+    fn test_held_input_is_bounded() {
+        let (mut mgr, hook) = held_fixture("#");
+        mgr.set_max_buffer_size(2 * MAX_HELD_EVENTS);
+        mgr.suppress();
+        hook.inject_event(char_press('a'));
+        for _ in 0..MAX_HELD_EVENTS {
+            hook.inject_event(char_press('b'));
+        }
+        mgr.gate().release(Vec::new());
+        assert_eq!(mgr.buffer(), "b".repeat(MAX_HELD_EVENTS));
+    }
+
     // -- Integration test with MockKeyboardHook --
 
     #[test]
@@ -0,0 +1,838 @@
+//! Keystroke traces for reproducing expansion engine behaviour.
+//!
+//! A trace is a JSON Lines file: one [`TraceEntry`] per line, each holding
+//! the milliseconds since recording started and a key event, mouse click or
+//! focus change, e.g.
+//!
+//! ```text
+//! {"atMs":0,"type":"focus","window":{"title":"notes","appName":"gedit","processId":null}}
+//! {"atMs":120,"type":"key","key":{"char":"g"},"eventType":"press"}
+//! {"atMs":160,"type":"key","key":"backspace","eventType":"press"}
+//! {"atMs":900,"type":"click"}
+//! ```
+//!
+//! [`TraceRecorder`] writes traces from the live input path when the
+//! `recordKeystrokeTrace` debug preference is on. [`ReplayHarness`] feeds a
+//! trace through a full [`EngineManager`] built on the mock platform and
+//! records what the engine would have sent, so suppression windows, buffer
+//! clears and undo can be regression-tested with real timing.
+
+use std::fs::OpenOptions;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::managers::clipboard_manager::{ClipboardError, ClipboardProvider};
+use crate::managers::engine_manager::EngineManager;
+use crate::managers::output_backend::{OutputAction, RecordingBackend};
+use crate::models::{Combo, Preferences};
+use crate::platform::keyboard_hook::{Key, KeyEvent, KeyEventType, Modifiers, MouseEvent, WindowInfo};
+use crate::platform::mock::{MockFocusDetector, MockKeyboardHook};
+
+/// How long [`ReplayHarness::settle`] waits for the engine to finish its
+/// expansions before giving up.
+pub const REPLAY_IDLE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Errors from reading or writing traces.
+#[derive(Debug, Error)]
+pub enum TraceError {
+    #[error("Trace I/O error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("Invalid trace entry on line {line}: {message}")]
+    Parse { line: usize, message: String },
+}
+
+/// One input event captured by the hook.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum TraceEvent {
+    /// A key press or release.
+    #[serde(rename_all = "camelCase")]
+    Key {
+        key: Key,
+        event_type: KeyEventType,
+        #[serde(default)]
+        modifiers: Modifiers,
+    },
+    /// A mouse button press.
+    Click,
+    /// The focus detector reported a window.
+    Focus { window: WindowInfo },
+}
+
+/// A trace line: an event and when it happened.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TraceEntry {
+    /// Milliseconds since the trace started.
+    pub at_ms: u64,
+    #[serde(flatten)]
+    pub event: TraceEvent,
+}
+
+impl TraceEntry {
+    /// A key press with no modifiers, for writing traces by hand.
+    pub fn press(at_ms: u64, key: Key) -> Self {
+        Self {
+            at_ms,
+            event: TraceEvent::Key {
+                key,
+                event_type: KeyEventType::Press,
+                modifiers: Modifiers::default(),
+            },
+        }
+    }
+}
+
+/// Parses a JSON Lines trace. Blank lines are skipped.
+pub fn parse_trace(text: &str) -> Result<Vec<TraceEntry>, TraceError> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(idx, line)| {
+            serde_json::from_str(line).map_err(|e| TraceError::Parse {
+                line: idx + 1,
+                message: e.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Reads a trace file.
+pub fn read_trace(path: &Path) -> Result<Vec<TraceEntry>, TraceError> {
+    parse_trace(&std::fs::read_to_string(path)?)
+}
+
+// ---------------------------------------------------------------------------
+// Recording
+// ---------------------------------------------------------------------------
+
+/// Appends events to a trace as they happen. Shared between the hook,
+/// mouse and focus callbacks, so every method takes `&self`.
+pub struct TraceRecorder {
+    writer: Mutex<Box<dyn Write + Send>>,
+    started: Instant,
+}
+
+impl TraceRecorder {
+    /// Records to `writer`; timestamps count from now.
+    pub fn new(writer: Box<dyn Write + Send>) -> Self {
+        Self {
+            writer: Mutex::new(writer),
+            started: Instant::now(),
+        }
+    }
+
+    /// Creates a new trace file at `path`, failing if one already exists.
+    /// The trace holds everything typed, so on Unix only the owner can read
+    /// it.
+    pub fn create(path: &Path) -> Result<Self, TraceError> {
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let file = options.open(path)?;
+        Ok(Self::new(Box::new(BufWriter::new(file))))
+    }
+
+    /// Records a key event at the time the hook saw it.
+    pub fn record_key(&self, event: &KeyEvent) {
+        self.write(
+            event.timestamp,
+            TraceEvent::Key {
+                key: event.key.clone(),
+                event_type: event.event_type,
+                modifiers: event.modifiers,
+            },
+        );
+    }
+
+    /// Records a mouse button press.
+    pub fn record_click(&self, event: &MouseEvent) {
+        self.write(event.timestamp, TraceEvent::Click);
+    }
+
+    /// Records a focus report.
+    pub fn record_focus(&self, window: &WindowInfo) {
+        self.write(
+            Instant::now(),
+            TraceEvent::Focus {
+                window: window.clone(),
+            },
+        );
+    }
+
+    fn write(&self, at: Instant, event: TraceEvent) {
+        let entry = TraceEntry {
+            at_ms: at.saturating_duration_since(self.started).as_millis() as u64,
+            event,
+        };
+        let line = match serde_json::to_string(&entry) {
+            Ok(line) => line,
+            Err(e) => {
+                tracing::warn!("Failed to serialize trace entry: {}", e);
+                return;
+            }
+        };
+        let mut writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        // Flush per line so a crash still leaves a usable trace.
+        if let Err(e) = writeln!(writer, "{}", line).and_then(|_| writer.flush()) {
+            tracing::warn!("Failed to write keystroke trace: {}", e);
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Replay
+// ---------------------------------------------------------------------------
+
+/// In-memory clipboard for replays.
+#[derive(Default)]
+struct ReplayClipboard {
+    text: String,
+}
+
+impl ClipboardProvider for ReplayClipboard {
+    fn read_text(&mut self) -> Result<String, ClipboardError> {
+        Ok(self.text.clone())
+    }
+
+    fn write_text(&mut self, text: &str) -> Result<(), ClipboardError> {
+        self.text = text.to_string();
+        Ok(())
+    }
+}
+
+/// A running [`EngineManager`] on the mock platform whose output goes to
+/// a [`RecordingBackend`] instead of the OS.
+pub struct ReplayHarness {
+    engine: EngineManager,
+    hook: MockKeyboardHook,
+    focus: MockFocusDetector,
+    output: RecordingBackend,
+}
+
+impl ReplayHarness {
+    /// Starts an engine with `combos` and `prefs` loaded.
+    pub fn new(combos: &[Combo], prefs: &Preferences) -> Self {
+        let hook = MockKeyboardHook::new();
+        let focus = MockFocusDetector::new();
+        let engine = EngineManager::with_platform(
+            Box::new(hook.clone()),
+            Box::new(focus.clone()),
+            Box::new(ReplayClipboard::default()),
+        );
+
+        let output = RecordingBackend::new();
+        engine
+            .set_output_backend(Box::new(output.clone()))
+            .expect("engine lock");
+        engine.load_combos(combos).expect("engine lock");
+        engine.apply_preferences(prefs).expect("engine lock");
+        engine.start().expect("mock hook starts");
+
+        Self {
+            engine,
+            hook,
+            focus,
+            output,
+        }
+    }
+
+    /// The engine under test.
+    pub fn engine(&self) -> &EngineManager {
+        &self.engine
+    }
+
+    /// Feeds `entries` to the engine as the user would have produced them:
+    /// each event waits until the engine has finished the expansions the
+    /// events before it started, then for its gap after the previous event
+    /// (or after the call, for the first). Returns once the engine is idle
+    /// again.
+    ///
+    /// Gaps are never shortened, so time-based rules such as the undo
+    /// window see at least the recorded delay however long output takes.
+    /// To act while an expansion is running, hold its output with
+    /// [`ReplayHarness::hold_output`] and use [`ReplayHarness::feed`].
+    pub fn replay(&self, entries: &[TraceEntry]) {
+        let mut previous_at_ms = 0;
+        for entry in entries {
+            self.settle();
+            let gap = entry.at_ms.saturating_sub(previous_at_ms);
+            if gap > 0 {
+                thread::sleep(Duration::from_millis(gap));
+            }
+            previous_at_ms = entry.at_ms;
+            self.feed(std::slice::from_ref(entry));
+        }
+        self.settle();
+    }
+
+    /// Feeds `entries` to the engine straight away, in order, ignoring
+    /// their timing and whatever the engine is doing.
+    pub fn feed(&self, entries: &[TraceEntry]) {
+        for entry in entries {
+            match &entry.event {
+                TraceEvent::Key {
+                    key,
+                    event_type,
+                    modifiers,
+                } => self
+                    .hook
+                    .inject_event(KeyEvent::new(key.clone(), *event_type, *modifiers)),
+                TraceEvent::Click => self.hook.inject_mouse_event(MouseEvent::click()),
+                TraceEvent::Focus { window } => self.focus.switch_focus(window.clone()),
+            }
+        }
+    }
+
+    /// Waits until no expansion or undo is running or queued.
+    ///
+    /// # Panics
+    ///
+    /// If the engine is still busy after [`REPLAY_IDLE_TIMEOUT`], e.g.
+    /// because its output is held.
+    pub fn settle(&self) {
+        let idle = self.engine.wait_until_idle(REPLAY_IDLE_TIMEOUT).expect("engine lock");
+        assert!(idle, "engine still busy after {:?}", REPLAY_IDLE_TIMEOUT);
+    }
+
+    /// Stops the engine's output before its next operation, until
+    /// [`ReplayHarness::release_output`].
+    pub fn hold_output(&self) {
+        self.output.hold();
+    }
+
+    /// Waits until a running job is stopped by [`ReplayHarness::hold_output`].
+    ///
+    /// # Panics
+    ///
+    /// If no job reaches the hold within [`REPLAY_IDLE_TIMEOUT`].
+    pub fn wait_for_held_output(&self) {
+        assert!(
+            self.output.wait_for_held(REPLAY_IDLE_TIMEOUT),
+            "no output held after {:?}",
+            REPLAY_IDLE_TIMEOUT
+        );
+    }
+
+    /// Lets held output through.
+    pub fn release_output(&self) {
+        self.output.release();
+    }
+
+    /// Everything the engine has output so far, oldest first.
+    pub fn outputs(&self) -> Vec<OutputAction> {
+        self.output.actions()
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    use crate::managers::engine_manager::EngineStatus;
+    use crate::managers::output_backend::KeyChord;
+    use crate::models::combo::{ComboBuilder, SnippetFormat};
+    use crate::models::matching::MatchingMode;
+    use crate::models::preferences::{PasteMethod, PasteRule, PasteRuleField};
+    use crate::utils::rich_text::html_to_text;
+
+    /// Shared buffer so tests can read what a recorder wrote.
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl SharedBuffer {
+        fn text(&self) -> String {
+            String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+        }
+    }
+
+    fn combo(keyword: &str, snippet: &str) -> Combo {
+        ComboBuilder::new()
+            .keyword(keyword)
+            .snippet(snippet)
+            .matching_mode(MatchingMode::Strict)
+            .build()
+            .unwrap()
+    }
+
+    /// Presses each character of `text`, `gap_ms` apart, starting at `at_ms`.
+    fn typing(at_ms: u64, gap_ms: u64, text: &str) -> Vec<TraceEntry> {
+        text.chars()
+            .enumerate()
+            .map(|(i, c)| {
+                let key = if c == ' ' { Key::Space } else { Key::Char(c) };
+                TraceEntry::press(at_ms + i as u64 * gap_ms, key)
+            })
+            .collect()
+    }
+
+    /// Focuses a window of class `app_name` at time 0.
+    fn focus(app_name: &str, title: &str) -> TraceEntry {
+        TraceEntry {
+            at_ms: 0,
+            event: TraceEvent::Focus {
+                window: WindowInfo {
+                    title: title.into(),
+                    app_name: app_name.into(),
+                    process_id: Some(7),
+                },
+            },
+        }
+    }
+
+    fn prefs_with_rule(rule: PasteRule) -> Preferences {
+        Preferences {
+            paste_method: PasteMethod::Clipboard,
+            paste_rules: vec![rule],
+            ..Default::default()
+        }
+    }
+
+    // ── Format ──
+
+    #[test]
+    fn test_parse_trace_lines() {
+        let trace = r#"
+{"atMs":0,"type":"focus","window":{"title":"notes","appName":"gedit","processId":42}}
+{"atMs":120,"type":"key","key":{"char":"g"},"eventType":"press"}
+{"atMs":130,"type":"key","key":"backspace","eventType":"release","modifiers":{"shift":true}}
+
+{"atMs":900,"type":"click"}
+"#;
+        let entries = parse_trace(trace).unwrap();
+        assert_eq!(entries.len(), 4);
+        assert_eq!(
+            entries[0].event,
+            TraceEvent::Focus {
+                window: WindowInfo {
+                    title: "notes".into(),
+                    app_name: "gedit".into(),
+                    process_id: Some(42),
+                }
+            }
+        );
+        assert_eq!(entries[1], TraceEntry::press(120, Key::Char('g')));
+        assert_eq!(
+            entries[2].event,
+            TraceEvent::Key {
+                key: Key::Backspace,
+                event_type: KeyEventType::Release,
+                modifiers: Modifiers { shift: true, ..Default::default() },
+            }
+        );
+        assert_eq!(entries[3], TraceEntry { at_ms: 900, event: TraceEvent::Click });
+    }
+
+    #[test]
+    fn test_parse_trace_reports_line() {
+        let err = parse_trace("{\"atMs\":0,\"type\":\"click\"}\n{\"atMs\":1}\n").unwrap_err();
+        assert!(matches!(err, TraceError::Parse { line: 2, .. }));
+    }
+
+    #[test]
+    fn test_recorder_roundtrip() {
+        let buffer = SharedBuffer::default();
+        let recorder = TraceRecorder::new(Box::new(buffer.clone()));
+        recorder.record_focus(&WindowInfo::default());
+        recorder.record_key(&KeyEvent::new(Key::Char('x'), KeyEventType::Press, Modifiers::default()));
+        recorder.record_click(&MouseEvent::click());
+
+        let entries = parse_trace(&buffer.text()).unwrap();
+        assert_eq!(entries.len(), 3);
+        assert!(matches!(entries[0].event, TraceEvent::Focus { .. }));
+        assert_eq!(entries[1].event, TraceEntry::press(0, Key::Char('x')).event);
+        assert_eq!(entries[2].event, TraceEvent::Click);
+        assert!(entries.windows(2).all(|pair| pair[0].at_ms <= pair[1].at_ms));
+    }
+
+    #[test]
+    fn test_recorder_creates_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("trace.jsonl");
+        let recorder = TraceRecorder::create(&path).unwrap();
+        recorder.record_click(&MouseEvent::click());
+        assert_eq!(read_trace(&path).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_recorder_file_is_private_and_new() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("trace.jsonl");
+        std::fs::write(&path, "").unwrap();
+        assert!(TraceRecorder::create(&path).is_err());
+
+        std::fs::remove_file(&path).unwrap();
+        let _recorder = TraceRecorder::create(&path).unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+
+    // ── Replay ──
+
+    #[test]
+    fn test_replay_expands_keyword() {
+        let harness = ReplayHarness::new(&[combo("gh", "https://github.com")], &Preferences::default());
+        harness.replay(&typing(0, 20, "gh"));
+        assert_eq!(
+            harness.outputs(),
+            vec![
+                OutputAction::DeleteGraphemes(2),
+                OutputAction::Paste("https://github.com".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_replay_combining_accent_keyword_deletes_graphemes() {
+        // "café" typed with a combining accent: five chars, four Backspaces.
+        let harness =
+            ReplayHarness::new(&[combo("cafe\u{301}", "Café au lait")], &Preferences::default());
+        harness.replay(&typing(0, 20, "cafe\u{301}"));
+        assert_eq!(
+            harness.outputs(),
+            vec![
+                OutputAction::DeleteGraphemes(4),
+                OutputAction::Paste("Café au lait".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_replay_zwj_emoji_keyword_deletes_graphemes() {
+        let keyword = "\u{1F469}\u{200D}\u{1F4BB}dev";
+        let harness = ReplayHarness::new(&[combo(keyword, "Jane Doe")], &Preferences::default());
+        harness.replay(&typing(0, 20, keyword));
+        assert_eq!(
+            harness.outputs(),
+            vec![OutputAction::DeleteGraphemes(4), OutputAction::Paste("Jane Doe".into())]
+        );
+    }
+
+    #[test]
+    fn test_replay_echoed_output_is_ignored() {
+        // The snippet ends in the keyword; the hook hearing the expansion
+        // being typed must not expand it again.
+        let prefs = Preferences {
+            paste_method: PasteMethod::SimulateKeystrokes,
+            ..Default::default()
+        };
+        let harness = ReplayHarness::new(&[combo("gh", "see gh")], &prefs);
+        let mut trace = typing(0, 20, "gh");
+        trace.push(TraceEntry::press(40, Key::Backspace));
+        trace.push(TraceEntry::press(45, Key::Backspace));
+        trace.extend(typing(50, 5, "see gh"));
+        harness.replay(&trace);
+        assert_eq!(harness.outputs().len(), 2);
+    }
+
+    #[test]
+    fn test_replay_keys_typed_during_expansion_are_replayed() {
+        let harness = ReplayHarness::new(
+            &[combo("gh", "https://github.com"), combo("omw", "on my way")],
+            &Preferences::default(),
+        );
+        harness.hold_output();
+        harness.feed(&typing(0, 20, "gh"));
+        harness.wait_for_held_output();
+        // Typed while the first expansion is running, so the hook is
+        // holding input.
+        harness.feed(&typing(0, 20, "omw"));
+        harness.release_output();
+        harness.settle();
+        assert_eq!(
+            harness.outputs(),
+            vec![
+                OutputAction::DeleteGraphemes(2),
+                OutputAction::Paste("https://github.com".into()),
+                OutputAction::DeleteGraphemes(3),
+                OutputAction::Paste("on my way".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_replay_click_breaks_keyword() {
+        let harness = ReplayHarness::new(&[combo("gh", "https://github.com")], &Preferences::default());
+        harness.replay(&[
+            TraceEntry::press(0, Key::Char('g')),
+            TraceEntry { at_ms: 20, event: TraceEvent::Click },
+            TraceEntry::press(40, Key::Char('h')),
+        ]);
+        assert!(harness.outputs().is_empty());
+    }
+
+    #[test]
+    fn test_replay_focus_change_breaks_keyword() {
+        let harness = ReplayHarness::new(&[combo("gh", "https://github.com")], &Preferences::default());
+        harness.replay(&[
+            TraceEntry::press(0, Key::Char('g')),
+            TraceEntry {
+                at_ms: 20,
+                event: TraceEvent::Focus {
+                    window: WindowInfo {
+                        title: "shell".into(),
+                        app_name: "terminal".into(),
+                        process_id: Some(7),
+                    },
+                },
+            },
+            TraceEntry::press(40, Key::Char('h')),
+        ]);
+        assert!(harness.outputs().is_empty());
+    }
+
+    #[test]
+    fn test_replay_focus_switch_during_expansion_aborts() {
+        let harness = ReplayHarness::new(&[combo("gh", "https://github.com")], &Preferences::default());
+        harness.replay(&[focus("gedit", "notes.txt")]);
+        harness.hold_output();
+        harness.feed(&typing(0, 20, "gh"));
+        harness.wait_for_held_output();
+        // Alt-Tab while the keyword is being deleted.
+        harness.feed(&[TraceEntry {
+            at_ms: 0,
+            event: TraceEvent::Focus {
+                window: WindowInfo {
+                    title: "shell".into(),
+                    app_name: "terminal".into(),
+                    process_id: Some(8),
+                },
+            },
+        }]);
+        harness.release_output();
+        harness.settle();
+        // The snippet is not pasted into the other window.
+        assert_eq!(harness.outputs(), vec![OutputAction::DeleteGraphemes(2)]);
+    }
+
+    #[test]
+    fn test_replay_pause_during_expansion_stops_output() {
+        let harness = ReplayHarness::new(&[combo("gh", "https://github.com")], &Preferences::default());
+        harness.hold_output();
+        harness.feed(&typing(0, 20, "gh"));
+        harness.wait_for_held_output();
+        let job = harness
+            .engine()
+            .running_expansion()
+            .unwrap()
+            .expect("expansion running");
+        thread::scope(|scope| {
+            // Blocks on the engine lock until the held job finishes.
+            let pausing = scope.spawn(|| harness.engine().pause());
+            let deadline = Instant::now() + REPLAY_IDLE_TIMEOUT;
+            while !job.is_cancelled() && Instant::now() < deadline {
+                thread::sleep(Duration::from_millis(1));
+            }
+            let cancelled = job.is_cancelled();
+            harness.release_output();
+            pausing.join().unwrap().unwrap();
+            assert!(cancelled, "pause did not cancel the running expansion");
+        });
+        harness.settle();
+        // The keyword was being deleted; the snippet is never pasted.
+        assert_eq!(harness.outputs(), vec![OutputAction::DeleteGraphemes(2)]);
+        assert_eq!(harness.engine().status().unwrap(), EngineStatus::Paused);
+    }
+
+    #[test]
+    fn test_replay_backspace_undoes_expansion() {
+        let prefs = Preferences {
+            undo_with_backspace: true,
+            ..Default::default()
+        };
+        let harness = ReplayHarness::new(&[combo("sig", "Regards")], &prefs);
+        let mut trace = typing(0, 20, "sig");
+        // Once input is released and the expansion's echo is no longer
+        // expected, so the hook takes it as typed.
+        trace.push(TraceEntry::press(440, Key::Backspace));
+        harness.replay(&trace);
+        assert_eq!(
+            harness.outputs(),
+            vec![
+                OutputAction::DeleteGraphemes(3),
+                OutputAction::Paste("Regards".into()),
+                OutputAction::DeleteGraphemes(6),
+                OutputAction::TypeText("sig".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_replay_undo_refused_in_another_window() {
+        let prefs = Preferences {
+            undo_with_backspace: true,
+            ..Default::default()
+        };
+        let harness = ReplayHarness::new(&[combo("sig", "Regards")], &prefs);
+        let mut trace = vec![focus("gedit", "notes.txt")];
+        trace.extend(typing(20, 20, "sig"));
+        harness.replay(&trace);
+        // A detector that misses the switch, so Backspace still reaches undo.
+        harness.focus.set_window_info(WindowInfo {
+            title: "shell".into(),
+            app_name: "terminal".into(),
+            process_id: Some(8),
+        });
+        harness.replay(&[TraceEntry::press(400, Key::Backspace)]);
+        assert_eq!(
+            harness.outputs(),
+            vec![
+                OutputAction::DeleteGraphemes(3),
+                OutputAction::Paste("Regards".into()),
+            ]
+        );
+    }
+
+    /// An HTML signature with a list and a link, whose plain text carries
+    /// list markers and the link target.
+    const SIGNATURE_HTML: &str = r#"<ul><li>Jane</li></ul><a href="https://example.com">site</a>"#;
+
+    /// Expands `sig` into [`SIGNATURE_HTML`].
+    fn html_signature() -> Combo {
+        ComboBuilder::new()
+            .keyword("sig")
+            .snippet(SIGNATURE_HTML)
+            .snippet_format(SnippetFormat::Html)
+            .matching_mode(MatchingMode::Strict)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_replay_backspace_after_pasted_html_is_not_undone() {
+        let prefs = Preferences {
+            undo_with_backspace: true,
+            ..Default::default()
+        };
+        let harness = ReplayHarness::new(&[html_signature()], &prefs);
+        let plain = html_to_text(SIGNATURE_HTML);
+        assert!(plain.contains("- Jane") && plain.contains("(https://example.com)"));
+        let mut trace = typing(0, 20, "sig");
+        trace.push(TraceEntry::press(440, Key::Backspace));
+        harness.replay(&trace);
+        // The pasted HTML is shorter than its plain text; deleting by the
+        // plain text's length would eat the user's own text.
+        assert_eq!(
+            harness.outputs(),
+            vec![OutputAction::DeleteGraphemes(3), OutputAction::Paste(plain)]
+        );
+    }
+
+    #[test]
+    fn test_replay_backspace_after_typed_html_is_undone() {
+        let prefs = Preferences {
+            undo_with_backspace: true,
+            paste_method: PasteMethod::SimulateKeystrokes,
+            ..Default::default()
+        };
+        let harness = ReplayHarness::new(&[html_signature()], &prefs);
+        let plain = html_to_text(SIGNATURE_HTML);
+        let mut trace = typing(0, 20, "sig");
+        trace.push(TraceEntry::press(1000, Key::Backspace));
+        harness.replay(&trace);
+        // Typed output is the plain text itself, so its length is exact.
+        assert_eq!(
+            harness.outputs(),
+            vec![
+                OutputAction::DeleteGraphemes(3),
+                OutputAction::TypeText(plain.clone()),
+                OutputAction::DeleteGraphemes(plain.chars().count() - 1),
+                OutputAction::TypeText("sig".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_replay_paste_rule_uses_custom_chord() {
+        let prefs = prefs_with_rule(PasteRule {
+            field: PasteRuleField::WindowClass,
+            pattern: "terminal".into(),
+            paste_method: PasteMethod::Clipboard,
+            paste_chord: "Ctrl+Shift+V".into(),
+        });
+        let harness = ReplayHarness::new(&[combo("gh", "https://github.com")], &prefs);
+        let mut trace = vec![focus("gnome-terminal-server", "~/src")];
+        trace.extend(typing(20, 20, "gh"));
+        harness.replay(&trace);
+        assert_eq!(
+            harness.outputs(),
+            vec![
+                OutputAction::DeleteGraphemes(2),
+                OutputAction::KeyChord("Ctrl+Shift+V".parse::<KeyChord>().unwrap()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_replay_paste_rule_overrides_method() {
+        let prefs = prefs_with_rule(PasteRule {
+            field: PasteRuleField::WindowTitle,
+            pattern: "ssh".into(),
+            paste_method: PasteMethod::SimulateKeystrokes,
+            paste_chord: String::new(),
+        });
+        let harness = ReplayHarness::new(&[combo("gh", "https://github.com")], &prefs);
+        let mut trace = vec![focus("kitty", "ssh build-box")];
+        trace.extend(typing(20, 20, "gh"));
+        harness.replay(&trace);
+        assert_eq!(
+            harness.outputs(),
+            vec![
+                OutputAction::DeleteGraphemes(2),
+                OutputAction::TypeText("https://github.com".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_replay_without_matching_rule_uses_global_method() {
+        let prefs = prefs_with_rule(PasteRule {
+            field: PasteRuleField::WindowClass,
+            pattern: "terminal".into(),
+            paste_method: PasteMethod::SimulateKeystrokes,
+            paste_chord: String::new(),
+        });
+        let harness = ReplayHarness::new(&[combo("gh", "https://github.com")], &prefs);
+        let mut trace = vec![focus("firefox", "GitHub")];
+        trace.extend(typing(20, 20, "gh"));
+        harness.replay(&trace);
+        assert_eq!(
+            harness.outputs(),
+            vec![
+                OutputAction::DeleteGraphemes(2),
+                OutputAction::Paste("https://github.com".into()),
+            ]
+        );
+    }
+}
@@ -7,7 +7,9 @@ use std::collections::HashMap;
 use thiserror::Error;
 use uuid::Uuid;
 
-use crate::models::{Combo, MatchingMode};
+use crate::models::{AutocorrectDictionary, Combo, MatchingMode, SnippetFormat};
+use crate::utils::graphemes::{grapheme_count, is_grapheme_boundary, tail_graphemes};
+use crate::utils::rich_text::render_snippet;
 
 /// Errors that can occur during matching operations.
 #[derive(Debug, Error)]
@@ -19,19 +21,31 @@ pub enum MatchingError {
 /// Result of a successful match.
 #[derive(Debug, Clone, PartialEq)]
 pub struct MatchResult {
-    /// The ID of the matched combo.
+    /// The ID of the matched combo (or of the owning autocorrect dictionary).
     pub combo_id: Uuid,
     /// The keyword that was matched.
     pub keyword: String,
-    /// The snippet to expand into.
+    /// The keyword exactly as it was typed (taken from the end of the buffer).
+    pub typed: String,
+    /// The snippet to expand into, with case propagation already applied
+    /// when the combo opts into it. Plain text, even for rich snippets.
     pub snippet: String,
-    /// Length of the keyword in the buffer (for deletion).
+    /// HTML rendering of a rich-text snippet, pasted alongside `snippet`.
+    pub html: Option<String>,
+    /// Asset name of an image snippet, pasted instead of `snippet` (which is
+    /// empty).
+    pub image: Option<String>,
+    /// Whether the combo asks to keep the snippet out of clipboard history.
+    pub exclude_from_history: bool,
+    /// Number of grapheme clusters `typed` occupies: the Backspaces needed
+    /// to delete it.
     pub keyword_len: usize,
 }
 
 /// Checks if `buffer` ends with `keyword` preceded by a word boundary.
 ///
 /// Word boundaries: start of buffer, space, tab, newline, or punctuation.
+/// The keyword must also start a grapheme cluster of the buffer.
 #[inline]
 fn is_strict_match(buffer: &str, keyword: &str, case_sensitive: bool) -> bool {
     if buffer.is_empty() || keyword.is_empty() {
@@ -50,6 +64,9 @@ fn is_strict_match(buffer: &str, keyword: &str, case_sensitive: bool) -> bool {
 
     // Check what precedes the keyword
     let prefix_len = buf.len() - kw.len();
+    if !is_grapheme_boundary(&buf, prefix_len) {
+        return false;
+    }
     if prefix_len == 0 {
         // Keyword is at start of buffer — valid boundary
         return true;
@@ -61,26 +78,67 @@ fn is_strict_match(buffer: &str, keyword: &str, case_sensitive: bool) -> bool {
     }
 }
 
-/// Checks if `buffer` simply ends with `keyword` (no boundary check).
+/// Checks if `buffer` simply ends with `keyword` (no word boundary check),
+/// starting a grapheme cluster: `é` typed as `e` plus a combining accent
+/// does not end with `e`.
 #[inline]
 fn is_loose_match(buffer: &str, keyword: &str, case_sensitive: bool) -> bool {
     if buffer.is_empty() || keyword.is_empty() {
         return false;
     }
 
+    let ends_at_cluster = |buf: &str, kw: &str| {
+        buf.ends_with(kw) && is_grapheme_boundary(buf, buf.len() - kw.len())
+    };
     if case_sensitive {
-        buffer.ends_with(keyword)
+        ends_at_cluster(buffer, keyword)
     } else {
-        buffer.to_lowercase().ends_with(&keyword.to_lowercase())
+        ends_at_cluster(&buffer.to_lowercase(), &keyword.to_lowercase())
     }
 }
 
+/// Mirrors the capitalization of the typed keyword onto `snippet`.
+///
+/// - All-caps input (at least two cased letters, e.g. `SIG`) upper-cases
+///   the whole snippet.
+/// - Input starting with an upper-case letter (e.g. `Sig`) capitalizes the
+///   first letter of the snippet.
+/// - Anything else leaves the snippet untouched.
+pub fn propagate_case(typed: &str, snippet: &str) -> String {
+    let cased: Vec<char> = typed
+        .chars()
+        .filter(|c| c.is_uppercase() || c.is_lowercase())
+        .collect();
+
+    if cased.len() >= 2 && cased.iter().all(|c| c.is_uppercase()) {
+        return snippet.to_uppercase();
+    }
+
+    if cased.first().is_some_and(|c| c.is_uppercase()) {
+        if let Some((idx, first)) = snippet.char_indices().find(|(_, c)| c.is_alphabetic()) {
+            let mut result = String::with_capacity(snippet.len());
+            result.push_str(&snippet[..idx]);
+            result.extend(first.to_uppercase());
+            result.push_str(&snippet[idx + first.len_utf8()..]);
+            return result;
+        }
+    }
+
+    snippet.to_string()
+}
+
 /// Returns true if the character is a word boundary.
 #[inline]
 fn is_word_boundary(c: char) -> bool {
     c.is_whitespace() || c.is_ascii_punctuation()
 }
 
+/// Apostrophes join the parts of a word ("don't", "o’clock") rather than
+/// ending it, so autocorrect never fires on them.
+fn is_apostrophe(c: char) -> bool {
+    c == '\'' || c == '\u{2019}'
+}
+
 /// Indexes active combos for efficient matching against typed text buffers.
 ///
 /// Combos are grouped by matching mode. A hash map keyed by keyword length
@@ -97,6 +155,16 @@ pub struct MatcherEngine {
     is_paused: bool,
     /// List of excluded application names.
     excluded_apps: Vec<String>,
+    /// Autocorrect entries from enabled dictionaries, keyed by lowercased typo.
+    autocorrect: HashMap<String, AutocorrectTarget>,
+}
+
+/// Correction looked up for a typo in an enabled autocorrect dictionary.
+#[derive(Debug, Clone)]
+struct AutocorrectTarget {
+    dictionary_id: Uuid,
+    typo: String,
+    correction: String,
 }
 
 /// Internal lightweight representation of a combo for matching.
@@ -104,10 +172,39 @@ pub struct MatcherEngine {
 struct ComboEntry {
     id: Uuid,
     keyword: String,
+    /// Plain-text rendering of the snippet.
     snippet: String,
+    html: Option<String>,
+    image: Option<String>,
     case_sensitive: bool,
-    /// Pre-computed keyword length in bytes (MT-1107).
-    keyword_byte_len: usize,
+    propagate_case: bool,
+    exclude_from_history: bool,
+    /// Pre-computed keyword length in grapheme clusters (MT-1107).
+    keyword_graphemes: usize,
+}
+
+impl ComboEntry {
+    /// Builds the match result for this entry against the given buffer.
+    fn to_match_result(&self, buffer: &str) -> MatchResult {
+        let typed = tail_graphemes(buffer, self.keyword_graphemes).to_string();
+        // Case propagation can't be mirrored into markup, so rich snippets
+        // are inserted as written.
+        let snippet = if self.propagate_case && self.html.is_none() {
+            propagate_case(&typed, &self.snippet)
+        } else {
+            self.snippet.clone()
+        };
+        MatchResult {
+            combo_id: self.id,
+            keyword: self.keyword.clone(),
+            typed,
+            snippet,
+            html: self.html.clone(),
+            image: self.image.clone(),
+            exclude_from_history: self.exclude_from_history,
+            keyword_len: self.keyword_graphemes,
+        }
+    }
 }
 
 impl MatcherEngine {
@@ -119,6 +216,7 @@ impl MatcherEngine {
             max_keyword_len: 0,
             is_paused: false,
             excluded_apps: Vec::new(),
+            autocorrect: HashMap::new(),
         }
     }
 
@@ -130,13 +228,18 @@ impl MatcherEngine {
 
         for combo in combos.iter().filter(|c| c.enabled) {
             let kw_len = combo.keyword.len();
-            let kw_char_count = combo.keyword.chars().count();
+            let rendered = render_snippet(&combo.snippet, combo.snippet_format);
             let entry = ComboEntry {
                 id: combo.id,
                 keyword: combo.keyword.clone(),
-                snippet: combo.snippet.clone(),
+                snippet: rendered.text,
+                html: rendered.html,
+                image: (combo.snippet_format == SnippetFormat::Image)
+                    .then(|| combo.snippet.clone()),
                 case_sensitive: combo.case_sensitive,
-                keyword_byte_len: kw_char_count,
+                propagate_case: combo.propagate_case,
+                exclude_from_history: combo.exclude_from_history,
+                keyword_graphemes: grapheme_count(&combo.keyword),
             };
             if kw_len > self.max_keyword_len {
                 self.max_keyword_len = kw_len;
@@ -156,6 +259,31 @@ impl MatcherEngine {
         );
     }
 
+    /// Loads (or reloads) the entries of all enabled autocorrect dictionaries.
+    ///
+    /// When two dictionaries define the same typo, the first one wins.
+    pub fn load_dictionaries(&mut self, dictionaries: &[AutocorrectDictionary]) {
+        self.autocorrect.clear();
+        for dict in dictionaries.iter().filter(|d| d.enabled) {
+            for entry in &dict.entries {
+                self.autocorrect
+                    .entry(entry.typo.to_lowercase())
+                    .or_insert_with(|| AutocorrectTarget {
+                        dictionary_id: dict.id,
+                        typo: entry.typo.clone(),
+                        correction: entry.correction.clone(),
+                    });
+            }
+        }
+        self.autocorrect.shrink_to_fit();
+        tracing::debug!("MatcherEngine loaded {} autocorrect entries", self.autocorrect.len());
+    }
+
+    /// Returns the number of indexed autocorrect entries.
+    pub fn autocorrect_count(&self) -> usize {
+        self.autocorrect.len()
+    }
+
     /// Sets the list of excluded application names.
     pub fn set_excluded_apps(&mut self, apps: Vec<String>) {
         self.excluded_apps = apps;
@@ -212,12 +340,7 @@ impl MatcherEngine {
             }
             for entry in entries {
                 if is_strict_match(buffer, &entry.keyword, entry.case_sensitive) {
-                    return Some(MatchResult {
-                        combo_id: entry.id,
-                        keyword: entry.keyword.clone(),
-                        snippet: entry.snippet.clone(),
-                        keyword_len: entry.keyword_byte_len,
-                    });
+                    return Some(entry.to_match_result(buffer));
                 }
             }
         }
@@ -229,20 +352,58 @@ impl MatcherEngine {
             }
             for entry in entries {
                 if is_loose_match(buffer, &entry.keyword, entry.case_sensitive) {
-                    return Some(MatchResult {
-                        combo_id: entry.id,
-                        keyword: entry.keyword.clone(),
-                        snippet: entry.snippet.clone(),
-                        keyword_len: entry.keyword_byte_len,
-                    });
+                    return Some(entry.to_match_result(buffer));
                 }
             }
         }
 
-        None
+        self.find_autocorrect(buffer)
+    }
+
+    /// Checks whether the buffer ends with a known typo followed by a single
+    /// word delimiter (e.g. `"teh "`).
+    ///
+    /// The delimiter is part of the deleted text and is re-typed after the
+    /// correction, so `keyword_len` covers the word plus the delimiter.
+    fn find_autocorrect(&self, buffer: &str) -> Option<MatchResult> {
+        if self.autocorrect.is_empty() {
+            return None;
+        }
+
+        let delimiter = buffer
+            .chars()
+            .last()
+            .filter(|&c| is_word_boundary(c) && !is_apostrophe(c))?;
+        let before = &buffer[..buffer.len() - delimiter.len_utf8()];
+        // Apostrophes inside words ("dont'" vs. "don't") belong to the word.
+        let word_start = before
+            .char_indices()
+            .rev()
+            .find(|&(_, c)| is_word_boundary(c) && !is_apostrophe(c))
+            .map_or(0, |(idx, c)| idx + c.len_utf8());
+        let word = before[word_start..].trim_start_matches(is_apostrophe);
+        if word.is_empty() {
+            return None;
+        }
+
+        let target = self.autocorrect.get(&word.to_lowercase())?;
+        let mut snippet = propagate_case(word, &target.correction);
+        snippet.push(delimiter);
+        let typed = format!("{}{}", word, delimiter);
+
+        Some(MatchResult {
+            combo_id: target.dictionary_id,
+            keyword: target.typo.clone(),
+            keyword_len: grapheme_count(&typed),
+            typed,
+            snippet,
+            html: None,
+            image: None,
+            exclude_from_history: true,
+        })
     }
 
-    /// Returns the number of indexed combos.
+    /// Returns the number of indexed combos (excluding autocorrect entries).
     pub fn combo_count(&self) -> usize {
         let strict: usize = self.strict_by_len.values().map(|v| v.len()).sum();
         let loose: usize = self.loose_by_len.values().map(|v| v.len()).sum();
@@ -391,6 +552,14 @@ mod tests {
         assert!(!is_loose_match("testSIG", "sig", true));
     }
 
+    #[test]
+    fn test_loose_keyword_must_start_a_grapheme() {
+        // The laptop ends the "woman technologist" ZWJ sequence.
+        assert!(!is_loose_match("\u{1F469}\u{200D}\u{1F4BB}", "\u{1F4BB}", false));
+        assert!(!is_loose_match("\u{1F469}\u{200D}\u{1F4BB}", "\u{200D}\u{1F4BB}", true));
+        assert!(is_loose_match("my \u{1F4BB}", "\u{1F4BB}", false));
+    }
+
     // ── MatcherEngine tests ───────────────────────────────────────
 
     #[test]
@@ -433,6 +602,100 @@ mod tests {
         assert_eq!(m.keyword_len, 3);
     }
 
+    #[test]
+    fn test_engine_phrase_keyword_match() {
+        let mut engine = MatcherEngine::new();
+        engine.load_combos(&[strict("on my way", "On my way, be there in 10 minutes!")]);
+
+        let m = engine.find_match("ok, on my way", None).expect("phrase should match");
+        assert_eq!(m.snippet, "On my way, be there in 10 minutes!");
+        // Deletion length counts the spaces between words.
+        assert_eq!(m.keyword_len, 9);
+
+        // The keyword follows a letter with no separator.
+        assert!(engine.find_match("xon my way", None).is_none());
+        assert!(engine.find_match("on my", None).is_none());
+    }
+
+    #[test]
+    fn test_engine_match_carries_typed_text() {
+        let mut engine = MatcherEngine::new();
+        engine.load_combos(&[strict("sig", "Best regards")]);
+        let m = engine.find_match("hello SiG", None).unwrap();
+        assert_eq!(m.keyword, "sig");
+        assert_eq!(m.typed, "SiG");
+        // Without opting in, the snippet is left as stored.
+        assert_eq!(m.snippet, "Best regards");
+    }
+
+    #[test]
+    fn test_engine_propagates_case_when_enabled() {
+        let mut engine = MatcherEngine::new();
+        let combo = ComboBuilder::new()
+            .keyword("omw")
+            .snippet("on my way")
+            .propagate_case(true)
+            .build()
+            .unwrap();
+        engine.load_combos(&[combo]);
+
+        assert_eq!(engine.find_match("omw", None).unwrap().snippet, "on my way");
+        assert_eq!(engine.find_match("Omw", None).unwrap().snippet, "On my way");
+        assert_eq!(engine.find_match("OMW", None).unwrap().snippet, "ON MY WAY");
+    }
+
+    #[test]
+    fn test_engine_renders_rich_snippets() {
+        let mut engine = MatcherEngine::new();
+        let combo = ComboBuilder::new()
+            .keyword("sig")
+            .snippet("**Jane** Doe")
+            .snippet_format(SnippetFormat::Markdown)
+            .propagate_case(true)
+            .build()
+            .unwrap();
+        engine.load_combos(&[combo, strict("omw", "on my way")]);
+
+        let result = engine.find_match("SIG", None).unwrap();
+        assert_eq!(result.snippet, "Jane Doe");
+        assert_eq!(result.html.as_deref(), Some("<p><strong>Jane</strong> Doe</p>"));
+        assert_eq!(engine.find_match("omw", None).unwrap().html, None);
+    }
+
+    #[test]
+    fn test_engine_passes_image_assets_through() {
+        let mut engine = MatcherEngine::new();
+        let combo = ComboBuilder::new()
+            .keyword("logo")
+            .snippet("3f2a.png")
+            .snippet_format(SnippetFormat::Image)
+            .propagate_case(true)
+            .build()
+            .unwrap();
+        engine.load_combos(&[combo, strict("omw", "on my way")]);
+
+        let result = engine.find_match("LOGO", None).unwrap();
+        assert_eq!(result.snippet, "");
+        assert_eq!(result.html, None);
+        assert_eq!(result.image.as_deref(), Some("3f2a.png"));
+        assert_eq!(engine.find_match("omw", None).unwrap().image, None);
+    }
+
+    #[test]
+    fn test_engine_passes_history_exclusion_through() {
+        let mut engine = MatcherEngine::new();
+        let combo = ComboBuilder::new()
+            .keyword("addr")
+            .snippet("1 Main St")
+            .exclude_from_history(false)
+            .build()
+            .unwrap();
+        engine.load_combos(&[combo, strict("acct", "12345678")]);
+
+        assert!(!engine.find_match("addr", None).unwrap().exclude_from_history);
+        assert!(engine.find_match("acct", None).unwrap().exclude_from_history);
+    }
+
     #[test]
     fn test_engine_strict_no_mid_word() {
         let mut engine = MatcherEngine::new();
@@ -622,6 +885,202 @@ mod tests {
         assert!(is_strict_match("hello sig", "sig", false));
     }
 
+    // ── Autocorrect dictionaries ──────────────────────────────────
+
+    fn typo_dictionary() -> AutocorrectDictionary {
+        use crate::models::AutocorrectEntry;
+        AutocorrectDictionary::new(
+            "Typos",
+            vec![
+                AutocorrectEntry::new("teh", "the"),
+                AutocorrectEntry::new("dont", "don't"),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_autocorrect_matches_on_delimiter() {
+        let mut engine = MatcherEngine::new();
+        let dict = typo_dictionary();
+        let dict_id = dict.id;
+        engine.load_dictionaries(&[dict]);
+        assert_eq!(engine.autocorrect_count(), 2);
+
+        // Not yet: the word may still be growing ("tehran").
+        assert!(engine.find_match("I saw teh", None).is_none());
+
+        let m = engine.find_match("I saw teh ", None).unwrap();
+        assert_eq!(m.combo_id, dict_id);
+        assert_eq!(m.keyword, "teh");
+        assert_eq!(m.typed, "teh ");
+        assert_eq!(m.snippet, "the ");
+        assert_eq!(m.keyword_len, 4);
+
+        let m = engine.find_match("teh.", None).unwrap();
+        assert_eq!(m.snippet, "the.");
+    }
+
+    #[test]
+    fn test_autocorrect_propagates_case() {
+        let mut engine = MatcherEngine::new();
+        engine.load_dictionaries(&[typo_dictionary()]);
+        assert_eq!(engine.find_match("Teh ", None).unwrap().snippet, "The ");
+        assert_eq!(engine.find_match("TEH ", None).unwrap().snippet, "THE ");
+        assert_eq!(engine.find_match("Dont ", None).unwrap().snippet, "Don't ");
+    }
+
+    #[test]
+    fn test_autocorrect_requires_whole_word() {
+        let mut engine = MatcherEngine::new();
+        engine.load_dictionaries(&[typo_dictionary()]);
+        assert!(engine.find_match("steh ", None).is_none());
+        assert!(engine.find_match("tehe ", None).is_none());
+        assert!(engine.find_match(" ", None).is_none());
+    }
+
+    #[test]
+    fn test_autocorrect_does_not_fire_on_apostrophe() {
+        use crate::models::AutocorrectEntry;
+        let mut engine = MatcherEngine::new();
+        engine.load_dictionaries(&[AutocorrectDictionary::new(
+            "Typos",
+            vec![
+                AutocorrectEntry::new("don", "done"),
+                AutocorrectEntry::new("cant", "can't"),
+            ],
+        )]);
+        // "don't" is still being typed.
+        assert!(engine.find_match("I don'", None).is_none());
+        assert!(engine.find_match("I don\u{2019}", None).is_none());
+        assert!(engine.find_match("I don't ", None).is_none());
+        assert!(engine.find_match("I don\u{2019}t ", None).is_none());
+        // A leading quote is not part of the word.
+        assert_eq!(engine.find_match("say \u{2019}cant ", None).unwrap().typed, "cant ");
+    }
+
+    #[test]
+    fn test_autocorrect_skips_disabled_dictionary() {
+        let mut engine = MatcherEngine::new();
+        let mut dict = typo_dictionary();
+        dict.enabled = false;
+        engine.load_dictionaries(&[dict]);
+        assert_eq!(engine.autocorrect_count(), 0);
+        assert!(engine.find_match("teh ", None).is_none());
+    }
+
+    #[test]
+    fn test_combos_take_precedence_over_autocorrect() {
+        let mut engine = MatcherEngine::new();
+        engine.load_combos(&[loose("teh.", "combo")]);
+        engine.load_dictionaries(&[typo_dictionary()]);
+        assert_eq!(engine.find_match("teh.", None).unwrap().snippet, "combo");
+        assert_eq!(engine.find_match("teh ", None).unwrap().snippet, "the ");
+    }
+
+    #[test]
+    fn test_autocorrect_respects_pause_and_exclusions() {
+        let mut engine = MatcherEngine::new();
+        engine.load_dictionaries(&[typo_dictionary()]);
+        engine.set_excluded_apps(vec!["keepass".to_string()]);
+        assert!(engine.find_match("teh ", Some("KeePassXC")).is_none());
+        engine.pause();
+        assert!(engine.find_match("teh ", None).is_none());
+    }
+
+    // ── Grapheme clusters ─────────────────────────────────────────
+
+    #[test]
+    fn test_engine_combining_accent_counts_one_grapheme() {
+        // "café" with the é typed as e plus a combining acute accent.
+        let mut engine = MatcherEngine::new();
+        engine.load_combos(&[strict("cafe\u{301}", "Café au lait")]);
+
+        let m = engine.find_match("a cafe\u{301}", None).expect("should match");
+        assert_eq!(m.typed, "cafe\u{301}");
+        assert_eq!(m.keyword_len, 4);
+
+        // "cafe" alone is not the keyword, and a keyword ending in "e"
+        // doesn't match once the accent has joined it.
+        assert!(engine.find_match("a cafe", None).is_none());
+        engine.load_combos(&[loose("fe", "x")]);
+        assert!(engine.find_match("cafe\u{301}", None).is_none());
+    }
+
+    #[test]
+    fn test_engine_zwj_emoji_counts_one_grapheme() {
+        let technologist = "\u{1F469}\u{200D}\u{1F4BB}";
+        let mut engine = MatcherEngine::new();
+        engine.load_combos(&[loose(&format!("{}!", technologist), "Jane Doe, engineer")]);
+
+        let m = engine
+            .find_match(&format!("hi {}!", technologist), None)
+            .expect("should match");
+        assert_eq!(m.keyword_len, 2);
+    }
+
+    #[test]
+    fn test_engine_emoji_keyword_not_matched_inside_cluster() {
+        let mut engine = MatcherEngine::new();
+        engine.load_combos(&[loose("\u{1F4BB}", "laptop")]);
+        assert!(engine.find_match("\u{1F469}\u{200D}\u{1F4BB}", None).is_none());
+        assert_eq!(engine.find_match("my \u{1F4BB}", None).unwrap().keyword_len, 1);
+    }
+
+    #[test]
+    fn test_autocorrect_counts_graphemes() {
+        use crate::models::AutocorrectEntry;
+        let mut engine = MatcherEngine::new();
+        engine.load_dictionaries(&[AutocorrectDictionary::new(
+            "Accents",
+            vec![AutocorrectEntry::new("nai\u{308}ev", "nai\u{308}ve")],
+        )]);
+        let m = engine.find_match("nai\u{308}ev ", None).unwrap();
+        assert_eq!(m.keyword_len, 6);
+    }
+
+    // ── Case propagation ──────────────────────────────────────────
+
+    #[test]
+    fn test_propagate_case_lowercase_unchanged() {
+        assert_eq!(propagate_case("teh", "the"), "the");
+    }
+
+    #[test]
+    fn test_propagate_case_capitalized() {
+        assert_eq!(propagate_case("Teh", "the"), "The");
+        assert_eq!(propagate_case("Sig", "best regards"), "Best regards");
+    }
+
+    #[test]
+    fn test_propagate_case_all_caps() {
+        assert_eq!(propagate_case("TEH", "the"), "THE");
+    }
+
+    #[test]
+    fn test_propagate_case_skips_leading_non_letters() {
+        assert_eq!(propagate_case("Qt", "\"quoted\""), "\"Quoted\"");
+    }
+
+    #[test]
+    fn test_propagate_case_mixed_case_only_capitalizes() {
+        assert_eq!(propagate_case("SiG", "regards"), "Regards");
+    }
+
+    #[test]
+    fn test_propagate_case_unicode() {
+        assert_eq!(propagate_case("Él", "école"), "École");
+        assert_eq!(propagate_case("ÉL", "straße"), "STRASSE");
+    }
+
+    #[test]
+    fn test_tail_graphemes_multibyte() {
+        assert_eq!(tail_graphemes("héllo", 3), "llo");
+        assert_eq!(tail_graphemes("héllo", 4), "éllo");
+        assert_eq!(tail_graphemes("he\u{301}llo", 4), "e\u{301}llo");
+        assert_eq!(tail_graphemes("ab", 5), "ab");
+        assert_eq!(tail_graphemes("ab", 0), "");
+    }
+
     #[test]
     fn test_default_trait() {
         let engine = MatcherEngine::default();
@@ -11,13 +11,16 @@ pub use combo_manager::{ComboManager, ComboManagerError};
 pub use combo_storage::ComboStorage;
 pub use file_watcher::FileWatcher;
 pub use preferences_storage::PreferencesStorage;
-pub use storage::{StorageError, ensure_dirs_exist, get_config_dir, get_combos_path, get_preferences_path, get_backups_dir, get_logs_dir};
+pub use storage::{StorageError, ensure_dirs_exist, get_config_dir, get_combos_path, get_preferences_path, get_backups_dir, get_logs_dir, get_assets_dir};
 
 pub mod input_manager;
 pub mod matching;
 pub mod clipboard_manager;
 pub mod substitution;
+pub mod output_backend;
 pub mod expansion_pipeline;
+pub mod expansion_worker;
+pub mod timing_calibrator;
 
 // Re-export Milestone 6 types
 pub use matching::{MatcherEngine, MatchResult};
@@ -51,10 +54,12 @@ pub mod import_manager;
 pub mod export_manager;
 pub mod backup_manager;
 pub mod update_manager;
+pub mod asset_manager;
 
 pub use import_manager::ImportManager;
 pub use export_manager::ExportManager;
 pub use backup_manager::BackupManager;
+pub use asset_manager::AssetManager;
 pub use update_manager::UpdateManager;
 
 // Milestone 11: Bug fixes and performance
@@ -64,6 +69,8 @@ pub mod bug_fixes;
 pub mod engine_manager;
 pub use engine_manager::{EngineManager, EngineStatus, EngineError};
 
+pub mod keystroke_trace;
+
 #[cfg(test)]
 mod tests {
     #[test]
@@ -0,0 +1,1432 @@
+//! Output backends for MuttonText.
+//!
+//! The [`SubstitutionEngine`](crate::managers::substitution::SubstitutionEngine)
+//! decides what an expansion sends — delete the keyword, paste or type the
+//! snippet, undo — and an [`OutputBackend`] sends it to the OS: XTest or a
+//! uinput virtual keyboard on Linux, CoreGraphics events on macOS and
+//! `rdev::simulate` elsewhere. [`RecordingBackend`] records the operations
+//! instead, so the engine can be tested end to end without a display.
+
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use rdev::{simulate, EventType};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::managers::substitution::{SubstitutionConfig, SubstitutionError};
+use crate::platform::keyboard_hook::{Key, Modifiers};
+#[cfg(target_os = "linux")]
+use crate::platform::uinput::with_virtual_keyboard;
+#[cfg(target_os = "linux")]
+use crate::platform::xtest::XTestKeyboard;
+
+// ---------------------------------------------------------------------------
+// Key chords
+// ---------------------------------------------------------------------------
+
+/// A key pressed while holding modifiers, e.g. Ctrl+Shift+V.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyChord {
+    #[serde(default)]
+    pub modifiers: Modifiers,
+    pub key: Key,
+}
+
+impl KeyChord {
+    pub fn new(modifiers: Modifiers, key: Key) -> Self {
+        Self { modifiers, key }
+    }
+
+    /// Cmd+V on macOS, Ctrl+V elsewhere.
+    pub fn platform_paste() -> Self {
+        let modifiers = if cfg!(target_os = "macos") {
+            Modifiers {
+                meta: true,
+                ..Default::default()
+            }
+        } else {
+            Modifiers {
+                ctrl: true,
+                ..Default::default()
+            }
+        };
+        Self::new(modifiers, Key::Char('v'))
+    }
+
+    /// Shift+Insert. `Key` has no Insert variant, so it is spelled
+    /// `Key::Other("Insert")`.
+    pub fn shift_insert() -> Self {
+        Self::new(
+            Modifiers {
+                shift: true,
+                ..Default::default()
+            },
+            Key::Other("Insert".to_string()),
+        )
+    }
+
+    /// Ctrl+Shift+U, which starts Unicode code point entry in GTK and IBus.
+    pub fn unicode_input() -> Self {
+        Self::new(
+            Modifiers {
+                ctrl: true,
+                shift: true,
+                ..Default::default()
+            },
+            Key::Char('u'),
+        )
+    }
+
+    /// The paste shortcut selected by `config`.
+    pub fn paste(config: &SubstitutionConfig) -> Self {
+        if config.use_shift_insert {
+            Self::shift_insert()
+        } else {
+            Self::platform_paste()
+        }
+    }
+}
+
+impl fmt::Display for KeyChord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names = [
+            (self.modifiers.ctrl, "Ctrl"),
+            (self.modifiers.shift, "Shift"),
+            (self.modifiers.alt, "Alt"),
+            (self.modifiers.meta, "Meta"),
+        ];
+        for (_, name) in names.iter().filter(|(held, _)| *held) {
+            write!(f, "{}+", name)?;
+        }
+        match self.key {
+            Key::Char(c) => write!(f, "{}", c.to_uppercase()),
+            ref key => write!(f, "{}", key),
+        }
+    }
+}
+
+/// Error for a shortcut string that [`KeyChord::from_str`] can't parse.
+#[derive(Debug, Error, PartialEq, Eq)]
+#[error("Invalid key chord '{0}'")]
+pub struct InvalidKeyChord(pub String);
+
+/// Parses shortcuts such as "Ctrl+Shift+V" or "Shift+Insert", the format
+/// produced by `Display`. Names are case-insensitive; "Cmd", "Super" and
+/// "Win" are accepted for Meta.
+impl FromStr for KeyChord {
+    type Err = InvalidKeyChord;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || InvalidKeyChord(s.to_string());
+        let mut parts: Vec<&str> = s.split('+').map(str::trim).collect();
+        let key_name = parts.pop().filter(|name| !name.is_empty()).ok_or_else(invalid)?;
+
+        let mut modifiers = Modifiers::default();
+        for part in parts {
+            match part.to_lowercase().as_str() {
+                "ctrl" | "control" => modifiers.ctrl = true,
+                "shift" => modifiers.shift = true,
+                "alt" | "option" => modifiers.alt = true,
+                "meta" | "cmd" | "command" | "super" | "win" => modifiers.meta = true,
+                _ => return Err(invalid()),
+            }
+        }
+
+        let mut chars = key_name.chars();
+        let key = match (chars.next(), chars.next()) {
+            (Some(c), None) => Key::Char(c.to_lowercase().next().unwrap_or(c)),
+            _ => match key_name.to_lowercase().as_str() {
+                "backspace" => Key::Backspace,
+                "enter" | "return" => Key::Enter,
+                "tab" => Key::Tab,
+                "esc" | "escape" => Key::Escape,
+                "space" => Key::Space,
+                "del" | "delete" => Key::Delete,
+                "insert" | "ins" => Key::Other("Insert".to_string()),
+                "left" => Key::Left,
+                "right" => Key::Right,
+                "up" => Key::Up,
+                "down" => Key::Down,
+                "home" => Key::Home,
+                "end" => Key::End,
+                "pageup" => Key::PageUp,
+                "pagedown" => Key::PageDown,
+                name => match name.strip_prefix('f').and_then(|n| n.parse::<u8>().ok()) {
+                    Some(n @ 1..=24) => Key::F(n),
+                    _ => return Err(invalid()),
+                },
+            },
+        };
+        Ok(Self::new(modifiers, key))
+    }
+}
+
+// ---------------------------------------------------------------------------
+// OutputBackend trait
+// ---------------------------------------------------------------------------
+
+/// One operation sent to an [`OutputBackend`], as logged by
+/// [`RecordingBackend`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OutputAction {
+    DeleteGraphemes(usize),
+    TypeText(String),
+    /// The paste shortcut, with the text that was on the clipboard.
+    Paste(String),
+    KeyChord(KeyChord),
+    MoveCursor(isize),
+}
+
+impl OutputAction {
+    /// The key presses a hook that hears synthetic input reports for this
+    /// operation, with `paste` standing in for [`OutputAction::Paste`].
+    pub fn echo(&self, paste: &KeyChord) -> Vec<Key> {
+        match self {
+            Self::DeleteGraphemes(count) => vec![Key::Backspace; *count],
+            Self::TypeText(text) => text
+                .chars()
+                .map(|c| match c {
+                    ' ' => Key::Space,
+                    '\n' => Key::Enter,
+                    '\t' => Key::Tab,
+                    c => Key::Char(c),
+                })
+                .collect(),
+            Self::Paste(_) => vec![paste.key.clone()],
+            Self::KeyChord(chord) => vec![chord.key.clone()],
+            Self::MoveCursor(offset) => vec![arrow_key(*offset); offset.unsigned_abs()],
+        }
+    }
+}
+
+/// How a backend enters characters its keymap can't type.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UnicodeInput {
+    /// Ctrl+Shift+U, the hex code point, then Space: the input sequence
+    /// GTK and IBus understand on Linux.
+    #[default]
+    KeySequence,
+    /// A key event carrying the character itself, such as a CoreGraphics
+    /// Unicode string or `SendInput` with `KEYEVENTF_UNICODE` on Windows.
+    Direct,
+}
+
+/// Sends synthetic input to the focused application.
+///
+/// Backends only translate operations into key events; limits, clipboard
+/// handling and batching live in the substitution engine.
+pub trait OutputBackend: Send {
+    /// Short name used in logs.
+    fn name(&self) -> &'static str;
+
+    /// Deletes `count` graphemes before the cursor by pressing Backspace.
+    fn delete_graphemes(&mut self, count: usize) -> Result<(), SubstitutionError>;
+
+    /// Types `text` at the cursor.
+    fn type_text(&mut self, text: &str) -> Result<(), SubstitutionError>;
+
+    /// Presses the paste shortcut. The caller has already put `text` on
+    /// the clipboard.
+    fn paste(&mut self, text: &str) -> Result<(), SubstitutionError>;
+
+    /// Presses and releases `chord`.
+    fn key_chord(&mut self, chord: &KeyChord) -> Result<(), SubstitutionError>;
+
+    /// Moves the cursor `offset` graphemes to the right, or to the left if
+    /// negative, with the arrow keys.
+    fn move_cursor(&mut self, offset: isize) -> Result<(), SubstitutionError>;
+
+    /// Returns `true` if `type_text` can produce `ch`. Backends that remap
+    /// keys or post Unicode strings can type anything.
+    fn can_type(&self, _ch: char) -> bool {
+        true
+    }
+
+    /// How [`Self::type_unicode`] enters characters on this platform.
+    fn unicode_input(&self) -> UnicodeInput;
+
+    /// Enters `ch`, which `can_type` rejected, as [`Self::unicode_input`]
+    /// says. [`UnicodeInput::Direct`] backends whose `type_text` can't send
+    /// the character override this.
+    fn type_unicode(&mut self, ch: char) -> Result<(), SubstitutionError> {
+        match self.unicode_input() {
+            UnicodeInput::KeySequence => type_unicode_sequence(self, ch),
+            UnicodeInput::Direct => self.type_text(ch.encode_utf8(&mut [0; 4])),
+        }
+    }
+}
+
+/// Enters `ch` on `backend` with [`UnicodeInput::KeySequence`].
+pub fn type_unicode_sequence<B: OutputBackend + ?Sized>(
+    backend: &mut B,
+    ch: char,
+) -> Result<(), SubstitutionError> {
+    backend.key_chord(&KeyChord::unicode_input())?;
+    backend.type_text(&format!("{:x} ", ch as u32))
+}
+
+/// Types `text` on `backend`, one character at a time where needed: runs
+/// the backend can type go through `type_text`, other characters through
+/// [`OutputBackend::type_unicode`].
+pub fn type_text_per_char(
+    backend: &mut dyn OutputBackend,
+    text: &str,
+) -> Result<(), SubstitutionError> {
+    if text.chars().all(|ch| backend.can_type(ch)) {
+        return backend.type_text(text);
+    }
+    let mut run_start = 0;
+    for (idx, ch) in text.char_indices() {
+        if backend.can_type(ch) {
+            continue;
+        }
+        if run_start < idx {
+            backend.type_text(&text[run_start..idx])?;
+        }
+        backend.type_unicode(ch)?;
+        run_start = idx + ch.len_utf8();
+    }
+    if run_start < text.len() {
+        backend.type_text(&text[run_start..])?;
+    }
+    Ok(())
+}
+
+/// The arrow key that moves the cursor in the direction of `offset`.
+fn arrow_key(offset: isize) -> Key {
+    if offset < 0 {
+        Key::Left
+    } else {
+        Key::Right
+    }
+}
+
+// ---------------------------------------------------------------------------
+// RecordingBackend
+// ---------------------------------------------------------------------------
+
+/// Records operations instead of sending them.
+///
+/// Clones share one log, so a test can keep a handle after handing the
+/// backend to an engine. Output can be held part way through a job with
+/// [`RecordingBackend::hold`], to act while the job is running.
+#[derive(Debug, Clone, Default)]
+pub struct RecordingBackend {
+    actions: Arc<Mutex<Vec<OutputAction>>>,
+    /// Characters `type_text` accepts; all of them when unset.
+    keymap: Option<fn(char) -> bool>,
+    unicode_input: UnicodeInput,
+    hold: Arc<OutputHold>,
+}
+
+/// Whether output is held, and how many operations are waiting for it.
+#[derive(Debug, Default)]
+struct OutputHold {
+    state: Mutex<(bool, usize)>,
+    changed: Condvar,
+}
+
+impl RecordingBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A backend that can only type the characters `keymap` accepts, like
+    /// one limited to a keyboard layout.
+    pub fn with_keymap(keymap: fn(char) -> bool) -> Self {
+        Self {
+            keymap: Some(keymap),
+            ..Self::default()
+        }
+    }
+
+    /// Enters characters outside the keymap with `unicode_input`, like the
+    /// platform backend under test.
+    pub fn unicode_input(mut self, unicode_input: UnicodeInput) -> Self {
+        self.unicode_input = unicode_input;
+        self
+    }
+
+    /// Everything recorded so far, oldest first.
+    pub fn actions(&self) -> Vec<OutputAction> {
+        self.actions.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Makes every following operation wait, unrecorded, until
+    /// [`RecordingBackend::release`].
+    pub fn hold(&self) {
+        self.hold.state.lock().unwrap_or_else(|e| e.into_inner()).0 = true;
+    }
+
+    /// Lets held operations through and stops holding new ones.
+    pub fn release(&self) {
+        self.hold.state.lock().unwrap_or_else(|e| e.into_inner()).0 = false;
+        self.hold.changed.notify_all();
+    }
+
+    /// Blocks until an operation is waiting on the hold, or `timeout`
+    /// elapses. Returns whether one is.
+    pub fn wait_for_held(&self, timeout: Duration) -> bool {
+        let state = self.hold.state.lock().unwrap_or_else(|e| e.into_inner());
+        let (state, _) = self
+            .hold
+            .changed
+            .wait_timeout_while(state, timeout, |(_, waiting)| *waiting == 0)
+            .unwrap_or_else(|e| e.into_inner());
+        state.1 > 0
+    }
+
+    fn record(&self, action: OutputAction) -> Result<(), SubstitutionError> {
+        {
+            let mut state = self.hold.state.lock().unwrap_or_else(|e| e.into_inner());
+            if state.0 {
+                state.1 += 1;
+                self.hold.changed.notify_all();
+                state = self
+                    .hold
+                    .changed
+                    .wait_while(state, |(held, _)| *held)
+                    .unwrap_or_else(|e| e.into_inner());
+                state.1 -= 1;
+            }
+        }
+        self.actions.lock().unwrap_or_else(|e| e.into_inner()).push(action);
+        Ok(())
+    }
+}
+
+impl OutputBackend for RecordingBackend {
+    fn name(&self) -> &'static str {
+        "recording"
+    }
+
+    fn delete_graphemes(&mut self, count: usize) -> Result<(), SubstitutionError> {
+        self.record(OutputAction::DeleteGraphemes(count))
+    }
+
+    fn type_text(&mut self, text: &str) -> Result<(), SubstitutionError> {
+        self.record(OutputAction::TypeText(text.to_string()))
+    }
+
+    fn paste(&mut self, text: &str) -> Result<(), SubstitutionError> {
+        self.record(OutputAction::Paste(text.to_string()))
+    }
+
+    fn key_chord(&mut self, chord: &KeyChord) -> Result<(), SubstitutionError> {
+        self.record(OutputAction::KeyChord(chord.clone()))
+    }
+
+    fn move_cursor(&mut self, offset: isize) -> Result<(), SubstitutionError> {
+        self.record(OutputAction::MoveCursor(offset))
+    }
+
+    fn can_type(&self, ch: char) -> bool {
+        match self.keymap {
+            Some(keymap) => keymap(ch),
+            None => true,
+        }
+    }
+
+    fn unicode_input(&self) -> UnicodeInput {
+        self.unicode_input
+    }
+}
+
+// ---------------------------------------------------------------------------
+// RdevBackend
+// ---------------------------------------------------------------------------
+
+/// Sends key events through `rdev::simulate`.
+///
+/// Characters are typed with their US-layout keys, the only layout rdev
+/// knows; anything else is sent as a Unicode key event with `SendInput` on
+/// Windows. Linux and macOS use [`XTestBackend`] and [`MacOsBackend`]
+/// instead.
+pub struct RdevBackend {
+    delay: Duration,
+    paste_chord: KeyChord,
+}
+
+impl RdevBackend {
+    pub fn new(config: &SubstitutionConfig) -> Self {
+        Self {
+            delay: Duration::from_millis(config.key_delay_ms),
+            paste_chord: KeyChord::paste(config),
+        }
+    }
+
+    /// Sends a single key event, then waits the key delay.
+    fn send(&self, event_type: EventType) -> Result<(), SubstitutionError> {
+        simulate(&event_type)
+            .map_err(|e| SubstitutionError::SimulationFailed(format!("{:?}", e)))?;
+        thread::sleep(self.delay);
+        Ok(())
+    }
+
+    /// Presses and releases `key` `count` times.
+    fn tap(&self, key: rdev::Key, count: usize) -> Result<(), SubstitutionError> {
+        for _ in 0..count {
+            self.send(EventType::KeyPress(key))?;
+            self.send(EventType::KeyRelease(key))?;
+        }
+        Ok(())
+    }
+}
+
+/// Shifted US-layout symbols, each on the same key as the character at the
+/// same position in [`US_UNSHIFTED`].
+const US_SHIFTED: &str = "~!@#$%^&*()_+{}|:\"<>?";
+const US_UNSHIFTED: &str = "`1234567890-=[]\\;',./";
+
+/// Returns the US-layout key that types `ch`, and whether it needs Shift.
+fn us_keystroke(ch: char) -> Option<(rdev::Key, bool)> {
+    use rdev::Key as R;
+    if let Some(idx) = US_SHIFTED.find(ch) {
+        let base = US_UNSHIFTED[idx..].chars().next()?;
+        return us_keystroke(base).map(|(key, _)| (key, true));
+    }
+    let key = match ch {
+        '\n' => R::Return,
+        '\t' => R::Tab,
+        ' ' => R::Space,
+        '`' => R::BackQuote,
+        '-' => R::Minus,
+        '=' => R::Equal,
+        '[' => R::LeftBracket,
+        ']' => R::RightBracket,
+        '\\' => R::BackSlash,
+        ';' => R::SemiColon,
+        '\'' => R::Quote,
+        ',' => R::Comma,
+        '.' => R::Dot,
+        '/' => R::Slash,
+        c if c.is_ascii_alphanumeric() => {
+            return rdev_key(&Key::Char(c)).map(|key| (key, c.is_ascii_uppercase()))
+        }
+        _ => return None,
+    };
+    Some((key, false))
+}
+
+/// Returns the rdev key for `key` when pressed as part of a shortcut.
+fn rdev_key(key: &Key) -> Option<rdev::Key> {
+    use rdev::Key as R;
+    let rdev_key = match key {
+        Key::Char(c) => match c.to_ascii_lowercase() {
+            'a' => R::KeyA,
+            'b' => R::KeyB,
+            'c' => R::KeyC,
+            'd' => R::KeyD,
+            'e' => R::KeyE,
+            'f' => R::KeyF,
+            'g' => R::KeyG,
+            'h' => R::KeyH,
+            'i' => R::KeyI,
+            'j' => R::KeyJ,
+            'k' => R::KeyK,
+            'l' => R::KeyL,
+            'm' => R::KeyM,
+            'n' => R::KeyN,
+            'o' => R::KeyO,
+            'p' => R::KeyP,
+            'q' => R::KeyQ,
+            'r' => R::KeyR,
+            's' => R::KeyS,
+            't' => R::KeyT,
+            'u' => R::KeyU,
+            'v' => R::KeyV,
+            'w' => R::KeyW,
+            'x' => R::KeyX,
+            'y' => R::KeyY,
+            'z' => R::KeyZ,
+            '0' => R::Num0,
+            '1' => R::Num1,
+            '2' => R::Num2,
+            '3' => R::Num3,
+            '4' => R::Num4,
+            '5' => R::Num5,
+            '6' => R::Num6,
+            '7' => R::Num7,
+            '8' => R::Num8,
+            '9' => R::Num9,
+            _ => return None,
+        },
+        Key::Backspace => R::Backspace,
+        Key::Enter => R::Return,
+        Key::Tab => R::Tab,
+        Key::Escape => R::Escape,
+        Key::Space => R::Space,
+        Key::Delete => R::Delete,
+        Key::Left => R::LeftArrow,
+        Key::Right => R::RightArrow,
+        Key::Up => R::UpArrow,
+        Key::Down => R::DownArrow,
+        Key::Home => R::Home,
+        Key::End => R::End,
+        Key::PageUp => R::PageUp,
+        Key::PageDown => R::PageDown,
+        Key::F(1) => R::F1,
+        Key::F(2) => R::F2,
+        Key::F(3) => R::F3,
+        Key::F(4) => R::F4,
+        Key::F(5) => R::F5,
+        Key::F(6) => R::F6,
+        Key::F(7) => R::F7,
+        Key::F(8) => R::F8,
+        Key::F(9) => R::F9,
+        Key::F(10) => R::F10,
+        Key::F(11) => R::F11,
+        Key::F(12) => R::F12,
+        Key::Other(name) if name == "Insert" => R::Insert,
+        _ => return None,
+    };
+    Some(rdev_key)
+}
+
+impl OutputBackend for RdevBackend {
+    fn name(&self) -> &'static str {
+        "rdev"
+    }
+
+    fn delete_graphemes(&mut self, count: usize) -> Result<(), SubstitutionError> {
+        tracing::debug!("Deleting {} characters via backspace", count);
+        self.tap(rdev::Key::Backspace, count)
+    }
+
+    /// Types the characters [`Self::can_type`] accepts; callers route the
+    /// rest through [`OutputBackend::type_unicode`].
+    fn type_text(&mut self, text: &str) -> Result<(), SubstitutionError> {
+        tracing::debug!("Inserting via keystrokes: {} chars", text.len());
+        for ch in text.chars().filter(|&ch| ch != '\r') {
+            let (key, shift) = match us_keystroke(ch) {
+                Some(keystroke) => keystroke,
+                None => {
+                    tracing::warn!("No US-layout key for U+{:04X}; skipped", ch as u32);
+                    continue;
+                }
+            };
+            if shift {
+                self.send(EventType::KeyPress(rdev::Key::ShiftLeft))?;
+            }
+            let result = self.tap(key, 1);
+            if shift {
+                self.send(EventType::KeyRelease(rdev::Key::ShiftLeft))?;
+            }
+            result?;
+        }
+        Ok(())
+    }
+
+    fn paste(&mut self, _text: &str) -> Result<(), SubstitutionError> {
+        let chord = self.paste_chord.clone();
+        self.key_chord(&chord)
+    }
+
+    fn key_chord(&mut self, chord: &KeyChord) -> Result<(), SubstitutionError> {
+        let key = rdev_key(&chord.key).ok_or_else(|| {
+            SubstitutionError::SimulationFailed(format!("Cannot simulate key {}", chord.key))
+        })?;
+        let held: Vec<rdev::Key> = [
+            (chord.modifiers.ctrl, rdev::Key::ControlLeft),
+            (chord.modifiers.shift, rdev::Key::ShiftLeft),
+            (chord.modifiers.alt, rdev::Key::Alt),
+            (chord.modifiers.meta, rdev::Key::MetaLeft),
+        ]
+        .iter()
+        .filter(|(on, _)| *on)
+        .map(|&(_, key)| key)
+        .collect();
+
+        for &modifier in &held {
+            self.send(EventType::KeyPress(modifier))?;
+        }
+        let result = self.tap(key, 1);
+        // Release the modifiers even if the key itself failed.
+        for &modifier in held.iter().rev() {
+            self.send(EventType::KeyRelease(modifier))?;
+        }
+        result
+    }
+
+    fn move_cursor(&mut self, offset: isize) -> Result<(), SubstitutionError> {
+        let key = if offset < 0 {
+            rdev::Key::LeftArrow
+        } else {
+            rdev::Key::RightArrow
+        };
+        self.tap(key, offset.unsigned_abs())
+    }
+
+    /// `\r` is dropped by `type_text`, so `\r\n` becomes one Enter.
+    fn can_type(&self, ch: char) -> bool {
+        ch == '\r' || us_keystroke(ch).is_some()
+    }
+
+    /// Other Unix desktops run the same GTK and IBus input methods as
+    /// Linux.
+    fn unicode_input(&self) -> UnicodeInput {
+        if cfg!(windows) {
+            UnicodeInput::Direct
+        } else {
+            UnicodeInput::KeySequence
+        }
+    }
+
+    #[cfg(windows)]
+    fn type_unicode(&mut self, ch: char) -> Result<(), SubstitutionError> {
+        send_unicode(ch)?;
+        thread::sleep(self.delay);
+        Ok(())
+    }
+}
+
+/// Presses and releases `ch` with `SendInput` and `KEYEVENTF_UNICODE`,
+/// one UTF-16 unit at a time, whatever the keyboard layout.
+#[cfg(windows)]
+fn send_unicode(ch: char) -> Result<(), SubstitutionError> {
+    use winapi::um::winuser::{
+        SendInput, INPUT, INPUT_KEYBOARD, KEYBDINPUT, KEYEVENTF_KEYUP, KEYEVENTF_UNICODE,
+    };
+
+    let mut utf16 = [0u16; 2];
+    let mut inputs = Vec::with_capacity(4);
+    for &unit in ch.encode_utf16(&mut utf16).iter() {
+        for flags in [KEYEVENTF_UNICODE, KEYEVENTF_UNICODE | KEYEVENTF_KEYUP] {
+            // SAFETY: INPUT is plain data, valid when zeroed.
+            let mut input: INPUT = unsafe { std::mem::zeroed() };
+            input.type_ = INPUT_KEYBOARD;
+            // SAFETY: `type_` selects the keyboard member of the union.
+            unsafe {
+                *input.u.ki_mut() = KEYBDINPUT {
+                    wVk: 0,
+                    wScan: unit,
+                    dwFlags: flags,
+                    time: 0,
+                    dwExtraInfo: 0,
+                };
+            }
+            inputs.push(input);
+        }
+    }
+    // SAFETY: `inputs` holds `inputs.len()` initialised INPUT structures.
+    let sent = unsafe {
+        SendInput(
+            inputs.len() as u32,
+            inputs.as_mut_ptr(),
+            std::mem::size_of::<INPUT>() as i32,
+        )
+    };
+    if sent as usize != inputs.len() {
+        return Err(SubstitutionError::SimulationFailed(format!(
+            "SendInput sent {} of {} events for U+{:04X}",
+            sent,
+            inputs.len(),
+            ch as u32
+        )));
+    }
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// XTestBackend (Linux)
+// ---------------------------------------------------------------------------
+
+/// Sends real key events through the XTest extension (Linux).
+///
+/// Works in terminals, where rdev's `Key::Unknown` doesn't, and types
+/// characters missing from the keymap by remapping a spare keycode. This is
+/// also the backend behind the historical "xdotool" paste method. A display
+/// connection is opened per operation.
+#[cfg(target_os = "linux")]
+pub struct XTestBackend {
+    delay: Duration,
+    pre_deletion_delay: Duration,
+    paste_chord: KeyChord,
+}
+
+#[cfg(target_os = "linux")]
+impl XTestBackend {
+    pub fn new(config: &SubstitutionConfig) -> Self {
+        Self {
+            delay: Duration::from_millis(config.key_delay_ms),
+            pre_deletion_delay: Duration::from_millis(config.pre_deletion_delay_ms),
+            paste_chord: KeyChord::paste(config),
+        }
+    }
+
+    fn with_keyboard(
+        &self,
+        f: impl FnOnce(&mut XTestKeyboard) -> Result<(), crate::platform::PlatformError>,
+    ) -> Result<(), SubstitutionError> {
+        XTestKeyboard::open()
+            .and_then(|mut keyboard| f(&mut keyboard))
+            .map_err(|e| SubstitutionError::SimulationFailed(e.to_string()))
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl OutputBackend for XTestBackend {
+    fn name(&self) -> &'static str {
+        "xtest"
+    }
+
+    /// Waits the configured pre-deletion delay first, so browsers (Firefox,
+    /// Chrome) have processed the last keystroke; otherwise the first
+    /// character of the keyword may remain undeleted.
+    fn delete_graphemes(&mut self, count: usize) -> Result<(), SubstitutionError> {
+        if count == 0 {
+            return Ok(());
+        }
+        tracing::debug!(
+            "Deleting {} characters via XTest backspace (with {:?} pre-deletion delay)",
+            count,
+            self.pre_deletion_delay
+        );
+        thread::sleep(self.pre_deletion_delay);
+        let delay = self.delay;
+        self.with_keyboard(|keyboard| {
+            keyboard.tap_repeated(x11::keysym::XK_BackSpace, count, delay)
+        })
+    }
+
+    fn type_text(&mut self, text: &str) -> Result<(), SubstitutionError> {
+        tracing::debug!("Inserting via XTest: {} chars", text.len());
+        let delay = self.delay;
+        self.with_keyboard(|keyboard| keyboard.type_text(text, delay))
+    }
+
+    fn paste(&mut self, _text: &str) -> Result<(), SubstitutionError> {
+        let chord = self.paste_chord.clone();
+        self.key_chord(&chord)
+    }
+
+    fn key_chord(&mut self, chord: &KeyChord) -> Result<(), SubstitutionError> {
+        let delay = self.delay;
+        self.with_keyboard(|keyboard| keyboard.tap_chord(chord.modifiers, &chord.key, delay))
+    }
+
+    fn move_cursor(&mut self, offset: isize) -> Result<(), SubstitutionError> {
+        if offset == 0 {
+            return Ok(());
+        }
+        let sym = if offset < 0 {
+            x11::keysym::XK_Left
+        } else {
+            x11::keysym::XK_Right
+        };
+        let delay = self.delay;
+        self.with_keyboard(|keyboard| keyboard.tap_repeated(sym, offset.unsigned_abs(), delay))
+    }
+
+    /// Unused while `type_text` remaps a spare keycode for any character.
+    fn unicode_input(&self) -> UnicodeInput {
+        UnicodeInput::KeySequence
+    }
+}
+
+// ---------------------------------------------------------------------------
+// UinputBackend (Linux)
+// ---------------------------------------------------------------------------
+
+/// Types on the shared uinput virtual keyboard (Linux).
+///
+/// Unlike XTest and rdev, this works on native Wayland.
+#[cfg(target_os = "linux")]
+pub struct UinputBackend {
+    delay: Duration,
+    pre_deletion_delay: Duration,
+    paste_chord: KeyChord,
+}
+
+#[cfg(target_os = "linux")]
+impl UinputBackend {
+    pub fn new(config: &SubstitutionConfig) -> Self {
+        Self {
+            delay: Duration::from_millis(config.key_delay_ms),
+            pre_deletion_delay: Duration::from_millis(config.pre_deletion_delay_ms),
+            paste_chord: KeyChord::paste(config),
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn uinput_error(e: crate::platform::PlatformError) -> SubstitutionError {
+    SubstitutionError::SimulationFailed(e.to_string())
+}
+
+#[cfg(target_os = "linux")]
+impl OutputBackend for UinputBackend {
+    fn name(&self) -> &'static str {
+        "uinput"
+    }
+
+    /// Honours the pre-deletion delay for the same reason as
+    /// [`XTestBackend`].
+    fn delete_graphemes(&mut self, count: usize) -> Result<(), SubstitutionError> {
+        if count == 0 {
+            return Ok(());
+        }
+        tracing::debug!("Deleting {} characters via uinput backspace", count);
+        thread::sleep(self.pre_deletion_delay);
+        let delay = self.delay;
+        with_virtual_keyboard(|keyboard| keyboard.backspace(count, delay)).map_err(uinput_error)
+    }
+
+    /// Types the characters [`Self::can_type`] accepts; callers route the
+    /// rest through [`OutputBackend::type_unicode`]. Any that still get
+    /// here are entered with the same Ctrl+Shift+U sequence.
+    fn type_text(&mut self, text: &str) -> Result<(), SubstitutionError> {
+        tracing::debug!("Inserting via uinput: {} chars", text.len());
+        let delay = self.delay;
+        with_virtual_keyboard(|keyboard| keyboard.type_text(text, delay)).map_err(uinput_error)
+    }
+
+    fn paste(&mut self, _text: &str) -> Result<(), SubstitutionError> {
+        let chord = self.paste_chord.clone();
+        self.key_chord(&chord)
+    }
+
+    fn key_chord(&mut self, chord: &KeyChord) -> Result<(), SubstitutionError> {
+        let delay = self.delay;
+        with_virtual_keyboard(|keyboard| keyboard.tap_chord(chord.modifiers, &chord.key, delay))
+            .map_err(uinput_error)
+    }
+
+    fn move_cursor(&mut self, offset: isize) -> Result<(), SubstitutionError> {
+        if offset == 0 {
+            return Ok(());
+        }
+        let delay = self.delay;
+        let key = arrow_key(offset);
+        with_virtual_keyboard(|keyboard| keyboard.tap_key(&key, offset.unsigned_abs(), delay))
+            .map_err(uinput_error)
+    }
+
+    /// Follows the virtual keyboard's layout. If the device can't be
+    /// created, `type_text` reports why.
+    fn can_type(&self, ch: char) -> bool {
+        with_virtual_keyboard(|keyboard| Ok(keyboard.can_type(ch))).unwrap_or(true)
+    }
+    fn unicode_input(&self) -> UnicodeInput {
+        UnicodeInput::KeySequence
+    }
+}
+
+// ---------------------------------------------------------------------------
+// MacOsBackend (macOS)
+// ---------------------------------------------------------------------------
+
+/// Minimal CoreGraphics bindings for posting keyboard events.
+#[cfg(target_os = "macos")]
+mod cg {
+    use std::ffi::c_void;
+
+    type CGEventRef = *mut c_void;
+    type CGEventSourceRef = *mut c_void;
+
+    #[link(name = "CoreGraphics", kind = "framework")]
+    extern "C" {
+        fn CGEventSourceCreate(state_id: i32) -> CGEventSourceRef;
+        fn CGEventCreateKeyboardEvent(
+            source: CGEventSourceRef,
+            virtual_key: u16,
+            key_down: bool,
+        ) -> CGEventRef;
+        fn CGEventSetFlags(event: CGEventRef, flags: u64);
+        fn CGEventKeyboardSetUnicodeString(event: CGEventRef, length: u64, string: *const u16);
+        fn CGEventPost(tap: u32, event: CGEventRef);
+    }
+
+    #[link(name = "CoreFoundation", kind = "framework")]
+    extern "C" {
+        fn CFRelease(cf: *const c_void);
+    }
+
+    /// `kCGEventSourceStateHIDSystemState`.
+    pub const HID_SYSTEM_STATE: i32 = 1;
+    /// `kCGEventSourceStatePrivate`; events from it ignore the modifiers
+    /// the user is holding.
+    pub const PRIVATE_STATE: i32 = -1;
+    /// `kCGHIDEventTap`.
+    const HID_EVENT_TAP: u32 = 0;
+
+    pub const FLAG_SHIFT: u64 = 0x0002_0000;
+    pub const FLAG_CONTROL: u64 = 0x0004_0000;
+    pub const FLAG_ALTERNATE: u64 = 0x0008_0000;
+    pub const FLAG_COMMAND: u64 = 0x0010_0000;
+
+    /// A CoreGraphics event source, released on drop.
+    pub struct Source(CGEventSourceRef);
+
+    impl Source {
+        pub fn new(state_id: i32) -> Self {
+            // SAFETY: plain constructor; a null result is handled by the
+            // event functions, which accept a null source.
+            Self(unsafe { CGEventSourceCreate(state_id) })
+        }
+
+        /// Posts a key event for `keycode`. `flags` replaces the event's
+        /// modifier flags when set; `unicode` overrides the character it
+        /// types. Returns false if the event could not be created.
+        pub fn post(&self, keycode: u16, down: bool, flags: Option<u64>, unicode: &[u16]) -> bool {
+            // SAFETY: the event is checked for null, used only here and
+            // released once; `unicode` outlives the call that reads it.
+            unsafe {
+                let event = CGEventCreateKeyboardEvent(self.0, keycode, down);
+                if event.is_null() {
+                    return false;
+                }
+                if let Some(flags) = flags {
+                    CGEventSetFlags(event, flags);
+                }
+                if !unicode.is_empty() {
+                    CGEventKeyboardSetUnicodeString(event, unicode.len() as u64, unicode.as_ptr());
+                }
+                CGEventPost(HID_EVENT_TAP, event);
+                CFRelease(event as *const c_void);
+            }
+            true
+        }
+    }
+
+    impl Drop for Source {
+        fn drop(&mut self) {
+            if !self.0.is_null() {
+                // SAFETY: the source was created by `new` and not released.
+                unsafe { CFRelease(self.0 as *const c_void) };
+            }
+        }
+    }
+}
+
+/// macOS virtual keycode for Backspace.
+#[cfg(target_os = "macos")]
+const MAC_BACKSPACE: u16 = 51;
+
+/// Returns the macOS virtual keycode (ANSI layout) for `key`.
+#[cfg(target_os = "macos")]
+fn mac_keycode(key: &Key) -> Option<u16> {
+    let code = match key {
+        Key::Char(c) => match c.to_ascii_lowercase() {
+            'a' => 0,
+            's' => 1,
+            'd' => 2,
+            'f' => 3,
+            'h' => 4,
+            'g' => 5,
+            'z' => 6,
+            'x' => 7,
+            'c' => 8,
+            'v' => 9,
+            'b' => 11,
+            'q' => 12,
+            'w' => 13,
+            'e' => 14,
+            'r' => 15,
+            'y' => 16,
+            't' => 17,
+            '1' => 18,
+            '2' => 19,
+            '3' => 20,
+            '4' => 21,
+            '6' => 22,
+            '5' => 23,
+            '9' => 25,
+            '7' => 26,
+            '8' => 28,
+            '0' => 29,
+            'o' => 31,
+            'u' => 32,
+            'i' => 34,
+            'p' => 35,
+            'l' => 37,
+            'j' => 38,
+            'k' => 40,
+            'n' => 45,
+            'm' => 46,
+            _ => return None,
+        },
+        Key::Enter => 36,
+        Key::Tab => 48,
+        Key::Space => 49,
+        Key::Backspace => MAC_BACKSPACE,
+        Key::Escape => 53,
+        Key::F(5) => 96,
+        Key::F(6) => 97,
+        Key::F(7) => 98,
+        Key::F(3) => 99,
+        Key::F(8) => 100,
+        Key::F(9) => 101,
+        Key::F(11) => 103,
+        Key::F(10) => 109,
+        Key::F(12) => 111,
+        Key::Other(name) if name == "Insert" => 114,
+        Key::Home => 115,
+        Key::PageUp => 116,
+        Key::Delete => 117,
+        Key::F(4) => 118,
+        Key::End => 119,
+        Key::F(2) => 120,
+        Key::PageDown => 121,
+        Key::F(1) => 122,
+        Key::Left => 123,
+        Key::Right => 124,
+        Key::Down => 125,
+        Key::Up => 126,
+        _ => return None,
+    };
+    Some(code)
+}
+
+/// CoreGraphics flags for `modifiers`. Meta is the Command key.
+#[cfg(target_os = "macos")]
+fn mac_flags(modifiers: Modifiers) -> u64 {
+    [
+        (modifiers.shift, cg::FLAG_SHIFT),
+        (modifiers.ctrl, cg::FLAG_CONTROL),
+        (modifiers.alt, cg::FLAG_ALTERNATE),
+        (modifiers.meta, cg::FLAG_COMMAND),
+    ]
+    .iter()
+    .filter(|(on, _)| *on)
+    .fold(0, |flags, &(_, flag)| flags | flag)
+}
+
+/// Posts CoreGraphics keyboard events (macOS).
+///
+/// `rdev::simulate` is unreliable on macOS Ventura, and its
+/// `Key::Unknown(code)` passes code points as virtual keycodes, so text is
+/// typed with `CGEventKeyboardSetUnicodeString` instead.
+#[cfg(target_os = "macos")]
+pub struct MacOsBackend {
+    delay: Duration,
+    paste_chord: KeyChord,
+}
+
+#[cfg(target_os = "macos")]
+impl MacOsBackend {
+    pub fn new(config: &SubstitutionConfig) -> Self {
+        Self {
+            delay: Duration::from_millis(config.key_delay_ms),
+            paste_chord: KeyChord::paste(config),
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+impl OutputBackend for MacOsBackend {
+    fn name(&self) -> &'static str {
+        "macos"
+    }
+
+    /// Posts all backspaces back to back, then pauses briefly so the OS
+    /// has processed them before anything is inserted.
+    fn delete_graphemes(&mut self, count: usize) -> Result<(), SubstitutionError> {
+        if count == 0 {
+            return Ok(());
+        }
+        tracing::debug!("Deleting {} characters via macOS CGEvent backspace (fast)", count);
+        let source = cg::Source::new(cg::HID_SYSTEM_STATE);
+        for _ in 0..count {
+            source.post(MAC_BACKSPACE, true, None, &[]);
+            source.post(MAC_BACKSPACE, false, None, &[]);
+        }
+        thread::sleep(Duration::from_millis(10));
+        Ok(())
+    }
+
+    fn type_text(&mut self, text: &str) -> Result<(), SubstitutionError> {
+        tracing::debug!("Inserting via macOS CGEvent keystrokes: {} chars", text.len());
+        let source = cg::Source::new(cg::HID_SYSTEM_STATE);
+        for ch in text.chars() {
+            let mut utf16 = [0u16; 2];
+            let encoded = ch.encode_utf16(&mut utf16);
+            // Keycode 0 is a dummy; the Unicode string decides the character.
+            if !source.post(0, true, None, encoded) {
+                return Err(SubstitutionError::SimulationFailed(format!(
+                    "CGEventCreateKeyboardEvent failed for '{}'",
+                    ch
+                )));
+            }
+            thread::sleep(self.delay);
+            source.post(0, false, None, encoded);
+            thread::sleep(self.delay);
+        }
+        Ok(())
+    }
+
+    fn paste(&mut self, _text: &str) -> Result<(), SubstitutionError> {
+        let chord = self.paste_chord.clone();
+        self.key_chord(&chord)
+    }
+
+    /// Modifiers are set as flags on the key events themselves, which
+    /// macOS honours without separate modifier key events.
+    fn key_chord(&mut self, chord: &KeyChord) -> Result<(), SubstitutionError> {
+        let keycode = mac_keycode(&chord.key).ok_or_else(|| {
+            SubstitutionError::SimulationFailed(format!("Cannot simulate key {}", chord.key))
+        })?;
+        let source = cg::Source::new(cg::PRIVATE_STATE);
+        source.post(keycode, true, Some(mac_flags(chord.modifiers)), &[]);
+        thread::sleep(Duration::from_millis(10));
+        source.post(keycode, false, Some(0), &[]);
+        Ok(())
+    }
+
+    fn move_cursor(&mut self, offset: isize) -> Result<(), SubstitutionError> {
+        let keycode = match mac_keycode(&arrow_key(offset)) {
+            Some(keycode) => keycode,
+            None => return Ok(()),
+        };
+        let source = cg::Source::new(cg::HID_SYSTEM_STATE);
+        for _ in 0..offset.unsigned_abs() {
+            source.post(keycode, true, None, &[]);
+            thread::sleep(self.delay);
+            source.post(keycode, false, None, &[]);
+            thread::sleep(self.delay);
+        }
+        Ok(())
+    }
+
+    /// `type_text` posts any character as a Unicode string.
+    fn unicode_input(&self) -> UnicodeInput {
+        UnicodeInput::Direct
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_platform_paste_chord() {
+        let chord = KeyChord::platform_paste();
+        assert_eq!(chord.key, Key::Char('v'));
+        if cfg!(target_os = "macos") {
+            assert!(chord.modifiers.meta);
+        } else {
+            assert!(chord.modifiers.ctrl);
+        }
+    }
+
+    #[test]
+    fn test_paste_chord_follows_config() {
+        let mut config = SubstitutionConfig::default();
+        assert_eq!(KeyChord::paste(&config), KeyChord::platform_paste());
+        config.use_shift_insert = true;
+        assert_eq!(KeyChord::paste(&config), KeyChord::shift_insert());
+    }
+
+    #[test]
+    fn test_type_text_per_char_routes_missing_chars() {
+        let mut backend = RecordingBackend::with_keymap(|ch| ch.is_ascii());
+        type_text_per_char(&mut backend, "café €5").unwrap();
+        let unicode = OutputAction::KeyChord(KeyChord::unicode_input());
+        assert_eq!(
+            backend.actions(),
+            vec![
+                OutputAction::TypeText("caf".into()),
+                unicode.clone(),
+                OutputAction::TypeText("e9 ".into()),
+                OutputAction::TypeText(" ".into()),
+                unicode,
+                OutputAction::TypeText("20ac ".into()),
+                OutputAction::TypeText("5".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_type_text_per_char_direct_unicode() {
+        let mut backend =
+            RecordingBackend::with_keymap(|ch| ch.is_ascii()).unicode_input(UnicodeInput::Direct);
+        type_text_per_char(&mut backend, "café €5").unwrap();
+        assert_eq!(
+            backend.actions(),
+            vec![
+                OutputAction::TypeText("caf".into()),
+                OutputAction::TypeText("é".into()),
+                OutputAction::TypeText(" ".into()),
+                OutputAction::TypeText("€".into()),
+                OutputAction::TypeText("5".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_type_text_per_char_cjk_and_emoji() {
+        let mut backend = RecordingBackend::with_keymap(|ch| ch.is_ascii());
+        type_text_per_char(&mut backend, "日本😀").unwrap();
+        let typed: Vec<OutputAction> = backend
+            .actions()
+            .into_iter()
+            .filter(|action| matches!(action, OutputAction::TypeText(_)))
+            .collect();
+        assert_eq!(
+            typed,
+            vec![
+                OutputAction::TypeText("65e5 ".into()),
+                OutputAction::TypeText("672c ".into()),
+                OutputAction::TypeText("1f600 ".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_type_text_per_char_single_call_when_typable() {
+        let mut backend = RecordingBackend::new();
+        type_text_per_char(&mut backend, "naïve ☃").unwrap();
+        assert_eq!(backend.actions(), vec![OutputAction::TypeText("naïve ☃".into())]);
+    }
+
+    #[test]
+    fn test_us_keystroke() {
+        use rdev::Key as R;
+        assert_eq!(us_keystroke('a'), Some((R::KeyA, false)));
+        assert_eq!(us_keystroke('A'), Some((R::KeyA, true)));
+        assert_eq!(us_keystroke('7'), Some((R::Num7, false)));
+        assert_eq!(us_keystroke('&'), Some((R::Num7, true)));
+        assert_eq!(us_keystroke('"'), Some((R::Quote, true)));
+        assert_eq!(us_keystroke('|'), Some((R::BackSlash, true)));
+        assert_eq!(us_keystroke('\n'), Some((R::Return, false)));
+        assert_eq!(us_keystroke('é'), None);
+        assert_eq!(us_keystroke('€'), None);
+        assert_eq!(US_SHIFTED.len(), US_UNSHIFTED.len());
+    }
+
+    #[test]
+    fn test_output_action_echo() {
+        let paste = KeyChord::shift_insert();
+        assert_eq!(OutputAction::DeleteGraphemes(2).echo(&paste), vec![Key::Backspace; 2]);
+        assert_eq!(
+            OutputAction::TypeText("a b\n".into()).echo(&paste),
+            vec![Key::Char('a'), Key::Space, Key::Char('b'), Key::Enter]
+        );
+        assert_eq!(OutputAction::Paste("x".into()).echo(&paste), vec![paste.key.clone()]);
+        assert_eq!(OutputAction::MoveCursor(-3).echo(&paste), vec![Key::Left; 3]);
+    }
+
+    #[test]
+    fn test_key_chord_display() {
+        let chord = KeyChord::new(
+            Modifiers {
+                ctrl: true,
+                shift: true,
+                ..Default::default()
+            },
+            Key::Char('v'),
+        );
+        assert_eq!(chord.to_string(), "Ctrl+Shift+V");
+        assert_eq!(KeyChord::shift_insert().to_string(), "Shift+Insert");
+    }
+
+    #[test]
+    fn test_key_chord_parse() {
+        let chord: KeyChord = "ctrl + shift + v".parse().unwrap();
+        assert_eq!(chord.to_string(), "Ctrl+Shift+V");
+        assert_eq!("Shift+Insert".parse::<KeyChord>().unwrap(), KeyChord::shift_insert());
+        let cmd: KeyChord = "Cmd+V".parse().unwrap();
+        assert!(cmd.modifiers.meta);
+        assert_eq!("F5".parse::<KeyChord>().unwrap().key, Key::F(5));
+        assert_eq!("Alt+PageDown".parse::<KeyChord>().unwrap().key, Key::PageDown);
+    }
+
+    #[test]
+    fn test_key_chord_parse_rejects_garbage() {
+        for text in ["", "Ctrl+", "Hyper+V", "Ctrl+Banana", "F25"] {
+            assert_eq!(
+                text.parse::<KeyChord>(),
+                Err(InvalidKeyChord(text.to_string())),
+                "{:?}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn test_key_chord_serde() {
+        let chord = KeyChord::new(
+            Modifiers {
+                ctrl: true,
+                ..Default::default()
+            },
+            Key::Char('v'),
+        );
+        let json = serde_json::to_string(&chord).unwrap();
+        assert_eq!(
+            json,
+            r#"{"modifiers":{"ctrl":true,"alt":false,"shift":false,"meta":false},"key":{"char":"v"}}"#
+        );
+        let parsed: KeyChord = serde_json::from_str(r#"{"key":"left"}"#).unwrap();
+        assert_eq!(parsed, KeyChord::new(Modifiers::default(), Key::Left));
+    }
+
+    #[test]
+    fn test_recording_backend_shares_log_between_clones() {
+        let recorder = RecordingBackend::new();
+        let mut backend: Box<dyn OutputBackend> = Box::new(recorder.clone());
+        backend.delete_graphemes(3).unwrap();
+        backend.paste("hello").unwrap();
+        backend.move_cursor(-2).unwrap();
+        backend.key_chord(&KeyChord::shift_insert()).unwrap();
+        backend.type_text("x").unwrap();
+        assert_eq!(
+            recorder.actions(),
+            vec![
+                OutputAction::DeleteGraphemes(3),
+                OutputAction::Paste("hello".into()),
+                OutputAction::MoveCursor(-2),
+                OutputAction::KeyChord(KeyChord::shift_insert()),
+                OutputAction::TypeText("x".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_recording_backend_holds_output_until_released() {
+        let recorder = RecordingBackend::new();
+        let mut backend = recorder.clone();
+        backend.delete_graphemes(1).unwrap();
+        recorder.hold();
+        let sender = thread::spawn(move || backend.paste("held").unwrap());
+        assert!(recorder.wait_for_held(Duration::from_secs(2)));
+        assert_eq!(recorder.actions(), vec![OutputAction::DeleteGraphemes(1)]);
+        recorder.release();
+        sender.join().unwrap();
+        assert_eq!(recorder.actions().len(), 2);
+        assert!(!recorder.wait_for_held(Duration::ZERO));
+    }
+
+    #[test]
+    fn test_rdev_key_for_shortcuts() {
+        assert_eq!(rdev_key(&Key::Char('V')), Some(rdev::Key::KeyV));
+        assert_eq!(rdev_key(&Key::Char('7')), Some(rdev::Key::Num7));
+        assert_eq!(rdev_key(&Key::Other("Insert".into())), Some(rdev::Key::Insert));
+        assert_eq!(rdev_key(&Key::Char('é')), None);
+        assert_eq!(rdev_key(&Key::F(13)), None);
+    }
+
+    #[test]
+    fn test_arrow_key_direction() {
+        assert_eq!(arrow_key(-3), Key::Left);
+        assert_eq!(arrow_key(4), Key::Right);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_xtest_backend_zero_counts_skip_display() {
+        // Nothing to send, so no X connection is needed.
+        let mut backend = XTestBackend::new(&SubstitutionConfig::default());
+        assert!(backend.delete_graphemes(0).is_ok());
+        assert!(backend.move_cursor(0).is_ok());
+    }
+}
@@ -5,7 +5,11 @@ use std::path::{Path, PathBuf};
 use thiserror::Error;
 use tracing;
 
-use crate::models::preferences::Preferences;
+use crate::managers::output_backend::KeyChord;
+use crate::managers::timing_calibrator::{
+    MAX_KEY_DELAY_MS, MAX_PASTE_RESTORE_DELAY_MS, MAX_TIMING_PROFILES,
+};
+use crate::models::preferences::{Preferences, TimingProfile};
 
 /// Errors from preferences management operations.
 #[derive(Debug, Error)]
@@ -110,6 +114,13 @@ impl PreferencesManager {
         Ok(removed)
     }
 
+    /// Replaces the timing profiles, e.g. with those the engine learned,
+    /// and saves.
+    pub fn set_timing_profiles(&mut self, profiles: Vec<TimingProfile>) -> Result<(), PreferencesError> {
+        self.preferences.timing_profiles = profiles;
+        self.save()
+    }
+
     /// Validates preferences values.
     fn validate(prefs: &Preferences) -> Result<(), PreferencesError> {
         if prefs.backup_interval_hours == 0 {
@@ -132,6 +143,71 @@ impl PreferencesManager {
                 "Max backups cannot exceed 1000".to_string(),
             ));
         }
+        if prefs.undo_window_ms == 0 {
+            return Err(PreferencesError::Validation(
+                "Undo window must be greater than 0".to_string(),
+            ));
+        }
+        if prefs.undo_window_ms > 60_000 {
+            return Err(PreferencesError::Validation(
+                "Undo window cannot exceed 60000 ms (1 minute)".to_string(),
+            ));
+        }
+        const MAX_PASTE_RULES: usize = 100;
+        if prefs.paste_rules.len() > MAX_PASTE_RULES {
+            return Err(PreferencesError::Validation(format!(
+                "Cannot have more than {} paste rules",
+                MAX_PASTE_RULES
+            )));
+        }
+        for rule in &prefs.paste_rules {
+            if rule.pattern.trim().is_empty() {
+                return Err(PreferencesError::Validation(
+                    "Paste rule pattern cannot be empty".to_string(),
+                ));
+            }
+            if !rule.paste_chord.is_empty() {
+                if let Err(e) = rule.paste_chord.parse::<KeyChord>() {
+                    return Err(PreferencesError::Validation(e.to_string()));
+                }
+            }
+        }
+        if prefs.timing_profiles.len() > MAX_TIMING_PROFILES {
+            return Err(PreferencesError::Validation(format!(
+                "Cannot have more than {} timing profiles",
+                MAX_TIMING_PROFILES
+            )));
+        }
+        for (i, profile) in prefs.timing_profiles.iter().enumerate() {
+            if profile.window_class.trim().is_empty() {
+                return Err(PreferencesError::Validation(
+                    "Timing profile window class cannot be empty".to_string(),
+                ));
+            }
+            if prefs.timing_profiles[..i]
+                .iter()
+                .any(|other| other.matches(&profile.window_class))
+            {
+                return Err(PreferencesError::Validation(format!(
+                    "Duplicate timing profile for {}",
+                    profile.window_class
+                )));
+            }
+            if profile.key_delay_ms > MAX_KEY_DELAY_MS
+                || profile.pre_deletion_delay_ms > MAX_KEY_DELAY_MS
+            {
+                return Err(PreferencesError::Validation(format!(
+                    "Key delays cannot exceed {} ms",
+                    MAX_KEY_DELAY_MS
+                )));
+            }
+            if profile.paste_restore_delay_ms > MAX_PASTE_RESTORE_DELAY_MS {
+                return Err(PreferencesError::Validation(format!(
+                    "Paste restore delay cannot exceed {} ms",
+                    MAX_PASTE_RESTORE_DELAY_MS
+                )));
+            }
+        }
         Ok(())
     }
 }
@@ -197,6 +273,139 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_update_rejects_invalid_undo_window() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("prefs.json");
+        let mut mgr = PreferencesManager::new(path).unwrap();
+
+        let prefs = Preferences {
+            undo_window_ms: 0,
+            ..Default::default()
+        };
+        assert!(mgr.update(prefs).is_err());
+
+        let prefs = Preferences {
+            undo_window_ms: 60_001,
+            ..Default::default()
+        };
+        assert!(mgr.update(prefs).is_err());
+    }
+
+    #[test]
+    fn test_update_validates_paste_rules() {
+        use crate::models::preferences::{PasteMethod, PasteRule, PasteRuleField};
+
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("prefs.json");
+        let mut mgr = PreferencesManager::new(path).unwrap();
+
+        let rule = PasteRule {
+            field: PasteRuleField::WindowClass,
+            pattern: "terminal".to_string(),
+            paste_method: PasteMethod::Clipboard,
+            paste_chord: "Ctrl+Shift+V".to_string(),
+        };
+        let prefs = Preferences {
+            paste_rules: vec![rule.clone()],
+            ..Default::default()
+        };
+        mgr.update(prefs).unwrap();
+        assert_eq!(mgr.get().paste_rules, vec![rule.clone()]);
+
+        let prefs = Preferences {
+            paste_rules: vec![PasteRule {
+                pattern: "  ".to_string(),
+                ..rule.clone()
+            }],
+            ..Default::default()
+        };
+        assert!(matches!(mgr.update(prefs), Err(PreferencesError::Validation(_))));
+
+        let prefs = Preferences {
+            paste_rules: vec![PasteRule {
+                paste_chord: "Ctrl+Banana".to_string(),
+                ..rule
+            }],
+            ..Default::default()
+        };
+        assert!(matches!(mgr.update(prefs), Err(PreferencesError::Validation(_))));
+    }
+
+    #[test]
+    fn test_update_validates_timing_profiles() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("prefs.json");
+        let mut mgr = PreferencesManager::new(path).unwrap();
+
+        let profile = TimingProfile {
+            window_class: "firefox".to_string(),
+            key_delay_ms: 10,
+            paste_restore_delay_ms: 400,
+            pre_deletion_delay_ms: 40,
+            learned: false,
+        };
+        let prefs = Preferences {
+            timing_profiles: vec![profile.clone()],
+            ..Default::default()
+        };
+        mgr.update(prefs).unwrap();
+        assert_eq!(mgr.get().timing_profiles, vec![profile.clone()]);
+
+        let invalid = [
+            TimingProfile {
+                window_class: " ".to_string(),
+                ..profile.clone()
+            },
+            TimingProfile {
+                key_delay_ms: MAX_KEY_DELAY_MS + 1,
+                ..profile.clone()
+            },
+            TimingProfile {
+                paste_restore_delay_ms: MAX_PASTE_RESTORE_DELAY_MS + 1,
+                ..profile.clone()
+            },
+        ];
+        for bad in invalid {
+            let prefs = Preferences {
+                timing_profiles: vec![bad],
+                ..Default::default()
+            };
+            assert!(matches!(mgr.update(prefs), Err(PreferencesError::Validation(_))));
+        }
+
+        let prefs = Preferences {
+            timing_profiles: vec![
+                profile.clone(),
+                TimingProfile {
+                    window_class: "Firefox".to_string(),
+                    ..profile
+                },
+            ],
+            ..Default::default()
+        };
+        assert!(matches!(mgr.update(prefs), Err(PreferencesError::Validation(_))));
+    }
+
+    #[test]
+    fn test_set_timing_profiles_saves_to_disk() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("prefs.json");
+        let mut mgr = PreferencesManager::new(path.clone()).unwrap();
+
+        let profiles = vec![TimingProfile {
+            window_class: "firefox".to_string(),
+            key_delay_ms: 5,
+            paste_restore_delay_ms: 300,
+            pre_deletion_delay_ms: 20,
+            learned: true,
+        }];
+        mgr.set_timing_profiles(profiles.clone()).unwrap();
+
+        let loaded = PreferencesManager::load(&path).unwrap();
+        assert_eq!(loaded.timing_profiles, profiles);
+    }
+
     #[test]
     fn test_reset_to_defaults() {
         let tmp = tempfile::tempdir().unwrap();
@@ -334,7 +543,7 @@ mod tests {
 
     #[test]
     fn test_every_field_save_load_roundtrip() {
-        use crate::models::preferences::{PasteMethod, Theme};
+        use crate::models::preferences::{PasteMethod, PasteRule, PasteRuleField, Theme};
         use crate::models::matching::MatchingMode;
 
         let tmp = tempfile::tempdir().unwrap();
@@ -358,6 +567,23 @@ mod tests {
             max_backups: 25,
             auto_check_updates: false,
             excluded_apps: vec!["1password".to_string(), "keepass".to_string()],
+            paste_rules: vec![PasteRule {
+                field: PasteRuleField::WindowTitle,
+                pattern: "ssh".to_string(),
+                paste_method: PasteMethod::XdotoolType,
+                paste_chord: String::new(),
+            }],
+            undo_with_backspace: true,
+            undo_window_ms: 1500,
+            record_keystroke_trace: true,
+            timing_profiles: vec![TimingProfile {
+                window_class: "firefox".to_string(),
+                key_delay_ms: 12,
+                paste_restore_delay_ms: 450,
+                pre_deletion_delay_ms: 40,
+                learned: true,
+            }],
+            adaptive_timing: false,
         };
         mgr.update(custom.clone()).unwrap();
 
@@ -378,6 +604,12 @@ mod tests {
         assert_eq!(loaded.max_backups, 25);
         assert_eq!(loaded.auto_check_updates, false);
         assert_eq!(loaded.excluded_apps, vec!["1password", "keepass"]);
+        assert_eq!(loaded.paste_rules, custom.paste_rules);
+        assert!(loaded.undo_with_backspace);
+        assert_eq!(loaded.undo_window_ms, 1500);
+        assert!(loaded.record_keystroke_trace);
+        assert_eq!(loaded.timing_profiles, custom.timing_profiles);
+        assert!(!loaded.adaptive_timing);
     }
 
     #[test]
@@ -422,7 +654,12 @@ mod tests {
         let path = tmp.path().join("prefs.json");
         let mut mgr = PreferencesManager::new(path.clone()).unwrap();
 
-        for method in [PasteMethod::Clipboard, PasteMethod::SimulateKeystrokes, PasteMethod::XdotoolType] {
+        for method in [
+            PasteMethod::Clipboard,
+            PasteMethod::SimulateKeystrokes,
+            PasteMethod::XdotoolType,
+            PasteMethod::Uinput,
+        ] {
             let mut prefs = Preferences::default();
             prefs.paste_method = method;
             mgr.update(prefs).unwrap();
@@ -500,6 +737,12 @@ mod tests {
             max_backups: 50,
             auto_check_updates: false,
             excluded_apps: vec!["app1".to_string()],
+            paste_rules: Vec::new(),
+            undo_with_backspace: true,
+            undo_window_ms: 5000,
+            record_keystroke_trace: true,
+            timing_profiles: Vec::new(),
+            adaptive_timing: false,
         };
         mgr.update(custom).unwrap();
 
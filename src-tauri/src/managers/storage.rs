@@ -47,6 +47,9 @@ const BACKUPS_DIR_NAME: &str = "backups";
 /// The subdirectory name for logs.
 const LOGS_DIR_NAME: &str = "logs";
 
+/// The subdirectory name for snippet assets such as images.
+const ASSETS_DIR_NAME: &str = "assets";
+
 /// Returns the platform-specific configuration directory for MuttonText.
 ///
 /// - Linux: `~/.config/muttontext/`
@@ -78,14 +81,21 @@ pub fn get_logs_dir() -> Result<PathBuf, StorageError> {
     Ok(get_config_dir()?.join(LOGS_DIR_NAME))
 }
 
+/// Returns the path to the snippet assets directory.
+pub fn get_assets_dir() -> Result<PathBuf, StorageError> {
+    Ok(get_config_dir()?.join(ASSETS_DIR_NAME))
+}
+
 /// Ensures all required directories exist, creating them if necessary.
 ///
-/// Creates the config directory, backups subdirectory, and logs subdirectory.
+/// Creates the config directory and its backups, logs, and assets
+/// subdirectories.
 pub fn ensure_dirs_exist() -> Result<(), StorageError> {
     let config_dir = get_config_dir()?;
     fs::create_dir_all(&config_dir)?;
     fs::create_dir_all(config_dir.join(BACKUPS_DIR_NAME))?;
     fs::create_dir_all(config_dir.join(LOGS_DIR_NAME))?;
+    fs::create_dir_all(config_dir.join(ASSETS_DIR_NAME))?;
     Ok(())
 }
 
@@ -134,6 +144,13 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_get_assets_dir_ends_with_assets() {
+        if let Ok(path) = get_assets_dir() {
+            assert!(path.ends_with(ASSETS_DIR_NAME));
+        }
+    }
+
     #[test]
     fn test_ensure_dirs_exist_creates_directories() {
         // Use a temp directory to test directory creation logic.
@@ -1,15 +1,28 @@
 //! Substitution engine for MuttonText.
 //!
 //! Handles deleting the typed keyword (via backspace key events) and inserting
-//! the expanded snippet (via clipboard paste or simulated keystrokes).
+//! the expanded snippet (via clipboard paste or simulated keystrokes). The
+//! key events themselves are sent by an [`OutputBackend`].
 
+use std::borrow::Cow;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use rdev::{simulate, EventType, Key};
 use thiserror::Error;
 
-use crate::managers::clipboard_manager::{ClipboardError, ClipboardManager, ClipboardProvider};
+use crate::managers::clipboard_manager::{
+    ClipboardError, ClipboardFormat, ClipboardManager, ClipboardProvider,
+};
+#[cfg(target_os = "macos")]
+use crate::managers::output_backend::MacOsBackend;
+use crate::managers::output_backend::{type_text_per_char, KeyChord, OutputBackend, UnicodeInput};
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+use crate::managers::output_backend::RdevBackend;
+#[cfg(target_os = "linux")]
+use crate::managers::output_backend::{UinputBackend, XTestBackend};
+use crate::models::preferences::PasteMethod;
+use crate::platform::keyboard_hook::{FocusDetector, WindowInfo};
+use crate::utils::graphemes::grapheme_count;
 
 /// Maximum allowed keyword length to prevent excessive backspace simulation.
 const MAX_KEYWORD_LENGTH: usize = 256;
@@ -39,8 +52,12 @@ pub enum SubstitutionError {
     SnippetTooLarge(usize, usize),
     #[error("Target window lost focus during substitution")]
     FocusLost,
+    #[error("Substitution was cancelled")]
+    Cancelled,
     #[error("Substitution timed out after {0} seconds")]
     Timeout(u64),
+    #[error("Image snippet unavailable: {0}")]
+    ImageUnavailable(String),
 }
 
 /// Configuration for the substitution engine.
@@ -58,7 +75,7 @@ pub struct SubstitutionConfig {
     pub chunk_delay_ms: u64,
     /// Delay before starting backspace deletion, in milliseconds.
     /// This allows applications (especially browsers) to fully process the last
-    /// keystroke before xdotool starts deleting. Without this delay, the first
+    /// keystroke before XTest starts deleting. Without this delay, the first
     /// character of the keyword may not be deleted in Firefox/Chrome.
     pub pre_deletion_delay_ms: u64,
 }
@@ -81,6 +98,96 @@ impl FocusChecker for NoOpFocusChecker {
     }
 }
 
+/// Focus checker that compares the focused window reported by a
+/// [`FocusDetector`] with the window that had focus when the keyword was
+/// matched.
+///
+/// Windows are compared by application and process, not by title: many
+/// editors change their title as soon as the keyword is deleted. When
+/// either window is unknown the checker can't tell, and reports focused.
+pub struct WindowFocusChecker<'a> {
+    detector: &'a dyn FocusDetector,
+    target: WindowInfo,
+}
+
+impl<'a> WindowFocusChecker<'a> {
+    pub fn new(detector: &'a dyn FocusDetector, target: WindowInfo) -> Self {
+        Self { detector, target }
+    }
+}
+
+impl FocusChecker for WindowFocusChecker<'_> {
+    fn is_target_focused(&self) -> bool {
+        let unknown = WindowInfo::default().app_name;
+        let current = match self.detector.get_active_window_info() {
+            Ok(info) => info,
+            Err(_) => return true,
+        };
+        if self.target.app_name == unknown || current.app_name == unknown {
+            return true;
+        }
+        current.app_name == self.target.app_name && current.process_id == self.target.process_id
+    }
+}
+
+/// Wraps an [`OutputBackend`], checking focus before every operation so an
+/// expansion stops with [`SubstitutionError::FocusLost`] between its
+/// delete, paste and key-action phases once the target window loses focus.
+pub struct FocusGuardedBackend<'a, C: FocusChecker> {
+    inner: &'a mut dyn OutputBackend,
+    checker: C,
+}
+
+impl<'a, C: FocusChecker> FocusGuardedBackend<'a, C> {
+    pub fn new(inner: &'a mut dyn OutputBackend, checker: C) -> Self {
+        Self { inner, checker }
+    }
+}
+
+impl<C: FocusChecker> OutputBackend for FocusGuardedBackend<'_, C> {
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    fn delete_graphemes(&mut self, count: usize) -> Result<(), SubstitutionError> {
+        check_focus(&self.checker)?;
+        self.inner.delete_graphemes(count)
+    }
+
+    fn type_text(&mut self, text: &str) -> Result<(), SubstitutionError> {
+        check_focus(&self.checker)?;
+        self.inner.type_text(text)
+    }
+
+    fn paste(&mut self, text: &str) -> Result<(), SubstitutionError> {
+        check_focus(&self.checker)?;
+        self.inner.paste(text)
+    }
+
+    fn key_chord(&mut self, chord: &KeyChord) -> Result<(), SubstitutionError> {
+        check_focus(&self.checker)?;
+        self.inner.key_chord(chord)
+    }
+
+    fn move_cursor(&mut self, offset: isize) -> Result<(), SubstitutionError> {
+        check_focus(&self.checker)?;
+        self.inner.move_cursor(offset)
+    }
+
+    fn can_type(&self, ch: char) -> bool {
+        self.inner.can_type(ch)
+    }
+
+    fn unicode_input(&self) -> UnicodeInput {
+        self.inner.unicode_input()
+    }
+
+    fn type_unicode(&mut self, ch: char) -> Result<(), SubstitutionError> {
+        check_focus(&self.checker)?;
+        self.inner.type_unicode(ch)
+    }
+}
+
 impl Default for SubstitutionConfig {
     fn default() -> Self {
         Self {
@@ -98,137 +205,129 @@ impl Default for SubstitutionConfig {
     }
 }
 
-/// Sends a single key event via rdev, with a configurable delay.
-fn send_key_event(event_type: EventType, delay: Duration) -> Result<(), SubstitutionError> {
-    simulate(&event_type).map_err(|e| {
-        SubstitutionError::SimulationFailed(format!("{:?}", e))
-    })?;
-    thread::sleep(delay);
+/// Returns an error if a keyword of `count` grapheme clusters is too long to
+/// delete.
+fn check_keyword_len(count: usize) -> Result<(), SubstitutionError> {
+    if count > MAX_KEYWORD_LENGTH {
+        return Err(SubstitutionError::KeywordTooLong(count, MAX_KEYWORD_LENGTH));
+    }
     Ok(())
 }
 
-/// Sends a key press (down + up) with delay.
-fn press_key(key: Key, delay: Duration) -> Result<(), SubstitutionError> {
-    send_key_event(EventType::KeyPress(key), delay)?;
-    send_key_event(EventType::KeyRelease(key), delay)?;
+/// Returns an error if `text` is too large to type key by key.
+fn check_snippet_size(text: &str) -> Result<(), SubstitutionError> {
+    if text.len() > MAX_SNIPPET_SIZE {
+        return Err(SubstitutionError::SnippetTooLarge(text.len(), MAX_SNIPPET_SIZE));
+    }
     Ok(())
 }
 
-/// macOS-specific fast keyword deletion using CoreGraphics.
-///
-/// Sends all backspace key events through CGEventPost with minimal delay,
-/// bypassing rdev::simulate which adds overhead on macOS Ventura.
-#[cfg(target_os = "macos")]
-fn delete_keyword_macos(count: usize, _config: &SubstitutionConfig) -> Result<(), SubstitutionError> {
-    if count > MAX_KEYWORD_LENGTH {
-        return Err(SubstitutionError::KeywordTooLong(count, MAX_KEYWORD_LENGTH));
-    }
-    if count == 0 {
-        return Ok(());
+/// How long to wait after writing the clipboard before pasting. The macOS
+/// pasteboard needs longer to commit new contents.
+fn clipboard_settle_delay(config: &SubstitutionConfig) -> Duration {
+    if cfg!(target_os = "macos") {
+        Duration::from_millis(50)
+    } else {
+        Duration::from_millis(config.key_delay_ms)
     }
-    tracing::debug!("Deleting {} characters via macOS CGEvent backspace (fast)", count);
-
-    use std::ffi::c_void;
-
-    type CGEventRef = *mut c_void;
-    type CGEventSourceRef = *mut c_void;
+}
 
-    #[link(name = "CoreGraphics", kind = "framework")]
-    extern "C" {
-        fn CGEventSourceCreate(state_id: i32) -> CGEventSourceRef;
-        fn CGEventCreateKeyboardEvent(
-            source: CGEventSourceRef,
-            virtual_key: u16,
-            key_down: bool,
-        ) -> CGEventRef;
-        fn CGEventPost(tap: u32, event: CGEventRef);
-    }
+/// Text to insert, with the HTML rendering of a rich-text snippet, or an
+/// image to paste.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Snippet<'a> {
+    /// Plain text, typed by keystroke methods and pasted as `text/plain`.
+    pub text: &'a str,
+    /// Pasted as `text/html` alongside `text` when set.
+    pub html: Option<&'a str>,
+    /// Pasted instead of the text when set.
+    pub image: Option<&'a ClipboardFormat>,
+    /// Ask clipboard managers not to record the snippet while it is on the
+    /// clipboard.
+    pub exclude_from_history: bool,
+}
 
-    #[link(name = "CoreFoundation", kind = "framework")]
-    extern "C" {
-        fn CFRelease(cf: *const c_void);
+impl<'a> Snippet<'a> {
+    pub fn plain(text: &'a str) -> Self {
+        Self {
+            text,
+            html: None,
+            image: None,
+            exclude_from_history: false,
+        }
     }
 
-    const HID_SYSTEM_STATE: i32 = 1;
-    const HID_EVENT_TAP: u32 = 0;
-    const BACKSPACE_KEYCODE: u16 = 51; // macOS virtual keycode for backspace
-
-    unsafe {
-        let source = CGEventSourceCreate(HID_SYSTEM_STATE);
-
-        for _ in 0..count {
-            let key_down = CGEventCreateKeyboardEvent(source, BACKSPACE_KEYCODE, true);
-            if !key_down.is_null() {
-                CGEventPost(HID_EVENT_TAP, key_down);
-                CFRelease(key_down as *const c_void);
-            }
-
-            let key_up = CGEventCreateKeyboardEvent(source, BACKSPACE_KEYCODE, false);
-            if !key_up.is_null() {
-                CGEventPost(HID_EVENT_TAP, key_up);
-                CFRelease(key_up as *const c_void);
-            }
+    pub fn rich(text: &'a str, html: &'a str) -> Self {
+        Self {
+            text,
+            html: Some(html),
+            image: None,
+            exclude_from_history: false,
         }
+    }
 
-        // Brief pause to let the OS process all backspaces before inserting
-        std::thread::sleep(std::time::Duration::from_millis(10));
-
-        if !source.is_null() {
-            CFRelease(source as *const c_void);
+    pub fn image(image: &'a ClipboardFormat) -> Self {
+        Self {
+            text: "",
+            html: None,
+            image: Some(image),
+            exclude_from_history: false,
         }
     }
 
-    Ok(())
-}
-
-/// Deletes `count` characters by sending backspace key events.
-pub fn delete_keyword(count: usize, config: &SubstitutionConfig) -> Result<(), SubstitutionError> {
-    if count > MAX_KEYWORD_LENGTH {
-        return Err(SubstitutionError::KeywordTooLong(count, MAX_KEYWORD_LENGTH));
+    /// Marks the snippet to be kept out of clipboard history.
+    pub fn excluded_from_history(mut self, exclude: bool) -> Self {
+        self.exclude_from_history = exclude;
+        self
     }
-    tracing::debug!("Deleting {} characters via backspace", count);
-    let delay = Duration::from_millis(config.key_delay_ms);
-    for _ in 0..count {
-        press_key(Key::Backspace, delay)?;
+
+    /// The clipboard contents that paste this snippet.
+    fn clipboard_format(&self) -> Cow<'a, ClipboardFormat> {
+        match (self.image, self.html) {
+            (Some(image), _) => Cow::Borrowed(image),
+            (None, Some(html)) => Cow::Owned(ClipboardFormat::Html {
+                html: html.to_string(),
+                alt_text: Some(self.text.to_string()),
+            }),
+            (None, None) => Cow::Owned(ClipboardFormat::Text(self.text.to_string())),
+        }
     }
-    Ok(())
 }
 
-/// Inserts text by writing it to the clipboard and simulating paste.
+/// Inserts a snippet by writing it to the clipboard and pressing
+/// `paste_chord`, or the backend's paste shortcut when it is `None`.
+/// Rich snippets are written as HTML with their text as the alternative,
+/// and image snippets as the image alone. Snippets excluded from history
+/// are written concealed.
 ///
 /// Preserves and restores the user's clipboard content.
 pub fn insert_via_clipboard<P: ClipboardProvider>(
-    text: &str,
+    backend: &mut dyn OutputBackend,
+    snippet: Snippet<'_>,
+    paste_chord: Option<&KeyChord>,
     clipboard_mgr: &mut ClipboardManager<P>,
     config: &SubstitutionConfig,
 ) -> Result<(), SubstitutionError> {
-    tracing::debug!("Inserting via clipboard: {} chars", text.len());
+    let text = snippet.text;
+    tracing::debug!("Inserting via clipboard ({}): {} chars", backend.name(), text.len());
 
     // Preserve current clipboard
     clipboard_mgr.preserve()?;
 
     // Write snippet to clipboard
-    clipboard_mgr.write(text)?;
+    let format = snippet.clipboard_format();
+    if snippet.exclude_from_history {
+        clipboard_mgr.write_concealed(&format)?;
+    } else {
+        clipboard_mgr.write_format(&format)?;
+    }
 
     // Small delay to ensure clipboard is ready
-    thread::sleep(Duration::from_millis(config.key_delay_ms));
-
-    // Simulate paste
-    let delay = Duration::from_millis(config.key_delay_ms);
-    let paste_result = if config.use_shift_insert {
-        send_key_event(EventType::KeyPress(Key::ShiftLeft), delay)
-            .and_then(|_| press_key(Key::Insert, delay))
-            .and_then(|_| send_key_event(EventType::KeyRelease(Key::ShiftLeft), delay))
-    } else {
-        // Use Cmd+V on macOS, Ctrl+V on other platforms
-        let paste_modifier = if cfg!(target_os = "macos") {
-            Key::MetaLeft
-        } else {
-            Key::ControlLeft
-        };
-        send_key_event(EventType::KeyPress(paste_modifier), delay)
-            .and_then(|_| press_key(Key::KeyV, delay))
-            .and_then(|_| send_key_event(EventType::KeyRelease(paste_modifier), delay))
+    thread::sleep(clipboard_settle_delay(config));
+
+    let paste_result = match paste_chord {
+        Some(chord) => backend.key_chord(chord),
+        None => backend.paste(text),
     };
 
     // Wait for paste to complete before restoring clipboard
@@ -244,410 +343,49 @@ pub fn insert_via_clipboard<P: ClipboardProvider>(
     Ok(())
 }
 
-/// macOS-specific clipboard paste using CoreGraphics CGEventPost.
-///
-/// Uses CGEventPost to simulate Cmd+V instead of rdev::simulate,
-/// which doesn't work reliably on macOS Ventura.
-#[cfg(target_os = "macos")]
-pub fn insert_via_clipboard_macos<P: ClipboardProvider>(
-    text: &str,
-    clipboard_mgr: &mut ClipboardManager<P>,
-    config: &SubstitutionConfig,
-) -> Result<(), SubstitutionError> {
-    tracing::debug!("Inserting via macOS clipboard paste (CGEvent): {} chars", text.len());
-
-    use std::ffi::c_void;
-
-    type CGEventRef = *mut c_void;
-    type CGEventSourceRef = *mut c_void;
-
-    #[link(name = "CoreGraphics", kind = "framework")]
-    extern "C" {
-        fn CGEventSourceCreate(state_id: i32) -> CGEventSourceRef;
-        fn CGEventCreateKeyboardEvent(
-            source: CGEventSourceRef,
-            virtual_key: u16,
-            key_down: bool,
-        ) -> CGEventRef;
-        fn CGEventSetFlags(event: CGEventRef, flags: u64);
-        fn CGEventPost(tap: u32, event: CGEventRef);
-    }
-
-    #[link(name = "CoreFoundation", kind = "framework")]
-    extern "C" {
-        fn CFRelease(cf: *const c_void);
-    }
-
-    const PRIVATE_STATE: i32 = -1;      // kCGEventSourceStatePrivate
-    const HID_EVENT_TAP: u32 = 0;
-    const V_KEYCODE: u16 = 9;           // macOS virtual keycode for 'v'
-    const CMD_FLAG: u64 = 0x00100000;   // kCGEventFlagMaskCommand
-
-    // Preserve current clipboard
-    clipboard_mgr.preserve()?;
-
-    // Write snippet to clipboard
-    clipboard_mgr.write(text)?;
-
-    // Delay to ensure clipboard content is committed to the pasteboard
-    std::thread::sleep(std::time::Duration::from_millis(50));
-
-    unsafe {
-        let source = CGEventSourceCreate(PRIVATE_STATE);
-
-        // V key down with Command flag — macOS reads flags from the event itself
-        let v_down = CGEventCreateKeyboardEvent(source, V_KEYCODE, true);
-        if !v_down.is_null() {
-            CGEventSetFlags(v_down, CMD_FLAG);
-            CGEventPost(HID_EVENT_TAP, v_down);
-            CFRelease(v_down as *const c_void);
-        }
-
-        std::thread::sleep(std::time::Duration::from_millis(10));
-
-        // V key up — clear modifier flags
-        let v_up = CGEventCreateKeyboardEvent(source, V_KEYCODE, false);
-        if !v_up.is_null() {
-            CGEventSetFlags(v_up, 0);
-            CGEventPost(HID_EVENT_TAP, v_up);
-            CFRelease(v_up as *const c_void);
-        }
-
-        if !source.is_null() {
-            CFRelease(source as *const c_void);
-        }
-    }
-
-    // Wait for paste to complete before restoring clipboard
-    std::thread::sleep(std::time::Duration::from_millis(config.paste_restore_delay_ms));
-
-    // Restore clipboard
-    clipboard_mgr.restore()?;
-
-    Ok(())
-}
-
-/// macOS-specific keystroke insertion using CoreGraphics.
-///
-/// Uses `CGEventKeyboardSetUnicodeString` to correctly inject Unicode
-/// characters, since `Key::Unknown(code)` incorrectly passes Unicode
-/// codepoints as virtual keycodes on macOS (CGKeyCodes map physical keys,
-/// not characters).
-#[cfg(target_os = "macos")]
-fn insert_via_keystrokes_macos(text: &str, config: &SubstitutionConfig) -> Result<(), SubstitutionError> {
-    use std::ffi::c_void;
-
-    type CGEventRef = *mut c_void;
-    type CGEventSourceRef = *mut c_void;
-
-    #[link(name = "CoreGraphics", kind = "framework")]
-    extern "C" {
-        fn CGEventSourceCreate(state_id: i32) -> CGEventSourceRef;
-        fn CGEventCreateKeyboardEvent(
-            source: CGEventSourceRef,
-            virtual_key: u16,
-            key_down: bool,
-        ) -> CGEventRef;
-        fn CGEventKeyboardSetUnicodeString(
-            event: CGEventRef,
-            length: u64,
-            string: *const u16,
-        );
-        fn CGEventPost(tap: u32, event: CGEventRef);
-    }
-
-    #[link(name = "CoreFoundation", kind = "framework")]
-    extern "C" {
-        fn CFRelease(cf: *const c_void);
-    }
-
-    // kCGEventSourceStateHIDSystemState = 1
-    const HID_SYSTEM_STATE: i32 = 1;
-    // kCGHIDEventTap = 0
-    const HID_EVENT_TAP: u32 = 0;
-
-    tracing::debug!("Inserting via macOS CGEvent keystrokes: {} chars", text.len());
-    let delay = Duration::from_millis(config.key_delay_ms);
-
-    unsafe {
-        let source = CGEventSourceCreate(HID_SYSTEM_STATE);
-
-        for ch in text.chars() {
-            let mut utf16 = [0u16; 2];
-            let encoded = ch.encode_utf16(&mut utf16);
-            let len = encoded.len();
-
-            // Key-down with dummy keycode 0; unicode string overrides the character
-            let key_down = CGEventCreateKeyboardEvent(source, 0, true);
-            if key_down.is_null() {
-                if !source.is_null() {
-                    CFRelease(source as *const c_void);
-                }
-                return Err(SubstitutionError::SimulationFailed(
-                    format!("CGEventCreateKeyboardEvent failed for '{}'", ch),
-                ));
-            }
-            CGEventKeyboardSetUnicodeString(key_down, len as u64, utf16.as_ptr());
-            CGEventPost(HID_EVENT_TAP, key_down);
-            CFRelease(key_down as *const c_void);
-
-            thread::sleep(delay);
-
-            // Key-up
-            let key_up = CGEventCreateKeyboardEvent(source, 0, false);
-            if !key_up.is_null() {
-                CGEventKeyboardSetUnicodeString(key_up, len as u64, utf16.as_ptr());
-                CGEventPost(HID_EVENT_TAP, key_up);
-                CFRelease(key_up as *const c_void);
-            }
-
-            thread::sleep(delay);
-        }
-
-        if !source.is_null() {
-            CFRelease(source as *const c_void);
-        }
-    }
-
-    Ok(())
-}
-
-/// Inserts text by simulating individual keystrokes.
-///
-/// On macOS, uses CoreGraphics `CGEventKeyboardSetUnicodeString` for correct
-/// Unicode character injection. On other platforms, uses rdev's simulate API.
-pub fn insert_via_keystrokes(text: &str, config: &SubstitutionConfig) -> Result<(), SubstitutionError> {
-    if text.len() > MAX_SNIPPET_SIZE {
-        return Err(SubstitutionError::SnippetTooLarge(text.len(), MAX_SNIPPET_SIZE));
-    }
-    tracing::debug!("Inserting via keystrokes: {} chars", text.len());
-
-    #[cfg(target_os = "macos")]
-    {
-        return insert_via_keystrokes_macos(text, config);
-    }
-
-    #[cfg(not(target_os = "macos"))]
-    {
-        let delay = Duration::from_millis(config.key_delay_ms);
-        for ch in text.chars() {
-            send_key_event(EventType::KeyPress(Key::Unknown(ch as u32)), delay)?;
-            send_key_event(EventType::KeyRelease(Key::Unknown(ch as u32)), delay)?;
-        }
-        Ok(())
-    }
-}
-
-/// Deletes `count` characters by sending backspace via xdotool (Linux).
-///
-/// This method works in terminals (including Claude Code CLI) by using the
-/// external xdotool command instead of rdev's simulate which doesn't work
-/// reliably in terminals.
-///
-/// IMPORTANT: Includes a pre-deletion delay (config.pre_deletion_delay_ms) to allow
-/// applications (especially browsers like Firefox/Chrome) to fully process the last
-/// keystroke before xdotool starts deleting. Without this delay, the first character
-/// of the keyword may remain undeleted in browser text fields.
-pub fn delete_keyword_xdotool(count: usize, config: &SubstitutionConfig) -> Result<(), SubstitutionError> {
-    if count > MAX_KEYWORD_LENGTH {
-        return Err(SubstitutionError::KeywordTooLong(count, MAX_KEYWORD_LENGTH));
-    }
-    tracing::debug!("Deleting {} characters via xdotool backspace (with {}ms pre-deletion delay)", count, config.pre_deletion_delay_ms);
-
-    // Wait for the target app to fully process the last keystroke
-    // This is critical for browsers (Firefox, Chrome) which have async input processing
-    if config.pre_deletion_delay_ms > 0 {
-        thread::sleep(Duration::from_millis(config.pre_deletion_delay_ms));
-    }
-
-    let output = std::process::Command::new("xdotool")
-        .arg("key")
-        .arg("--clearmodifiers")
-        .arg("--repeat")
-        .arg(count.to_string())
-        .arg("--delay")
-        .arg(config.key_delay_ms.to_string())
-        .arg("BackSpace")
-        .output()
-        .map_err(|e| SubstitutionError::SimulationFailed(format!("xdotool key failed: {}", e)))?;
-
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(SubstitutionError::SimulationFailed(format!("xdotool key error: {}", stderr)));
-    }
-
-    Ok(())
-}
-
-/// Inserts text using xdotool type command (Linux terminal compatible).
-///
-/// This method works in terminals (including Claude Code CLI) by using the
-/// external xdotool command instead of rdev's Key::Unknown which doesn't work
-/// in terminals.
-pub fn insert_via_xdotool(text: &str, config: &SubstitutionConfig) -> Result<(), SubstitutionError> {
-    if text.len() > MAX_SNIPPET_SIZE {
-        return Err(SubstitutionError::SnippetTooLarge(text.len(), MAX_SNIPPET_SIZE));
-    }
-    tracing::debug!("Inserting via xdotool: {} chars", text.len());
-
-    let output = std::process::Command::new("xdotool")
-        .arg("type")
-        .arg("--clearmodifiers")
-        .arg("--delay")
-        .arg(config.key_delay_ms.to_string())
-        .arg("--")
-        .arg(text)
-        .output()
-        .map_err(|e| SubstitutionError::SimulationFailed(format!("xdotool failed: {}", e)))?;
-
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(SubstitutionError::SimulationFailed(format!("xdotool error: {}", stderr)));
-    }
-
-    Ok(())
+/// Record of the most recent expansion, kept so it can be undone.
+#[derive(Debug, Clone)]
+pub struct LastExpansion {
+    /// The keyword exactly as it was typed (and deleted) by the expansion.
+    pub typed: String,
+    /// Number of grapheme clusters the expansion inserted.
+    pub inserted_len: usize,
+    /// Number of inserted grapheme clusters that sit to the right of the
+    /// cursor (non-zero when the snippet positioned the cursor with
+    /// `#{cursor}`).
+    pub cursor_offset: usize,
+    /// When the expansion finished.
+    pub at: Instant,
 }
 
-/// macOS-specific text insertion using osascript and clipboard (AppleScript paste).
-///
-/// Uses `pbcopy` to write text to the clipboard and `osascript` to trigger
-/// Cmd+V via System Events. This is an alternative to the CGEvent-based
-/// clipboard paste that works through the Accessibility framework.
-///
-/// Note: Requires Accessibility permissions for System Events.
-#[cfg(target_os = "macos")]
-pub fn insert_via_osascript_paste_macos(
-    text: &str,
-    config: &SubstitutionConfig,
-) -> Result<(), SubstitutionError> {
-    if text.len() > MAX_SNIPPET_SIZE {
-        return Err(SubstitutionError::SnippetTooLarge(text.len(), MAX_SNIPPET_SIZE));
-    }
-    tracing::debug!("Inserting via macOS osascript paste: {} chars", text.len());
-
-    use std::io::Write;
-
-    // Write text to clipboard via pbcopy
-    let mut child = std::process::Command::new("pbcopy")
-        .stdin(std::process::Stdio::piped())
-        .spawn()
-        .map_err(|e| SubstitutionError::SimulationFailed(format!("pbcopy spawn failed: {}", e)))?;
-
-    if let Some(mut stdin) = child.stdin.take() {
-        stdin.write_all(text.as_bytes())
-            .map_err(|e| SubstitutionError::SimulationFailed(format!("pbcopy write failed: {}", e)))?;
-    }
-
-    let status = child.wait()
-        .map_err(|e| SubstitutionError::SimulationFailed(format!("pbcopy wait failed: {}", e)))?;
-
-    if !status.success() {
-        return Err(SubstitutionError::SimulationFailed("pbcopy exited with error".to_string()));
-    }
-
-    // Delay to ensure clipboard content is committed
-    std::thread::sleep(std::time::Duration::from_millis(50));
-
-    // Use CGEvent to paste (same as clipboard method — more reliable than osascript)
-    use std::ffi::c_void;
-
-    type CGEventRef = *mut c_void;
-    type CGEventSourceRef = *mut c_void;
-
-    #[link(name = "CoreGraphics", kind = "framework")]
-    extern "C" {
-        fn CGEventSourceCreate(state_id: i32) -> CGEventSourceRef;
-        fn CGEventCreateKeyboardEvent(
-            source: CGEventSourceRef,
-            virtual_key: u16,
-            key_down: bool,
-        ) -> CGEventRef;
-        fn CGEventSetFlags(event: CGEventRef, flags: u64);
-        fn CGEventPost(tap: u32, event: CGEventRef);
-    }
-
-    #[link(name = "CoreFoundation", kind = "framework")]
-    extern "C" {
-        fn CFRelease(cf: *const c_void);
-    }
-
-    const PRIVATE_STATE: i32 = -1;      // kCGEventSourceStatePrivate
-    const HID_EVENT_TAP: u32 = 0;
-    const V_KEYCODE: u16 = 9;
-    const CMD_FLAG: u64 = 0x00100000;   // kCGEventFlagMaskCommand
-
-    unsafe {
-        let source = CGEventSourceCreate(PRIVATE_STATE);
-
-        let v_down = CGEventCreateKeyboardEvent(source, V_KEYCODE, true);
-        if !v_down.is_null() {
-            CGEventSetFlags(v_down, CMD_FLAG);
-            CGEventPost(HID_EVENT_TAP, v_down);
-            CFRelease(v_down as *const c_void);
-        }
-
-        std::thread::sleep(std::time::Duration::from_millis(10));
-
-        let v_up = CGEventCreateKeyboardEvent(source, V_KEYCODE, false);
-        if !v_up.is_null() {
-            CGEventSetFlags(v_up, 0);
-            CGEventPost(HID_EVENT_TAP, v_up);
-            CFRelease(v_up as *const c_void);
-        }
-
-        if !source.is_null() {
-            CFRelease(source as *const c_void);
+impl LastExpansion {
+    /// Records an expansion of `typed` into `inserted`.
+    ///
+    /// `cursor_position` is the byte offset of the cursor within `inserted`,
+    /// as reported by the variable evaluator, or `None` if the cursor was left
+    /// at the end of the snippet.
+    pub fn new(typed: impl Into<String>, inserted: &str, cursor_position: Option<usize>) -> Self {
+        let cursor_offset = cursor_position
+            .and_then(|pos| inserted.get(pos..))
+            .map_or(0, grapheme_count);
+        Self {
+            typed: typed.into(),
+            inserted_len: grapheme_count(inserted),
+            cursor_offset,
+            at: Instant::now(),
         }
     }
 
-    // Wait for paste to complete
-    std::thread::sleep(std::time::Duration::from_millis(config.paste_restore_delay_ms));
-
-    Ok(())
-}
-
-/// macOS-specific keyword deletion using osascript (AppleScript backspace).
-///
-/// Uses `osascript` with System Events to send backspace key events.
-/// This is an alternative to the CGEvent-based backspace that works through
-/// the Accessibility framework.
-#[cfg(target_os = "macos")]
-#[allow(dead_code)] // Available as alternative to CGEvent-based deletion
-fn delete_keyword_osascript_macos(count: usize, config: &SubstitutionConfig) -> Result<(), SubstitutionError> {
-    if count > MAX_KEYWORD_LENGTH {
-        return Err(SubstitutionError::KeywordTooLong(count, MAX_KEYWORD_LENGTH));
-    }
-    if count == 0 {
-        return Ok(());
-    }
-    tracing::debug!("Deleting {} characters via macOS osascript backspace", count);
-
-    // Pre-deletion delay for browser compatibility
-    if config.pre_deletion_delay_ms > 0 {
-        std::thread::sleep(std::time::Duration::from_millis(config.pre_deletion_delay_ms));
+    /// Returns true if the expansion happened less than `window` ago.
+    pub fn is_within(&self, window: Duration) -> bool {
+        self.at.elapsed() < window
     }
 
-    // Use osascript to send backspace key events via System Events
-    // key code 51 = macOS backspace
-    let script = format!(
-        "tell application \"System Events\"\nrepeat {} times\nkey code 51\ndelay 0.005\nend repeat\nend tell",
-        count
-    );
-
-    let output = std::process::Command::new("osascript")
-        .arg("-e")
-        .arg(&script)
-        .output()
-        .map_err(|e| SubstitutionError::SimulationFailed(format!("osascript failed: {}", e)))?;
-
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(SubstitutionError::SimulationFailed(
-            format!("osascript error: {}", stderr),
-        ));
+    /// Number of Backspaces needed to remove the inserted text before the
+    /// cursor, given that the user's own Backspace already removed one.
+    pub fn undo_backspaces(&self) -> usize {
+        (self.inserted_len - self.cursor_offset).saturating_sub(1)
     }
-
-    Ok(())
 }
 
 /// Represents a complete substitution operation.
@@ -678,86 +416,127 @@ impl SubstitutionEngine {
         self.config = config;
     }
 
-    /// Performs a full substitution: delete keyword, then insert snippet.
+    /// Creates the output backend for `method` on this platform.
     ///
-    /// Uses clipboard-based insertion.
-    /// On Linux, uses xdotool for keyword deletion (terminal-compatible).
-    pub fn substitute_via_clipboard<P: ClipboardProvider>(
-        &self,
-        keyword_len: usize,
-        snippet: &str,
-        clipboard_mgr: &mut ClipboardManager<P>,
-    ) -> Result<(), SubstitutionError> {
-        #[cfg(target_os = "macos")]
+    /// On Linux every method but uinput sends keys through XTest
+    /// (terminal-compatible); on macOS all methods post CoreGraphics events.
+    pub fn backend_for(&self, method: PasteMethod) -> Result<Box<dyn OutputBackend>, SubstitutionError> {
+        #[cfg(target_os = "linux")]
         {
-            delete_keyword_macos(keyword_len, &self.config)?;
-            return insert_via_clipboard_macos(snippet, clipboard_mgr, &self.config);
+            if method == PasteMethod::Uinput {
+                return Ok(Box::new(UinputBackend::new(&self.config)));
+            }
+            Ok(Box::new(XTestBackend::new(&self.config)))
         }
-        #[cfg(not(target_os = "macos"))]
+        #[cfg(not(target_os = "linux"))]
         {
-            if cfg!(target_os = "linux") {
-                delete_keyword_xdotool(keyword_len, &self.config)?;
-            } else {
-                delete_keyword(keyword_len, &self.config)?;
+            if method == PasteMethod::Uinput {
+                return Err(SubstitutionError::SimulationFailed(
+                    "uinput output is only available on Linux".to_string(),
+                ));
             }
-            insert_via_clipboard(snippet, clipboard_mgr, &self.config)?;
-            Ok(())
+            #[cfg(target_os = "macos")]
+            return Ok(Box::new(MacOsBackend::new(&self.config)));
+            #[cfg(not(target_os = "macos"))]
+            return Ok(Box::new(RdevBackend::new(&self.config)));
         }
     }
 
-    /// Performs a full substitution: delete keyword, then insert snippet.
+    /// Performs a full substitution with the insertion style of `method`:
+    /// the clipboard for [`PasteMethod::Clipboard`], typed keys otherwise.
     ///
-    /// Uses keystroke-based insertion.
-    /// On Linux, uses xdotool for both deletion and insertion (terminal-compatible).
-    /// On other platforms, uses rdev.
-    pub fn substitute_via_keystrokes(
+    /// On macOS, [`PasteMethod::XdotoolType`] pastes through the clipboard
+    /// too, since there is no xdotool equivalent for typing, and images are
+    /// always pasted. `paste_chord` overrides the paste shortcut when the
+    /// clipboard is used. Keystroke methods type the plain text of rich
+    /// snippets.
+    pub fn substitute<P: ClipboardProvider>(
         &self,
+        backend: &mut dyn OutputBackend,
+        method: PasteMethod,
+        paste_chord: Option<&KeyChord>,
         keyword_len: usize,
-        snippet: &str,
+        snippet: Snippet<'_>,
+        clipboard_mgr: &mut ClipboardManager<P>,
     ) -> Result<(), SubstitutionError> {
-        #[cfg(target_os = "macos")]
-        {
-            delete_keyword_macos(keyword_len, &self.config)?;
-            return insert_via_keystrokes(snippet, &self.config);
-        }
-        #[cfg(not(target_os = "macos"))]
-        {
-            if cfg!(target_os = "linux") {
-                delete_keyword_xdotool(keyword_len, &self.config)?;
-                insert_via_xdotool(snippet, &self.config)?;
-            } else {
-                delete_keyword(keyword_len, &self.config)?;
-                insert_via_keystrokes(snippet, &self.config)?;
-            }
-            Ok(())
+        if Self::uses_clipboard(method, &snippet) {
+            self.substitute_via_clipboard(backend, paste_chord, keyword_len, snippet, clipboard_mgr)
+        } else {
+            self.substitute_via_keystrokes(backend, keyword_len, snippet.text)
         }
     }
 
+    /// Whether [`Self::substitute`] pastes `snippet` through the clipboard
+    /// rather than typing it.
+    pub fn uses_clipboard(method: PasteMethod, snippet: &Snippet<'_>) -> bool {
+        method == PasteMethod::Clipboard
+            || snippet.image.is_some()
+            || (cfg!(target_os = "macos") && method == PasteMethod::XdotoolType)
+    }
+
     /// Performs a full substitution: delete keyword, then insert snippet.
     ///
-    /// On macOS, uses osascript (AppleScript System Events) as the equivalent
-    /// of xdotool. Uses CGEvent backspace for deletion (fast and reliable)
-    /// and osascript for paste.
-    /// On Linux, uses xdotool type command (terminal compatible).
-    pub fn substitute_via_xdotool(
+    /// Uses clipboard-based insertion, pasting with `paste_chord` if given.
+    pub fn substitute_via_clipboard<P: ClipboardProvider>(
         &self,
+        backend: &mut dyn OutputBackend,
+        paste_chord: Option<&KeyChord>,
+        keyword_len: usize,
+        snippet: Snippet<'_>,
+        clipboard_mgr: &mut ClipboardManager<P>,
+    ) -> Result<(), SubstitutionError> {
+        check_keyword_len(keyword_len)?;
+        backend.delete_graphemes(keyword_len)?;
+        insert_via_clipboard(backend, snippet, paste_chord, clipboard_mgr, &self.config)
+    }
+
+    /// Performs a full substitution: delete keyword, then insert snippet.
+    ///
+    /// Uses keystroke-based insertion. Characters the backend's keymap lacks
+    /// are entered with the Unicode input sequence.
+    pub fn substitute_via_keystrokes(
+        &self,
+        backend: &mut dyn OutputBackend,
         keyword_len: usize,
         snippet: &str,
     ) -> Result<(), SubstitutionError> {
-        #[cfg(target_os = "macos")]
-        {
-            // On macOS, use osascript (AppleScript System Events) as the
-            // equivalent of xdotool. Uses CGEvent backspace for deletion
-            // (fast and reliable) and osascript for paste.
-            delete_keyword_macos(keyword_len, &self.config)?;
-            return insert_via_osascript_paste_macos(snippet, &self.config);
+        check_keyword_len(keyword_len)?;
+        check_snippet_size(snippet)?;
+        backend.delete_graphemes(keyword_len)?;
+        type_text_per_char(backend, snippet)
+    }
+
+    /// Reverts `last` after the user pressed Backspace once: removes the rest
+    /// of the inserted text and retypes the original keyword.
+    ///
+    /// Text after the cursor is reached by moving the cursor to the end of
+    /// the insertion first. Deletion is split into batches so snippets
+    /// longer than the keyword limit can still be undone.
+    pub fn undo_expansion(
+        &self,
+        backend: &mut dyn OutputBackend,
+        last: &LastExpansion,
+    ) -> Result<(), SubstitutionError> {
+        tracing::debug!(
+            "Undoing expansion via {}: {} chars inserted, cursor offset {}",
+            backend.name(),
+            last.inserted_len,
+            last.cursor_offset
+        );
+        let mut forward = last.cursor_offset;
+        while forward > 0 {
+            let batch = forward.min(MAX_KEYWORD_LENGTH);
+            backend.move_cursor(batch as isize)?;
+            forward -= batch;
         }
-        #[cfg(not(target_os = "macos"))]
-        {
-            delete_keyword_xdotool(keyword_len, &self.config)?;
-            insert_via_xdotool(snippet, &self.config)?;
-            Ok(())
+
+        let mut remaining = last.undo_backspaces() + last.cursor_offset;
+        while remaining > 0 {
+            let batch = remaining.min(MAX_KEYWORD_LENGTH);
+            backend.delete_graphemes(batch)?;
+            remaining -= batch;
         }
+        type_text_per_char(backend, &last.typed)
     }
 }
 
@@ -773,14 +552,18 @@ pub fn check_focus(checker: &dyn FocusChecker) -> Result<(), SubstitutionError>
 /// Inserts a large text by splitting it into chunks and pasting each chunk
 /// separately with a small delay between chunks (MT-1104).
 ///
-/// This avoids overwhelming the target application's input buffer.
+/// This avoids overwhelming the target application's input buffer. Each
+/// chunk is written concealed when `exclude_from_history` is set.
 pub fn insert_via_clipboard_chunked<P: ClipboardProvider>(
+    backend: &mut dyn OutputBackend,
     text: &str,
+    exclude_from_history: bool,
     clipboard_mgr: &mut ClipboardManager<P>,
     config: &SubstitutionConfig,
 ) -> Result<(), SubstitutionError> {
     if text.len() <= CHUNKED_PASTE_THRESHOLD {
-        return insert_via_clipboard(text, clipboard_mgr, config);
+        let snippet = Snippet::plain(text).excluded_from_history(exclude_from_history);
+        return insert_via_clipboard(backend, snippet, None, clipboard_mgr, config);
     }
 
     tracing::debug!(
@@ -799,73 +582,15 @@ pub fn insert_via_clipboard_chunked<P: ClipboardProvider>(
         let end = std::cmp::min(offset + PASTE_CHUNK_SIZE, chars.len());
         let chunk: String = chars[offset..end].iter().collect();
 
-        clipboard_mgr.write(&chunk)?;
-        thread::sleep(Duration::from_millis(config.key_delay_ms));
-
-        // Simulate paste - use CGEvent on macOS for reliability
-        #[cfg(target_os = "macos")]
-        {
-            use std::ffi::c_void;
-            type CGEventRef = *mut c_void;
-            type CGEventSourceRef = *mut c_void;
-            #[link(name = "CoreGraphics", kind = "framework")]
-            extern "C" {
-                fn CGEventSourceCreate(state_id: i32) -> CGEventSourceRef;
-                fn CGEventCreateKeyboardEvent(source: CGEventSourceRef, virtual_key: u16, key_down: bool) -> CGEventRef;
-                fn CGEventSetFlags(event: CGEventRef, flags: u64);
-                fn CGEventPost(tap: u32, event: CGEventRef);
-            }
-            #[link(name = "CoreFoundation", kind = "framework")]
-            extern "C" {
-                fn CFRelease(cf: *const c_void);
-            }
-            const CMD_FLAG_CHUNK: u64 = 0x00100000;
-            unsafe {
-                let source = CGEventSourceCreate(1); // HID system state
-                let cmd_down = CGEventCreateKeyboardEvent(source, 55, true);
-                if !cmd_down.is_null() {
-                    CGEventSetFlags(cmd_down, CMD_FLAG_CHUNK);
-                    CGEventPost(0, cmd_down);
-                    CFRelease(cmd_down as *const c_void);
-                }
-                std::thread::sleep(Duration::from_millis(5));
-                let v_down = CGEventCreateKeyboardEvent(source, 9, true);
-                if !v_down.is_null() {
-                    CGEventSetFlags(v_down, CMD_FLAG_CHUNK);
-                    CGEventPost(0, v_down);
-                    CFRelease(v_down as *const c_void);
-                }
-                std::thread::sleep(Duration::from_millis(5));
-                let v_up = CGEventCreateKeyboardEvent(source, 9, false);
-                if !v_up.is_null() {
-                    CGEventSetFlags(v_up, CMD_FLAG_CHUNK);
-                    CGEventPost(0, v_up);
-                    CFRelease(v_up as *const c_void);
-                }
-                std::thread::sleep(Duration::from_millis(5));
-                let cmd_up = CGEventCreateKeyboardEvent(source, 55, false);
-                if !cmd_up.is_null() {
-                    CGEventSetFlags(cmd_up, 0);
-                    CGEventPost(0, cmd_up);
-                    CFRelease(cmd_up as *const c_void);
-                }
-                if !source.is_null() {
-                    CFRelease(source as *const c_void);
-                }
-            }
-        }
-        #[cfg(not(target_os = "macos"))]
-        {
-            let delay = Duration::from_millis(config.key_delay_ms);
-            let paste_modifier = if cfg!(target_os = "macos") {
-                Key::MetaLeft
-            } else {
-                Key::ControlLeft
-            };
-            send_key_event(EventType::KeyPress(paste_modifier), delay)?;
-            press_key(Key::KeyV, delay)?;
-            send_key_event(EventType::KeyRelease(paste_modifier), delay)?;
+        let format = ClipboardFormat::Text(chunk.clone());
+        if exclude_from_history {
+            clipboard_mgr.write_concealed(&format)?;
+        } else {
+            clipboard_mgr.write_format(&format)?;
         }
+        thread::sleep(clipboard_settle_delay(config));
+
+        backend.paste(&chunk)?;
 
         thread::sleep(Duration::from_millis(config.paste_restore_delay_ms));
 
@@ -892,10 +617,77 @@ impl Default for SubstitutionEngine {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::managers::output_backend::{OutputAction, RecordingBackend};
+    use crate::platform::mock::MockFocusDetector;
+
+    // Note: We cannot send real key events in unit tests (no display server
+    // in CI), so substitutions run against a RecordingBackend. Integration/E2E
+    // tests cover actual key simulation.
+
+    /// In-memory clipboard.
+    struct MemoryClipboard(String);
+
+    impl ClipboardProvider for MemoryClipboard {
+        fn read_text(&mut self) -> Result<String, ClipboardError> {
+            Ok(self.0.clone())
+        }
+
+        fn write_text(&mut self, text: &str) -> Result<(), ClipboardError> {
+            self.0 = text.to_string();
+            Ok(())
+        }
+    }
+
+    /// Clipboard that logs every format written to it.
+    #[derive(Clone, Default)]
+    struct LoggingClipboard(std::sync::Arc<std::sync::Mutex<Vec<ClipboardFormat>>>);
+
+    impl ClipboardProvider for LoggingClipboard {
+        fn read_text(&mut self) -> Result<String, ClipboardError> {
+            Ok(String::new())
+        }
+
+        fn write_text(&mut self, text: &str) -> Result<(), ClipboardError> {
+            self.write_format(&ClipboardFormat::Text(text.to_string()))
+        }
+
+        fn write_format(&mut self, format: &ClipboardFormat) -> Result<(), ClipboardError> {
+            self.0.lock().unwrap().push(format.clone());
+            Ok(())
+        }
+    }
+
+    /// Clipboard that logs whether each write was concealed.
+    #[derive(Clone, Default)]
+    struct ConcealLog(std::sync::Arc<std::sync::Mutex<Vec<bool>>>);
+
+    impl ClipboardProvider for ConcealLog {
+        fn read_text(&mut self) -> Result<String, ClipboardError> {
+            Ok(String::new())
+        }
+
+        fn write_text(&mut self, _text: &str) -> Result<(), ClipboardError> {
+            self.0.lock().unwrap().push(false);
+            Ok(())
+        }
 
-    // Note: We cannot run actual rdev::simulate in unit tests (no display server
-    // in CI), so tests focus on configuration, engine creation, and logic paths.
-    // Integration/E2E tests cover actual key simulation.
+        fn write_concealed(&mut self, _format: &ClipboardFormat) -> Result<(), ClipboardError> {
+            self.0.lock().unwrap().push(true);
+            Ok(())
+        }
+    }
+
+    /// Config without delays, so clipboard tests don't sleep.
+    fn fast_config() -> SubstitutionConfig {
+        SubstitutionConfig {
+            key_delay_ms: 0,
+            paste_restore_delay_ms: 0,
+            use_shift_insert: false,
+            timeout_secs: 5,
+            chunk_delay_ms: 0,
+            pre_deletion_delay_ms: 0,
+        }
+    }
 
     #[test]
     fn test_config_defaults() {
@@ -1002,6 +794,82 @@ mod tests {
         assert!(matches!(result.unwrap_err(), SubstitutionError::FocusLost));
     }
 
+    /// Reports focused for the first `n` checks only.
+    struct FocusedFor(std::sync::atomic::AtomicUsize);
+    impl FocusChecker for FocusedFor {
+        fn is_target_focused(&self) -> bool {
+            use std::sync::atomic::Ordering;
+            self.0
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+                .is_ok()
+        }
+    }
+
+    fn window(app_name: &str, title: &str, process_id: u32) -> WindowInfo {
+        WindowInfo {
+            title: title.into(),
+            app_name: app_name.into(),
+            process_id: Some(process_id),
+        }
+    }
+
+    #[test]
+    fn test_window_focus_checker_compares_app_and_process() {
+        let detector = MockFocusDetector::new();
+        detector.set_window_info(window("gedit", "notes.txt", 42));
+        let checker = WindowFocusChecker::new(&detector, window("gedit", "notes.txt", 42));
+        assert!(checker.is_target_focused());
+
+        // Editors mark unsaved changes in the title once the keyword is deleted.
+        detector.set_window_info(window("gedit", "*notes.txt", 42));
+        assert!(checker.is_target_focused());
+
+        detector.set_window_info(window("gedit", "notes.txt", 43));
+        assert!(!checker.is_target_focused());
+        detector.set_window_info(window("kitty", "notes.txt", 42));
+        assert!(!checker.is_target_focused());
+    }
+
+    #[test]
+    fn test_window_focus_checker_trusts_unknown_windows() {
+        let detector = MockFocusDetector::new();
+        let checker = WindowFocusChecker::new(&detector, window("gedit", "notes.txt", 42));
+        assert!(checker.is_target_focused());
+
+        detector.set_window_info(window("kitty", "shell", 7));
+        let checker = WindowFocusChecker::new(&detector, WindowInfo::default());
+        assert!(checker.is_target_focused());
+    }
+
+    #[test]
+    fn test_focus_guard_aborts_before_deleting() {
+        let engine = SubstitutionEngine::new(fast_config());
+        let mut recording = RecordingBackend::new();
+        let mut backend = FocusGuardedBackend::new(&mut recording, NeverFocused);
+        let result = engine.substitute_via_keystrokes(&mut backend, 2, "https://github.com");
+        assert!(matches!(result, Err(SubstitutionError::FocusLost)));
+        assert!(recording.actions().is_empty());
+    }
+
+    #[test]
+    fn test_focus_lost_before_paste_restores_clipboard() {
+        let engine = SubstitutionEngine::new(fast_config());
+        let mut recording = RecordingBackend::new();
+        let mut backend = FocusGuardedBackend::new(&mut recording, FocusedFor(1.into()));
+        let mut clipboard = ClipboardManager::new(MemoryClipboard("mine".into()));
+        let result = engine.substitute(
+            &mut backend,
+            PasteMethod::Clipboard,
+            None,
+            2,
+            Snippet::plain("https://github.com"),
+            &mut clipboard,
+        );
+        assert!(matches!(result, Err(SubstitutionError::FocusLost)));
+        assert_eq!(recording.actions(), vec![OutputAction::DeleteGraphemes(2)]);
+        assert_eq!(clipboard.read().unwrap(), "mine");
+    }
+
     #[test]
     fn test_focus_lost_error_display() {
         let err = SubstitutionError::FocusLost;
@@ -1064,44 +932,279 @@ mod tests {
         assert!(!config.use_shift_insert, "Should default to Ctrl+V (not Shift+Insert)");
     }
 
-    // ── Xdotool deletion tests ─────────────────────────────────
+    // ── Substitution through a backend ─────────────────────────
 
     #[test]
-    fn test_delete_keyword_xdotool_validates_length() {
-        let config = SubstitutionConfig::default();
-        let result = delete_keyword_xdotool(MAX_KEYWORD_LENGTH + 1, &config);
-        assert!(result.is_err());
-        assert!(matches!(result.unwrap_err(), SubstitutionError::KeywordTooLong(..)));
+    fn test_substitute_via_keystrokes_deletes_then_types() {
+        let engine = SubstitutionEngine::with_defaults();
+        let mut backend = RecordingBackend::new();
+        engine.substitute_via_keystrokes(&mut backend, 3, "Regards").unwrap();
+        assert_eq!(
+            backend.actions(),
+            vec![OutputAction::DeleteGraphemes(3), OutputAction::TypeText("Regards".into())]
+        );
+    }
+
+    #[test]
+    fn test_substitute_via_keystrokes_enters_missing_chars_as_unicode() {
+        let engine = SubstitutionEngine::with_defaults();
+        let mut output = RecordingBackend::with_keymap(|ch| ch.is_ascii());
+        // The keymap must reach the engine through wrapping backends too.
+        let mut backend = FocusGuardedBackend::new(&mut output, AlwaysFocused);
+        engine.substitute_via_keystrokes(&mut backend, 3, "5€ ñ").unwrap();
+        let unicode = OutputAction::KeyChord(KeyChord::unicode_input());
+        assert_eq!(
+            output.actions(),
+            vec![
+                OutputAction::DeleteGraphemes(3),
+                OutputAction::TypeText("5".into()),
+                unicode.clone(),
+                OutputAction::TypeText("20ac ".into()),
+                OutputAction::TypeText(" ".into()),
+                unicode,
+                OutputAction::TypeText("f1 ".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_substitute_via_clipboard_pastes_and_restores() {
+        let engine = SubstitutionEngine::new(fast_config());
+        let mut backend = RecordingBackend::new();
+        let mut clipboard = ClipboardManager::new(MemoryClipboard("mine".into()));
+        engine
+            .substitute_via_clipboard(
+                &mut backend,
+                None,
+                2,
+                Snippet::plain("https://github.com"),
+                &mut clipboard,
+            )
+            .unwrap();
+        assert_eq!(
+            backend.actions(),
+            vec![
+                OutputAction::DeleteGraphemes(2),
+                OutputAction::Paste("https://github.com".into()),
+            ]
+        );
+        assert_eq!(clipboard.read().unwrap(), "mine");
+    }
+
+    #[test]
+    fn test_substitute_dispatches_on_paste_method() {
+        let engine = SubstitutionEngine::new(fast_config());
+        let mut clipboard = ClipboardManager::new(MemoryClipboard(String::new()));
+
+        let mut backend = RecordingBackend::new();
+        engine
+            .substitute(&mut backend, PasteMethod::Clipboard, None, 1, Snippet::plain("a"), &mut clipboard)
+            .unwrap();
+        assert_eq!(backend.actions()[1], OutputAction::Paste("a".into()));
+
+        let mut backend = RecordingBackend::new();
+        engine
+            .substitute(
+                &mut backend,
+                PasteMethod::SimulateKeystrokes,
+                None,
+                1,
+                Snippet::rich("b", "<b>b</b>"),
+                &mut clipboard,
+            )
+            .unwrap();
+        assert_eq!(backend.actions()[1], OutputAction::TypeText("b".into()));
+    }
+
+    #[test]
+    fn test_substitute_via_clipboard_publishes_html() {
+        let engine = SubstitutionEngine::new(fast_config());
+        let mut backend = RecordingBackend::new();
+        let log = LoggingClipboard::default();
+        let mut clipboard = ClipboardManager::new(log.clone());
+        engine
+            .substitute(
+                &mut backend,
+                PasteMethod::Clipboard,
+                None,
+                3,
+                Snippet::rich("Jane", "<b>Jane</b>"),
+                &mut clipboard,
+            )
+            .unwrap();
+        assert_eq!(backend.actions()[1], OutputAction::Paste("Jane".into()));
+        assert_eq!(
+            log.0.lock().unwrap().as_slice(),
+            &[
+                ClipboardFormat::Html {
+                    html: "<b>Jane</b>".into(),
+                    alt_text: Some("Jane".into()),
+                },
+                // The preserved (empty) clipboard, restored afterwards.
+                ClipboardFormat::Text(String::new()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_substitute_pastes_images_with_keystroke_methods() {
+        let engine = SubstitutionEngine::new(fast_config());
+        let mut backend = RecordingBackend::new();
+        let log = LoggingClipboard::default();
+        let mut clipboard = ClipboardManager::new(log.clone());
+        let image = ClipboardFormat::Image {
+            width: 1,
+            height: 1,
+            bytes: vec![0, 0, 0, 255],
+        };
+        engine
+            .substitute(
+                &mut backend,
+                PasteMethod::SimulateKeystrokes,
+                None,
+                4,
+                Snippet::image(&image),
+                &mut clipboard,
+            )
+            .unwrap();
+        assert_eq!(
+            backend.actions(),
+            vec![OutputAction::DeleteGraphemes(4), OutputAction::Paste(String::new())]
+        );
+        assert_eq!(log.0.lock().unwrap()[0], image);
+    }
+
+    #[test]
+    fn test_substitute_via_clipboard_with_custom_chord() {
+        let engine = SubstitutionEngine::new(fast_config());
+        let mut backend = RecordingBackend::new();
+        let mut clipboard = ClipboardManager::new(MemoryClipboard("mine".into()));
+        let chord: KeyChord = "Ctrl+Shift+V".parse().unwrap();
+        engine
+            .substitute(
+                &mut backend,
+                PasteMethod::Clipboard,
+                Some(&chord),
+                1,
+                Snippet::plain("x"),
+                &mut clipboard,
+            )
+            .unwrap();
+        assert_eq!(
+            backend.actions(),
+            vec![OutputAction::DeleteGraphemes(1), OutputAction::KeyChord(chord)]
+        );
+        assert_eq!(clipboard.read().unwrap(), "mine");
+    }
+
+    #[test]
+    fn test_substitute_validates_keyword_length() {
+        let engine = SubstitutionEngine::with_defaults();
+        let mut backend = RecordingBackend::new();
+        let result = engine.substitute_via_keystrokes(&mut backend, MAX_KEYWORD_LENGTH + 1, "x");
+        assert!(matches!(result, Err(SubstitutionError::KeywordTooLong(..))));
+        assert!(backend.actions().is_empty());
+    }
+
+    #[test]
+    fn test_substitute_via_keystrokes_validates_snippet_size() {
+        let engine = SubstitutionEngine::with_defaults();
+        let mut backend = RecordingBackend::new();
+        let huge_text = "a".repeat(MAX_SNIPPET_SIZE + 1);
+        let result = engine.substitute_via_keystrokes(&mut backend, 2, &huge_text);
+        assert!(matches!(result, Err(SubstitutionError::SnippetTooLarge(..))));
+        assert!(backend.actions().is_empty());
+    }
+
+    #[test]
+    fn test_chunked_paste_splits_large_text() {
+        let mut backend = RecordingBackend::new();
+        let mut clipboard = ClipboardManager::new(MemoryClipboard("mine".into()));
+        let text = "x".repeat(CHUNKED_PASTE_THRESHOLD + 1);
+        insert_via_clipboard_chunked(&mut backend, &text, false, &mut clipboard, &fast_config())
+            .unwrap();
+        let sizes: Vec<usize> = backend
+            .actions()
+            .iter()
+            .map(|action| match action {
+                OutputAction::Paste(chunk) => chunk.len(),
+                other => panic!("unexpected {:?}", other),
+            })
+            .collect();
+        assert_eq!(sizes, vec![PASTE_CHUNK_SIZE, PASTE_CHUNK_SIZE, 1]);
+        assert_eq!(clipboard.read().unwrap(), "mine");
+    }
+
+    #[test]
+    fn test_snippet_excluded_from_history_is_written_concealed() {
+        let engine = SubstitutionEngine::new(fast_config());
+        let mut backend = RecordingBackend::new();
+        let log = ConcealLog::default();
+        let mut clipboard = ClipboardManager::new(log.clone());
+        for exclude in [true, false] {
+            engine
+                .substitute(
+                    &mut backend,
+                    PasteMethod::Clipboard,
+                    None,
+                    4,
+                    Snippet::plain("12345678").excluded_from_history(exclude),
+                    &mut clipboard,
+                )
+                .unwrap();
+        }
+        // Snippet then restore, twice; the user's content is never concealed.
+        assert_eq!(log.0.lock().unwrap().as_slice(), &[true, false, false, false]);
     }
 
     #[test]
-    fn test_delete_keyword_xdotool_zero_length() {
-        // Zero length should succeed (no-op)
-        // Note: This will actually try to run xdotool with --repeat 0
-        // We can't test actual execution in CI, but we can test the validation logic
-        // which should allow zero
-        assert!(MAX_KEYWORD_LENGTH > 0);
+    fn test_chunked_paste_conceals_every_chunk() {
+        let mut backend = RecordingBackend::new();
+        let log = ConcealLog::default();
+        let mut clipboard = ClipboardManager::new(log.clone());
+        let text = "x".repeat(CHUNKED_PASTE_THRESHOLD + 1);
+        insert_via_clipboard_chunked(&mut backend, &text, true, &mut clipboard, &fast_config())
+            .unwrap();
+        assert_eq!(log.0.lock().unwrap().as_slice(), &[true, true, true, false]);
     }
 
+    #[cfg(target_os = "linux")]
     #[test]
-    fn test_substitute_via_keystrokes_uses_xdotool_on_linux() {
-        // This test verifies the dispatch logic based on cfg!(target_os = "linux")
-        // Actual execution would require xdotool and a display server
+    fn test_backend_for_linux_methods() {
         let engine = SubstitutionEngine::with_defaults();
-        // We can't test actual execution, but we document the expected behavior:
-        // On Linux: calls delete_keyword_xdotool + insert_via_xdotool
-        // On others: calls delete_keyword + insert_via_keystrokes
-        assert!(engine.config().key_delay_ms > 0);
+        let name = |method| engine.backend_for(method).unwrap().name();
+        assert_eq!(name(PasteMethod::Clipboard), "xtest");
+        assert_eq!(name(PasteMethod::SimulateKeystrokes), "xtest");
+        assert_eq!(name(PasteMethod::XdotoolType), "xtest");
+        assert_eq!(name(PasteMethod::Uinput), "uinput");
     }
 
     #[test]
-    fn test_substitute_via_xdotool_always_uses_xdotool() {
-        // substitute_via_xdotool should always use xdotool functions
-        // regardless of platform
+    fn test_backend_for_unicode_input() {
+        // Ctrl+Shift+U only means something to GTK and IBus on Linux.
+        let expected = if cfg!(target_os = "linux") {
+            UnicodeInput::KeySequence
+        } else {
+            UnicodeInput::Direct
+        };
         let engine = SubstitutionEngine::with_defaults();
-        // We can't test actual execution, but we document that this method
-        // unconditionally calls delete_keyword_xdotool + insert_via_xdotool
-        assert!(engine.config().key_delay_ms > 0);
+        for method in [
+            PasteMethod::Clipboard,
+            PasteMethod::SimulateKeystrokes,
+            PasteMethod::XdotoolType,
+        ] {
+            assert_eq!(engine.backend_for(method).unwrap().unicode_input(), expected);
+        }
+        if let Ok(backend) = engine.backend_for(PasteMethod::Uinput) {
+            assert_eq!(backend.unicode_input(), UnicodeInput::KeySequence);
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    #[test]
+    fn test_backend_for_uinput_needs_linux() {
+        let engine = SubstitutionEngine::with_defaults();
+        assert!(engine.backend_for(PasteMethod::Uinput).is_err());
     }
 
     #[test]
@@ -1159,65 +1262,121 @@ mod tests {
             "Default paste restore delay should be 200ms for reliable clipboard paste");
     }
 
-    // ── macOS osascript paste validation ─────────────────────────
+    // ── Undo last expansion ──────────────────────────────────────
 
     #[test]
-    fn test_insert_via_osascript_validates_snippet_size() {
-        // insert_via_osascript_paste_macos should validate snippet size
-        // We test this on macOS only since the function is cfg(target_os = "macos")
-        #[cfg(target_os = "macos")]
-        {
-            let config = SubstitutionConfig::default();
-            let huge_text = "a".repeat(MAX_SNIPPET_SIZE + 1);
-            let result = insert_via_osascript_paste_macos(&huge_text, &config);
-            assert!(result.is_err());
-            assert!(matches!(result.unwrap_err(), SubstitutionError::SnippetTooLarge(..)));
-        }
+    fn test_last_expansion_counts_chars() {
+        let last = LastExpansion::new("café", "Café au lait", None);
+        assert_eq!(last.typed, "café");
+        assert_eq!(last.inserted_len, 12);
+        assert_eq!(last.cursor_offset, 0);
     }
 
     #[test]
-    fn test_delete_keyword_osascript_validates_length() {
-        // delete_keyword_osascript_macos should validate keyword length
-        #[cfg(target_os = "macos")]
-        {
-            let config = SubstitutionConfig::default();
-            let result = delete_keyword_osascript_macos(MAX_KEYWORD_LENGTH + 1, &config);
-            assert!(result.is_err());
-            assert!(matches!(result.unwrap_err(), SubstitutionError::KeywordTooLong(..)));
-        }
+    fn test_last_expansion_counts_graphemes() {
+        // A ZWJ emoji and an accent typed as e plus a combining mark are one
+        // Backspace each.
+        // Cursor placed after the emoji and the space (byte offset 12).
+        let last = LastExpansion::new("dev", "\u{1F469}\u{200D}\u{1F4BB} cafe\u{301}", Some(12));
+        assert_eq!(last.inserted_len, 6);
+        assert_eq!(last.cursor_offset, 4);
+        assert_eq!(last.undo_backspaces(), 1);
     }
 
     #[test]
-    fn test_delete_keyword_osascript_zero_is_noop() {
-        #[cfg(target_os = "macos")]
-        {
-            let config = SubstitutionConfig::default();
-            let result = delete_keyword_osascript_macos(0, &config);
-            assert!(result.is_ok());
-        }
+    fn test_last_expansion_cursor_offset() {
+        // Cursor placed before "world" (byte offset 6).
+        let last = LastExpansion::new("hw", "Hello world", Some(6));
+        assert_eq!(last.cursor_offset, 5);
+        assert_eq!(last.undo_backspaces(), 5);
+    }
+
+    #[test]
+    fn test_last_expansion_cursor_offset_ignores_invalid_position() {
+        let last = LastExpansion::new("e", "é", Some(1));
+        assert_eq!(last.cursor_offset, 0);
+    }
+
+    #[test]
+    fn test_undo_backspaces_accounts_for_user_backspace() {
+        let last = LastExpansion::new("sig", "Best regards", None);
+        assert_eq!(last.undo_backspaces(), 11);
+    }
+
+    #[test]
+    fn test_undo_backspaces_empty_snippet() {
+        let last = LastExpansion::new("x", "", None);
+        assert_eq!(last.undo_backspaces(), 0);
+    }
+
+    #[test]
+    fn test_last_expansion_window() {
+        let last = LastExpansion::new("sig", "Best regards", None);
+        assert!(last.is_within(Duration::from_secs(60)));
+        assert!(!last.is_within(Duration::ZERO));
     }
 
-    // ── Platform dispatch tests ──────────────────────────────────
+    #[test]
+    fn test_undo_expansion_retypes_keyword() {
+        let engine = SubstitutionEngine::with_defaults();
+        let mut backend = RecordingBackend::new();
+        let last = LastExpansion::new("sig", "Regards", None);
+        engine.undo_expansion(&mut backend, &last).unwrap();
+        assert_eq!(
+            backend.actions(),
+            vec![OutputAction::DeleteGraphemes(6), OutputAction::TypeText("sig".into())]
+        );
+    }
 
     #[test]
-    fn test_substitute_via_xdotool_uses_osascript_on_macos() {
-        // On macOS, substitute_via_xdotool should use osascript-based functions
-        // instead of xdotool (which doesn't exist on macOS).
-        // We verify the engine can be constructed and the method signature matches.
+    fn test_undo_expansion_retypes_non_ascii_keyword() {
         let engine = SubstitutionEngine::with_defaults();
-        // The dispatch logic uses #[cfg(target_os)] so we can't test both branches
-        // in one binary, but we can verify the engine is properly configured
-        assert_eq!(engine.config().paste_restore_delay_ms, 200);
+        let mut backend = RecordingBackend::with_keymap(|ch| ch.is_ascii());
+        let last = LastExpansion::new("né", "née", None);
+        engine.undo_expansion(&mut backend, &last).unwrap();
+        assert_eq!(
+            backend.actions(),
+            vec![
+                OutputAction::DeleteGraphemes(2),
+                OutputAction::TypeText("n".into()),
+                OutputAction::KeyChord(KeyChord::unicode_input()),
+                OutputAction::TypeText("e9 ".into()),
+            ]
+        );
     }
 
     #[test]
-    fn test_substitute_via_xdotool_doc_update() {
-        // Verify the xdotool method exists and engine works with all three methods
+    fn test_undo_expansion_moves_past_text_after_cursor() {
         let engine = SubstitutionEngine::with_defaults();
-        // On macOS: substitute_via_xdotool uses delete_keyword_macos + insert_via_osascript_paste_macos
-        // On Linux: substitute_via_xdotool uses delete_keyword_xdotool + insert_via_xdotool
-        assert!(engine.config().key_delay_ms > 0);
-        assert!(engine.config().paste_restore_delay_ms > 0);
+        let mut backend = RecordingBackend::new();
+        let last = LastExpansion::new("hw", "Hello world", Some(6));
+        engine.undo_expansion(&mut backend, &last).unwrap();
+        assert_eq!(
+            backend.actions(),
+            vec![
+                OutputAction::MoveCursor(5),
+                OutputAction::DeleteGraphemes(10),
+                OutputAction::TypeText("hw".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_undo_expansion_batches_long_snippets() {
+        let engine = SubstitutionEngine::with_defaults();
+        let mut backend = RecordingBackend::new();
+        let snippet = "x".repeat(MAX_KEYWORD_LENGTH * 2 + 11);
+        let last = LastExpansion::new("long", &snippet, None);
+        engine.undo_expansion(&mut backend, &last).unwrap();
+        assert_eq!(
+            backend.actions(),
+            vec![
+                OutputAction::DeleteGraphemes(MAX_KEYWORD_LENGTH),
+                OutputAction::DeleteGraphemes(MAX_KEYWORD_LENGTH),
+                OutputAction::DeleteGraphemes(10),
+                OutputAction::TypeText("long".into()),
+            ]
+        );
     }
 
     // ── Chunked paste platform dispatch ──────────────────────────
@@ -0,0 +1,333 @@
+//! Per-application substitution timing, learned from expansions.
+//!
+//! The global delays in [`SubstitutionConfig`] are a compromise: slow
+//! applications drop input sent at that pace while fast ones wait for
+//! nothing. A [`TimingProfile`] overrides them for one window class.
+//!
+//! Profiles are learned from how far delivery of an expansion's output
+//! lagged behind the expansion: when a hook that hears our own output has
+//! not reported all of it by the time the job finished, the clipboard was
+//! restored, or the next key sent, before the system caught up. Each lagging
+//! expansion raises the delay for its window class; a run of clean ones
+//! steps it back towards the global value.
+
+use std::collections::HashMap;
+
+use crate::managers::substitution::SubstitutionConfig;
+use crate::models::preferences::TimingProfile;
+use crate::platform::keyboard_hook::WindowInfo;
+
+/// Maximum number of timing profiles, learned or not.
+pub const MAX_TIMING_PROFILES: usize = 100;
+
+/// Upper bound for the key and pre-deletion delays of a profile.
+pub const MAX_KEY_DELAY_MS: u64 = 1000;
+
+/// Upper bound for the paste restore delay of a profile.
+pub const MAX_PASTE_RESTORE_DELAY_MS: u64 = 5000;
+
+/// Learning never raises the key delay past this.
+const MAX_LEARNED_KEY_DELAY_MS: u64 = 50;
+
+/// Learning never raises the paste restore delay past this.
+const MAX_LEARNED_PASTE_RESTORE_DELAY_MS: u64 = 2000;
+
+/// Typed output may still be this many keys behind when a job finishes: the
+/// last keys are sent just before it returns.
+const KEYSTROKE_LAG_TOLERANCE: usize = 3;
+
+/// Clean expansions in a row after which a learned delay is relaxed.
+const RELAX_AFTER: u32 = 20;
+
+/// How an expansion inserted its snippet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Insertion {
+    /// Written to the clipboard and pasted; lag means the clipboard was
+    /// restored too early.
+    Clipboard,
+    /// Typed key by key; lag means keys were sent too fast.
+    Keystrokes,
+}
+
+/// Holds the timing profiles and adjusts the learned ones.
+#[derive(Debug, Default)]
+pub struct TimingCalibrator {
+    profiles: Vec<TimingProfile>,
+    adaptive: bool,
+    /// Clean expansions since the last adjustment, per lowercase window
+    /// class and insertion style.
+    clean_runs: HashMap<(String, Insertion), u32>,
+}
+
+impl TimingCalibrator {
+    pub fn new(profiles: Vec<TimingProfile>, adaptive: bool) -> Self {
+        Self {
+            profiles,
+            adaptive,
+            clean_runs: HashMap::new(),
+        }
+    }
+
+    /// Replaces the profiles, e.g. after they were edited in preferences.
+    pub fn set_profiles(&mut self, profiles: Vec<TimingProfile>, adaptive: bool) {
+        self.profiles = profiles;
+        self.adaptive = adaptive;
+        self.clean_runs.clear();
+    }
+
+    pub fn profiles(&self) -> &[TimingProfile] {
+        &self.profiles
+    }
+
+    /// The profile for windows of `window_class`, if any.
+    pub fn profile_for(&self, window_class: &str) -> Option<&TimingProfile> {
+        self.profiles.iter().find(|profile| profile.matches(window_class))
+    }
+
+    /// `base` with the delays of the profile for `window_class`, if any.
+    pub fn config_for(&self, window_class: &str, base: &SubstitutionConfig) -> SubstitutionConfig {
+        let mut config = base.clone();
+        if let Some(profile) = self.profile_for(window_class) {
+            config.key_delay_ms = profile.key_delay_ms;
+            config.paste_restore_delay_ms = profile.paste_restore_delay_ms;
+            config.pre_deletion_delay_ms = profile.pre_deletion_delay_ms;
+        }
+        config
+    }
+
+    /// Records an expansion into a window of `window_class` whose output
+    /// was `unheard` keys behind when it finished. `base` is the global
+    /// timing, which learned profiles start from and relax back to.
+    ///
+    /// Returns true if the profiles changed.
+    pub fn observe(
+        &mut self,
+        window_class: &str,
+        insertion: Insertion,
+        unheard: usize,
+        base: &SubstitutionConfig,
+    ) -> bool {
+        if !self.adaptive || window_class.is_empty() || window_class == WindowInfo::default().app_name {
+            return false;
+        }
+        let lagged = match insertion {
+            Insertion::Clipboard => unheard > 0,
+            Insertion::Keystrokes => unheard > KEYSTROKE_LAG_TOLERANCE,
+        };
+        let index = self
+            .profiles
+            .iter()
+            .position(|profile| profile.matches(window_class));
+        if let Some(i) = index {
+            if !self.profiles[i].learned {
+                return false;
+            }
+        }
+        let runs_key = (window_class.to_lowercase(), insertion);
+
+        if lagged {
+            self.clean_runs.remove(&runs_key);
+            let i = match index {
+                Some(i) => i,
+                None if self.profiles.len() < MAX_TIMING_PROFILES => {
+                    self.profiles.push(TimingProfile {
+                        window_class: window_class.to_string(),
+                        key_delay_ms: base.key_delay_ms,
+                        paste_restore_delay_ms: base.paste_restore_delay_ms,
+                        pre_deletion_delay_ms: base.pre_deletion_delay_ms,
+                        learned: true,
+                    });
+                    self.profiles.len() - 1
+                }
+                None => return false,
+            };
+            let profile = &mut self.profiles[i];
+            let changed = match insertion {
+                // Half as long again, by at least 50 ms.
+                Insertion::Clipboard => raise(
+                    &mut profile.paste_restore_delay_ms,
+                    2,
+                    50,
+                    MAX_LEARNED_PASTE_RESTORE_DELAY_MS,
+                ),
+                // Twice as long.
+                Insertion::Keystrokes => {
+                    raise(&mut profile.key_delay_ms, 1, 1, MAX_LEARNED_KEY_DELAY_MS)
+                }
+            };
+            if changed {
+                tracing::info!(
+                    "Output to {} lagged by {} keys; timing now {} ms per key, {} ms before restoring the clipboard",
+                    window_class,
+                    unheard,
+                    profile.key_delay_ms,
+                    profile.paste_restore_delay_ms
+                );
+            }
+            return changed || index.is_none();
+        }
+
+        let i = match index {
+            Some(i) => i,
+            None => return false,
+        };
+        let runs = self.clean_runs.entry(runs_key).or_insert(0);
+        *runs += 1;
+        if *runs < RELAX_AFTER {
+            return false;
+        }
+        *runs = 0;
+
+        let profile = &mut self.profiles[i];
+        let changed = match insertion {
+            Insertion::Clipboard => relax(&mut profile.paste_restore_delay_ms, base.paste_restore_delay_ms),
+            Insertion::Keystrokes => relax(&mut profile.key_delay_ms, base.key_delay_ms),
+        };
+        if profile.key_delay_ms == base.key_delay_ms
+            && profile.paste_restore_delay_ms == base.paste_restore_delay_ms
+            && profile.pre_deletion_delay_ms == base.pre_deletion_delay_ms
+        {
+            tracing::info!("Timing for {} is back to the defaults", window_class);
+            self.profiles.remove(i);
+            return true;
+        }
+        changed
+    }
+}
+
+/// Adds `value / divisor`, but at least `min_step`, to `value`, up to
+/// `max`. Returns true if `value` changed.
+fn raise(value: &mut u64, divisor: u64, min_step: u64, max: u64) -> bool {
+    let step = (*value / divisor).max(min_step);
+    let raised = value.saturating_add(step).min(max).max(*value);
+    let changed = raised != *value;
+    *value = raised;
+    changed
+}
+
+/// Moves `value` halfway back down to `base`. Returns true if it changed.
+fn relax(value: &mut u64, base: u64) -> bool {
+    if *value <= base {
+        return false;
+    }
+    *value -= (*value - base).div_ceil(2);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn base() -> SubstitutionConfig {
+        SubstitutionConfig::default()
+    }
+
+    fn manual(window_class: &str) -> TimingProfile {
+        TimingProfile {
+            window_class: window_class.to_string(),
+            key_delay_ms: 1,
+            paste_restore_delay_ms: 60,
+            pre_deletion_delay_ms: 0,
+            learned: false,
+        }
+    }
+
+    // -- Applying profiles --
+
+    #[test]
+    fn test_config_for_applies_matching_profile() {
+        let calibrator = TimingCalibrator::new(vec![manual("kitty")], true);
+        let config = calibrator.config_for("Kitty", &base());
+        assert_eq!(config.key_delay_ms, 1);
+        assert_eq!(config.paste_restore_delay_ms, 60);
+        assert_eq!(config.pre_deletion_delay_ms, 0);
+        assert_eq!(config.timeout_secs, base().timeout_secs);
+    }
+
+    #[test]
+    fn test_config_for_other_class_is_base() {
+        let calibrator = TimingCalibrator::new(vec![manual("kitty")], true);
+        let config = calibrator.config_for("firefox", &base());
+        assert_eq!(config.key_delay_ms, base().key_delay_ms);
+        assert_eq!(config.paste_restore_delay_ms, base().paste_restore_delay_ms);
+    }
+
+    // -- Learning --
+
+    #[test]
+    fn test_late_paste_learns_profile() {
+        let mut calibrator = TimingCalibrator::new(Vec::new(), true);
+        assert!(calibrator.observe("firefox", Insertion::Clipboard, 1, &base()));
+
+        let profile = calibrator.profile_for("firefox").expect("learned profile");
+        assert!(profile.learned);
+        assert_eq!(profile.paste_restore_delay_ms, 300);
+        assert_eq!(profile.key_delay_ms, base().key_delay_ms);
+    }
+
+    #[test]
+    fn test_repeated_lag_raises_up_to_limit() {
+        let mut calibrator = TimingCalibrator::new(Vec::new(), true);
+        for _ in 0..20 {
+            calibrator.observe("firefox", Insertion::Clipboard, 1, &base());
+        }
+        let profile = calibrator.profile_for("firefox").unwrap();
+        assert_eq!(profile.paste_restore_delay_ms, MAX_LEARNED_PASTE_RESTORE_DELAY_MS);
+        assert!(!calibrator.observe("firefox", Insertion::Clipboard, 1, &base()));
+    }
+
+    #[test]
+    fn test_typing_lag_within_tolerance_is_ignored() {
+        let mut calibrator = TimingCalibrator::new(Vec::new(), true);
+        assert!(!calibrator.observe("code", Insertion::Keystrokes, KEYSTROKE_LAG_TOLERANCE, &base()));
+        assert!(calibrator.profiles().is_empty());
+
+        assert!(calibrator.observe("code", Insertion::Keystrokes, 10, &base()));
+        assert_eq!(calibrator.profile_for("code").unwrap().key_delay_ms, 10);
+    }
+
+    #[test]
+    fn test_clean_runs_relax_back_to_defaults() {
+        let mut calibrator = TimingCalibrator::new(Vec::new(), true);
+        calibrator.observe("firefox", Insertion::Clipboard, 2, &base());
+
+        for _ in 0..RELAX_AFTER - 1 {
+            assert!(!calibrator.observe("firefox", Insertion::Clipboard, 0, &base()));
+        }
+        assert!(calibrator.observe("firefox", Insertion::Clipboard, 0, &base()));
+        assert_eq!(calibrator.profile_for("firefox").unwrap().paste_restore_delay_ms, 250);
+
+        for _ in 0..RELAX_AFTER * 10 {
+            calibrator.observe("firefox", Insertion::Clipboard, 0, &base());
+        }
+        assert!(calibrator.profile_for("firefox").is_none());
+    }
+
+    #[test]
+    fn test_manual_profiles_are_never_adjusted() {
+        let mut calibrator = TimingCalibrator::new(vec![manual("kitty")], true);
+        assert!(!calibrator.observe("kitty", Insertion::Clipboard, 5, &base()));
+        assert_eq!(calibrator.profiles(), &[manual("kitty")]);
+    }
+
+    #[test]
+    fn test_nothing_learned_when_disabled_or_unknown() {
+        let mut calibrator = TimingCalibrator::new(Vec::new(), false);
+        assert!(!calibrator.observe("firefox", Insertion::Clipboard, 5, &base()));
+
+        calibrator.set_profiles(Vec::new(), true);
+        let unknown = WindowInfo::default().app_name;
+        assert!(!calibrator.observe(&unknown, Insertion::Clipboard, 5, &base()));
+        assert!(calibrator.profiles().is_empty());
+    }
+
+    #[test]
+    fn test_no_profiles_learned_past_limit() {
+        let profiles = (0..MAX_TIMING_PROFILES)
+            .map(|i| manual(&format!("app{}", i)))
+            .collect();
+        let mut calibrator = TimingCalibrator::new(profiles, true);
+        assert!(!calibrator.observe("firefox", Insertion::Clipboard, 5, &base()));
+        assert_eq!(calibrator.profiles().len(), MAX_TIMING_PROFILES);
+    }
+}
@@ -12,6 +12,8 @@ pub enum TrayState {
     Paused,
     /// The current foreground application is in the exclusion list.
     ExcludedApp,
+    /// A password field has focus, so expansion is paused until it loses focus.
+    PasswordField,
 }
 
 impl Default for TrayState {
@@ -66,6 +68,9 @@ impl TrayMenuItem {
 /// Manages system tray icon state and menu construction.
 pub struct TrayManager {
     state: TrayState,
+    /// Whether a password field has focus. Shown over `Active` only, and
+    /// kept apart from `state` so the user's choice survives it.
+    password_field: bool,
 }
 
 impl TrayManager {
@@ -73,12 +78,17 @@ impl TrayManager {
     pub fn new() -> Self {
         Self {
             state: TrayState::Active,
+            password_field: false,
         }
     }
 
     /// Returns the current tray state.
     pub fn state(&self) -> TrayState {
-        self.state
+        if self.password_field && self.state == TrayState::Active {
+            TrayState::PasswordField
+        } else {
+            self.state
+        }
     }
 
     /// Sets the tray state.
@@ -86,6 +96,11 @@ impl TrayManager {
         self.state = state;
     }
 
+    /// Records whether a password field has focus.
+    pub fn set_password_field(&mut self, focused: bool) {
+        self.password_field = focused;
+    }
+
     /// Builds the list of menu items for the tray context menu.
     pub fn build_menu_items(&self) -> Vec<TrayMenuItem> {
         let is_active = self.state == TrayState::Active;
@@ -104,10 +119,11 @@ impl TrayManager {
 
     /// Returns a tooltip string describing the current state.
     pub fn tooltip_text(&self) -> String {
-        match self.state {
+        match self.state() {
             TrayState::Active => "MuttonText - Active".to_string(),
             TrayState::Paused => "MuttonText - Paused".to_string(),
             TrayState::ExcludedApp => "MuttonText - Disabled (excluded app)".to_string(),
+            TrayState::PasswordField => "MuttonText - Paused (password field)".to_string(),
         }
     }
 }
@@ -210,6 +226,27 @@ mod tests {
         assert!(mgr.tooltip_text().contains("excluded"));
     }
 
+    #[test]
+    fn test_password_field_shown_over_active() {
+        let mut mgr = TrayManager::new();
+        mgr.set_password_field(true);
+        assert_eq!(mgr.state(), TrayState::PasswordField);
+        assert!(mgr.tooltip_text().contains("password field"));
+        mgr.set_password_field(false);
+        assert_eq!(mgr.state(), TrayState::Active);
+    }
+
+    #[test]
+    fn test_password_field_keeps_user_pause() {
+        let mut mgr = TrayManager::new();
+        mgr.set_state(TrayState::Paused);
+        mgr.set_password_field(true);
+        assert_eq!(mgr.state(), TrayState::Paused);
+        mgr.set_password_field(false);
+        mgr.set_state(TrayState::Active);
+        assert_eq!(mgr.state(), TrayState::Active);
+    }
+
     #[test]
     fn test_tray_state_serialization() {
         let json = serde_json::to_string(&TrayState::Active).unwrap();
@@ -0,0 +1,134 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// A single typo → correction pair.
+///
+/// Serialized as a two-element array (`["teh", "the"]`) rather than an
+/// object so that dictionaries with tens of thousands of entries stay
+/// compact in `combos.json`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(from = "(String, String)", into = "(String, String)")]
+pub struct AutocorrectEntry {
+    pub typo: String,
+    pub correction: String,
+}
+
+impl AutocorrectEntry {
+    pub fn new(typo: impl Into<String>, correction: impl Into<String>) -> Self {
+        Self {
+            typo: typo.into(),
+            correction: correction.into(),
+        }
+    }
+}
+
+impl From<(String, String)> for AutocorrectEntry {
+    fn from((typo, correction): (String, String)) -> Self {
+        Self { typo, correction }
+    }
+}
+
+impl From<AutocorrectEntry> for (String, String) {
+    fn from(entry: AutocorrectEntry) -> Self {
+        (entry.typo, entry.correction)
+    }
+}
+
+/// A lightweight group of autocorrect entries.
+///
+/// Unlike combos, entries carry no IDs, timestamps, or usage statistics;
+/// only the dictionary itself does. Entries always match on word delimiters
+/// and propagate the typed capitalization onto the correction.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AutocorrectDictionary {
+    pub id: Uuid,
+    pub name: String,
+    pub enabled: bool,
+    pub entries: Vec<AutocorrectEntry>,
+    pub created_at: DateTime<Utc>,
+    pub modified_at: DateTime<Utc>,
+}
+
+impl AutocorrectDictionary {
+    /// Creates a new, enabled dictionary with the given entries.
+    pub fn new(name: impl Into<String>, entries: Vec<AutocorrectEntry>) -> Self {
+        let now = Utc::now();
+        Self {
+            id: Uuid::new_v4(),
+            name: name.into(),
+            enabled: true,
+            entries,
+            created_at: now,
+            modified_at: now,
+        }
+    }
+}
+
+/// Dictionary metadata without its entries, for listing in the UI.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AutocorrectDictionarySummary {
+    pub id: Uuid,
+    pub name: String,
+    pub enabled: bool,
+    pub entry_count: usize,
+    pub created_at: DateTime<Utc>,
+    pub modified_at: DateTime<Utc>,
+}
+
+impl From<&AutocorrectDictionary> for AutocorrectDictionarySummary {
+    fn from(dict: &AutocorrectDictionary) -> Self {
+        Self {
+            id: dict.id,
+            name: dict.name.clone(),
+            enabled: dict.enabled,
+            entry_count: dict.entries.len(),
+            created_at: dict.created_at,
+            modified_at: dict.modified_at,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entry_serializes_as_pair() {
+        let entry = AutocorrectEntry::new("teh", "the");
+        let json = serde_json::to_string(&entry).expect("serialize");
+        assert_eq!(json, r#"["teh","the"]"#);
+        let back: AutocorrectEntry = serde_json::from_str(&json).expect("deserialize");
+        assert_eq!(back, entry);
+    }
+
+    #[test]
+    fn test_dictionary_new() {
+        let dict = AutocorrectDictionary::new("Typos", vec![AutocorrectEntry::new("teh", "the")]);
+        assert_eq!(dict.name, "Typos");
+        assert!(dict.enabled);
+        assert_eq!(dict.entries.len(), 1);
+        assert_eq!(dict.created_at, dict.modified_at);
+    }
+
+    #[test]
+    fn test_dictionary_json_uses_camel_case() {
+        let dict = AutocorrectDictionary::new("Typos", vec![AutocorrectEntry::new("teh", "the")]);
+        let json = serde_json::to_string(&dict).expect("serialize");
+        assert!(json.contains("createdAt"));
+        assert!(json.contains(r#""entries":[["teh","the"]]"#));
+    }
+
+    #[test]
+    fn test_summary_from_dictionary() {
+        let dict = AutocorrectDictionary::new(
+            "Typos",
+            vec![AutocorrectEntry::new("teh", "the"), AutocorrectEntry::new("adn", "and")],
+        );
+        let summary = AutocorrectDictionarySummary::from(&dict);
+        assert_eq!(summary.id, dict.id);
+        assert_eq!(summary.entry_count, 2);
+    }
+}
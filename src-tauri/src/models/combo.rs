@@ -12,12 +12,29 @@ pub enum ComboValidationError {
     EmptyKeyword,
     #[error("Keyword must be at least 2 characters, got {0}")]
     KeywordTooShort(usize),
-    #[error("Keyword must not contain spaces")]
-    KeywordContainsSpaces,
+    #[error("Keyword must be words separated by single spaces, without tabs or line breaks")]
+    KeywordInvalidWhitespace,
     #[error("Snippet must not be empty")]
     EmptySnippet,
 }
 
+/// How a combo's snippet is written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SnippetFormat {
+    /// Inserted exactly as written.
+    #[default]
+    Plain,
+    /// Pasted as rich text, with a plain-text rendering for apps and paste
+    /// methods that can't take HTML.
+    Html,
+    /// Converted to HTML, then handled like [`SnippetFormat::Html`].
+    Markdown,
+    /// The snippet names an image in the assets directory, which is pasted
+    /// through the clipboard whatever the paste method.
+    Image,
+}
+
 /// A combo maps a typed keyword to an expanded text snippet.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "camelCase")]
@@ -30,6 +47,16 @@ pub struct Combo {
     pub group_id: Uuid,
     pub matching_mode: MatchingMode,
     pub case_sensitive: bool,
+    /// Mirror the capitalization of the typed keyword onto the snippet
+    /// (`Sig` → capitalized first letter, `SIG` → upper-cased snippet).
+    #[serde(default)]
+    pub propagate_case: bool,
+    #[serde(default)]
+    pub snippet_format: SnippetFormat,
+    /// Ask clipboard managers not to record the snippet when it is pasted
+    /// through the clipboard, for snippets such as account numbers.
+    #[serde(default = "default_exclude_from_history")]
+    pub exclude_from_history: bool,
     pub enabled: bool,
     pub use_count: u64,
     pub last_used: Option<DateTime<Utc>>,
@@ -37,8 +64,17 @@ pub struct Combo {
     pub modified_at: DateTime<Utc>,
 }
 
+fn default_exclude_from_history() -> bool {
+    true
+}
+
 impl Combo {
     /// Validates this combo's keyword and snippet fields.
+    ///
+    /// Keywords may be multi-word phrases (e.g. `on my way`): single spaces
+    /// between words are allowed, but leading/trailing whitespace, repeated
+    /// spaces, tabs and line breaks are not, since the input buffer never
+    /// retains them.
     pub fn validate(&self) -> Result<(), ComboValidationError> {
         if self.keyword.is_empty() {
             return Err(ComboValidationError::EmptyKeyword);
@@ -46,8 +82,11 @@ impl Combo {
         if self.keyword.len() < 2 {
             return Err(ComboValidationError::KeywordTooShort(self.keyword.len()));
         }
-        if self.keyword.contains(' ') {
-            return Err(ComboValidationError::KeywordContainsSpaces);
+        if self.keyword.trim() != self.keyword
+            || self.keyword.chars().any(|c| c.is_whitespace() && c != ' ')
+            || self.keyword.contains("  ")
+        {
+            return Err(ComboValidationError::KeywordInvalidWhitespace);
         }
         if self.snippet.is_empty() {
             return Err(ComboValidationError::EmptySnippet);
@@ -66,6 +105,9 @@ pub struct ComboBuilder {
     group_id: Option<Uuid>,
     matching_mode: Option<MatchingMode>,
     case_sensitive: Option<bool>,
+    propagate_case: Option<bool>,
+    snippet_format: Option<SnippetFormat>,
+    exclude_from_history: Option<bool>,
     enabled: Option<bool>,
 }
 
@@ -109,6 +151,21 @@ impl ComboBuilder {
         self
     }
 
+    pub fn propagate_case(mut self, propagate_case: bool) -> Self {
+        self.propagate_case = Some(propagate_case);
+        self
+    }
+
+    pub fn snippet_format(mut self, format: SnippetFormat) -> Self {
+        self.snippet_format = Some(format);
+        self
+    }
+
+    pub fn exclude_from_history(mut self, exclude: bool) -> Self {
+        self.exclude_from_history = Some(exclude);
+        self
+    }
+
     pub fn enabled(mut self, enabled: bool) -> Self {
         self.enabled = Some(enabled);
         self
@@ -127,6 +184,11 @@ impl ComboBuilder {
             group_id: self.group_id.unwrap_or_else(Uuid::new_v4),
             matching_mode: self.matching_mode.unwrap_or_default(),
             case_sensitive: self.case_sensitive.unwrap_or(false),
+            propagate_case: self.propagate_case.unwrap_or(false),
+            snippet_format: self.snippet_format.unwrap_or_default(),
+            exclude_from_history: self
+                .exclude_from_history
+                .unwrap_or_else(default_exclude_from_history),
             enabled: self.enabled.unwrap_or(true),
             use_count: 0,
             last_used: None,
@@ -138,6 +200,59 @@ impl ComboBuilder {
     }
 }
 
+/// Changes to an existing combo. Fields left `None` keep their value.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ComboPatch {
+    pub name: Option<String>,
+    pub keyword: Option<String>,
+    pub snippet: Option<String>,
+    pub group_id: Option<Uuid>,
+    pub matching_mode: Option<MatchingMode>,
+    pub case_sensitive: Option<bool>,
+    pub propagate_case: Option<bool>,
+    pub snippet_format: Option<SnippetFormat>,
+    pub exclude_from_history: Option<bool>,
+    pub enabled: Option<bool>,
+}
+
+impl ComboPatch {
+    /// Writes the fields that are set into `combo`. Does not validate or
+    /// touch the timestamps.
+    pub fn apply_to(self, combo: &mut Combo) {
+        if let Some(name) = self.name {
+            combo.name = name;
+        }
+        if let Some(keyword) = self.keyword {
+            combo.keyword = keyword;
+        }
+        if let Some(snippet) = self.snippet {
+            combo.snippet = snippet;
+        }
+        if let Some(group_id) = self.group_id {
+            combo.group_id = group_id;
+        }
+        if let Some(mode) = self.matching_mode {
+            combo.matching_mode = mode;
+        }
+        if let Some(cs) = self.case_sensitive {
+            combo.case_sensitive = cs;
+        }
+        if let Some(pc) = self.propagate_case {
+            combo.propagate_case = pc;
+        }
+        if let Some(format) = self.snippet_format {
+            combo.snippet_format = format;
+        }
+        if let Some(exclude) = self.exclude_from_history {
+            combo.exclude_from_history = exclude;
+        }
+        if let Some(en) = self.enabled {
+            combo.enabled = en;
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -161,6 +276,7 @@ mod tests {
         assert_eq!(combo.group_id, group_id);
         assert!(combo.enabled);
         assert!(!combo.case_sensitive);
+        assert!(!combo.propagate_case);
         assert_eq!(combo.matching_mode, MatchingMode::Strict);
         assert_eq!(combo.use_count, 0);
         assert!(combo.last_used.is_none());
@@ -191,6 +307,7 @@ mod tests {
             .snippet("full text")
             .matching_mode(MatchingMode::Loose)
             .case_sensitive(true)
+            .propagate_case(true)
             .enabled(false)
             .build()
             .unwrap();
@@ -198,6 +315,7 @@ mod tests {
         assert_eq!(combo.description, "A full combo");
         assert_eq!(combo.matching_mode, MatchingMode::Loose);
         assert!(combo.case_sensitive);
+        assert!(combo.propagate_case);
         assert!(!combo.enabled);
     }
 
@@ -214,12 +332,37 @@ mod tests {
     }
 
     #[test]
-    fn test_builder_fails_keyword_with_spaces() {
-        let result = ComboBuilder::new()
-            .keyword("my key")
-            .snippet("text")
-            .build();
-        assert_eq!(result, Err(ComboValidationError::KeywordContainsSpaces));
+    fn test_builder_allows_phrase_keyword() {
+        let combo = ComboBuilder::new()
+            .keyword("on my way")
+            .snippet("On my way, be there in 10 minutes!")
+            .build()
+            .expect("phrase keywords are valid");
+        assert_eq!(combo.keyword, "on my way");
+    }
+
+    #[test]
+    fn test_builder_fails_keyword_with_surrounding_spaces() {
+        for keyword in [" sig", "sig ", " on my way "] {
+            let result = ComboBuilder::new().keyword(keyword).snippet("text").build();
+            assert_eq!(result, Err(ComboValidationError::KeywordInvalidWhitespace));
+        }
+    }
+
+    #[test]
+    fn test_builder_fails_keyword_with_repeated_spaces() {
+        for keyword in ["on  my way", "on my   way"] {
+            let result = ComboBuilder::new().keyword(keyword).snippet("text").build();
+            assert_eq!(result, Err(ComboValidationError::KeywordInvalidWhitespace));
+        }
+    }
+
+    #[test]
+    fn test_builder_fails_keyword_with_tab_or_newline() {
+        for keyword in ["my\tkey", "my\nkey"] {
+            let result = ComboBuilder::new().keyword(keyword).snippet("text").build();
+            assert_eq!(result, Err(ComboValidationError::KeywordInvalidWhitespace));
+        }
     }
 
     #[test]
@@ -249,6 +392,46 @@ mod tests {
         assert!(combo.is_ok());
     }
 
+    // ── ComboPatch tests ────────────────────────────────────────────
+
+    #[test]
+    fn test_patch_deserializes_camel_case_fields() {
+        let patch: ComboPatch = serde_json::from_str(
+            r#"{"snippetFormat":"html","excludeFromHistory":false,"description":"ignored"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            patch,
+            ComboPatch {
+                snippet_format: Some(SnippetFormat::Html),
+                exclude_from_history: Some(false),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_patch_changes_only_set_fields() {
+        let mut combo = ComboBuilder::new()
+            .name("Sig")
+            .keyword("sig")
+            .snippet("Regards")
+            .build()
+            .unwrap();
+        let original = combo.clone();
+        ComboPatch {
+            snippet: Some("<b>Regards</b>".into()),
+            snippet_format: Some(SnippetFormat::Html),
+            ..Default::default()
+        }
+        .apply_to(&mut combo);
+        assert_eq!(combo.snippet, "<b>Regards</b>");
+        assert_eq!(combo.snippet_format, SnippetFormat::Html);
+        assert_eq!(combo.name, original.name);
+        assert_eq!(combo.keyword, original.keyword);
+        assert_eq!(combo.enabled, original.enabled);
+    }
+
     // ── Serialization tests ─────────────────────────────────────────
 
     #[test]
@@ -286,6 +469,54 @@ mod tests {
         assert!(!json.contains("matching_mode"));
     }
 
+    #[test]
+    fn test_combo_deserializes_without_propagate_case() {
+        let combo = ComboBuilder::new()
+            .keyword("pc")
+            .snippet("text")
+            .build()
+            .unwrap();
+        let mut value = serde_json::to_value(&combo).expect("serialize");
+        assert!(value.as_object_mut().unwrap().remove("propagateCase").is_some());
+        let deserialized: Combo = serde_json::from_value(value).expect("deserialize");
+        assert!(!deserialized.propagate_case);
+    }
+
+    #[test]
+    fn test_combo_snippet_format() {
+        let combo = ComboBuilder::new()
+            .keyword("sig")
+            .snippet("**Jane**")
+            .snippet_format(SnippetFormat::Markdown)
+            .build()
+            .unwrap();
+        let mut value = serde_json::to_value(&combo).expect("serialize");
+        assert_eq!(value["snippetFormat"], "markdown");
+
+        value.as_object_mut().unwrap().remove("snippetFormat");
+        let deserialized: Combo = serde_json::from_value(value).expect("deserialize");
+        assert_eq!(deserialized.snippet_format, SnippetFormat::Plain);
+    }
+
+    #[test]
+    fn test_combo_exclude_from_history_defaults_on() {
+        let combo = ComboBuilder::new()
+            .keyword("acct")
+            .snippet("12345678")
+            .build()
+            .unwrap();
+        assert!(combo.exclude_from_history);
+
+        let mut value = serde_json::to_value(&combo).expect("serialize");
+        value["excludeFromHistory"] = serde_json::Value::Bool(false);
+        let deserialized: Combo = serde_json::from_value(value.clone()).expect("deserialize");
+        assert!(!deserialized.exclude_from_history);
+
+        value.as_object_mut().unwrap().remove("excludeFromHistory");
+        let deserialized: Combo = serde_json::from_value(value).expect("deserialize");
+        assert!(deserialized.exclude_from_history);
+    }
+
     #[test]
     fn test_combo_clone() {
         let combo = ComboBuilder::new()
@@ -1,17 +1,20 @@
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use super::autocorrect::AutocorrectDictionary;
 use super::combo::Combo;
 use super::group::Group;
 
-/// The top-level container for all groups and combos.
-/// Persisted as `combos.json`.
+/// The top-level container for all groups, combos, and autocorrect
+/// dictionaries. Persisted as `combos.json`.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct ComboLibrary {
     pub version: String,
     pub groups: Vec<Group>,
     pub combos: Vec<Combo>,
+    #[serde(default)]
+    pub dictionaries: Vec<AutocorrectDictionary>,
 }
 
 impl ComboLibrary {
@@ -21,6 +24,7 @@ impl ComboLibrary {
             version: version.into(),
             groups: Vec::new(),
             combos: Vec::new(),
+            dictionaries: Vec::new(),
         }
     }
 
@@ -46,6 +50,18 @@ impl ComboLibrary {
         self.combos.iter().filter(|c| c.group_id == group_id).collect()
     }
 
+    /// Adds an autocorrect dictionary to the library.
+    pub fn add_dictionary(&mut self, dictionary: AutocorrectDictionary) {
+        self.dictionaries.push(dictionary);
+    }
+
+    /// Removes a dictionary by its ID. Returns `true` if one was removed.
+    pub fn remove_dictionary(&mut self, dictionary_id: Uuid) -> bool {
+        let before = self.dictionaries.len();
+        self.dictionaries.retain(|d| d.id != dictionary_id);
+        self.dictionaries.len() < before
+    }
+
     /// Finds the first combo whose keyword matches exactly (case-sensitive).
     pub fn find_combo_by_keyword(&self, keyword: &str) -> Option<&Combo> {
         self.combos.iter().find(|c| c.keyword == keyword)
@@ -169,6 +185,27 @@ mod tests {
         assert_eq!(lib, deserialized);
     }
 
+    #[test]
+    fn test_add_and_remove_dictionary() {
+        use crate::models::autocorrect::AutocorrectEntry;
+
+        let mut lib = ComboLibrary::new("1.0");
+        let dict = AutocorrectDictionary::new("Typos", vec![AutocorrectEntry::new("teh", "the")]);
+        let dict_id = dict.id;
+        lib.add_dictionary(dict);
+        assert_eq!(lib.dictionaries.len(), 1);
+        assert!(lib.remove_dictionary(dict_id));
+        assert!(!lib.remove_dictionary(dict_id));
+        assert!(lib.dictionaries.is_empty());
+    }
+
+    #[test]
+    fn test_library_without_dictionaries_deserializes() {
+        let json = r#"{"version":"1.0","groups":[],"combos":[]}"#;
+        let lib: ComboLibrary = serde_json::from_str(json).expect("deserialize");
+        assert!(lib.dictionaries.is_empty());
+    }
+
     #[test]
     fn test_library_json_uses_camel_case() {
         let lib = ComboLibrary::new("1.0");
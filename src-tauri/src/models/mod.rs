@@ -1,8 +1,10 @@
 //! Data models for MuttonText.
 //!
 //! This module defines the core domain types: combos, groups, preferences,
-//! matching modes, and the top-level combo library container.
+//! matching modes, autocorrect dictionaries, and the top-level combo library
+//! container.
 
+pub mod autocorrect;
 pub mod combo;
 pub mod group;
 pub mod library;
@@ -10,7 +12,8 @@ pub mod matching;
 pub mod preferences;
 
 // Re-export primary types for convenience.
-pub use combo::{Combo, ComboBuilder, ComboValidationError};
+pub use autocorrect::{AutocorrectDictionary, AutocorrectDictionarySummary, AutocorrectEntry};
+pub use combo::{Combo, ComboBuilder, ComboPatch, ComboValidationError, SnippetFormat};
 pub use group::Group;
 pub use library::ComboLibrary;
 pub use matching::MatchingMode;
@@ -10,8 +10,12 @@ pub enum PasteMethod {
     Clipboard,
     /// Simulate individual keystrokes to type the snippet.
     SimulateKeystrokes,
-    /// Use xdotool type command (Linux only, works in terminals).
+    /// Type the snippet via XTest (Linux only, works in terminals). The
+    /// variant keeps its historical name for settings compatibility.
     XdotoolType,
+    /// Type through a uinput virtual keyboard (Linux only, works on native
+    /// Wayland).
+    Uinput,
 }
 
 impl Default for PasteMethod {
@@ -24,6 +28,66 @@ impl Default for PasteMethod {
     }
 }
 
+/// Which property of the focused window a [`PasteRule`] matches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PasteRuleField {
+    /// WM_CLASS on X11, the app id on Wayland, the app name elsewhere.
+    WindowClass,
+    WindowTitle,
+}
+
+/// Overrides the paste method, and optionally the paste shortcut, for
+/// windows whose class or title contains `pattern` (case-insensitive).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PasteRule {
+    pub field: PasteRuleField,
+    pub pattern: String,
+    pub paste_method: PasteMethod,
+    /// Shortcut pressed to paste, e.g. "Ctrl+Shift+V". Empty uses the
+    /// default paste shortcut.
+    #[serde(default)]
+    pub paste_chord: String,
+}
+
+impl PasteRule {
+    /// Whether this rule applies to a window with the given class and title.
+    pub fn matches(&self, window_class: &str, window_title: &str) -> bool {
+        let haystack = match self.field {
+            PasteRuleField::WindowClass => window_class,
+            PasteRuleField::WindowTitle => window_title,
+        };
+        !self.pattern.is_empty()
+            && haystack
+                .to_lowercase()
+                .contains(&self.pattern.to_lowercase())
+    }
+}
+
+/// Timing used instead of the global substitution delays in windows of one
+/// class. The engine creates and adjusts `learned` profiles itself; it
+/// never changes the others.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimingProfile {
+    /// WM_CLASS on X11, the app id on Wayland, the app name elsewhere.
+    pub window_class: String,
+    pub key_delay_ms: u64,
+    pub paste_restore_delay_ms: u64,
+    pub pre_deletion_delay_ms: u64,
+    #[serde(default)]
+    pub learned: bool,
+}
+
+impl TimingProfile {
+    /// Whether this profile applies to windows of `window_class`
+    /// (case-insensitive).
+    pub fn matches(&self, window_class: &str) -> bool {
+        self.window_class.eq_ignore_ascii_case(window_class)
+    }
+}
+
 /// Application color theme.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -60,6 +124,33 @@ pub struct Preferences {
     pub max_backups: u32,
     pub auto_check_updates: bool,
     pub excluded_apps: Vec<String>,
+    /// Per-application paste overrides; the first matching rule wins.
+    #[serde(default)]
+    pub paste_rules: Vec<PasteRule>,
+    /// Pressing Backspace right after an expansion restores the keyword.
+    #[serde(default)]
+    pub undo_with_backspace: bool,
+    /// How long after an expansion the undo Backspace is honoured, in milliseconds.
+    #[serde(default = "default_undo_window_ms")]
+    pub undo_window_ms: u64,
+    /// Debug: record every keystroke, click and focus change to a trace
+    /// file in the logs directory. Not shown in the UI.
+    #[serde(default)]
+    pub record_keystroke_trace: bool,
+    /// Per-application timing; at most one profile per window class.
+    #[serde(default)]
+    pub timing_profiles: Vec<TimingProfile>,
+    /// Let the engine learn timing profiles for slow applications.
+    #[serde(default = "default_adaptive_timing")]
+    pub adaptive_timing: bool,
+}
+
+fn default_undo_window_ms() -> u64 {
+    3000
+}
+
+fn default_adaptive_timing() -> bool {
+    true
 }
 
 impl Default for Preferences {
@@ -81,6 +172,12 @@ impl Default for Preferences {
             max_backups: 10,
             auto_check_updates: true,
             excluded_apps: Vec::new(),
+            paste_rules: Vec::new(),
+            undo_with_backspace: false,
+            undo_window_ms: default_undo_window_ms(),
+            record_keystroke_trace: false,
+            timing_profiles: Vec::new(),
+            adaptive_timing: default_adaptive_timing(),
         }
     }
 }
@@ -107,6 +204,7 @@ mod tests {
             PasteMethod::Clipboard,
             PasteMethod::SimulateKeystrokes,
             PasteMethod::XdotoolType,
+            PasteMethod::Uinput,
         ] {
             let json = serde_json::to_string(method).expect("serialize");
             let deserialized: PasteMethod = serde_json::from_str(&json).expect("deserialize");
@@ -121,6 +219,95 @@ mod tests {
         assert_eq!(json, "\"xdotoolType\"");
     }
 
+    #[test]
+    fn test_paste_method_uinput_serialization() {
+        let json = serde_json::to_string(&PasteMethod::Uinput).expect("serialize");
+        assert_eq!(json, "\"uinput\"");
+    }
+
+    // ── PasteRule tests ─────────────────────────────────────────────
+
+    fn terminal_rule() -> PasteRule {
+        PasteRule {
+            field: PasteRuleField::WindowClass,
+            pattern: "Terminal".to_string(),
+            paste_method: PasteMethod::Clipboard,
+            paste_chord: "Ctrl+Shift+V".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_paste_rule_matches_class_case_insensitively() {
+        let rule = terminal_rule();
+        assert!(rule.matches("gnome-terminal-server", "~/src"));
+        assert!(!rule.matches("firefox", "Terminal tips - Mozilla Firefox"));
+    }
+
+    #[test]
+    fn test_paste_rule_matches_title() {
+        let rule = PasteRule {
+            field: PasteRuleField::WindowTitle,
+            pattern: "vim".to_string(),
+            ..terminal_rule()
+        };
+        assert!(rule.matches("kitty", "NVIM - notes.md"));
+        assert!(!rule.matches("vim", "notes.md"));
+    }
+
+    #[test]
+    fn test_paste_rule_empty_pattern_never_matches() {
+        let rule = PasteRule {
+            pattern: String::new(),
+            ..terminal_rule()
+        };
+        assert!(!rule.matches("gnome-terminal-server", "~/src"));
+    }
+
+    #[test]
+    fn test_paste_rule_serialization() {
+        let json = serde_json::to_value(terminal_rule()).expect("serialize");
+        assert_eq!(json["field"], "windowClass");
+        assert_eq!(json["pasteMethod"], "clipboard");
+        assert_eq!(json["pasteChord"], "Ctrl+Shift+V");
+
+        let rule: PasteRule = serde_json::from_str(
+            r#"{"field":"windowTitle","pattern":"ssh","pasteMethod":"simulateKeystrokes"}"#,
+        )
+        .expect("deserialize");
+        assert_eq!(rule.field, PasteRuleField::WindowTitle);
+        assert!(rule.paste_chord.is_empty());
+    }
+
+    // ── TimingProfile tests ─────────────────────────────────────────
+
+    #[test]
+    fn test_timing_profile_matches_class_exactly() {
+        let profile = TimingProfile {
+            window_class: "Firefox".to_string(),
+            key_delay_ms: 10,
+            paste_restore_delay_ms: 300,
+            pre_deletion_delay_ms: 20,
+            learned: false,
+        };
+        assert!(profile.matches("firefox"));
+        assert!(!profile.matches("firefox-esr"));
+    }
+
+    #[test]
+    fn test_timing_profile_serialization() {
+        let profile: TimingProfile = serde_json::from_str(
+            r#"{"windowClass":"code","keyDelayMs":8,"pasteRestoreDelayMs":250,"preDeletionDelayMs":20}"#,
+        )
+        .expect("deserialize");
+        assert_eq!(profile.window_class, "code");
+        assert_eq!(profile.paste_restore_delay_ms, 250);
+        assert!(!profile.learned);
+
+        let json = serde_json::to_value(&profile).expect("serialize");
+        assert_eq!(json["keyDelayMs"], 8);
+        assert_eq!(json["learned"], false);
+    }
+
     // ── Theme tests ─────────────────────────────────────────────────
 
     #[test]
@@ -223,6 +410,18 @@ mod tests {
         assert!(prefs.excluded_apps.is_empty());
     }
 
+    #[test]
+    fn test_preferences_default_undo_disabled() {
+        let prefs = Preferences::default();
+        assert!(!prefs.undo_with_backspace);
+        assert_eq!(prefs.undo_window_ms, 3000);
+    }
+
+    #[test]
+    fn test_preferences_default_trace_recording_off() {
+        assert!(!Preferences::default().record_keystroke_trace);
+    }
+
     // ── Preferences serialization ───────────────────────────────────
 
     #[test]
@@ -259,6 +458,38 @@ mod tests {
         assert_eq!(deserialized.excluded_apps[0], "1password");
     }
 
+    #[test]
+    fn test_preferences_deserialize_without_undo_fields() {
+        let mut value = serde_json::to_value(Preferences::default()).expect("serialize");
+        let obj = value.as_object_mut().expect("object");
+        obj.remove("undoWithBackspace");
+        obj.remove("undoWindowMs");
+        obj.remove("recordKeystrokeTrace");
+        let prefs: Preferences = serde_json::from_value(value).expect("deserialize");
+        assert!(!prefs.undo_with_backspace);
+        assert_eq!(prefs.undo_window_ms, 3000);
+        assert!(!prefs.record_keystroke_trace);
+    }
+
+    #[test]
+    fn test_preferences_deserialize_without_paste_rules() {
+        let mut value = serde_json::to_value(Preferences::default()).expect("serialize");
+        value.as_object_mut().expect("object").remove("pasteRules");
+        let prefs: Preferences = serde_json::from_value(value).expect("deserialize");
+        assert!(prefs.paste_rules.is_empty());
+    }
+
+    #[test]
+    fn test_preferences_deserialize_without_timing_fields() {
+        let mut value = serde_json::to_value(Preferences::default()).expect("serialize");
+        let obj = value.as_object_mut().expect("object");
+        obj.remove("timingProfiles");
+        obj.remove("adaptiveTiming");
+        let prefs: Preferences = serde_json::from_value(value).expect("deserialize");
+        assert!(prefs.timing_profiles.is_empty());
+        assert!(prefs.adaptive_timing);
+    }
+
     #[test]
     fn test_preferences_clone() {
         let prefs = Preferences::default();
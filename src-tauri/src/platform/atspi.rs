@@ -0,0 +1,481 @@
+//! Password-field detection through the AT-SPI accessibility bus.
+//!
+//! Toolkits that expose accessibility (GTK, Qt, Firefox, Chromium) emit
+//! `object:state-changed:focused` events on the AT-SPI bus whenever the
+//! keyboard focus moves between widgets. When a widget gains focus the
+//! watcher asks for its role; a `ROLE_PASSWORD_TEXT` field pauses
+//! expansion until focus leaves it, whichever application it lives in.
+//!
+//! The AT-SPI bus is separate from the session bus: its address is looked
+//! up through `org.a11y.Bus` on the session bus. Applications that do not
+//! expose accessibility are simply never reported.
+
+#![cfg(target_os = "linux")]
+
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+/// AT-SPI role of a text entry whose contents are hidden.
+pub const ROLE_PASSWORD_TEXT: u32 = 40;
+
+/// Interface and member of the focus events the watcher follows.
+pub const OBJECT_EVENT_INTERFACE: &str = "org.a11y.atspi.Event.Object";
+pub const STATE_CHANGED: &str = "StateChanged";
+
+const ACCESSIBLE_INTERFACE: &str = "org.a11y.atspi.Accessible";
+
+fn invalid_data<E: std::fmt::Display>(e: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e.to_string())
+}
+
+/// Identifies an accessible object: the owning application's bus name
+/// (absent on peer-to-peer connections) and its object path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccessibleRef {
+    pub sender: Option<String>,
+    pub path: String,
+}
+
+/// Decides from focus events whether a password field is focused and
+/// reports each change of that answer.
+pub struct PasswordFieldTracker {
+    /// The focused password field, if any.
+    focused_field: Option<AccessibleRef>,
+    on_change: Box<dyn Fn(bool) + Send + Sync>,
+}
+
+impl PasswordFieldTracker {
+    pub fn new(on_change: Box<dyn Fn(bool) + Send + Sync>) -> Self {
+        Self {
+            focused_field: None,
+            on_change,
+        }
+    }
+
+    /// Returns `true` while a password field has focus.
+    pub fn in_password_field(&self) -> bool {
+        self.focused_field.is_some()
+    }
+
+    /// An object gained focus.
+    pub fn focus_gained(&mut self, object: AccessibleRef, role: u32) {
+        let was_active = self.in_password_field();
+        self.focused_field = if role == ROLE_PASSWORD_TEXT {
+            Some(object)
+        } else {
+            None
+        };
+        self.notify(was_active);
+    }
+
+    /// An object lost focus. Only the focused password field matters;
+    /// other widgets losing focus say nothing about the next one.
+    pub fn focus_lost(&mut self, object: &AccessibleRef) {
+        if self.focused_field.as_ref() == Some(object) {
+            self.focused_field = None;
+            self.notify(true);
+        }
+    }
+
+    fn notify(&self, was_active: bool) {
+        let active = self.in_password_field();
+        if active != was_active {
+            tracing::debug!("Password field focused: {}", active);
+            (self.on_change)(active);
+        }
+    }
+}
+
+/// Watches the AT-SPI bus for focused password fields on a background
+/// thread. Dropping the watcher closes its connection, which wakes the
+/// thread from waiting for the next event, and joins it.
+pub struct PasswordFieldWatcher {
+    shutdown: Arc<AtomicBool>,
+    /// The watcher thread's connection, once it has one.
+    connection: Arc<Mutex<Option<zbus::blocking::Connection>>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl PasswordFieldWatcher {
+    /// Connects to the AT-SPI bus and reports `true` when a password field
+    /// gains focus and `false` when focus leaves it. If no accessibility
+    /// bus is running the watcher logs once and never reports.
+    pub fn start(on_change: Box<dyn Fn(bool) + Send + Sync>) -> Self {
+        Self::start_with(connect_accessibility_bus, on_change)
+    }
+
+    /// Like [`PasswordFieldWatcher::start`], but watches the connection
+    /// `connect` returns, e.g. one to a mock accessibility service.
+    pub fn start_with<C>(connect: C, on_change: Box<dyn Fn(bool) + Send + Sync>) -> Self
+    where
+        C: FnOnce() -> io::Result<zbus::blocking::Connection> + Send + 'static,
+    {
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let connection = Arc::new(Mutex::new(None));
+        let (thread_shutdown, thread_connection) = (shutdown.clone(), connection.clone());
+        let spawned = thread::Builder::new()
+            .name("muttontext-atspi".into())
+            .spawn(move || {
+                let mut tracker = PasswordFieldTracker::new(on_change);
+                let result = connect().and_then(|connection| {
+                    // Checked under the lock so a concurrent drop either
+                    // sees the connection and closes it, or stops us here.
+                    {
+                        let mut shared = thread_connection.lock().unwrap_or_else(|e| e.into_inner());
+                        if thread_shutdown.load(Ordering::SeqCst) {
+                            return Ok(());
+                        }
+                        *shared = Some(connection.clone());
+                    }
+                    watch_focus(&connection, &mut tracker, &thread_shutdown)
+                });
+                match result {
+                    Err(_) if thread_shutdown.load(Ordering::SeqCst) => {}
+                    Err(e) => tracing::info!(
+                        "AT-SPI unavailable ({}); password fields will not pause expansion",
+                        e
+                    ),
+                    Ok(()) => {}
+                }
+                // Never leave expansion paused behind a dead connection.
+                if tracker.in_password_field() {
+                    (tracker.on_change)(false);
+                }
+            });
+        let thread = match spawned {
+            Ok(thread) => Some(thread),
+            Err(e) => {
+                tracing::warn!("Failed to spawn AT-SPI watcher: {}", e);
+                None
+            }
+        };
+        Self {
+            shutdown,
+            connection,
+            thread,
+        }
+    }
+}
+
+impl Drop for PasswordFieldWatcher {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+        let connection = self
+            .connection
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take();
+        if let Some(connection) = connection {
+            if let Err(e) = connection.close() {
+                tracing::debug!("Closing the AT-SPI connection failed: {}", e);
+            }
+        }
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                tracing::warn!("AT-SPI watcher thread panicked");
+            }
+        }
+    }
+}
+
+/// Looks up the AT-SPI bus address on the session bus and connects to it.
+fn connect_accessibility_bus() -> io::Result<zbus::blocking::Connection> {
+    let session = zbus::blocking::Connection::session().map_err(invalid_data)?;
+    let address: String = session
+        .call_method(
+            Some("org.a11y.Bus"),
+            "/org/a11y/bus",
+            Some("org.a11y.Bus"),
+            "GetAddress",
+            &(),
+        )
+        .and_then(|reply| reply.body().deserialize())
+        .map_err(invalid_data)?;
+    zbus::blocking::connection::Builder::address(address.as_str())
+        .and_then(|builder| builder.build())
+        .map_err(invalid_data)
+}
+
+/// Calls a method on the AT-SPI registry daemon.
+fn call_registry<B>(
+    connection: &zbus::blocking::Connection,
+    method: &str,
+    body: &B,
+) -> zbus::Result<zbus::Message>
+where
+    B: serde::Serialize + zbus::zvariant::DynamicType,
+{
+    connection.call_method(
+        Some("org.a11y.atspi.Registry"),
+        "/org/a11y/atspi/registry",
+        Some("org.a11y.atspi.Registry"),
+        method,
+        body,
+    )
+}
+
+/// Asks the registry to forward focus events. Toolkits skip events nobody
+/// registered for; at-spi2-core 2.46 added the properties and sender
+/// arguments, so the old single-argument form is tried as a fallback.
+fn register_focus_events(connection: &zbus::blocking::Connection) {
+    const EVENT: &str = "object:state-changed:focused";
+    let registered = call_registry(connection, "RegisterEvent", &(EVENT, Vec::<String>::new(), ""))
+        .or_else(|_| call_registry(connection, "RegisterEvent", &(EVENT,)));
+    if let Err(e) = registered {
+        tracing::debug!("AT-SPI registry refused focus events: {}", e);
+    }
+}
+
+/// Reads the kind and `detail1` of a `StateChanged` event. The trailing
+/// arguments changed type across AT-SPI releases, so only the leading
+/// ones are decoded.
+pub fn parse_state_changed(message: &zbus::Message) -> Option<(String, i32)> {
+    let body = message.body();
+    // Multi-argument bodies are reported as a structure: "(siiva{sv})".
+    if !body.signature().to_string().trim_start_matches('(').starts_with("sii") {
+        return None;
+    }
+    let (kind, detail1, _detail2): (String, i32, i32) = body.deserialize_unchecked().ok()?;
+    Some((kind, detail1))
+}
+
+/// Queries an accessible object's role.
+fn query_role(connection: &zbus::blocking::Connection, object: &AccessibleRef) -> zbus::Result<u32> {
+    connection
+        .call_method(
+            object.sender.as_deref(),
+            object.path.as_str(),
+            Some(ACCESSIBLE_INTERFACE),
+            "GetRole",
+            &(),
+        )?
+        .body()
+        .deserialize()
+}
+
+/// Feeds focus events from `connection` into `tracker` until `shutdown` is
+/// set or the connection closes.
+pub fn watch_focus(
+    connection: &zbus::blocking::Connection,
+    tracker: &mut PasswordFieldTracker,
+    shutdown: &AtomicBool,
+) -> io::Result<()> {
+    let rule = zbus::MatchRule::builder()
+        .msg_type(zbus::message::Type::Signal)
+        .interface(OBJECT_EVENT_INTERFACE)
+        .and_then(|builder| builder.member(STATE_CHANGED))
+        .map_err(invalid_data)?
+        .build();
+    let messages = zbus::blocking::MessageIterator::for_match_rule(rule, connection, None)
+        .map_err(invalid_data)?;
+    register_focus_events(connection);
+
+    for message in messages {
+        if shutdown.load(Ordering::SeqCst) {
+            break;
+        }
+        let message = message.map_err(invalid_data)?;
+        let (kind, detail1) = match parse_state_changed(&message) {
+            Some(event) => event,
+            None => continue,
+        };
+        if kind != "focused" {
+            continue;
+        }
+        let header = message.header();
+        let object = match header.path() {
+            Some(path) => AccessibleRef {
+                sender: header.sender().map(|sender| sender.to_string()),
+                path: path.to_string(),
+            },
+            None => continue,
+        };
+        if detail1 == 0 {
+            tracker.focus_lost(&object);
+            continue;
+        }
+        match query_role(connection, &object) {
+            Ok(role) => tracker.focus_gained(object, role),
+            // The widget may be gone already; treat it as an ordinary one.
+            Err(e) => {
+                tracing::debug!("GetRole failed for {}: {}", object.path, e);
+                tracker.focus_gained(object, 0);
+            }
+        }
+    }
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::os::unix::net::UnixStream;
+    use std::sync::Mutex;
+    use std::time::{Duration, Instant};
+    use zbus::zvariant::Value;
+
+    fn recording_tracker() -> (PasswordFieldTracker, Arc<Mutex<Vec<bool>>>) {
+        let changes = Arc::new(Mutex::new(Vec::new()));
+        let recorded = changes.clone();
+        let tracker = PasswordFieldTracker::new(Box::new(move |active| {
+            recorded.lock().unwrap().push(active);
+        }));
+        (tracker, changes)
+    }
+
+    fn object(path: &str) -> AccessibleRef {
+        AccessibleRef {
+            sender: Some(":1.7".into()),
+            path: path.into(),
+        }
+    }
+
+    // ── Tracker ──
+
+    #[test]
+    fn test_tracker_reports_password_field_focus() {
+        let (mut tracker, changes) = recording_tracker();
+        tracker.focus_gained(object("/entry/user"), 61);
+        assert!(!tracker.in_password_field());
+        tracker.focus_gained(object("/entry/pass"), ROLE_PASSWORD_TEXT);
+        assert!(tracker.in_password_field());
+        tracker.focus_gained(object("/button/ok"), 43);
+        assert!(!tracker.in_password_field());
+        assert_eq!(*changes.lock().unwrap(), vec![true, false]);
+    }
+
+    #[test]
+    fn test_tracker_ignores_other_objects_losing_focus() {
+        let (mut tracker, changes) = recording_tracker();
+        tracker.focus_gained(object("/entry/pass"), ROLE_PASSWORD_TEXT);
+        tracker.focus_lost(&object("/entry/user"));
+        assert!(tracker.in_password_field());
+        tracker.focus_lost(&object("/entry/pass"));
+        assert!(!tracker.in_password_field());
+        assert_eq!(*changes.lock().unwrap(), vec![true, false]);
+    }
+
+    #[test]
+    fn test_tracker_moving_between_password_fields_stays_active() {
+        let (mut tracker, changes) = recording_tracker();
+        tracker.focus_gained(object("/entry/pass"), ROLE_PASSWORD_TEXT);
+        tracker.focus_gained(object("/entry/confirm"), ROLE_PASSWORD_TEXT);
+        // The first field's late focus-lost must not end the pause.
+        tracker.focus_lost(&object("/entry/pass"));
+        assert!(tracker.in_password_field());
+        assert_eq!(*changes.lock().unwrap(), vec![true]);
+    }
+
+    // ── Mock accessibility service ──
+
+    /// An application exposing two accessible widgets.
+    struct MockAccessible {
+        role: u32,
+    }
+
+    #[zbus::interface(name = "org.a11y.atspi.Accessible")]
+    impl MockAccessible {
+        fn get_role(&self) -> u32 {
+            self.role
+        }
+    }
+
+    fn emit_focus(app: &zbus::blocking::Connection, path: &str, focused: bool) {
+        let properties: HashMap<String, Value> = HashMap::new();
+        app.emit_signal(
+            None::<&str>,
+            path,
+            OBJECT_EVENT_INTERFACE,
+            STATE_CHANGED,
+            &("focused", i32::from(focused), 0i32, Value::from(0i32), properties),
+        )
+        .unwrap();
+    }
+
+    fn wait_for(changes: &Mutex<Vec<bool>>, len: usize) -> Vec<bool> {
+        let deadline = Instant::now() + Duration::from_secs(3);
+        loop {
+            let seen = changes.lock().unwrap().clone();
+            if seen.len() >= len || Instant::now() > deadline {
+                return seen;
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+    }
+
+    /// Connects an application serving a plain entry at `/accessible/1` and
+    /// a password entry at `/accessible/2` to a watcher connection.
+    fn mock_accessibility_bus() -> (zbus::blocking::Connection, zbus::blocking::Connection) {
+        let (app_socket, watcher_socket) = UnixStream::pair().unwrap();
+        let server = thread::spawn(move || {
+            zbus::blocking::connection::Builder::unix_stream(app_socket)
+                .server(zbus::Guid::generate())
+                .unwrap()
+                .p2p()
+                .serve_at("/org/a11y/atspi/accessible/1", MockAccessible { role: 61 })
+                .unwrap()
+                .serve_at("/org/a11y/atspi/accessible/2", MockAccessible { role: ROLE_PASSWORD_TEXT })
+                .unwrap()
+                .build()
+                .unwrap()
+        });
+        let watcher_connection = zbus::blocking::connection::Builder::unix_stream(watcher_socket)
+            .p2p()
+            .build()
+            .unwrap();
+        (server.join().unwrap(), watcher_connection)
+    }
+
+    #[test]
+    fn test_watch_focus_over_mock_accessibility_bus() {
+        let (app, watcher_connection) = mock_accessibility_bus();
+        let (mut tracker, changes) = recording_tracker();
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let watcher_shutdown = shutdown.clone();
+        let watcher = thread::spawn(move || {
+            watch_focus(&watcher_connection, &mut tracker, &watcher_shutdown).unwrap();
+        });
+        // Let the watcher subscribe before the first event.
+        thread::sleep(Duration::from_millis(100));
+
+        emit_focus(&app, "/org/a11y/atspi/accessible/1", true);
+        emit_focus(&app, "/org/a11y/atspi/accessible/1", false);
+        emit_focus(&app, "/org/a11y/atspi/accessible/2", true);
+        assert_eq!(wait_for(&changes, 1), vec![true]);
+
+        emit_focus(&app, "/org/a11y/atspi/accessible/2", false);
+        assert_eq!(wait_for(&changes, 2), vec![true, false]);
+
+        shutdown.store(true, Ordering::SeqCst);
+        emit_focus(&app, "/org/a11y/atspi/accessible/1", true);
+        watcher.join().unwrap();
+    }
+
+    #[test]
+    fn test_dropping_watcher_stops_it_without_further_events() {
+        let (app, watcher_connection) = mock_accessibility_bus();
+        let changes = Arc::new(Mutex::new(Vec::new()));
+        let recorded = changes.clone();
+        let watcher = PasswordFieldWatcher::start_with(
+            move || Ok(watcher_connection),
+            Box::new(move |active| recorded.lock().unwrap().push(active)),
+        );
+        thread::sleep(Duration::from_millis(100));
+        emit_focus(&app, "/org/a11y/atspi/accessible/2", true);
+        assert_eq!(wait_for(&changes, 1), vec![true]);
+
+        // No event follows, so only closing the connection can end the wait.
+        let started = Instant::now();
+        drop(watcher);
+        assert!(started.elapsed() < Duration::from_secs(1));
+        // The pause is lifted when the watcher goes away.
+        assert_eq!(*changes.lock().unwrap(), vec![true, false]);
+    }
+}
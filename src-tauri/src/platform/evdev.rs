@@ -0,0 +1,1002 @@
+//! Keyboard capture straight from the kernel's evdev devices.
+//!
+//! Under a pure Wayland session (no XWayland) there is no X server for
+//! `rdev` to listen on. [`EvdevKeyboardHook`] instead reads key events from
+//! `/dev/input/event*`, which works on any display server for users in the
+//! `input` group. Scancodes are translated through the active XKB keymap
+//! (see [`crate::platform::xkb`]), and keyboards plugged in after the hook
+//! starts are picked up by a periodic rescan of the device directory.
+//! When a mouse callback is set, pointer devices are read too so clicks
+//! reach the input manager.
+//!
+//! The hook reads from a configurable device directory, so recorded evdev
+//! streams saved as plain files can be replayed through it in tests.
+
+#![cfg(target_os = "linux")]
+
+use std::collections::HashSet;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, ErrorKind, Read};
+use std::os::raw::c_long;
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::platform::keyboard_hook::{KeyEvent, KeyboardHook, MouseEvent, PlatformError};
+use crate::platform::linux::{
+    active_layout, rdev_key_from_x11, rdev_mouse_event, wait_readable, KeyTranslator,
+};
+use crate::platform::rdev_common::is_modifier;
+use crate::platform::uinput::VIRTUAL_KEYBOARD_NAME;
+use crate::platform::xkb::EVDEV_OFFSET;
+
+/// Where the kernel exposes evdev device nodes.
+const DEFAULT_DEVICE_DIR: &str = "/dev/input";
+
+/// Where sysfs describes the capabilities of each input device.
+const DEFAULT_SYSFS_DIR: &str = "/sys/class/input";
+
+/// How often the device directory is rescanned for hot-plugged keyboards.
+const HOTPLUG_SCAN_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How often the translation thread checks whether the hook was stopped.
+const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// `EV_KEY` from `linux/input-event-codes.h`.
+const EV_KEY: u16 = 1;
+
+/// `BTN_LEFT`, `BTN_RIGHT` and `BTN_MIDDLE`; the other mouse buttons run
+/// up to `BTN_TASK` (0x117).
+const BTN_LEFT: u16 = 0x110;
+const BTN_RIGHT: u16 = 0x111;
+const BTN_MIDDLE: u16 = 0x112;
+const BTN_TASK: u16 = 0x117;
+
+/// Keys every typing keyboard has; devices lacking any of them (power
+/// buttons, lid switches, media remotes) are ignored.
+const REQUIRED_KEYS: [usize; 4] = [
+    28, // KEY_ENTER
+    30, // KEY_A
+    44, // KEY_Z
+    57, // KEY_SPACE
+];
+
+/// Size of a `struct input_event`: a `timeval` (two C longs) followed by
+/// a `u16` type, a `u16` code and an `i32` value.
+pub const INPUT_EVENT_SIZE: usize = 2 * std::mem::size_of::<c_long>() + 8;
+
+// ---------------------------------------------------------------------------
+// Input events
+// ---------------------------------------------------------------------------
+
+/// A raw evdev `struct input_event`, without its timestamp.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InputEvent {
+    pub event_type: u16,
+    pub code: u16,
+    pub value: i32,
+}
+
+impl InputEvent {
+    /// An `EV_KEY` event: `value` is 0 for release, 1 for press and 2 for
+    /// autorepeat.
+    pub fn key(code: u16, value: i32) -> Self {
+        Self {
+            event_type: EV_KEY,
+            code,
+            value,
+        }
+    }
+
+    /// Decodes one record in native byte order. Returns `None` if `bytes`
+    /// is not exactly [`INPUT_EVENT_SIZE`] long.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != INPUT_EVENT_SIZE {
+            return None;
+        }
+        let body = &bytes[INPUT_EVENT_SIZE - 8..];
+        Some(Self {
+            event_type: u16::from_ne_bytes([body[0], body[1]]),
+            code: u16::from_ne_bytes([body[2], body[3]]),
+            value: i32::from_ne_bytes([body[4], body[5], body[6], body[7]]),
+        })
+    }
+
+    /// Encodes the event as a record with a zeroed timestamp, the inverse
+    /// of [`InputEvent::from_bytes`].
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![0u8; INPUT_EVENT_SIZE - 8];
+        bytes.extend_from_slice(&self.event_type.to_ne_bytes());
+        bytes.extend_from_slice(&self.code.to_ne_bytes());
+        bytes.extend_from_slice(&self.value.to_ne_bytes());
+        bytes
+    }
+
+    /// Converts a key event into the equivalent rdev event so it can go
+    /// through the same [`KeyTranslator`] as the X11 hook. Mouse buttons
+    /// become rdev button events. Non-key events and autorepeats of
+    /// modifier keys produce `None`.
+    fn to_rdev(self) -> Option<rdev::EventType> {
+        if self.event_type != EV_KEY {
+            return None;
+        }
+        if (BTN_LEFT..=BTN_TASK).contains(&self.code) {
+            let button = match self.code {
+                BTN_LEFT => rdev::Button::Left,
+                BTN_RIGHT => rdev::Button::Right,
+                BTN_MIDDLE => rdev::Button::Middle,
+                code => rdev::Button::Unknown((code - BTN_LEFT) as u8),
+            };
+            return match self.value {
+                0 => Some(rdev::EventType::ButtonRelease(button)),
+                1 => Some(rdev::EventType::ButtonPress(button)),
+                _ => None,
+            };
+        }
+        let key = rdev_key_from_x11(u32::from(self.code) + EVDEV_OFFSET);
+        match self.value {
+            0 => Some(rdev::EventType::KeyRelease(key)),
+            1 => Some(rdev::EventType::KeyPress(key)),
+            2 if !is_modifier(&key) => Some(rdev::EventType::KeyPress(key)),
+            _ => None,
+        }
+    }
+}
+
+/// Decodes a recorded evdev stream. A trailing partial record is ignored.
+pub fn decode_events(bytes: &[u8]) -> Vec<InputEvent> {
+    bytes
+        .chunks_exact(INPUT_EVENT_SIZE)
+        .filter_map(InputEvent::from_bytes)
+        .collect()
+}
+
+// ---------------------------------------------------------------------------
+// Device discovery
+// ---------------------------------------------------------------------------
+
+/// Returns true if a sysfs `capabilities/key` bitmap includes the keys of a
+/// typing keyboard.
+///
+/// The bitmap is a list of hex words, most significant first, each the
+/// width of a C long, e.g. `"1000000000007 ff9f207ac14057ff febeffdfffefffff fffffffffffffffe"`.
+pub fn is_keyboard_bitmap(bitmap: &str) -> bool {
+    bitmap_has_keys(bitmap, &REQUIRED_KEYS)
+}
+
+/// Returns true if a sysfs `capabilities/key` bitmap includes a left mouse
+/// button, as mice, touchpads and trackpoints have.
+pub fn is_pointer_bitmap(bitmap: &str) -> bool {
+    bitmap_has_keys(bitmap, &[usize::from(BTN_LEFT)])
+}
+
+fn bitmap_has_keys(bitmap: &str, keys: &[usize]) -> bool {
+    let word_bits = 8 * std::mem::size_of::<c_long>();
+    let words: Option<Vec<u64>> = bitmap
+        .split_whitespace()
+        .rev()
+        .map(|w| u64::from_str_radix(w, 16).ok())
+        .collect();
+    let words = match words {
+        Some(words) => words,
+        None => return false,
+    };
+    keys.iter().all(|&key| {
+        words
+            .get(key / word_bits)
+            .is_some_and(|word| (word >> (key % word_bits)) & 1 == 1)
+    })
+}
+
+/// Lists the `event*` nodes in `device_dir` whose sysfs capabilities mark
+/// them as keyboards, in name order. MuttonText's own uinput device is
+/// skipped.
+fn scan_keyboards(device_dir: &Path, sysfs_dir: &Path) -> Vec<PathBuf> {
+    scan_devices(device_dir, sysfs_dir, is_keyboard_bitmap)
+}
+
+/// Lists the `event*` nodes in `device_dir` whose sysfs capabilities mark
+/// them as pointing devices with buttons, in name order.
+fn scan_pointers(device_dir: &Path, sysfs_dir: &Path) -> Vec<PathBuf> {
+    scan_devices(device_dir, sysfs_dir, is_pointer_bitmap)
+}
+
+/// Lists the `event*` nodes in `device_dir` whose sysfs key bitmap
+/// satisfies `wanted`, in name order, skipping our own uinput device.
+fn scan_devices(device_dir: &Path, sysfs_dir: &Path, wanted: fn(&str) -> bool) -> Vec<PathBuf> {
+    let entries = match fs::read_dir(device_dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            tracing::debug!("Cannot list {}: {}", device_dir.display(), e);
+            return Vec::new();
+        }
+    };
+    let mut devices: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with("event"))
+        .filter(|entry| {
+            let device = sysfs_dir.join(entry.file_name()).join("device");
+            let is_wanted = fs::read_to_string(device.join("capabilities/key"))
+                .is_ok_and(|bitmap| wanted(&bitmap));
+            // Our own virtual keyboard's output must not be read back.
+            let is_own = fs::read_to_string(device.join("name"))
+                .is_ok_and(|name| name.trim() == VIRTUAL_KEYBOARD_NAME);
+            is_wanted && !is_own
+        })
+        .map(|entry| entry.path())
+        .collect();
+    devices.sort();
+    devices
+}
+
+/// A pipe that wakes every thread polling it once [`Wakeup::wake`] has been
+/// called. The byte written is never read, so it keeps waking later polls.
+struct Wakeup {
+    read: OwnedFd,
+    write: OwnedFd,
+}
+
+impl Wakeup {
+    fn new() -> io::Result<Self> {
+        let mut fds = [0 as RawFd; 2];
+        // SAFETY: `fds` has room for the two descriptors pipe2 writes.
+        if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC | libc::O_NONBLOCK) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: pipe2 succeeded, so both descriptors are open and ours.
+        let (read, write) = unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) };
+        Ok(Self { read, write })
+    }
+
+    fn wake(&self) {
+        // SAFETY: writes one byte from a live buffer to our own pipe. A full
+        // pipe already wakes its pollers, so the result can be ignored.
+        unsafe { libc::write(self.write.as_raw_fd(), [1u8].as_ptr().cast(), 1) };
+    }
+
+    /// Waits up to `timeout` and returns true if the pipe was woken.
+    fn wait(&self, timeout: Duration) -> bool {
+        wait_readable(self.read.as_raw_fd(), timeout)
+    }
+
+    /// Blocks until `fd` is readable (or hung up) and returns true, or
+    /// returns false once the pipe is woken.
+    fn wait_for(&self, fd: RawFd) -> bool {
+        let mut fds = [
+            libc::pollfd {
+                fd,
+                events: libc::POLLIN,
+                revents: 0,
+            },
+            libc::pollfd {
+                fd: self.read.as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            },
+        ];
+        loop {
+            // SAFETY: `fds` is a valid array of two pollfds for the call.
+            let ready = unsafe { libc::poll(fds.as_mut_ptr(), 2, -1) };
+            if ready < 0 && io::Error::last_os_error().kind() == ErrorKind::Interrupted {
+                continue;
+            }
+            return ready > 0 && fds[1].revents == 0;
+        }
+    }
+}
+
+/// State shared by the threads of one run of the hook.
+struct HookRun {
+    /// Set by `stop()`; events read afterwards are dropped.
+    shutdown: AtomicBool,
+    /// Woken by `stop()` so readers blocked in `poll` exit at once.
+    wakeup: Wakeup,
+    /// Devices being read, so the hotplug scan skips them.
+    open: Mutex<HashSet<PathBuf>>,
+    /// Reader threads, joined by `stop()`.
+    readers: Mutex<Vec<JoinHandle<()>>>,
+}
+
+impl HookRun {
+    fn new() -> io::Result<Self> {
+        Ok(Self {
+            shutdown: AtomicBool::new(false),
+            wakeup: Wakeup::new()?,
+            open: Mutex::new(HashSet::new()),
+            readers: Mutex::new(Vec::new()),
+        })
+    }
+
+    fn is_shut_down(&self) -> bool {
+        self.shutdown.load(Ordering::SeqCst)
+    }
+
+    fn shut_down(&self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+        self.wakeup.wake();
+    }
+
+    /// Joins the readers spawned so far. Call after `shut_down`, once
+    /// nothing spawns new ones.
+    fn join_readers(&self) {
+        let readers = match self.readers.lock() {
+            Ok(mut readers) => std::mem::take(&mut *readers),
+            Err(_) => return,
+        };
+        for reader in readers {
+            if reader.join().is_err() {
+                tracing::warn!("Evdev reader thread panicked");
+            }
+        }
+    }
+}
+
+/// Reads events from one device until it disappears, the recording ends or
+/// the hook is stopped.
+///
+/// The device is opened non-blocking and polled together with the run's
+/// wakeup pipe, so `stop()` ends the reader without waiting for a key. A
+/// removed device fails its next read and is dropped from `open` so the
+/// hotplug scan can pick it up again if it returns. Reaching the end of a
+/// recorded stream keeps it in `open` so it is not replayed.
+fn read_device(path: PathBuf, mut file: File, tx: Sender<InputEvent>, run: Arc<HookRun>) {
+    let mut record = [0u8; INPUT_EVENT_SIZE];
+    let mut filled = 0;
+    while run.wakeup.wait_for(file.as_raw_fd()) {
+        match file.read(&mut record[filled..]) {
+            Ok(0) => return,
+            Ok(read) => filled += read,
+            Err(e) if matches!(e.kind(), ErrorKind::Interrupted | ErrorKind::WouldBlock) => {
+                continue
+            }
+            Err(e) => {
+                tracing::info!("Keyboard {} went away: {}", path.display(), e);
+                if let Ok(mut open) = run.open.lock() {
+                    open.remove(&path);
+                }
+                return;
+            }
+        }
+        if filled < INPUT_EVENT_SIZE {
+            continue;
+        }
+        filled = 0;
+        if run.is_shut_down() {
+            return;
+        }
+        if let Some(event) = InputEvent::from_bytes(&record) {
+            if tx.send(event).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+/// Opens every device in `devices` that is not already being read and
+/// spawns a reader thread for it. Returns how many devices were refused
+/// for lack of permission.
+fn open_devices(devices: &[PathBuf], tx: &Sender<InputEvent>, run: &Arc<HookRun>) -> usize {
+    let mut denied = 0;
+    for path in devices {
+        {
+            let open = match run.open.lock() {
+                Ok(open) => open,
+                Err(_) => return denied,
+            };
+            if open.contains(path) {
+                continue;
+            }
+        }
+        let opened = OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(path);
+        let file = match opened {
+            Ok(file) => file,
+            Err(e) => {
+                if e.kind() == ErrorKind::PermissionDenied {
+                    denied += 1;
+                }
+                tracing::debug!("Cannot open {}: {}", path.display(), e);
+                continue;
+            }
+        };
+        tracing::info!("Reading input events from {}", path.display());
+        if let Ok(mut open) = run.open.lock() {
+            open.insert(path.clone());
+        }
+        let (path, tx, reader_run) = (path.clone(), tx.clone(), run.clone());
+        let spawned = thread::Builder::new()
+            .name("muttontext-evdev-reader".into())
+            .spawn(move || read_device(path, file, tx, reader_run));
+        match spawned {
+            Ok(reader) => {
+                if let Ok(mut readers) = run.readers.lock() {
+                    readers.push(reader);
+                }
+            }
+            Err(e) => tracing::error!("Failed to spawn evdev reader: {}", e),
+        }
+    }
+    denied
+}
+
+// ---------------------------------------------------------------------------
+// EvdevKeyboardHook
+// ---------------------------------------------------------------------------
+
+/// Keyboard hook that reads evdev devices directly.
+///
+/// Requires read access to `/dev/input/event*`, normally granted by
+/// membership of the `input` group. Events from all keyboards are merged
+/// and translated through a single XKB state; the keymap comes from
+/// the X server when an X display exists, otherwise from `systemd-localed`,
+/// and follows layout changes (see [`crate::platform::xkb::ActiveLayout`]).
+///
+/// Unlike [`crate::platform::linux::LinuxKeyboardHook`], the hook can be
+/// restarted after `stop()`. Stopping joins the device readers and the
+/// hotplug scanner; the translation thread is not joined because it may be
+/// waiting for a lock the caller of `stop()` holds, and it exits on its
+/// own once the readers are gone.
+pub struct EvdevKeyboardHook {
+    device_dir: PathBuf,
+    sysfs_dir: PathBuf,
+    running: Arc<AtomicBool>,
+    /// Threads and devices of the current run.
+    run: Option<Arc<HookRun>>,
+    hotplug: Option<JoinHandle<()>>,
+    /// Receives clicks; pointer devices are only opened when set.
+    mouse_callback: Option<Arc<dyn Fn(MouseEvent) + Send + Sync>>,
+}
+
+impl EvdevKeyboardHook {
+    pub fn new() -> Self {
+        Self::with_paths(DEFAULT_DEVICE_DIR, DEFAULT_SYSFS_DIR)
+    }
+
+    /// Creates a hook that reads devices from `device_dir` and their
+    /// capabilities from `sysfs_dir`, e.g. to replay recorded streams.
+    pub fn with_paths(device_dir: impl Into<PathBuf>, sysfs_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            device_dir: device_dir.into(),
+            sysfs_dir: sysfs_dir.into(),
+            running: Arc::new(AtomicBool::new(false)),
+            run: None,
+            hotplug: None,
+            mouse_callback: None,
+        }
+    }
+
+    /// Ends the current run and joins its readers and hotplug scanner.
+    fn finish_run(&mut self) {
+        let run = match self.run.take() {
+            Some(run) => run,
+            None => return,
+        };
+        run.shut_down();
+        if let Some(hotplug) = self.hotplug.take() {
+            if hotplug.join().is_err() {
+                tracing::warn!("Evdev hotplug thread panicked");
+            }
+        }
+        run.join_readers();
+    }
+}
+
+impl Default for EvdevKeyboardHook {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl KeyboardHook for EvdevKeyboardHook {
+    fn start(
+        &mut self,
+        callback: Box<dyn Fn(KeyEvent) + Send + Sync>,
+    ) -> Result<(), PlatformError> {
+        if self.running.load(Ordering::SeqCst) {
+            return Err(PlatformError::AlreadyRunning);
+        }
+
+        let run = Arc::new(HookRun::new().map_err(|e| PlatformError::Internal(e.to_string()))?);
+        let (tx, rx) = mpsc::channel::<InputEvent>();
+
+        let keyboards = scan_keyboards(&self.device_dir, &self.sysfs_dir);
+        let denied = open_devices(&keyboards, &tx, &run);
+        if !keyboards.is_empty() && denied == keyboards.len() {
+            run.shut_down();
+            return Err(PlatformError::PermissionDenied(format!(
+                "cannot read keyboards in {}; add your user to the 'input' group",
+                self.device_dir.display()
+            )));
+        }
+        if keyboards.is_empty() {
+            tracing::warn!(
+                "No keyboards found in {}; waiting for one to be plugged in",
+                self.device_dir.display()
+            );
+        }
+        let with_pointers = self.mouse_callback.is_some();
+        if with_pointers {
+            open_devices(&scan_pointers(&self.device_dir, &self.sysfs_dir), &tx, &run);
+        }
+
+        let thread_run = run.clone();
+        let mouse_callback = self.mouse_callback.clone();
+        let spawned = thread::Builder::new()
+            .name("muttontext-keyboard-hook".into())
+            .spawn(move || {
+                tracing::info!("Evdev keyboard hook thread started");
+                let mut translator = KeyTranslator::following(active_layout());
+                loop {
+                    match rx.recv_timeout(SHUTDOWN_POLL_INTERVAL) {
+                        Ok(event) => {
+                            let rdev_event = match event.to_rdev() {
+                                Some(rdev_event) => rdev_event,
+                                None => continue,
+                            };
+                            let ke = translator.translate(&rdev_event);
+                            if thread_run.is_shut_down() {
+                                break;
+                            }
+                            if let Some(ke) = ke {
+                                callback(ke);
+                            } else if let Some(ref on_mouse) = mouse_callback {
+                                if let Some(me) = rdev_mouse_event(&rdev_event) {
+                                    on_mouse(me);
+                                }
+                            }
+                        }
+                        Err(RecvTimeoutError::Timeout) => {
+                            if thread_run.is_shut_down() {
+                                break;
+                            }
+                        }
+                        Err(RecvTimeoutError::Disconnected) => break,
+                    }
+                }
+                tracing::info!("Evdev keyboard hook thread exited");
+            });
+        if let Err(e) = spawned {
+            run.shut_down();
+            run.join_readers();
+            return Err(PlatformError::Internal(e.to_string()));
+        }
+
+        let (device_dir, sysfs_dir) = (self.device_dir.clone(), self.sysfs_dir.clone());
+        let scan_run = run.clone();
+        let spawned = thread::Builder::new()
+            .name("muttontext-evdev-hotplug".into())
+            .spawn(move || {
+                while !scan_run.wakeup.wait(HOTPLUG_SCAN_INTERVAL) {
+                    open_devices(&scan_keyboards(&device_dir, &sysfs_dir), &tx, &scan_run);
+                    if with_pointers {
+                        open_devices(&scan_pointers(&device_dir, &sysfs_dir), &tx, &scan_run);
+                    }
+                }
+            });
+        let hotplug = match spawned {
+            Ok(hotplug) => hotplug,
+            Err(e) => {
+                run.shut_down();
+                run.join_readers();
+                return Err(PlatformError::Internal(e.to_string()));
+            }
+        };
+
+        self.run = Some(run);
+        self.hotplug = Some(hotplug);
+        self.running.store(true, Ordering::SeqCst);
+        tracing::info!("EvdevKeyboardHook started");
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<(), PlatformError> {
+        if !self.running.load(Ordering::SeqCst) {
+            return Err(PlatformError::NotRunning);
+        }
+        self.running.store(false, Ordering::SeqCst);
+        self.finish_run();
+        tracing::info!("EvdevKeyboardHook stopped");
+        Ok(())
+    }
+
+    fn is_running(&self) -> bool {
+        self.running.load(Ordering::SeqCst)
+    }
+
+    /// Our uinput device is skipped and XTest never reaches the kernel.
+    fn hears_synthetic_input(&self) -> bool {
+        false
+    }
+
+    /// Takes effect on the next `start()`.
+    fn set_mouse_callback(&mut self, callback: Box<dyn Fn(MouseEvent) + Send + Sync>) {
+        self.mouse_callback = Some(Arc::from(callback));
+    }
+}
+
+impl Drop for EvdevKeyboardHook {
+    fn drop(&mut self) {
+        self.finish_run();
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::keyboard_hook::{Key, KeyEventType};
+    use crate::platform::xkb::{XkbNames, XkbTranslator};
+    use std::io::Write;
+    use std::time::Instant;
+
+    // evdev scancodes of the keys used below.
+    const KEY_LEFTSHIFT: u16 = 42;
+    const KEY_LEFTCTRL: u16 = 29;
+    const KEY_Y: u16 = 21;
+    const KEY_A: u16 = 30;
+    const KEY_C: u16 = 46;
+    const KEY_BACKSPACE: u16 = 14;
+
+    /// sysfs key bitmap of a typical 64-bit keyboard.
+    const KEYBOARD_BITMAP: &str =
+        "1000000000007 ff9f207ac14057ff febeffdfffefffff fffffffffffffffe";
+
+    /// sysfs key bitmap of a three-button mouse (BTN_LEFT to BTN_MIDDLE).
+    const MOUSE_BITMAP: &str = "70000 0 0 0 0";
+
+    fn tap(code: u16) -> Vec<InputEvent> {
+        vec![InputEvent::key(code, 1), InputEvent::key(code, 0)]
+    }
+
+    fn record(events: &[InputEvent]) -> Vec<u8> {
+        events.iter().flat_map(|e| e.to_bytes()).collect()
+    }
+
+    fn pressed_keys(xkb: Option<XkbTranslator>, events: &[InputEvent]) -> Vec<KeyEvent> {
+        let mut translator = KeyTranslator::new(xkb);
+        events
+            .iter()
+            .filter_map(|e| e.to_rdev())
+            .filter_map(|e| translator.translate(&e))
+            .filter(|ke| ke.event_type == KeyEventType::Press)
+            .collect()
+    }
+
+    /// Lays out a fake `/dev/input` and `/sys/class/input` pair in `root`.
+    fn add_device(root: &Path, name: &str, bitmap: &str, stream: &[u8]) {
+        let caps = root.join("sys").join(name).join("device/capabilities");
+        fs::create_dir_all(&caps).unwrap();
+        fs::write(caps.join("key"), bitmap).unwrap();
+        fs::create_dir_all(root.join("dev")).unwrap();
+        fs::write(root.join("dev").join(name), stream).unwrap();
+    }
+
+    fn wait_for(received: &Received, count: usize) -> Vec<KeyEvent> {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while received.lock().unwrap().len() < count && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(20));
+        }
+        received.lock().unwrap().clone()
+    }
+
+    type Received = Arc<Mutex<Vec<KeyEvent>>>;
+
+    fn collecting_callback() -> (Received, Box<dyn Fn(KeyEvent) + Send + Sync>) {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let sink = received.clone();
+        (received, Box::new(move |ke| sink.lock().unwrap().push(ke)))
+    }
+
+    // ── Records ──
+
+    #[test]
+    fn test_input_event_roundtrip() {
+        let event = InputEvent::key(KEY_A, 1);
+        let bytes = event.to_bytes();
+        assert_eq!(bytes.len(), INPUT_EVENT_SIZE);
+        assert_eq!(InputEvent::from_bytes(&bytes), Some(event));
+        assert_eq!(InputEvent::from_bytes(&bytes[1..]), None);
+    }
+
+    #[test]
+    fn test_decode_events_ignores_partial_record() {
+        let mut bytes = record(&tap(KEY_A));
+        bytes.extend_from_slice(&[0, 1, 2]);
+        assert_eq!(decode_events(&bytes), tap(KEY_A));
+    }
+
+    // ── Keyboard detection ──
+
+    #[test]
+    fn test_keyboard_bitmap_detected() {
+        assert!(is_keyboard_bitmap(KEYBOARD_BITMAP));
+    }
+
+    #[test]
+    fn test_power_button_bitmap_rejected() {
+        // KEY_POWER (116) only.
+        assert!(!is_keyboard_bitmap("10000000000000 0"));
+        assert!(!is_keyboard_bitmap("0"));
+        assert!(!is_keyboard_bitmap("not hex"));
+    }
+
+    #[test]
+    fn test_pointer_bitmap_detected() {
+        assert!(is_pointer_bitmap(MOUSE_BITMAP));
+        assert!(!is_keyboard_bitmap(MOUSE_BITMAP));
+        assert!(!is_pointer_bitmap(KEYBOARD_BITMAP));
+    }
+
+    #[test]
+    fn test_scan_pointers_finds_only_mice() {
+        let dir = tempfile::tempdir().unwrap();
+        add_device(dir.path(), "event0", KEYBOARD_BITMAP, &[]);
+        add_device(dir.path(), "event4", MOUSE_BITMAP, &[]);
+
+        let found = scan_pointers(&dir.path().join("dev"), &dir.path().join("sys"));
+        assert_eq!(found, vec![dir.path().join("dev/event4")]);
+    }
+
+    #[test]
+    fn test_scan_keyboards_filters_by_capabilities() {
+        let dir = tempfile::tempdir().unwrap();
+        add_device(dir.path(), "event3", KEYBOARD_BITMAP, &[]);
+        add_device(dir.path(), "event1", "10000000000000 0", &[]);
+        add_device(dir.path(), "event0", KEYBOARD_BITMAP, &[]);
+        fs::write(dir.path().join("dev/mice"), b"").unwrap();
+
+        let found = scan_keyboards(&dir.path().join("dev"), &dir.path().join("sys"));
+        assert_eq!(
+            found,
+            vec![dir.path().join("dev/event0"), dir.path().join("dev/event3")]
+        );
+    }
+
+    #[test]
+    fn test_scan_skips_own_virtual_keyboard() {
+        let dir = tempfile::tempdir().unwrap();
+        add_device(dir.path(), "event0", KEYBOARD_BITMAP, &[]);
+        add_device(dir.path(), "event1", KEYBOARD_BITMAP, &[]);
+        fs::write(
+            dir.path().join("sys/event1/device/name"),
+            format!("{}\n", VIRTUAL_KEYBOARD_NAME),
+        )
+        .unwrap();
+
+        let found = scan_keyboards(&dir.path().join("dev"), &dir.path().join("sys"));
+        assert_eq!(found, vec![dir.path().join("dev/event0")]);
+    }
+
+    #[test]
+    fn test_scan_missing_directory_is_empty() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(scan_keyboards(&dir.path().join("nope"), dir.path()).is_empty());
+    }
+
+    // ── Translation ──
+
+    #[test]
+    fn test_recorded_stream_translates_with_us_fallback() {
+        let mut events = tap(KEY_A);
+        events.push(InputEvent::key(KEY_LEFTSHIFT, 1));
+        events.extend(tap(KEY_Y));
+        events.push(InputEvent::key(KEY_LEFTSHIFT, 0));
+        events.extend(tap(KEY_BACKSPACE));
+
+        let keys: Vec<Key> = pressed_keys(None, &decode_events(&record(&events)))
+            .into_iter()
+            .map(|ke| ke.key)
+            .collect();
+        assert_eq!(keys, vec![Key::Char('a'), Key::Char('y'), Key::Backspace]);
+    }
+
+    #[test]
+    #[ignore = "needs the xkeyboard-config data files"]
+    fn test_recorded_stream_translates_with_layout() {
+        let xkb = XkbTranslator::new(&XkbNames::layout("de", "")).expect("de keymap");
+        let mut events = tap(KEY_Y);
+        events.push(InputEvent::key(KEY_LEFTSHIFT, 1));
+        events.extend(tap(KEY_A));
+        events.push(InputEvent::key(KEY_LEFTSHIFT, 0));
+
+        let keys: Vec<Key> = pressed_keys(Some(xkb), &events)
+            .into_iter()
+            .map(|ke| ke.key)
+            .collect();
+        assert_eq!(keys, vec![Key::Char('z'), Key::Char('A')]);
+    }
+
+    #[test]
+    fn test_modifier_state_attached() {
+        let events = vec![
+            InputEvent::key(KEY_LEFTCTRL, 1),
+            InputEvent::key(KEY_C, 1),
+            InputEvent::key(KEY_C, 0),
+            InputEvent::key(KEY_LEFTCTRL, 0),
+        ];
+        let pressed = pressed_keys(None, &events);
+        assert_eq!(pressed.len(), 1);
+        assert!(pressed[0].modifiers.ctrl);
+    }
+
+    #[test]
+    fn test_autorepeat_repeats_keys_but_not_modifiers() {
+        let events = vec![
+            InputEvent::key(KEY_LEFTSHIFT, 1),
+            InputEvent::key(KEY_LEFTSHIFT, 2),
+            InputEvent::key(KEY_LEFTSHIFT, 0),
+            InputEvent::key(KEY_BACKSPACE, 1),
+            InputEvent::key(KEY_BACKSPACE, 2),
+            InputEvent::key(KEY_BACKSPACE, 2),
+            InputEvent::key(KEY_BACKSPACE, 0),
+        ];
+        let keys: Vec<Key> = pressed_keys(None, &events)
+            .into_iter()
+            .map(|ke| ke.key)
+            .collect();
+        assert_eq!(keys, vec![Key::Backspace; 3]);
+    }
+
+    #[test]
+    fn test_non_key_events_ignored() {
+        let syn = InputEvent {
+            event_type: 0,
+            code: 0,
+            value: 0,
+        };
+        assert_eq!(syn.to_rdev(), None);
+    }
+
+    #[test]
+    fn test_mouse_buttons_become_rdev_buttons() {
+        assert_eq!(
+            InputEvent::key(BTN_LEFT, 1).to_rdev(),
+            Some(rdev::EventType::ButtonPress(rdev::Button::Left))
+        );
+        assert_eq!(
+            InputEvent::key(BTN_RIGHT, 0).to_rdev(),
+            Some(rdev::EventType::ButtonRelease(rdev::Button::Right))
+        );
+        assert_eq!(
+            InputEvent::key(0x113, 1).to_rdev(),
+            Some(rdev::EventType::ButtonPress(rdev::Button::Unknown(3)))
+        );
+    }
+
+    // ── Hook ──
+
+    #[test]
+    fn test_hook_replays_recorded_device() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut events = tap(KEY_A);
+        events.extend(tap(KEY_C));
+        add_device(dir.path(), "event0", KEYBOARD_BITMAP, &record(&events));
+
+        let mut hook = EvdevKeyboardHook::with_paths(dir.path().join("dev"), dir.path().join("sys"));
+        let (received, callback) = collecting_callback();
+        hook.start(callback).unwrap();
+        assert!(hook.is_running());
+
+        let presses: Vec<Key> = wait_for(&received, 4)
+            .into_iter()
+            .filter(|ke| ke.event_type == KeyEventType::Press)
+            .map(|ke| ke.key)
+            .collect();
+        assert_eq!(presses, vec![Key::Char('a'), Key::Char('c')]);
+        hook.stop().unwrap();
+        assert!(!hook.is_running());
+    }
+
+    #[test]
+    fn test_hook_forwards_clicks_with_mouse_callback() {
+        let dir = tempfile::tempdir().unwrap();
+        add_device(dir.path(), "event0", KEYBOARD_BITMAP, &record(&tap(KEY_A)));
+        let clicks = vec![
+            InputEvent::key(BTN_LEFT, 1),
+            InputEvent::key(BTN_LEFT, 0),
+            InputEvent::key(BTN_RIGHT, 1),
+            InputEvent::key(BTN_RIGHT, 0),
+        ];
+        add_device(dir.path(), "event4", MOUSE_BITMAP, &record(&clicks));
+
+        let mut hook = EvdevKeyboardHook::with_paths(dir.path().join("dev"), dir.path().join("sys"));
+        let clicked = Arc::new(Mutex::new(0));
+        let counter = clicked.clone();
+        hook.set_mouse_callback(Box::new(move |_| *counter.lock().unwrap() += 1));
+        let (received, callback) = collecting_callback();
+        hook.start(callback).unwrap();
+
+        assert_eq!(wait_for(&received, 2).len(), 2);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while *clicked.lock().unwrap() < 2 && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(20));
+        }
+        hook.stop().unwrap();
+        // Presses count as clicks, releases do not.
+        assert_eq!(*clicked.lock().unwrap(), 2);
+    }
+
+    #[test]
+    fn test_hook_ignores_mice_without_mouse_callback() {
+        let dir = tempfile::tempdir().unwrap();
+        add_device(dir.path(), "event4", MOUSE_BITMAP, &record(&tap(BTN_LEFT)));
+
+        let mut hook = EvdevKeyboardHook::with_paths(dir.path().join("dev"), dir.path().join("sys"));
+        let (_received, callback) = collecting_callback();
+        hook.start(callback).unwrap();
+        let open = hook.run.as_ref().unwrap().open.lock().unwrap().clone();
+        hook.stop().unwrap();
+        assert!(open.is_empty());
+    }
+
+    #[test]
+    fn test_hook_picks_up_hotplugged_keyboard() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("dev")).unwrap();
+        let mut hook = EvdevKeyboardHook::with_paths(dir.path().join("dev"), dir.path().join("sys"));
+        let (received, callback) = collecting_callback();
+        hook.start(callback).unwrap();
+        assert!(received.lock().unwrap().is_empty());
+
+        add_device(dir.path(), "event7", KEYBOARD_BITMAP, &record(&tap(KEY_A)));
+        let events = wait_for(&received, 2);
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].key, Key::Char('a'));
+        hook.stop().unwrap();
+    }
+
+    #[test]
+    fn test_stop_ends_reader_waiting_for_input() {
+        let dir = tempfile::tempdir().unwrap();
+        add_device(dir.path(), "event0", KEYBOARD_BITMAP, &[]);
+        // A FIFO blocks its reader like a real device between key presses.
+        let fifo = dir.path().join("dev/event0");
+        fs::remove_file(&fifo).unwrap();
+        let c_path = std::ffi::CString::new(fifo.to_str().unwrap()).unwrap();
+        assert_eq!(unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) }, 0);
+
+        let mut hook = EvdevKeyboardHook::with_paths(dir.path().join("dev"), dir.path().join("sys"));
+        let (received, callback) = collecting_callback();
+        hook.start(callback).unwrap();
+        let mut device = OpenOptions::new()
+            .write(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(&fifo)
+            .expect("reader has the FIFO open");
+        device.write_all(&record(&tap(KEY_A))).unwrap();
+        assert_eq!(wait_for(&received, 2).len(), 2);
+
+        let started = Instant::now();
+        hook.stop().unwrap();
+        assert!(started.elapsed() < HOTPLUG_SCAN_INTERVAL);
+        // The reader was joined, so nobody holds the read end any more.
+        let err = device.write_all(&record(&tap(KEY_A))).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::BrokenPipe);
+    }
+
+    #[test]
+    fn test_hook_can_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("dev")).unwrap();
+        let mut hook = EvdevKeyboardHook::with_paths(dir.path().join("dev"), dir.path().join("sys"));
+        hook.start(Box::new(|_| {})).unwrap();
+        assert!(matches!(
+            hook.start(Box::new(|_| {})),
+            Err(PlatformError::AlreadyRunning)
+        ));
+        hook.stop().unwrap();
+        assert!(matches!(hook.stop(), Err(PlatformError::NotRunning)));
+        hook.start(Box::new(|_| {})).unwrap();
+        hook.stop().unwrap();
+    }
+}
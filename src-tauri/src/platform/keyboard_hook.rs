@@ -5,6 +5,8 @@
 //! and the `PlatformError` error type.
 
 use std::fmt;
+
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 // ---------------------------------------------------------------------------
@@ -31,7 +33,8 @@ pub enum PlatformError {
 // ---------------------------------------------------------------------------
 
 /// A physical or logical key.
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub enum Key {
     Char(char),
     Backspace,
@@ -77,14 +80,16 @@ impl fmt::Display for Key {
 }
 
 /// Whether a key was pressed or released.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub enum KeyEventType {
     Press,
     Release,
 }
 
 /// Active modifier keys at the time of an event.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Modifiers {
     pub ctrl: bool,
     pub alt: bool,
@@ -158,12 +163,23 @@ pub struct MouseEvent {
     pub timestamp: std::time::Instant,
 }
 
+impl MouseEvent {
+    /// A button press happening now.
+    pub fn click() -> Self {
+        Self {
+            event_type: MouseEventType::Click,
+            timestamp: std::time::Instant::now(),
+        }
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Window info / focus detection
 // ---------------------------------------------------------------------------
 
 /// Information about the currently focused window.
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct WindowInfo {
     pub title: String,
     pub app_name: String,
@@ -198,12 +214,30 @@ pub trait KeyboardHook: Send + Sync {
 
     /// Returns `true` if the hook is currently active.
     fn is_running(&self) -> bool;
+
+    /// Register a callback for mouse button presses, delivered by the same
+    /// listener as key events. Must be called before `start`. Hooks that
+    /// cannot observe the mouse ignore the callback.
+    fn set_mouse_callback(&mut self, _callback: Box<dyn Fn(MouseEvent) + Send + Sync>) {}
+
+    /// Returns `true` if keys sent by MuttonText's own output backends are
+    /// delivered back to the callback. Hooks that read the hardware directly
+    /// never see synthetic input.
+    fn hears_synthetic_input(&self) -> bool {
+        true
+    }
 }
 
 /// Detects which window currently has focus.
 pub trait FocusDetector: Send + Sync {
     /// Returns information about the currently active/focused window.
     fn get_active_window_info(&self) -> Result<WindowInfo, PlatformError>;
+
+    /// Registers a callback invoked when the focused window changes.
+    ///
+    /// Detectors that cannot observe focus changes ignore the callback;
+    /// callers then rely on polling `get_active_window_info`.
+    fn on_focus_change(&mut self, _callback: Box<dyn Fn(WindowInfo) + Send + Sync>) {}
 }
 
 // ---------------------------------------------------------------------------
@@ -275,6 +309,20 @@ mod tests {
         assert_eq!(info.process_id, None);
     }
 
+    #[test]
+    fn test_key_serialization() {
+        assert_eq!(serde_json::to_string(&Key::Char('a')).unwrap(), r#"{"char":"a"}"#);
+        assert_eq!(serde_json::to_string(&Key::Backspace).unwrap(), r#""backspace""#);
+        let key: Key = serde_json::from_str(r#"{"f":5}"#).unwrap();
+        assert_eq!(key, Key::F(5));
+    }
+
+    #[test]
+    fn test_modifiers_deserialize_partial() {
+        let m: Modifiers = serde_json::from_str(r#"{"ctrl":true}"#).unwrap();
+        assert_eq!(m, Modifiers { ctrl: true, ..Default::default() });
+    }
+
     #[test]
     fn test_platform_error_display() {
         let e = PlatformError::AlreadyRunning;
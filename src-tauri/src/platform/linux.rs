@@ -1,29 +1,43 @@
 //! Linux (X11/Wayland) keyboard hook and focus detection.
 //!
-//! Uses the `rdev` crate for system-wide keyboard listening.
-//! Focus detection uses `xdotool` and `xprop` commands to query X11 windows.
+//! Uses the `rdev` crate for system-wide keyboard and mouse-button
+//! listening. Keys are translated through the active XKB keymap (see
+//! [`crate::platform::xkb`]) so non-US layouts, dead keys and compose
+//! sequences produce the characters the user actually typed.
+//! Focus detection follows `_NET_ACTIVE_WINDOW` over a dedicated X11
+//! connection, or the compositor's IPC on sway, Hyprland and KWin (see
+//! [`crate::platform::wayland_focus`]), and caches the active window's
+//! details.
 //!
-//! # Wayland Limitations
+//! # Wayland
 //!
 //! Wayland's security model restricts global keyboard listening.
-//! Under pure Wayland (no XWayland), `rdev` may not receive events
-//! unless the compositor provides a protocol like
-//! `zwp_input_method_v2` or `wlr-input-inhibitor`. Users on Wayland
-//! may need to run MuttonText under XWayland or grant special
-//! compositor permissions. This is a known limitation shared by all
-//! text expanders on Wayland.
+//! Under XWayland, `rdev` still receives events through the X11
+//! compatibility layer. Under pure Wayland (no XWayland) the engine
+//! switches to [`crate::platform::evdev::EvdevKeyboardHook`], which reads
+//! `/dev/input` directly and requires the user to be in the `input` group.
 
 #![cfg(target_os = "linux")]
 
+use std::ffi::CString;
+use std::io;
+use std::os::raw::{c_int, c_long, c_uchar, c_ulong};
+use std::os::unix::io::RawFd;
+use std::path::{Path, PathBuf};
+use std::ptr;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex, OnceLock};
 use std::thread;
+use std::time::{Duration, Instant};
+
+use x11::xlib;
 
 use crate::platform::keyboard_hook::{
-    FocusDetector, KeyEvent, KeyEventType, KeyboardHook, Modifiers, PlatformError,
-    WindowInfo,
+    FocusDetector, KeyEvent, KeyEventType, KeyboardHook, MouseEvent, PlatformError, WindowInfo,
 };
-use crate::platform::rdev_common::{is_modifier, rdev_key_to_key};
+use crate::platform::rdev_common::{rdev_key_to_key, ModifierTracker};
+use crate::platform::wayland_focus;
+use crate::platform::xkb::{self, ActiveLayout, XkbNames, XkbTranslator};
 
 // ---------------------------------------------------------------------------
 // LinuxKeyboardHook
@@ -41,6 +55,9 @@ pub struct LinuxKeyboardHook {
     /// Track if hook was ever started (even if later stopped).
     /// rdev::listen cannot be cleanly stopped and restarted.
     started_once: AtomicBool,
+    /// Receives button presses from the same `rdev::listen` call; rdev
+    /// only supports one listener per process.
+    mouse_callback: Option<Arc<dyn Fn(MouseEvent) + Send + Sync>>,
 }
 
 impl LinuxKeyboardHook {
@@ -48,8 +65,236 @@ impl LinuxKeyboardHook {
         Self {
             running: Arc::new(AtomicBool::new(false)),
             started_once: AtomicBool::new(false),
+            mouse_callback: None,
+        }
+    }
+}
+
+/// Map an rdev event to a mouse event the input manager cares about.
+/// Only button presses count; releases, motion and wheel scrolling leave
+/// the caret where it was.
+pub(crate) fn rdev_mouse_event(event: &rdev::EventType) -> Option<MouseEvent> {
+    match event {
+        rdev::EventType::ButtonPress(_) => Some(MouseEvent::click()),
+        _ => None,
+    }
+}
+
+/// X11 keycodes of the keys rdev knows about, matching rdev's own keycode
+/// table on Linux.
+const X11_KEYCODES: &[(rdev::Key, u32)] = {
+    use rdev::Key as RK;
+    &[
+        (RK::Escape, 9),
+        (RK::Num1, 10),
+        (RK::Num2, 11),
+        (RK::Num3, 12),
+        (RK::Num4, 13),
+        (RK::Num5, 14),
+        (RK::Num6, 15),
+        (RK::Num7, 16),
+        (RK::Num8, 17),
+        (RK::Num9, 18),
+        (RK::Num0, 19),
+        (RK::Minus, 20),
+        (RK::Equal, 21),
+        (RK::Backspace, 22),
+        (RK::Tab, 23),
+        (RK::KeyQ, 24),
+        (RK::KeyW, 25),
+        (RK::KeyE, 26),
+        (RK::KeyR, 27),
+        (RK::KeyT, 28),
+        (RK::KeyY, 29),
+        (RK::KeyU, 30),
+        (RK::KeyI, 31),
+        (RK::KeyO, 32),
+        (RK::KeyP, 33),
+        (RK::LeftBracket, 34),
+        (RK::RightBracket, 35),
+        (RK::Return, 36),
+        (RK::ControlLeft, 37),
+        (RK::KeyA, 38),
+        (RK::KeyS, 39),
+        (RK::KeyD, 40),
+        (RK::KeyF, 41),
+        (RK::KeyG, 42),
+        (RK::KeyH, 43),
+        (RK::KeyJ, 44),
+        (RK::KeyK, 45),
+        (RK::KeyL, 46),
+        (RK::SemiColon, 47),
+        (RK::Quote, 48),
+        (RK::BackQuote, 49),
+        (RK::ShiftLeft, 50),
+        (RK::BackSlash, 51),
+        (RK::KeyZ, 52),
+        (RK::KeyX, 53),
+        (RK::KeyC, 54),
+        (RK::KeyV, 55),
+        (RK::KeyB, 56),
+        (RK::KeyN, 57),
+        (RK::KeyM, 58),
+        (RK::Comma, 59),
+        (RK::Dot, 60),
+        (RK::Slash, 61),
+        (RK::ShiftRight, 62),
+        (RK::KpMultiply, 63),
+        (RK::Alt, 64),
+        (RK::Space, 65),
+        (RK::CapsLock, 66),
+        (RK::F1, 67),
+        (RK::F2, 68),
+        (RK::F3, 69),
+        (RK::F4, 70),
+        (RK::F5, 71),
+        (RK::F6, 72),
+        (RK::F7, 73),
+        (RK::F8, 74),
+        (RK::F9, 75),
+        (RK::F10, 76),
+        (RK::NumLock, 77),
+        (RK::ScrollLock, 78),
+        (RK::Kp7, 79),
+        (RK::Kp8, 80),
+        (RK::Kp9, 81),
+        (RK::KpMinus, 82),
+        (RK::Kp4, 83),
+        (RK::Kp5, 84),
+        (RK::Kp6, 85),
+        (RK::KpPlus, 86),
+        (RK::Kp1, 87),
+        (RK::Kp2, 88),
+        (RK::Kp3, 89),
+        (RK::Kp0, 90),
+        (RK::KpDelete, 91),
+        (RK::IntlBackslash, 94),
+        (RK::F11, 95),
+        (RK::F12, 96),
+        (RK::KpReturn, 104),
+        (RK::ControlRight, 105),
+        (RK::KpDivide, 106),
+        (RK::PrintScreen, 107),
+        (RK::AltGr, 108),
+        (RK::Home, 110),
+        (RK::UpArrow, 111),
+        (RK::PageUp, 112),
+        (RK::LeftArrow, 113),
+        (RK::RightArrow, 114),
+        (RK::End, 115),
+        (RK::DownArrow, 116),
+        (RK::PageDown, 117),
+        (RK::Insert, 118),
+        (RK::Delete, 119),
+        (RK::Pause, 127),
+        (RK::MetaLeft, 133),
+        (RK::MetaRight, 134),
+    ]
+};
+
+/// Returns the X11 keycode for an rdev key, the inverse of rdev's own
+/// keycode table on Linux.
+fn x11_keycode(key: &rdev::Key) -> Option<u32> {
+    if let rdev::Key::Unknown(code) = key {
+        return Some(*code);
+    }
+    X11_KEYCODES
+        .iter()
+        .find(|(k, _)| k == key)
+        .map(|&(_, code)| code)
+}
+
+/// Returns the rdev key for an X11 keycode. Codes rdev has no variant for
+/// are reported as `Unknown`.
+pub(crate) fn rdev_key_from_x11(code: u32) -> rdev::Key {
+    X11_KEYCODES
+        .iter()
+        .find(|&&(_, c)| c == code)
+        .map(|&(k, _)| k)
+        .unwrap_or(rdev::Key::Unknown(code))
+}
+
+/// Converts rdev events into `KeyEvent`s.
+///
+/// rdev reports neither modifier state nor layout-aware characters, so
+/// modifiers are tracked here and, when an XKB keymap is available, key
+/// presses are translated through it. Without a keymap, keys are mapped
+/// assuming a US layout. The evdev hook feeds its events through the same
+/// translator.
+pub(crate) struct KeyTranslator {
+    modifiers: ModifierTracker,
+    xkb: Option<XkbTranslator>,
+    /// The layout being followed and the generation `xkb` was built from.
+    layout: Option<(Arc<ActiveLayout>, u64)>,
+}
+
+impl KeyTranslator {
+    pub(crate) fn new(xkb: Option<XkbTranslator>) -> Self {
+        Self {
+            modifiers: ModifierTracker::default(),
+            xkb,
+            layout: None,
         }
     }
+
+    /// Translates through `layout`'s keymap, rebuilding it whenever the
+    /// layout changes.
+    pub(crate) fn following(layout: Arc<ActiveLayout>) -> Self {
+        let generation = layout.generation();
+        let xkb = XkbTranslator::from_layout(&layout)
+            .map_err(|e| tracing::warn!("{}; falling back to US key mapping", e))
+            .ok();
+        Self {
+            modifiers: ModifierTracker::default(),
+            xkb,
+            layout: Some((layout, generation)),
+        }
+    }
+
+    fn refresh_layout(&mut self) {
+        let (layout, seen) = match self.layout.as_mut() {
+            Some(followed) => followed,
+            None => return,
+        };
+        let generation = layout.generation();
+        if generation == *seen {
+            return;
+        }
+        *seen = generation;
+        match XkbTranslator::from_layout(layout) {
+            Ok(xkb) => self.xkb = Some(xkb),
+            Err(e) => tracing::warn!("{}; keeping the previous keymap", e),
+        }
+    }
+
+    /// Translates one rdev event. Bare modifier keys, dead keys, keys in
+    /// the middle of a compose sequence and non-keyboard events produce
+    /// `None`.
+    pub(crate) fn translate(&mut self, event_type: &rdev::EventType) -> Option<KeyEvent> {
+        let (event_type, rdev_key) = match *event_type {
+            rdev::EventType::KeyPress(k) => (KeyEventType::Press, k),
+            rdev::EventType::KeyRelease(k) => (KeyEventType::Release, k),
+            _ => return None, // mouse events go through rdev_mouse_event
+        };
+        self.refresh_layout();
+        let pressed = event_type == KeyEventType::Press;
+        let is_modifier = self.modifiers.update(&rdev_key, pressed);
+
+        // Every key, modifiers included, is fed to the XKB state so that
+        // shift levels, locks and layout switches stay in sync.
+        let key = match (self.xkb.as_mut(), x11_keycode(&rdev_key)) {
+            (Some(xkb), Some(code)) if pressed => xkb.press(code),
+            (Some(xkb), Some(code)) => {
+                xkb.release(code);
+                Some(rdev_key_to_key(&rdev_key))
+            }
+            _ => Some(rdev_key_to_key(&rdev_key)),
+        };
+        if is_modifier {
+            return None; // don't forward bare modifier presses
+        }
+        key.map(|key| KeyEvent::new(key, event_type, self.modifiers.modifiers()))
+    }
 }
 
 impl Default for LinuxKeyboardHook {
@@ -76,31 +321,28 @@ impl KeyboardHook for LinuxKeyboardHook {
         self.started_once.store(true, Ordering::SeqCst);
         let running = self.running.clone();
         let callback: Arc<dyn Fn(KeyEvent) + Send + Sync> = Arc::from(callback);
+        let mouse_callback = self.mouse_callback.clone();
 
         thread::Builder::new()
             .name("muttontext-keyboard-hook".into())
             .spawn(move || {
                 tracing::info!("Linux keyboard hook thread started");
+                let mut translator = KeyTranslator::following(active_layout());
                 // rdev::listen blocks until an error occurs.
                 if let Err(e) = rdev::listen(move |event| {
+                    // Translate even while stopped so modifier and XKB
+                    // state stay in sync.
+                    let ke = translator.translate(&event.event_type);
                     if !running.load(Ordering::SeqCst) {
                         return;
                     }
-                    let (event_type, rdev_key) = match event.event_type {
-                        rdev::EventType::KeyPress(k) => (KeyEventType::Press, k),
-                        rdev::EventType::KeyRelease(k) => (KeyEventType::Release, k),
-                        _ => return, // ignore mouse etc.
-                    };
-                    if is_modifier(&rdev_key) {
-                        return; // don't forward bare modifier presses
+                    if let Some(ke) = ke {
+                        callback(ke);
+                    } else if let Some(ref on_mouse) = mouse_callback {
+                        if let Some(me) = rdev_mouse_event(&event.event_type) {
+                            on_mouse(me);
+                        }
                     }
-                    let key = rdev_key_to_key(&rdev_key);
-                    // NOTE: rdev does not provide modifier state directly;
-                    // a full implementation would track it ourselves. For now
-                    // we pass empty modifiers — the InputManager does not rely
-                    // on modifiers for buffer management.
-                    let ke = KeyEvent::new(key, event_type, Modifiers::default());
-                    callback(ke);
                 }) {
                     tracing::error!("rdev listen error: {:?}", e);
                 }
@@ -126,190 +368,569 @@ impl KeyboardHook for LinuxKeyboardHook {
     fn is_running(&self) -> bool {
         self.running.load(Ordering::SeqCst)
     }
+
+    fn set_mouse_callback(&mut self, callback: Box<dyn Fn(MouseEvent) + Send + Sync>) {
+        self.mouse_callback = Some(Arc::from(callback));
+    }
 }
 
 // ---------------------------------------------------------------------------
 // LinuxFocusDetector
 // ---------------------------------------------------------------------------
 
-/// Focus detector for Linux using X11 tools.
-///
-/// Uses `xdotool` and `xprop` commands to query the active window.
-/// Falls back to "Unknown" if the tools are not available or the
-/// environment is not X11.
-pub struct LinuxFocusDetector;
+/// How long focus watchers block on their connection before re-checking
+/// the shutdown flag.
+pub(crate) const FOCUS_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Delay before a focus watcher reconnects after losing its source.
+const FOCUS_RECONNECT_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Longest property value read from a window (in 32-bit units).
+const MAX_PROPERTY_LENGTH: c_long = 1024;
+
+pub(crate) type FocusCallback = Arc<dyn Fn(WindowInfo) + Send + Sync>;
+
+/// Where the focus detector learns about the active window.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FocusBackend {
+    /// `_NET_ACTIVE_WINDOW` on the X server (or XWayland).
+    X11,
+    /// The sway or i3 IPC socket.
+    Sway(PathBuf),
+    /// Hyprland's request (`.socket.sock`) and event (`.socket2.sock`) sockets.
+    Hyprland { requests: PathBuf, events: PathBuf },
+    /// A KWin script that reports activations over D-Bus.
+    KWin,
+    /// No usable source; focus is reported as "Unknown".
+    Unavailable,
+}
 
-impl LinuxFocusDetector {
-    pub fn new() -> Self {
-        Self
+impl FocusBackend {
+    /// Chooses a backend from the session environment.
+    pub fn detect() -> Self {
+        Self::from_env(|name| std::env::var(name).ok().filter(|value| !value.is_empty()))
     }
 
-    /// Get the active window ID using xdotool.
-    fn get_active_window_id() -> Option<String> {
-        use std::process::Command;
-
-        let output = Command::new("xdotool")
-            .args(["getactivewindow"])
-            .output()
-            .ok()?;
+    /// Chooses a backend using `var` to read environment variables.
+    ///
+    /// Compositor IPC is preferred because under Wayland `_NET_ACTIVE_WINDOW`
+    /// only sees XWayland clients.
+    pub fn from_env(var: impl Fn(&str) -> Option<String>) -> Self {
+        if let Some(socket) = var("SWAYSOCK").or_else(|| var("I3SOCK")) {
+            return Self::Sway(PathBuf::from(socket));
+        }
+        if let Some(signature) = var("HYPRLAND_INSTANCE_SIGNATURE") {
+            let dir = hyprland_socket_dir(var("XDG_RUNTIME_DIR").as_deref(), &signature);
+            return Self::Hyprland {
+                requests: dir.join(".socket.sock"),
+                events: dir.join(".socket2.sock"),
+            };
+        }
+        let wayland = var("WAYLAND_DISPLAY").is_some()
+            || var("XDG_SESSION_TYPE").as_deref() == Some("wayland");
+        let kde = var("XDG_CURRENT_DESKTOP")
+            .is_some_and(|desktop| desktop.split(':').any(|d| d.eq_ignore_ascii_case("KDE")));
+        if wayland && kde {
+            return Self::KWin;
+        }
+        if var("DISPLAY").is_some() {
+            return Self::X11;
+        }
+        Self::Unavailable
+    }
 
-        if output.status.success() {
-            let window_id = String::from_utf8_lossy(&output.stdout).trim().to_string();
-            if !window_id.is_empty() {
-                return Some(window_id);
+    /// Follows focus until `state` is shut down, reconnecting when the
+    /// source goes away.
+    fn run(self, state: Arc<FocusState>) {
+        if self == Self::Unavailable {
+            tracing::warn!("No focus source available; focus detection reports Unknown");
+            return;
+        }
+        while !state.is_shut_down() {
+            let result = match &self {
+                Self::X11 => watch_x11(&state),
+                Self::Sway(socket) => wayland_focus::watch_sway(socket, &state),
+                Self::Hyprland { requests, events } => {
+                    wayland_focus::watch_hyprland(requests, events, &state)
+                }
+                Self::KWin => wayland_focus::watch_kwin(&state),
+                Self::Unavailable => Ok(()),
+            };
+            if let Err(e) = result {
+                tracing::debug!("Focus backend {:?} stopped: {}", self, e);
+            }
+            let deadline = Instant::now() + FOCUS_RECONNECT_INTERVAL;
+            while !state.is_shut_down() && Instant::now() < deadline {
+                thread::sleep(FOCUS_POLL_INTERVAL);
             }
         }
-        None
     }
+}
 
-    /// Get window title using xprop with the window ID.
-    fn get_window_title(window_id: &str) -> Option<String> {
-        use std::process::Command;
-
-        // Validate window_id contains only ASCII digits (no command injection)
-        if !window_id.chars().all(|c| c.is_ascii_digit()) {
-            tracing::warn!("Invalid window_id format: {}", window_id);
-            return None;
+/// Returns Hyprland's socket directory: `$XDG_RUNTIME_DIR/hypr/<signature>`
+/// on current releases, `/tmp/hypr/<signature>` on older ones.
+pub fn hyprland_socket_dir(runtime_dir: Option<&str>, signature: &str) -> PathBuf {
+    if let Some(runtime_dir) = runtime_dir {
+        let dir = Path::new(runtime_dir).join("hypr").join(signature);
+        if dir.is_dir() {
+            return dir;
         }
+    }
+    Path::new("/tmp/hypr").join(signature)
+}
 
-        let output = Command::new("/usr/bin/xprop")
-            .args(["-id", window_id, "_NET_WM_NAME", "WM_NAME"])
-            .output()
-            .ok()?;
+/// Cached focus shared between a detector and its watcher thread.
+pub(crate) struct FocusState {
+    cache: Mutex<Option<WindowInfo>>,
+    on_change: Mutex<Option<FocusCallback>>,
+    shutdown: AtomicBool,
+}
 
-        if output.status.success() {
-            let text = String::from_utf8_lossy(&output.stdout);
+impl FocusState {
+    pub(crate) fn new() -> Self {
+        Self {
+            cache: Mutex::new(None),
+            on_change: Mutex::new(None),
+            shutdown: AtomicBool::new(false),
+        }
+    }
 
-            // xprop returns lines like:
-            // _NET_WM_NAME(UTF8_STRING) = "Window Title"
-            // WM_NAME(STRING) = "Window Title"
-            // We prefer _NET_WM_NAME as it supports UTF-8
-            for line in text.lines() {
-                if line.contains("_NET_WM_NAME") || line.contains("WM_NAME") {
-                    if let Some(title) = Self::extract_quoted_value(line) {
-                        return Some(title);
-                    }
-                }
-            }
+    /// Returns the cached window, or "Unknown" before the first report.
+    pub(crate) fn current(&self) -> WindowInfo {
+        match self.cache.lock() {
+            Ok(cache) => cache.clone().unwrap_or_default(),
+            Err(_) => WindowInfo::default(),
         }
-        None
     }
 
-    /// Get window class (app name) using xprop with the window ID.
-    fn get_window_class(window_id: &str) -> Option<String> {
-        use std::process::Command;
+    pub(crate) fn set_callback(&self, callback: FocusCallback) {
+        if let Ok(mut slot) = self.on_change.lock() {
+            *slot = Some(callback);
+        }
+    }
 
-        // Validate window_id contains only ASCII digits (no command injection)
-        if !window_id.chars().all(|c| c.is_ascii_digit()) {
-            tracing::warn!("Invalid window_id format: {}", window_id);
-            return None;
+    /// Updates the cached window without reporting a focus switch
+    /// (e.g. after a title change).
+    pub(crate) fn store(&self, info: WindowInfo) {
+        if let Ok(mut cache) = self.cache.lock() {
+            *cache = Some(info);
         }
+    }
 
-        let output = Command::new("/usr/bin/xprop")
-            .args(["-id", window_id, "WM_CLASS"])
-            .output()
-            .ok()?;
+    /// Caches `info` and notifies the registered callback of a focus switch.
+    pub(crate) fn switch(&self, info: WindowInfo) {
+        tracing::debug!("Active window changed: {} ({})", info.app_name, info.title);
+        self.store(info.clone());
+        let callback = self.on_change.lock().ok().and_then(|slot| slot.clone());
+        if let Some(callback) = callback {
+            callback(info);
+        }
+    }
 
-        if output.status.success() {
-            let text = String::from_utf8_lossy(&output.stdout);
+    pub(crate) fn is_shut_down(&self) -> bool {
+        self.shutdown.load(Ordering::SeqCst)
+    }
+}
 
-            // xprop returns: WM_CLASS(STRING) = "instance", "class"
-            // We want the class (second value)
-            if let Some(class) = Self::extract_window_class(&text) {
-                return Some(class);
-            }
-        }
-        None
+/// Focus detector for Linux driven by focus change events.
+///
+/// A background thread follows the [`FocusBackend`] chosen for the session
+/// (X11 `_NET_ACTIVE_WINDOW`, sway/i3, Hyprland or KWin) and caches the
+/// active window's title, class and PID whenever it changes.
+/// `get_active_window_info` returns the cached value, so checking for a
+/// match on every keystroke never talks to the display server.
+///
+/// Until the backend reports a window, "Unknown" is returned.
+pub struct LinuxFocusDetector {
+    state: Arc<FocusState>,
+}
+
+impl LinuxFocusDetector {
+    pub fn new() -> Self {
+        let backend = FocusBackend::detect();
+        tracing::info!("Using {:?} focus backend", backend);
+        Self::with_backend(backend)
     }
 
-    /// Get process ID using xprop with the window ID.
-    fn get_window_pid(window_id: &str) -> Option<u32> {
-        use std::process::Command;
+    /// Creates a detector that follows the given backend.
+    pub fn with_backend(backend: FocusBackend) -> Self {
+        let state = Arc::new(FocusState::new());
+        let watcher_state = state.clone();
+        thread::spawn(move || backend.run(watcher_state));
+        Self { state }
+    }
+}
 
-        // Validate window_id contains only ASCII digits (no command injection)
-        if !window_id.chars().all(|c| c.is_ascii_digit()) {
-            tracing::warn!("Invalid window_id format: {}", window_id);
-            return None;
-        }
+impl Default for LinuxFocusDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
-        let output = Command::new("/usr/bin/xprop")
-            .args(["-id", window_id, "_NET_WM_PID"])
-            .output()
-            .ok()?;
+impl Drop for LinuxFocusDetector {
+    fn drop(&mut self) {
+        self.state.shutdown.store(true, Ordering::SeqCst);
+    }
+}
 
-        if output.status.success() {
-            let text = String::from_utf8_lossy(&output.stdout);
+impl FocusDetector for LinuxFocusDetector {
+    fn get_active_window_info(&self) -> Result<WindowInfo, PlatformError> {
+        Ok(self.state.current())
+    }
 
-            // xprop returns: _NET_WM_PID(CARDINAL) = 12345
-            for part in text.split('=') {
-                if let Ok(pid) = part.trim().parse::<u32>() {
-                    return Some(pid);
+    fn on_focus_change(&mut self, callback: Box<dyn Fn(WindowInfo) + Send + Sync>) {
+        self.state.set_callback(Arc::from(callback));
+    }
+}
+
+/// Blocks until `fd` is readable or `timeout` elapses. Returns true if
+/// data (or a hangup) is waiting.
+pub(crate) fn wait_readable(fd: RawFd, timeout: Duration) -> bool {
+    let mut poll_fd = libc::pollfd {
+        fd,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    // SAFETY: `poll_fd` is a valid pollfd for the duration of the call.
+    let ready = unsafe { libc::poll(&mut poll_fd, 1, timeout.as_millis() as c_int) };
+    ready > 0
+}
+
+// ---------------------------------------------------------------------------
+// X11 focus backend
+// ---------------------------------------------------------------------------
+
+/// Atoms the X11 focus watcher reads.
+struct FocusAtoms {
+    net_active_window: xlib::Atom,
+    net_wm_name: xlib::Atom,
+    net_wm_pid: xlib::Atom,
+}
+
+/// Follows `_NET_ACTIVE_WINDOW` on a dedicated X connection.
+fn watch_x11(state: &FocusState) -> io::Result<()> {
+    // SAFETY: the display is opened, used and closed on this thread only.
+    unsafe {
+        let display = xlib::XOpenDisplay(ptr::null());
+        if display.is_null() {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "cannot open X display"));
+        }
+        ignore_errors_on(display);
+
+        let root = xlib::XDefaultRootWindow(display);
+        let atoms = FocusAtoms {
+            net_active_window: intern_atom(display, "_NET_ACTIVE_WINDOW"),
+            net_wm_name: intern_atom(display, "_NET_WM_NAME"),
+            net_wm_pid: intern_atom(display, "_NET_WM_PID"),
+        };
+        xlib::XSelectInput(display, root, xlib::PropertyChangeMask);
+
+        let mut active = active_window(display, root, &atoms);
+        focus_x11_window(state, display, &atoms, 0, active);
+
+        while !state.is_shut_down() {
+            if xlib::XPending(display) == 0 {
+                wait_readable(xlib::XConnectionNumber(display), FOCUS_POLL_INTERVAL);
+                continue;
+            }
+            let mut event: xlib::XEvent = std::mem::zeroed();
+            xlib::XNextEvent(display, &mut event);
+            if event.get_type() != xlib::PropertyNotify {
+                continue;
+            }
+            let property = xlib::XPropertyEvent::from(event);
+
+            if property.window == root && property.atom == atoms.net_active_window {
+                let window = active_window(display, root, &atoms);
+                if window != active {
+                    focus_x11_window(state, display, &atoms, active, window);
+                    active = window;
                 }
+            } else if property.window == active
+                && (property.atom == atoms.net_wm_name || property.atom == xlib::XA_WM_NAME)
+            {
+                // Title changes update the cache but are not focus switches.
+                state.store(window_info(display, active, &atoms));
             }
         }
-        None
+
+        forget_display(display);
+        xlib::XCloseDisplay(display);
     }
+    Ok(())
+}
 
-    /// Extract a quoted string value from xprop output.
-    /// Example: `_NET_WM_NAME(UTF8_STRING) = "Firefox"` → `"Firefox"`
-    fn extract_quoted_value(line: &str) -> Option<String> {
-        // Find the part after '='
-        let after_eq = line.split('=').nth(1)?;
+/// Moves title tracking from `previous` to `window` and reports the switch.
+unsafe fn focus_x11_window(
+    state: &FocusState,
+    display: *mut xlib::Display,
+    atoms: &FocusAtoms,
+    previous: xlib::Window,
+    window: xlib::Window,
+) {
+    if previous != 0 {
+        xlib::XSelectInput(display, previous, xlib::NoEventMask);
+    }
+    let info = if window == 0 {
+        WindowInfo::default()
+    } else {
+        xlib::XSelectInput(display, window, xlib::PropertyChangeMask);
+        window_info(display, window, atoms)
+    };
+    state.switch(info);
+}
 
-        // Find content between quotes
-        let start = after_eq.find('"')? + 1;
-        let end = after_eq[start..].find('"')? + start;
+unsafe fn intern_atom(display: *mut xlib::Display, name: &str) -> xlib::Atom {
+    let name = CString::new(name).expect("atom names contain no NUL");
+    xlib::XInternAtom(display, name.as_ptr(), xlib::False)
+}
 
-        Some(after_eq[start..end].to_string())
+/// Reads a property of the given format (8 or 32) as a vector of items.
+unsafe fn read_property<T: Copy>(
+    display: *mut xlib::Display,
+    window: xlib::Window,
+    property: xlib::Atom,
+    format: c_int,
+) -> Option<Vec<T>> {
+    let mut actual_type: xlib::Atom = 0;
+    let mut actual_format: c_int = 0;
+    let mut item_count: c_ulong = 0;
+    let mut bytes_after: c_ulong = 0;
+    let mut data: *mut c_uchar = ptr::null_mut();
+
+    let status = xlib::XGetWindowProperty(
+        display,
+        window,
+        property,
+        0,
+        MAX_PROPERTY_LENGTH,
+        xlib::False,
+        xlib::AnyPropertyType as xlib::Atom,
+        &mut actual_type,
+        &mut actual_format,
+        &mut item_count,
+        &mut bytes_after,
+        &mut data,
+    );
+    if status != xlib::Success as c_int || data.is_null() {
+        return None;
     }
+    let items = if actual_format == format {
+        Some(std::slice::from_raw_parts(data as *const T, item_count as usize).to_vec())
+    } else {
+        None
+    };
+    xlib::XFree(data as *mut _);
+    items
+}
 
-    /// Extract window class from WM_CLASS output.
-    /// Example: `WM_CLASS(STRING) = "firefox", "Firefox"` → `"Firefox"`
-    fn extract_window_class(text: &str) -> Option<String> {
-        // Find the part after '='
-        let after_eq = text.split('=').nth(1)?;
+/// Returns the window named by the root's `_NET_ACTIVE_WINDOW`, or 0.
+unsafe fn active_window(
+    display: *mut xlib::Display,
+    root: xlib::Window,
+    atoms: &FocusAtoms,
+) -> xlib::Window {
+    // Format-32 properties are returned as C longs.
+    read_property::<c_ulong>(display, root, atoms.net_active_window, 32)
+        .and_then(|ids| ids.first().copied())
+        .unwrap_or(0)
+}
 
-        // Split by comma to get both instance and class
-        let parts: Vec<&str> = after_eq.split(',').collect();
+unsafe fn window_info(
+    display: *mut xlib::Display,
+    window: xlib::Window,
+    atoms: &FocusAtoms,
+) -> WindowInfo {
+    let title = read_property::<u8>(display, window, atoms.net_wm_name, 8)
+        .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+        .or_else(|| {
+            read_property::<u8>(display, window, xlib::XA_WM_NAME, 8)
+                .map(|bytes| latin1_to_string(&bytes))
+        })
+        .filter(|title| !title.is_empty())
+        .unwrap_or_else(|| "Unknown".to_string());
+    let app_name = read_property::<u8>(display, window, xlib::XA_WM_CLASS, 8)
+        .and_then(|bytes| parse_wm_class(&bytes))
+        .unwrap_or_else(|| "Unknown".to_string());
+    let process_id = read_property::<c_ulong>(display, window, atoms.net_wm_pid, 32)
+        .and_then(|pids| pids.first().copied())
+        .and_then(|pid| u32::try_from(pid).ok());
+
+    WindowInfo {
+        title,
+        app_name,
+        process_id,
+    }
+}
 
-        // We want the second part (class), or fall back to first (instance)
-        let class_part = parts.get(1).or_else(|| parts.first())?;
+/// Extracts the class from a `WM_CLASS` value (`"instance\0class\0"`),
+/// falling back to the instance name.
+fn parse_wm_class(data: &[u8]) -> Option<String> {
+    let mut parts = data
+        .split(|&b| b == 0)
+        .filter(|part| !part.is_empty())
+        .map(|part| String::from_utf8_lossy(part).into_owned());
+    let instance = parts.next()?;
+    Some(parts.next().unwrap_or(instance))
+}
 
-        // Extract the quoted value
-        let trimmed = class_part.trim();
-        let start = trimmed.find('"')? + 1;
-        let end = trimmed[start..].find('"')? + start;
+/// Decodes a `STRING` property, which X11 defines as Latin-1.
+fn latin1_to_string(data: &[u8]) -> String {
+    data.iter()
+        .take_while(|&&b| b != 0)
+        .map(|&b| char::from(b))
+        .collect()
+}
 
-        Some(trimmed[start..end].to_string())
-    }
+// ---------------------------------------------------------------------------
+// Keyboard layout
+// ---------------------------------------------------------------------------
+
+/// `XkbUseCoreKbd`: the device ID naming the core keyboard.
+const XKB_USE_CORE_KBD: u32 = 0x0100;
+
+/// Returns the process-wide [`ActiveLayout`], starting the thread that keeps
+/// it current on first use. The thread follows the X server when there is
+/// one and `systemd-localed` otherwise; like the shared virtual keyboard it
+/// lives as long as the process.
+pub(crate) fn active_layout() -> Arc<ActiveLayout> {
+    static LAYOUT: OnceLock<Arc<ActiveLayout>> = OnceLock::new();
+    LAYOUT
+        .get_or_init(|| {
+            let layout = Arc::new(ActiveLayout::query());
+            let followed = layout.clone();
+            let has_display = std::env::var_os("DISPLAY").is_some_and(|d| !d.is_empty());
+            let spawned = thread::Builder::new()
+                .name("muttontext-xkb-layout".into())
+                .spawn(move || {
+                    let result = if has_display {
+                        watch_x11_layout(&followed)
+                    } else {
+                        xkb::watch_localed(&followed)
+                    };
+                    if let Err(e) = result {
+                        tracing::info!("Keyboard layout changes will not be followed: {}", e);
+                    }
+                });
+            if let Err(e) = spawned {
+                tracing::warn!("Failed to spawn keyboard layout watcher: {}", e);
+            }
+            layout
+        })
+        .clone()
 }
 
-impl Default for LinuxFocusDetector {
-    fn default() -> Self {
-        Self::new()
+/// Reads the X server's keymap names from `_XKB_RULES_NAMES` on the root
+/// window, the property `setxkbmap` and desktop settings write.
+pub(crate) fn query_x11_rules_names() -> Option<XkbNames> {
+    // SAFETY: the display is opened, used and closed within this call.
+    unsafe {
+        let display = xlib::XOpenDisplay(ptr::null());
+        if display.is_null() {
+            return None;
+        }
+        let root = xlib::XDefaultRootWindow(display);
+        let rules_names = intern_atom(display, "_XKB_RULES_NAMES");
+        let names = read_property::<u8>(display, root, rules_names, 8)
+            .and_then(|data| XkbNames::parse_rules_names(&data));
+        xlib::XCloseDisplay(display);
+        names
     }
 }
 
-impl FocusDetector for LinuxFocusDetector {
-    fn get_active_window_info(&self) -> Result<WindowInfo, PlatformError> {
-        // Try to get window information via X11 tools
-        if let Some(window_id) = Self::get_active_window_id() {
-            let title = Self::get_window_title(&window_id)
-                .unwrap_or_else(|| "Unknown".to_string());
-            let app_name = Self::get_window_class(&window_id)
-                .unwrap_or_else(|| "Unknown".to_string());
-            let process_id = Self::get_window_pid(&window_id);
+/// Follows the X server's keymap on a dedicated connection: `setxkbmap`
+/// and desktop settings rewrite `_XKB_RULES_NAMES` on the root window,
+/// while switching between the layouts of one keymap only changes the
+/// locked group, which X11 does not announce through properties and is
+/// polled instead.
+fn watch_x11_layout(layout: &ActiveLayout) -> Result<(), PlatformError> {
+    // SAFETY: the display is opened, used and closed on this thread only.
+    unsafe {
+        let display = xlib::XOpenDisplay(ptr::null());
+        if display.is_null() {
+            return Err(PlatformError::Internal("cannot open X display".into()));
+        }
+        ignore_errors_on(display);
+
+        let root = xlib::XDefaultRootWindow(display);
+        let rules_names = intern_atom(display, "_XKB_RULES_NAMES");
+        xlib::XSelectInput(display, root, xlib::PropertyChangeMask);
+        let read_names = || {
+            read_property::<u8>(display, root, rules_names, 8)
+                .and_then(|data| XkbNames::parse_rules_names(&data))
+        };
+        if let Some(names) = read_names() {
+            layout.set_names(names);
+        }
 
-            return Ok(WindowInfo {
-                title,
-                app_name,
-                process_id,
-            });
+        loop {
+            let mut state: xlib::XkbStateRec = std::mem::zeroed();
+            if xlib::XkbGetState(display, XKB_USE_CORE_KBD, &mut state) == xlib::Success as c_int {
+                layout.set_group(state.locked_group.into());
+            }
+            if xlib::XPending(display) == 0 {
+                wait_readable(xlib::XConnectionNumber(display), FOCUS_POLL_INTERVAL);
+                continue;
+            }
+            let mut event: xlib::XEvent = std::mem::zeroed();
+            xlib::XNextEvent(display, &mut event);
+            if event.get_type() != xlib::PropertyNotify {
+                continue;
+            }
+            let property = xlib::XPropertyEvent::from(event);
+            if property.window == root && property.atom == rules_names {
+                if let Some(names) = read_names() {
+                    layout.set_names(names);
+                }
+            }
         }
+    }
+}
 
-        // Fall back to default if X11 tools are not available
-        tracing::debug!("Failed to get active window info; xdotool/xprop may not be installed or X11 not available");
-        Ok(WindowInfo::default())
+// ---------------------------------------------------------------------------
+// X error handling
+// ---------------------------------------------------------------------------
+
+type XErrorHandler = unsafe extern "C" fn(*mut xlib::Display, *mut xlib::XErrorEvent) -> c_int;
+
+/// Displays whose X errors are expected and ignored. The focus watcher
+/// queries windows that may be destroyed at any moment, which Xlib's
+/// default handler would turn into a process exit.
+static QUIET_DISPLAYS: Mutex<Vec<usize>> = Mutex::new(Vec::new());
+
+/// Handler that was installed before ours; errors on other displays (such as
+/// GTK's) are forwarded to it.
+static PREVIOUS_ERROR_HANDLER: OnceLock<Option<XErrorHandler>> = OnceLock::new();
+
+unsafe extern "C" fn quiet_error_handler(
+    display: *mut xlib::Display,
+    event: *mut xlib::XErrorEvent,
+) -> c_int {
+    let quiet = QUIET_DISPLAYS
+        .lock()
+        .map(|displays| displays.contains(&(display as usize)))
+        .unwrap_or(false);
+    if quiet {
+        tracing::trace!("Ignoring X error {} from focus watcher", (*event).error_code);
+        return 0;
+    }
+    match PREVIOUS_ERROR_HANDLER.get().copied().flatten() {
+        Some(previous) => previous(display, event),
+        None => 0,
+    }
+}
+
+/// Installs the chaining error handler (once) and silences `display`.
+unsafe fn ignore_errors_on(display: *mut xlib::Display) {
+    PREVIOUS_ERROR_HANDLER.get_or_init(|| xlib::XSetErrorHandler(Some(quiet_error_handler)));
+    if let Ok(mut displays) = QUIET_DISPLAYS.lock() {
+        displays.push(display as usize);
+    }
+}
+
+fn forget_display(display: *mut xlib::Display) {
+    if let Ok(mut displays) = QUIET_DISPLAYS.lock() {
+        displays.retain(|&d| d != display as usize);
     }
 }
 
@@ -323,8 +944,8 @@ impl FocusDetector for LinuxFocusDetector {
 /// - **NotAvailable**: Not running under Wayland at all (pure X11)
 /// - **XWaylandFallback**: Running under Wayland but XWayland is available;
 ///   rdev can use X11 API via XWayland compatibility layer
-/// - **NativePortal**: Running under native Wayland; would need Portal API
-///   (ashpd) for global input (not yet implemented)
+/// - **NativePortal**: Running under native Wayland without XWayland;
+///   keyboard input is read from evdev devices instead
 /// - **Unknown**: Cannot determine session type
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum WaylandStatus {
@@ -332,7 +953,7 @@ pub enum WaylandStatus {
     NotAvailable,
     /// Running under Wayland but XWayland is available as fallback.
     XWaylandFallback,
-    /// Running under native Wayland (Portal API would be required).
+    /// Running under native Wayland without XWayland (evdev capture).
     NativePortal,
     /// Session type could not be determined.
     Unknown,
@@ -353,7 +974,7 @@ pub enum WaylandStatus {
 ///
 /// - `NotAvailable`: Neither Wayland nor XWayland detected (pure X11)
 /// - `XWaylandFallback`: Wayland is running AND XWayland is available
-/// - `NativePortal`: Wayland is running but no XWayland (needs Portal API)
+/// - `NativePortal`: Wayland is running but no XWayland (evdev capture)
 /// - `Unknown`: Cannot determine session type
 ///
 /// # Compositor-Specific Notes
@@ -373,12 +994,11 @@ pub enum WaylandStatus {
 /// - Global input requires wlr-protocols or Portal API
 /// - rdev works via XWayland if enabled
 ///
-/// ## Future Work: Native Wayland Support
+/// ## Native Wayland (no XWayland)
 ///
-/// For pure Wayland compositors without XWayland, we would need:
-/// - Portal API via `ashpd` crate for global shortcuts
-/// - Text input protocol (`zwp_input_method_v2`) for snippet insertion
-/// - This is deferred to a future milestone
+/// Keyboard input is captured from `/dev/input/event*` by
+/// [`crate::platform::evdev::EvdevKeyboardHook`], which needs read access
+/// to the devices (membership of the `input` group).
 pub fn detect_wayland_status() -> WaylandStatus {
     use std::env;
 
@@ -419,83 +1039,6 @@ pub fn is_xwayland_available() -> bool {
     std::env::var("DISPLAY").is_ok()
 }
 
-// ---------------------------------------------------------------------------
-// Wayland Keyboard Hook (Future Work)
-// ---------------------------------------------------------------------------
-
-/// Placeholder for a future native Wayland keyboard hook.
-///
-/// # Wayland Security Model Limitations
-///
-/// Wayland's security model restricts global input listening for privacy
-/// and security reasons. Unlike X11, applications cannot arbitrarily
-/// capture keyboard events system-wide.
-///
-/// ## Current Status (v1.0)
-///
-/// MuttonText works under Wayland via **XWayland fallback**:
-/// - Most compositors (GNOME, KDE, Sway) ship with XWayland enabled
-/// - `rdev` hooks into the X11 compatibility layer
-/// - This provides full keyboard monitoring functionality
-///
-/// ## Future: Native Wayland Support
-///
-/// For pure Wayland (no XWayland), we would need:
-///
-/// ### Option 1: Portal API (Recommended)
-/// - Use `org.freedesktop.portal.GlobalShortcuts` for trigger shortcuts
-/// - Use `org.freedesktop.portal.InputCapture` (if available) for monitoring
-/// - Implemented via `ashpd` crate
-/// - Requires user permission grant via desktop environment
-///
-/// ### Option 2: Input Method Protocol
-/// - Use `zwp_input_method_v2` Wayland protocol
-/// - Requires compositor support (not universal)
-/// - More complex implementation
-///
-/// ### Option 3: Compositor-Specific Extensions
-/// - GNOME: Shell extensions with custom D-Bus API
-/// - KDE: KWin scripts
-/// - Sway: wlr-input-inhibitor protocol
-/// - Not portable across compositors
-///
-/// ## Testing on Wayland
-///
-/// To test Wayland support, check session type:
-/// ```bash
-/// echo $XDG_SESSION_TYPE  # Should output "wayland"
-/// echo $WAYLAND_DISPLAY   # Should be set (e.g., "wayland-0")
-/// echo $DISPLAY           # If set, XWayland is available
-/// ```
-///
-/// If `$DISPLAY` is not set, you're on pure Wayland and MuttonText
-/// will need the Portal API implementation (future work).
-///
-/// ## Compositor Compatibility
-///
-/// | Compositor | XWayland Default | Native Portal Support |
-/// |------------|------------------|------------------------|
-/// | GNOME      | ✅ Yes           | ⏳ Partial (v43+)      |
-/// | KDE Plasma | ✅ Yes           | ⏳ Partial (v5.27+)    |
-/// | Sway       | ✅ Yes (optional)| ❌ Limited             |
-/// | Hyprland   | ✅ Yes           | ❌ Limited             |
-/// | Cosmic     | ⏳ TBD           | ⏳ TBD                 |
-///
-/// **Recommendation for users:** Ensure XWayland is enabled in your compositor
-/// settings if MuttonText doesn't work out of the box.
-pub struct WaylandKeyboardHook;
-
-impl WaylandKeyboardHook {
-    #[allow(dead_code)]
-    pub fn new() -> Result<Self, PlatformError> {
-        Err(PlatformError::NotSupported(
-            "Wayland global keyboard hooks require compositor-specific protocols \
-             (e.g. zwp_input_method_v2) or Portal API. Use XWayland or the X11 backend."
-                .into(),
-        ))
-    }
-}
-
 // ---------------------------------------------------------------------------
 // Tests (only compiled on Linux)
 // ---------------------------------------------------------------------------
@@ -507,7 +1050,7 @@ mod tests {
     #[test]
     fn test_linux_focus_detector_fallback() {
         // This test verifies that the focus detector returns gracefully
-        // even if xdotool/xprop are not installed (returns default)
+        // even without an X display (returns default)
         let det = LinuxFocusDetector::new();
         let result = det.get_active_window_info();
         assert!(result.is_ok());
@@ -515,52 +1058,227 @@ mod tests {
     }
 
     #[test]
-    fn test_extract_quoted_value() {
-        let line = r#"_NET_WM_NAME(UTF8_STRING) = "Firefox""#;
+    fn test_parse_wm_class() {
+        assert_eq!(parse_wm_class(b"firefox\0Firefox\0"), Some("Firefox".to_string()));
+        assert_eq!(
+            parse_wm_class(b"gnome-terminal-server\0Gnome-terminal\0"),
+            Some("Gnome-terminal".to_string())
+        );
+
+        // Single class value (fallback to instance)
+        assert_eq!(parse_wm_class(b"code\0"), Some("code".to_string()));
+        assert_eq!(parse_wm_class(b""), None);
+    }
+
+    #[test]
+    fn test_latin1_title() {
+        assert_eq!(latin1_to_string(b"Caf\xe9 - Editor\0"), "Café - Editor");
+        assert_eq!(latin1_to_string(b""), "");
+    }
+
+    fn backend_for(vars: &[(&str, &str)]) -> FocusBackend {
+        FocusBackend::from_env(|name| {
+            vars.iter().find(|(key, _)| *key == name).map(|(_, value)| value.to_string())
+        })
+    }
+
+    #[test]
+    fn test_focus_backend_sway_and_i3() {
+        assert_eq!(
+            backend_for(&[("SWAYSOCK", "/run/user/1000/sway-ipc.sock"), ("DISPLAY", ":0")]),
+            FocusBackend::Sway(PathBuf::from("/run/user/1000/sway-ipc.sock"))
+        );
         assert_eq!(
-            LinuxFocusDetector::extract_quoted_value(line),
-            Some("Firefox".to_string())
+            backend_for(&[("I3SOCK", "/tmp/i3-ipc.sock"), ("DISPLAY", ":0")]),
+            FocusBackend::Sway(PathBuf::from("/tmp/i3-ipc.sock"))
         );
+    }
+
+    #[test]
+    fn test_focus_backend_hyprland_socket_dir() {
+        let runtime = tempfile::tempdir().unwrap();
+        let runtime_dir = runtime.path().to_str().unwrap();
 
-        let line = r#"WM_NAME(STRING) = "Terminal - bash""#;
+        // Older releases keep their sockets under /tmp/hypr.
         assert_eq!(
-            LinuxFocusDetector::extract_quoted_value(line),
-            Some("Terminal - bash".to_string())
+            backend_for(&[("HYPRLAND_INSTANCE_SIGNATURE", "abc_123"), ("XDG_RUNTIME_DIR", runtime_dir)]),
+            FocusBackend::Hyprland {
+                requests: PathBuf::from("/tmp/hypr/abc_123/.socket.sock"),
+                events: PathBuf::from("/tmp/hypr/abc_123/.socket2.sock"),
+            }
         );
 
-        let line = "invalid line";
-        assert_eq!(LinuxFocusDetector::extract_quoted_value(line), None);
+        let dir = runtime.path().join("hypr").join("abc_123");
+        std::fs::create_dir_all(&dir).unwrap();
+        assert_eq!(
+            backend_for(&[("HYPRLAND_INSTANCE_SIGNATURE", "abc_123"), ("XDG_RUNTIME_DIR", runtime_dir)]),
+            FocusBackend::Hyprland {
+                requests: dir.join(".socket.sock"),
+                events: dir.join(".socket2.sock"),
+            }
+        );
     }
 
     #[test]
-    fn test_extract_window_class() {
-        let text = r#"WM_CLASS(STRING) = "firefox", "Firefox""#;
+    fn test_focus_backend_kwin_only_on_wayland() {
         assert_eq!(
-            LinuxFocusDetector::extract_window_class(text),
-            Some("Firefox".to_string())
+            backend_for(&[
+                ("XDG_CURRENT_DESKTOP", "KDE"),
+                ("WAYLAND_DISPLAY", "wayland-0"),
+                ("DISPLAY", ":1"),
+            ]),
+            FocusBackend::KWin
         );
-
-        let text = r#"WM_CLASS(STRING) = "gnome-terminal-server", "Gnome-terminal""#;
+        // Plasma on X11 is served by _NET_ACTIVE_WINDOW.
         assert_eq!(
-            LinuxFocusDetector::extract_window_class(text),
-            Some("Gnome-terminal".to_string())
+            backend_for(&[("XDG_CURRENT_DESKTOP", "KDE"), ("DISPLAY", ":0")]),
+            FocusBackend::X11
         );
+    }
 
-        // Single class value (fallback to instance)
-        let text = r#"WM_CLASS(STRING) = "code""#;
+    #[test]
+    fn test_focus_backend_fallbacks() {
+        assert_eq!(backend_for(&[("DISPLAY", ":0")]), FocusBackend::X11);
+        // GNOME on Wayland without XWayland has no supported source.
         assert_eq!(
-            LinuxFocusDetector::extract_window_class(text),
-            Some("code".to_string())
+            backend_for(&[("XDG_CURRENT_DESKTOP", "GNOME"), ("WAYLAND_DISPLAY", "wayland-0")]),
+            FocusBackend::Unavailable
         );
+    }
+
+    // ── Under X (Xvfb in CI) ──
+
+    /// Test client that plays window manager by setting properties directly.
+    struct FakeWindowManager {
+        display: *mut xlib::Display,
+        root: xlib::Window,
+    }
+
+    impl FakeWindowManager {
+        /// Tests that need a display are ignored by default; CI runs them
+        /// with `--ignored` under `xvfb-run`.
+        fn open() -> Self {
+            // SAFETY: a null name selects `$DISPLAY`; the result is checked.
+            let display = unsafe { xlib::XOpenDisplay(ptr::null()) };
+            assert!(!display.is_null(), "X display");
+            let root = unsafe { xlib::XDefaultRootWindow(display) };
+            Self { display, root }
+        }
+
+        fn create_window(&self, class: &[u8], title: &str, pid: c_ulong) -> xlib::Window {
+            unsafe {
+                let window =
+                    xlib::XCreateSimpleWindow(self.display, self.root, 0, 0, 10, 10, 0, 0, 0);
+                self.set_property(window, xlib::XA_WM_CLASS, xlib::XA_STRING, 8, class);
+                self.set_title(window, title);
+                let pid_atom = intern_atom(self.display, "_NET_WM_PID");
+                self.set_property(window, pid_atom, xlib::XA_CARDINAL, 32, &long_bytes(pid));
+                window
+            }
+        }
+
+        fn set_title(&self, window: xlib::Window, title: &str) {
+            unsafe {
+                let name = intern_atom(self.display, "_NET_WM_NAME");
+                let utf8 = intern_atom(self.display, "UTF8_STRING");
+                self.set_property(window, name, utf8, 8, title.as_bytes());
+            }
+        }
+
+        fn activate(&self, window: xlib::Window) {
+            unsafe {
+                let active = intern_atom(self.display, "_NET_ACTIVE_WINDOW");
+                self.set_property(self.root, active, xlib::XA_WINDOW, 32, &long_bytes(window));
+            }
+        }
+
+        unsafe fn set_property(
+            &self,
+            window: xlib::Window,
+            property: xlib::Atom,
+            kind: xlib::Atom,
+            format: c_int,
+            data: &[u8],
+        ) {
+            let count = if format == 32 {
+                data.len() / std::mem::size_of::<c_ulong>()
+            } else {
+                data.len()
+            };
+            xlib::XChangeProperty(
+                self.display,
+                window,
+                property,
+                kind,
+                format,
+                xlib::PropModeReplace,
+                data.as_ptr(),
+                count as c_int,
+            );
+            xlib::XSync(self.display, xlib::False);
+        }
+    }
+
+    impl Drop for FakeWindowManager {
+        fn drop(&mut self) {
+            // SAFETY: the display was opened in `open` and is closed once.
+            unsafe { xlib::XCloseDisplay(self.display) };
+        }
+    }
+
+    /// Format-32 property data is passed to Xlib as C longs.
+    fn long_bytes(value: c_ulong) -> Vec<u8> {
+        value.to_ne_bytes().to_vec()
+    }
 
-        let text = "invalid";
-        assert_eq!(LinuxFocusDetector::extract_window_class(text), None);
+    fn wait_for(det: &LinuxFocusDetector, pred: impl Fn(&WindowInfo) -> bool) -> WindowInfo {
+        let deadline = std::time::Instant::now() + Duration::from_secs(3);
+        loop {
+            let info = det.get_active_window_info().unwrap();
+            if pred(&info) || std::time::Instant::now() > deadline {
+                return info;
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
     }
 
     #[test]
-    fn test_wayland_hook_not_supported() {
-        let result = WaylandKeyboardHook::new();
-        assert!(result.is_err());
+    #[ignore = "needs an X server"]
+    fn test_focus_detector_follows_active_window() {
+        let wm = FakeWindowManager::open();
+        let editor = wm.create_window(b"gedit\0Gedit\0", "notes.txt", 4242);
+        let terminal = wm.create_window(b"xterm\0XTerm\0", "bash", 4343);
+
+        let mut det = LinuxFocusDetector::with_backend(FocusBackend::X11);
+        let switches = Arc::new(Mutex::new(Vec::<String>::new()));
+        let recorded = switches.clone();
+        det.on_focus_change(Box::new(move |info| {
+            recorded.lock().unwrap().push(info.app_name);
+        }));
+
+        wm.activate(editor);
+        let info = wait_for(&det, |info| info.app_name == "Gedit");
+        assert_eq!(
+            info,
+            WindowInfo {
+                title: "notes.txt".into(),
+                app_name: "Gedit".into(),
+                process_id: Some(4242),
+            }
+        );
+
+        // Title changes refresh the cache without counting as a switch.
+        wm.set_title(editor, "notes.txt *");
+        let info = wait_for(&det, |info| info.title == "notes.txt *");
+        assert_eq!(info.title, "notes.txt *");
+
+        wm.activate(terminal);
+        let info = wait_for(&det, |info| info.app_name == "XTerm");
+        assert_eq!(info.process_id, Some(4343));
+
+        let switches = switches.lock().unwrap();
+        assert_eq!(switches.last().map(String::as_str), Some("XTerm"));
+        assert_eq!(switches.iter().filter(|app| *app == "Gedit").count(), 1);
     }
 
     #[test]
@@ -585,6 +1303,245 @@ mod tests {
         }
     }
 
+    // ---------------------------------------------------------------------------
+    // Modifier Tracking Tests
+    // ---------------------------------------------------------------------------
+
+    use crate::managers::input_manager::InputManager;
+    use crate::platform::keyboard_hook::{Key, Modifiers};
+    use crate::platform::mock::MockKeyboardHook;
+    use rdev::EventType as RE;
+
+    /// Translates a recorded rdev stream into the events the hook would
+    /// forward, using the US fallback mapping.
+    fn translate_all(events: &[RE]) -> Vec<KeyEvent> {
+        let mut translator = KeyTranslator::new(None);
+        events.iter().filter_map(|e| translator.translate(e)).collect()
+    }
+
+    /// Translates a recorded rdev stream through an XKB keymap and returns
+    /// the keys of the forwarded press events.
+    fn pressed_keys_with_layout(layout: &str, events: &[RE]) -> Vec<Key> {
+        let mut xkb = XkbTranslator::new(&XkbNames::layout(layout, "")).expect("keymap");
+        xkb.set_compose_table("<dead_acute> <e> : \"é\" eacute\n")
+            .expect("compose table");
+        let mut translator = KeyTranslator::new(Some(xkb));
+        events
+            .iter()
+            .filter_map(|e| translator.translate(e))
+            .filter(|ke| ke.event_type == KeyEventType::Press)
+            .map(|ke| ke.key)
+            .collect()
+    }
+
+    /// Feeds a recorded rdev stream through `MockKeyboardHook` into an
+    /// `InputManager` and returns the resulting buffer.
+    fn buffer_after(events: &[RE]) -> String {
+        let mut mgr = InputManager::new();
+        let hook = MockKeyboardHook::new();
+        mgr.set_keyboard_hook(Box::new(hook.clone()));
+        mgr.start().unwrap();
+        for ke in translate_all(events) {
+            hook.inject_event(ke);
+        }
+        mgr.buffer()
+    }
+
+    #[test]
+    fn test_translate_skips_bare_modifiers() {
+        let events = translate_all(&[
+            RE::KeyPress(rdev::Key::ShiftLeft),
+            RE::KeyRelease(rdev::Key::ShiftLeft),
+            RE::KeyPress(rdev::Key::AltGr),
+        ]);
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn test_translate_ignores_mouse_events() {
+        let events = translate_all(&[RE::ButtonPress(rdev::Button::Left)]);
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn test_rdev_mouse_event_only_for_button_press() {
+        assert!(rdev_mouse_event(&RE::ButtonPress(rdev::Button::Left)).is_some());
+        assert!(rdev_mouse_event(&RE::ButtonPress(rdev::Button::Right)).is_some());
+        assert!(rdev_mouse_event(&RE::ButtonRelease(rdev::Button::Left)).is_none());
+        assert!(rdev_mouse_event(&RE::MouseMove { x: 1.0, y: 2.0 }).is_none());
+        assert!(rdev_mouse_event(&RE::Wheel { delta_x: 0, delta_y: 1 }).is_none());
+        assert!(rdev_mouse_event(&RE::KeyPress(rdev::Key::KeyA)).is_none());
+    }
+
+    #[test]
+    fn test_translate_attaches_held_modifiers() {
+        let events = translate_all(&[
+            RE::KeyPress(rdev::Key::ControlRight),
+            RE::KeyPress(rdev::Key::KeyC),
+            RE::KeyRelease(rdev::Key::KeyC),
+            RE::KeyRelease(rdev::Key::ControlRight),
+            RE::KeyPress(rdev::Key::KeyC),
+        ]);
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0].key, Key::Char('c'));
+        assert!(events[0].modifiers.ctrl);
+        assert!(events[1].modifiers.ctrl);
+        assert_eq!(events[2].modifiers, Modifiers::default());
+    }
+
+    #[test]
+    fn test_ctrl_shortcut_resets_buffer() {
+        let buffer = buffer_after(&[
+            RE::KeyPress(rdev::Key::KeyS),
+            RE::KeyPress(rdev::Key::KeyI),
+            RE::KeyPress(rdev::Key::ControlLeft),
+            RE::KeyPress(rdev::Key::KeyC),
+            RE::KeyRelease(rdev::Key::KeyC),
+            RE::KeyRelease(rdev::Key::ControlLeft),
+            RE::KeyPress(rdev::Key::KeyG),
+        ]);
+        assert_eq!(buffer, "g");
+    }
+
+    #[test]
+    fn test_alt_tab_resets_buffer() {
+        let buffer = buffer_after(&[
+            RE::KeyPress(rdev::Key::KeyS),
+            RE::KeyPress(rdev::Key::Alt),
+            RE::KeyPress(rdev::Key::KeyA),
+            RE::KeyRelease(rdev::Key::Alt),
+        ]);
+        assert_eq!(buffer, "");
+    }
+
+    #[test]
+    fn test_meta_shortcut_resets_buffer() {
+        let buffer = buffer_after(&[
+            RE::KeyPress(rdev::Key::KeyS),
+            RE::KeyPress(rdev::Key::MetaLeft),
+            RE::KeyPress(rdev::Key::KeyE),
+            RE::KeyRelease(rdev::Key::MetaLeft),
+        ]);
+        assert_eq!(buffer, "");
+    }
+
+    #[test]
+    fn test_shift_does_not_reset_buffer() {
+        let buffer = buffer_after(&[
+            RE::KeyPress(rdev::Key::KeyS),
+            RE::KeyPress(rdev::Key::ShiftRight),
+            RE::KeyPress(rdev::Key::KeyI),
+            RE::KeyRelease(rdev::Key::ShiftRight),
+            RE::KeyPress(rdev::Key::KeyG),
+        ]);
+        assert_eq!(buffer, "sig");
+    }
+
+    #[test]
+    fn test_ctrl_released_on_one_side_still_held() {
+        let buffer = buffer_after(&[
+            RE::KeyPress(rdev::Key::ControlLeft),
+            RE::KeyPress(rdev::Key::ControlRight),
+            RE::KeyRelease(rdev::Key::ControlLeft),
+            RE::KeyPress(rdev::Key::KeyS),
+        ]);
+        assert_eq!(buffer, "");
+    }
+
+    #[test]
+    fn test_x11_keycode_matches_rdev_table() {
+        assert_eq!(x11_keycode(&rdev::Key::KeyA), Some(38));
+        assert_eq!(x11_keycode(&rdev::Key::Space), Some(65));
+        assert_eq!(x11_keycode(&rdev::Key::MetaRight), Some(134));
+        assert_eq!(x11_keycode(&rdev::Key::Unknown(300)), Some(300));
+    }
+
+    // ---------------------------------------------------------------------------
+    // Layout-Aware Translation Tests
+    // ---------------------------------------------------------------------------
+
+    #[test]
+    #[ignore = "needs the xkeyboard-config data files"]
+    fn test_layout_translation_german() {
+        let keys = pressed_keys_with_layout(
+            "de",
+            &[
+                RE::KeyPress(rdev::Key::KeyZ),
+                RE::KeyRelease(rdev::Key::KeyZ),
+                RE::KeyPress(rdev::Key::KeyY),
+                RE::KeyRelease(rdev::Key::KeyY),
+                RE::KeyPress(rdev::Key::Quote),
+                RE::KeyRelease(rdev::Key::Quote),
+            ],
+        );
+        assert_eq!(keys, vec![Key::Char('y'), Key::Char('z'), Key::Char('ä')]);
+    }
+
+    #[test]
+    #[ignore = "needs the xkeyboard-config data files"]
+    fn test_layout_translation_shift_level() {
+        let keys = pressed_keys_with_layout(
+            "us",
+            &[
+                RE::KeyPress(rdev::Key::ShiftLeft),
+                RE::KeyPress(rdev::Key::KeyA),
+                RE::KeyRelease(rdev::Key::KeyA),
+                RE::KeyPress(rdev::Key::Num1),
+                RE::KeyRelease(rdev::Key::Num1),
+                RE::KeyRelease(rdev::Key::ShiftLeft),
+                RE::KeyPress(rdev::Key::KeyA),
+            ],
+        );
+        assert_eq!(keys, vec![Key::Char('A'), Key::Char('!'), Key::Char('a')]);
+    }
+
+    #[test]
+    #[ignore = "needs the xkeyboard-config data files"]
+    fn test_layout_translation_dead_key() {
+        let keys = pressed_keys_with_layout(
+            "de",
+            &[
+                RE::KeyPress(rdev::Key::Equal),
+                RE::KeyRelease(rdev::Key::Equal),
+                RE::KeyPress(rdev::Key::KeyE),
+                RE::KeyRelease(rdev::Key::KeyE),
+            ],
+        );
+        assert_eq!(keys, vec![Key::Char('é')]);
+    }
+
+    #[test]
+    #[ignore = "needs the xkeyboard-config data files"]
+    fn test_layout_translation_keeps_modifier_state() {
+        let xkb = XkbTranslator::new(&XkbNames::layout("us", "")).expect("keymap");
+        let mut translator = KeyTranslator::new(Some(xkb));
+        assert!(translator.translate(&RE::KeyPress(rdev::Key::ControlLeft)).is_none());
+        let ke = translator
+            .translate(&RE::KeyPress(rdev::Key::KeyC))
+            .expect("key event");
+        assert_eq!(ke.key, Key::Char('c'));
+        assert!(ke.modifiers.ctrl);
+    }
+
+    #[test]
+    #[ignore = "needs the xkeyboard-config data files"]
+    fn test_translator_follows_layout_changes() {
+        let layout = Arc::new(ActiveLayout::new(XkbNames::layout("us", "")));
+        let mut translator = KeyTranslator::following(layout.clone());
+        let mut tap_y = || {
+            let ke = translator.translate(&RE::KeyPress(rdev::Key::KeyY));
+            translator.translate(&RE::KeyRelease(rdev::Key::KeyY));
+            ke.map(|ke| ke.key)
+        };
+        assert_eq!(tap_y(), Some(Key::Char('y')));
+        layout.set_names(XkbNames::layout("de", ""));
+        assert_eq!(tap_y(), Some(Key::Char('z')));
+        layout.set_names(XkbNames::layout("us,de", ""));
+        assert_eq!(tap_y(), Some(Key::Char('y')));
+        layout.set_group(1);
+        assert_eq!(tap_y(), Some(Key::Char('z')));
+    }
+
     // ---------------------------------------------------------------------------
     // Wayland Detection Tests
     // ---------------------------------------------------------------------------
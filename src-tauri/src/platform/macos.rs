@@ -18,7 +18,7 @@ use std::sync::Arc;
 use std::thread;
 
 use crate::platform::keyboard_hook::{
-    FocusDetector, Key, KeyEvent, KeyEventType, KeyboardHook, Modifiers, PlatformError,
+    FocusDetector, Key, KeyEvent, KeyEventType, KeyboardHook, Modifiers, MouseEvent, PlatformError,
     WindowInfo,
 };
 
@@ -211,6 +211,8 @@ pub struct MacOSKeyboardHook {
     /// Track if hook was ever started (even if later stopped).
     /// The CGEventTap run loop cannot be cleanly stopped and restarted.
     started_once: AtomicBool,
+    /// Receives mouse-down events from the same event tap.
+    mouse_callback: Option<Arc<dyn Fn(MouseEvent) + Send + Sync>>,
 }
 
 impl MacOSKeyboardHook {
@@ -218,6 +220,7 @@ impl MacOSKeyboardHook {
         Self {
             running: Arc::new(AtomicBool::new(false)),
             started_once: AtomicBool::new(false),
+            mouse_callback: None,
         }
     }
 }
@@ -245,6 +248,7 @@ impl KeyboardHook for MacOSKeyboardHook {
         self.started_once.store(true, Ordering::SeqCst);
         let running = self.running.clone();
         let callback: Arc<dyn Fn(KeyEvent) + Send + Sync> = Arc::from(callback);
+        let mouse_callback = self.mouse_callback.clone();
 
         let ctrl_down = Arc::new(AtomicBool::new(false));
         let alt_down = Arc::new(AtomicBool::new(false));
@@ -294,6 +298,9 @@ impl KeyboardHook for MacOSKeyboardHook {
                 const K_CG_EVENT_KEY_DOWN: u32 = 10;
                 const K_CG_EVENT_KEY_UP: u32 = 11;
                 const K_CG_EVENT_FLAGS_CHANGED: u32 = 12;
+                const K_CG_EVENT_LEFT_MOUSE_DOWN: u32 = 1;
+                const K_CG_EVENT_RIGHT_MOUSE_DOWN: u32 = 3;
+                const K_CG_EVENT_OTHER_MOUSE_DOWN: u32 = 25;
                 const K_CG_EVENT_TAP_DISABLED_BY_TIMEOUT: u32 = 0xFFFFFFFE;
 
                 // CGEventTapLocation
@@ -307,6 +314,9 @@ impl KeyboardHook for MacOSKeyboardHook {
                 const EVENT_MASK: u64 = (1u64 << K_CG_EVENT_KEY_DOWN)
                     | (1u64 << K_CG_EVENT_KEY_UP)
                     | (1u64 << K_CG_EVENT_FLAGS_CHANGED);
+                const MOUSE_EVENT_MASK: u64 = (1u64 << K_CG_EVENT_LEFT_MOUSE_DOWN)
+                    | (1u64 << K_CG_EVENT_RIGHT_MOUSE_DOWN)
+                    | (1u64 << K_CG_EVENT_OTHER_MOUSE_DOWN);
 
                 #[link(name = "CoreGraphics", kind = "framework")]
                 extern "C" {
@@ -357,6 +367,7 @@ impl KeyboardHook for MacOSKeyboardHook {
                 /// Context for the CGEventTap callback
                 struct TapContext {
                     callback: Arc<dyn Fn(KeyEvent) + Send + Sync>,
+                    mouse_callback: Option<Arc<dyn Fn(MouseEvent) + Send + Sync>>,
                     running: Arc<AtomicBool>,
                     ctrl: Arc<AtomicBool>,
                     alt: Arc<AtomicBool>,
@@ -476,6 +487,13 @@ impl KeyboardHook for MacOSKeyboardHook {
                             let ke = KeyEvent::new(key, key_event_type, modifiers);
                             (ctx.callback)(ke);
                         }
+                        K_CG_EVENT_LEFT_MOUSE_DOWN
+                        | K_CG_EVENT_RIGHT_MOUSE_DOWN
+                        | K_CG_EVENT_OTHER_MOUSE_DOWN => {
+                            if let Some(ref on_mouse) = ctx.mouse_callback {
+                                on_mouse(MouseEvent::click());
+                            }
+                        }
                         K_CG_EVENT_FLAGS_CHANGED => {
                             // Update modifier state from flags
                             ctx.ctrl.store((flags & K_CG_EVENT_FLAG_MASK_CONTROL) != 0, Ordering::SeqCst);
@@ -490,8 +508,14 @@ impl KeyboardHook for MacOSKeyboardHook {
                 }
 
                 // Create the tap context
+                let event_mask = if mouse_callback.is_some() {
+                    EVENT_MASK | MOUSE_EVENT_MASK
+                } else {
+                    EVENT_MASK
+                };
                 let ctx = Box::new(TapContext {
                     callback,
+                    mouse_callback,
                     running: running.clone(),
                     ctrl,
                     alt,
@@ -506,7 +530,7 @@ impl KeyboardHook for MacOSKeyboardHook {
                         K_CG_SESSION_EVENT_TAP,
                         K_CG_HEAD_INSERT_EVENT_TAP,
                         K_CG_EVENT_TAP_OPTION_LISTEN_ONLY,
-                        EVENT_MASK,
+                        event_mask,
                         tap_callback,
                         ctx_ptr as *mut c_void,
                     );
@@ -571,6 +595,10 @@ impl KeyboardHook for MacOSKeyboardHook {
     fn is_running(&self) -> bool {
         self.running.load(Ordering::SeqCst)
     }
+
+    fn set_mouse_callback(&mut self, callback: Box<dyn Fn(MouseEvent) + Send + Sync>) {
+        self.mouse_callback = Some(Arc::from(callback));
+    }
 }
 
 // ---------------------------------------------------------------------------
@@ -581,6 +609,7 @@ impl KeyboardHook for MacOSKeyboardHook {
 extern "C" {
     fn IOHIDManagerCreate(allocator: *const c_void, options: u32) -> *mut c_void;
     fn IOHIDManagerSetDeviceMatching(manager: *mut c_void, matching: *const c_void);
+    fn IOHIDManagerSetDeviceMatchingMultiple(manager: *mut c_void, multiple: *const c_void);
     fn IOHIDManagerRegisterInputValueCallback(
         manager: *mut c_void,
         callback: unsafe extern "C" fn(*mut c_void, u32, *mut c_void, *mut c_void),
@@ -622,6 +651,12 @@ extern "C" {
         key_callbacks: *const c_void,
         value_callbacks: *const c_void,
     ) -> *const c_void;
+    fn CFArrayCreate(
+        allocator: *const c_void,
+        values: *const *const c_void,
+        num_values: isize,
+        callbacks: *const c_void,
+    ) -> *const c_void;
     fn CFRelease(cf: *const c_void);
     fn CFStringCreateWithCString(
         alloc: *const c_void,
@@ -633,6 +668,7 @@ extern "C" {
     static kCFAllocatorDefault: *const c_void;
     static kCFTypeDictionaryKeyCallBacks: [u8; 48];
     static kCFTypeDictionaryValueCallBacks: [u8; 40];
+    static kCFTypeArrayCallBacks: [u8; 40];
 }
 
 /// CFNumber type constant for 32-bit signed integer.
@@ -644,8 +680,12 @@ const K_CF_STRING_ENCODING_UTF8_HID: u32 = 0x0800_0100;
 const K_HID_USAGE_PAGE_GENERIC_DESKTOP: u32 = 0x01;
 /// HID usage for Keyboard within Generic Desktop.
 const K_HID_USAGE_KEYBOARD: u32 = 0x06;
+/// HID usage for Mouse within Generic Desktop (trackpads report as mice).
+const K_HID_USAGE_MOUSE: u32 = 0x02;
 /// HID usage page for Keyboard/Keypad.
 const K_HID_USAGE_PAGE_KEYBOARD: u32 = 0x07;
+/// HID usage page for buttons, such as mouse buttons.
+const K_HID_USAGE_PAGE_BUTTON: u32 = 0x09;
 
 // ---------------------------------------------------------------------------
 // IOHIDKeyboardHook
@@ -654,6 +694,7 @@ const K_HID_USAGE_PAGE_KEYBOARD: u32 = 0x07;
 /// Context passed through the IOHIDManager callback's `void* context` pointer.
 struct HIDCallbackContext {
     callback: Arc<dyn Fn(KeyEvent) + Send + Sync>,
+    mouse_callback: Option<Arc<dyn Fn(MouseEvent) + Send + Sync>>,
     running: Arc<AtomicBool>,
     shift_down: AtomicBool,
     ctrl_down: AtomicBool,
@@ -679,7 +720,8 @@ unsafe impl Sync for SendableRunLoop {}
 /// # How it works
 ///
 /// 1. An `IOHIDManager` is created and configured to match keyboard devices
-///    (Generic Desktop / Keyboard usage pair).
+///    (Generic Desktop / Keyboard usage pair), and mice as well when a mouse
+///    callback is set.
 /// 2. An input-value callback is registered that fires for every HID event.
 /// 3. The manager is scheduled on a `CFRunLoop` which runs on a dedicated
 ///    background thread.
@@ -689,6 +731,8 @@ pub struct IOHIDKeyboardHook {
     running: Arc<AtomicBool>,
     /// Handle to the run loop of the background thread so we can stop it.
     run_loop: Arc<std::sync::Mutex<Option<SendableRunLoop>>>,
+    /// Receives button presses from matched mice.
+    mouse_callback: Option<Arc<dyn Fn(MouseEvent) + Send + Sync>>,
 }
 
 impl IOHIDKeyboardHook {
@@ -696,6 +740,7 @@ impl IOHIDKeyboardHook {
         Self {
             running: Arc::new(AtomicBool::new(false)),
             run_loop: Arc::new(std::sync::Mutex::new(None)),
+            mouse_callback: None,
         }
     }
 }
@@ -803,7 +848,17 @@ unsafe extern "C" fn hid_input_value_callback(
     let usage = IOHIDElementGetUsage(element);
     let int_value = IOHIDValueGetIntegerValue(value);
 
-    // We only care about keyboard/keypad events (usage page 0x07).
+    // Any mouse button press is a click; releases are ignored.
+    if usage_page == K_HID_USAGE_PAGE_BUTTON {
+        if int_value != 0 {
+            if let Some(ref on_mouse) = ctx.mouse_callback {
+                on_mouse(MouseEvent::click());
+            }
+        }
+        return;
+    }
+
+    // Otherwise we only care about keyboard/keypad events (usage page 0x07).
     if usage_page != K_HID_USAGE_PAGE_KEYBOARD {
         return;
     }
@@ -857,13 +912,14 @@ unsafe fn cf_number_create(value: i32) -> *const c_void {
     )
 }
 
-/// Create the device matching dictionary for keyboard devices.
+/// Create the device matching dictionary for Generic Desktop devices with
+/// the given usage, e.g. keyboards or mice.
 ///
 /// # Safety
 ///
 /// Calls CoreFoundation FFI. The returned pointer must be released with
 /// `CFRelease` when no longer needed.
-unsafe fn create_keyboard_matching_dict() -> *const c_void {
+unsafe fn create_matching_dict(usage: u32) -> *const c_void {
     let page_key_str = b"DeviceUsagePage\0";
     let usage_key_str = b"DeviceUsage\0";
 
@@ -879,7 +935,7 @@ unsafe fn create_keyboard_matching_dict() -> *const c_void {
     );
 
     let page_val = cf_number_create(K_HID_USAGE_PAGE_GENERIC_DESKTOP as i32);
-    let usage_val = cf_number_create(K_HID_USAGE_KEYBOARD as i32);
+    let usage_val = cf_number_create(usage as i32);
 
     let keys = [page_key, usage_key];
     let values = [page_val, usage_val];
@@ -914,6 +970,7 @@ impl KeyboardHook for IOHIDKeyboardHook {
         self.running.store(true, Ordering::SeqCst);
         let running = self.running.clone();
         let run_loop_handle = self.run_loop.clone();
+        let mouse_callback = self.mouse_callback.clone();
 
         thread::Builder::new()
             .name("muttontext-iohid-hook".into())
@@ -923,8 +980,10 @@ impl KeyboardHook for IOHIDKeyboardHook {
                 // Build the callback context. It is heap-allocated and leaked
                 // intentionally — it must live as long as the IOHIDManager
                 // callback is registered (i.e., until the run loop exits).
+                let match_mice = mouse_callback.is_some();
                 let ctx = Box::new(HIDCallbackContext {
                     callback: Arc::from(callback),
+                    mouse_callback,
                     running: running.clone(),
                     shift_down: AtomicBool::new(false),
                     ctrl_down: AtomicBool::new(false),
@@ -944,10 +1003,24 @@ impl KeyboardHook for IOHIDKeyboardHook {
                         return;
                     }
 
-                    // 2. Create and set device matching dictionary
-                    let matching = create_keyboard_matching_dict();
-                    IOHIDManagerSetDeviceMatching(manager, matching);
-                    CFRelease(matching);
+                    // 2. Create and set device matching dictionaries
+                    let keyboards = create_matching_dict(K_HID_USAGE_KEYBOARD);
+                    if match_mice {
+                        let mice = create_matching_dict(K_HID_USAGE_MOUSE);
+                        let dicts = [keyboards, mice];
+                        let multiple = CFArrayCreate(
+                            kCFAllocatorDefault,
+                            dicts.as_ptr(),
+                            2,
+                            kCFTypeArrayCallBacks.as_ptr() as *const c_void,
+                        );
+                        IOHIDManagerSetDeviceMatchingMultiple(manager, multiple);
+                        CFRelease(multiple);
+                        CFRelease(mice);
+                    } else {
+                        IOHIDManagerSetDeviceMatching(manager, keyboards);
+                    }
+                    CFRelease(keyboards);
 
                     // 3. Register input value callback
                     IOHIDManagerRegisterInputValueCallback(
@@ -1043,6 +1116,15 @@ impl KeyboardHook for IOHIDKeyboardHook {
     fn is_running(&self) -> bool {
         self.running.load(Ordering::SeqCst)
     }
+
+    /// IOHID sees device reports, which CGEvent posts never produce.
+    fn hears_synthetic_input(&self) -> bool {
+        false
+    }
+
+    fn set_mouse_callback(&mut self, callback: Box<dyn Fn(MouseEvent) + Send + Sync>) {
+        self.mouse_callback = Some(Arc::from(callback));
+    }
 }
 
 // ---------------------------------------------------------------------------
@@ -4,7 +4,7 @@ use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex, MutexGuard};
 
 use crate::platform::keyboard_hook::{
-    FocusDetector, KeyEvent, KeyboardHook, PlatformError, WindowInfo,
+    FocusDetector, KeyEvent, KeyboardHook, MouseEvent, PlatformError, WindowInfo,
 };
 
 /// Helper to handle poisoned mutexes gracefully by recovering the inner data.
@@ -16,10 +16,17 @@ fn lock_mutex<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
 // MockKeyboardHook
 // ---------------------------------------------------------------------------
 
+type MouseCallback = Box<dyn Fn(MouseEvent) + Send + Sync>;
+
 /// A keyboard hook that records calls and lets tests inject events.
+///
+/// Clones share state, so a test can keep a clone to inject events after
+/// handing the hook to an `InputManager`.
+#[derive(Clone)]
 pub struct MockKeyboardHook {
     running: Arc<AtomicBool>,
     callback: Arc<Mutex<Option<Box<dyn Fn(KeyEvent) + Send + Sync>>>>,
+    mouse_callback: Arc<Mutex<Option<MouseCallback>>>,
 }
 
 impl MockKeyboardHook {
@@ -27,6 +34,19 @@ impl MockKeyboardHook {
         Self {
             running: Arc::new(AtomicBool::new(false)),
             callback: Arc::new(Mutex::new(None)),
+            mouse_callback: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Simulate a mouse button press. Like a real hook, nothing is
+    /// delivered while the hook is stopped.
+    pub fn inject_mouse_event(&self, event: MouseEvent) {
+        if !self.running.load(Ordering::SeqCst) {
+            return;
+        }
+        let cb = lock_mutex(&self.mouse_callback);
+        if let Some(ref f) = *cb {
+            f(event);
         }
     }
 
@@ -72,21 +92,32 @@ impl KeyboardHook for MockKeyboardHook {
     fn is_running(&self) -> bool {
         self.running.load(Ordering::SeqCst)
     }
+
+    fn set_mouse_callback(&mut self, callback: Box<dyn Fn(MouseEvent) + Send + Sync>) {
+        *lock_mutex(&self.mouse_callback) = Some(callback);
+    }
 }
 
 // ---------------------------------------------------------------------------
 // MockFocusDetector
 // ---------------------------------------------------------------------------
 
+type FocusCallback = Arc<dyn Fn(WindowInfo) + Send + Sync>;
+
 /// A focus detector that returns a configurable `WindowInfo`.
+///
+/// Clones share state, like [`MockKeyboardHook`].
+#[derive(Clone)]
 pub struct MockFocusDetector {
     info: Arc<Mutex<WindowInfo>>,
+    on_change: Arc<Mutex<Option<FocusCallback>>>,
 }
 
 impl MockFocusDetector {
     pub fn new() -> Self {
         Self {
             info: Arc::new(Mutex::new(WindowInfo::default())),
+            on_change: Arc::new(Mutex::new(None)),
         }
     }
 
@@ -94,6 +125,16 @@ impl MockFocusDetector {
     pub fn set_window_info(&self, info: WindowInfo) {
         *lock_mutex(&self.info) = info;
     }
+
+    /// Set the window info and notify the `on_focus_change` callback, as an
+    /// event-driven detector does when focus moves.
+    pub fn switch_focus(&self, info: WindowInfo) {
+        self.set_window_info(info.clone());
+        let callback = lock_mutex(&self.on_change).clone();
+        if let Some(callback) = callback {
+            callback(info);
+        }
+    }
 }
 
 impl Default for MockFocusDetector {
@@ -106,6 +147,10 @@ impl FocusDetector for MockFocusDetector {
     fn get_active_window_info(&self) -> Result<WindowInfo, PlatformError> {
         Ok(lock_mutex(&self.info).clone())
     }
+
+    fn on_focus_change(&mut self, callback: Box<dyn Fn(WindowInfo) + Send + Sync>) {
+        *lock_mutex(&self.on_change) = Some(Arc::from(callback));
+    }
 }
 
 // ---------------------------------------------------------------------------
@@ -154,6 +199,46 @@ mod tests {
         assert_eq!(count.load(Ordering::SeqCst), 1);
     }
 
+    #[test]
+    fn test_mock_hook_clone_shares_callback() {
+        let mut hook = MockKeyboardHook::new();
+        let injector = hook.clone();
+        let count = Arc::new(AtomicUsize::new(0));
+        let count_clone = count.clone();
+
+        hook.start(Box::new(move |_ev| {
+            count_clone.fetch_add(1, Ordering::SeqCst);
+        }))
+        .unwrap();
+        assert!(injector.is_running());
+
+        let ev = KeyEvent::new(Key::Char('a'), KeyEventType::Press, Modifiers::default());
+        injector.inject_event(ev);
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_mock_hook_mouse_events_only_while_running() {
+        let mut hook = MockKeyboardHook::new();
+        let injector = hook.clone();
+        let clicks = Arc::new(AtomicUsize::new(0));
+        let clicks_clone = clicks.clone();
+        hook.set_mouse_callback(Box::new(move |_ev| {
+            clicks_clone.fetch_add(1, Ordering::SeqCst);
+        }));
+
+        injector.inject_mouse_event(MouseEvent::click());
+        assert_eq!(clicks.load(Ordering::SeqCst), 0);
+
+        hook.start(Box::new(|_| {})).unwrap();
+        injector.inject_mouse_event(MouseEvent::click());
+        assert_eq!(clicks.load(Ordering::SeqCst), 1);
+
+        hook.stop().unwrap();
+        injector.inject_mouse_event(MouseEvent::click());
+        assert_eq!(clicks.load(Ordering::SeqCst), 1);
+    }
+
     #[test]
     fn test_mock_focus_detector_default() {
         let det = MockFocusDetector::new();
@@ -172,4 +257,22 @@ mod tests {
         det.set_window_info(custom.clone());
         assert_eq!(det.get_active_window_info().unwrap(), custom);
     }
+
+    #[test]
+    fn test_mock_focus_detector_switch_notifies() {
+        let mut det = MockFocusDetector::new();
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let seen_clone = seen.clone();
+        det.on_focus_change(Box::new(move |info| {
+            lock_mutex(&seen_clone).push(info.app_name);
+        }));
+
+        det.switch_focus(WindowInfo {
+            title: "Doc".into(),
+            app_name: "writer".into(),
+            process_id: None,
+        });
+        assert_eq!(*lock_mutex(&seen), vec!["writer".to_string()]);
+        assert_eq!(det.get_active_window_info().unwrap().app_name, "writer");
+    }
 }
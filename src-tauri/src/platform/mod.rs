@@ -10,9 +10,27 @@ pub mod mock;
 #[cfg(any(target_os = "linux", target_os = "macos"))]
 pub(crate) mod rdev_common;
 
+#[cfg(target_os = "linux")]
+pub mod atspi;
+
+#[cfg(target_os = "linux")]
+pub mod evdev;
+
 #[cfg(target_os = "linux")]
 pub mod linux;
 
+#[cfg(target_os = "linux")]
+pub mod uinput;
+
+#[cfg(target_os = "linux")]
+pub mod wayland_focus;
+
+#[cfg(target_os = "linux")]
+pub mod xkb;
+
+#[cfg(target_os = "linux")]
+pub mod xtest;
+
 #[cfg(target_os = "macos")]
 pub mod macos;
 
@@ -26,10 +44,13 @@ pub use keyboard_hook::{
 pub use mock::{MockFocusDetector, MockKeyboardHook};
 
 // Re-export platform implementations.
+#[cfg(target_os = "linux")]
+pub use evdev::EvdevKeyboardHook;
+
 #[cfg(target_os = "linux")]
 pub use linux::{
-    detect_wayland_status, is_xwayland_available, LinuxFocusDetector, LinuxKeyboardHook,
-    WaylandStatus,
+    detect_wayland_status, is_xwayland_available, FocusBackend, LinuxFocusDetector,
+    LinuxKeyboardHook, WaylandStatus,
 };
 
 #[cfg(target_os = "macos")]
@@ -5,7 +5,7 @@
 
 #![cfg(any(target_os = "linux", target_os = "macos"))]
 
-use crate::platform::keyboard_hook::Key;
+use crate::platform::keyboard_hook::{Key, Modifiers};
 
 /// Converts an rdev key to our internal Key representation.
 pub(crate) fn rdev_key_to_key(rdev_key: &rdev::Key) -> Key {
@@ -77,7 +77,7 @@ pub(crate) fn rdev_key_to_key(rdev_key: &rdev::Key) -> Key {
     }
 }
 
-/// Track modifier state from rdev events.
+/// Returns true if `key` is a modifier key.
 pub(crate) fn is_modifier(key: &rdev::Key) -> bool {
     matches!(
         key,
@@ -92,6 +92,59 @@ pub(crate) fn is_modifier(key: &rdev::Key) -> bool {
     )
 }
 
+/// Tracks which modifier keys are held, from rdev press/release events.
+///
+/// rdev reports key events without modifier state, so hooks feed every
+/// event through a tracker and attach its snapshot to the `KeyEvent`.
+/// Left and right keys are tracked separately so releasing one side
+/// while the other is still held keeps the modifier active. AltGr is
+/// deliberately not treated as Alt: it selects a third-level character
+/// on many layouts and is part of normal typing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct ModifierTracker {
+    shift_left: bool,
+    shift_right: bool,
+    ctrl_left: bool,
+    ctrl_right: bool,
+    alt: bool,
+    meta_left: bool,
+    meta_right: bool,
+}
+
+impl ModifierTracker {
+    /// Records a press or release of `key`. Returns `true` if the key is a
+    /// modifier (including AltGr), so callers can skip forwarding it.
+    pub(crate) fn update(&mut self, key: &rdev::Key, pressed: bool) -> bool {
+        use rdev::Key as RK;
+        if !is_modifier(key) {
+            return false;
+        }
+        let slot = match key {
+            RK::ShiftLeft => &mut self.shift_left,
+            RK::ShiftRight => &mut self.shift_right,
+            RK::ControlLeft => &mut self.ctrl_left,
+            RK::ControlRight => &mut self.ctrl_right,
+            RK::Alt => &mut self.alt,
+            RK::MetaLeft => &mut self.meta_left,
+            RK::MetaRight => &mut self.meta_right,
+            // AltGr
+            _ => return true,
+        };
+        *slot = pressed;
+        true
+    }
+
+    /// Returns the modifiers currently held.
+    pub(crate) fn modifiers(&self) -> Modifiers {
+        Modifiers {
+            ctrl: self.ctrl_left || self.ctrl_right,
+            alt: self.alt,
+            shift: self.shift_left || self.shift_right,
+            meta: self.meta_left || self.meta_right,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -109,6 +162,58 @@ mod tests {
         assert_eq!(rdev_key_to_key(&rdev::Key::F1), Key::F(1));
     }
 
+    #[test]
+    fn test_tracker_press_and_release() {
+        let mut tracker = ModifierTracker::default();
+        assert!(tracker.update(&rdev::Key::ControlLeft, true));
+        assert!(tracker.modifiers().ctrl);
+        assert!(tracker.update(&rdev::Key::ControlLeft, false));
+        assert!(tracker.modifiers().is_empty());
+    }
+
+    #[test]
+    fn test_tracker_left_and_right_independent() {
+        let mut tracker = ModifierTracker::default();
+        tracker.update(&rdev::Key::ShiftLeft, true);
+        tracker.update(&rdev::Key::ShiftRight, true);
+        tracker.update(&rdev::Key::ShiftLeft, false);
+        assert!(tracker.modifiers().shift);
+        tracker.update(&rdev::Key::ShiftRight, false);
+        assert!(!tracker.modifiers().shift);
+    }
+
+    #[test]
+    fn test_tracker_all_modifiers() {
+        let mut tracker = ModifierTracker::default();
+        tracker.update(&rdev::Key::ControlRight, true);
+        tracker.update(&rdev::Key::Alt, true);
+        tracker.update(&rdev::Key::ShiftRight, true);
+        tracker.update(&rdev::Key::MetaRight, true);
+        assert_eq!(
+            tracker.modifiers(),
+            Modifiers {
+                ctrl: true,
+                alt: true,
+                shift: true,
+                meta: true,
+            }
+        );
+    }
+
+    #[test]
+    fn test_tracker_altgr_is_not_alt() {
+        let mut tracker = ModifierTracker::default();
+        assert!(tracker.update(&rdev::Key::AltGr, true));
+        assert!(tracker.modifiers().is_empty());
+    }
+
+    #[test]
+    fn test_tracker_ignores_regular_keys() {
+        let mut tracker = ModifierTracker::default();
+        assert!(!tracker.update(&rdev::Key::KeyA, true));
+        assert!(tracker.modifiers().is_empty());
+    }
+
     #[test]
     fn test_is_modifier() {
         assert!(is_modifier(&rdev::Key::ShiftLeft));
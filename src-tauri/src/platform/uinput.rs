@@ -0,0 +1,547 @@
+//! Virtual keyboard output through the kernel's uinput device.
+//!
+//! `rdev::simulate`, `xdotool` and the X11 clipboard all need an X server,
+//! so none of them can insert text on native Wayland. A uinput device looks
+//! like a real keyboard to the compositor and works on any display server.
+//! Text is typed through the active XKB keymap (see
+//! [`crate::platform::xkb::KeystrokeMap`]); characters the layout lacks are
+//! entered with the Ctrl+Shift+U Unicode sequence understood by GTK and
+//! IBus.
+//!
+//! Opening `/dev/uinput` normally requires a udev rule such as
+//! `KERNEL=="uinput", GROUP="input", MODE="0660"`.
+
+#![cfg(target_os = "linux")]
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::io::AsRawFd;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use crate::platform::evdev::InputEvent;
+use crate::platform::keyboard_hook::{Key, Modifiers, PlatformError};
+use crate::platform::linux::active_layout;
+use crate::platform::xkb::{ActiveLayout, KeystrokeMap, EVDEV_OFFSET};
+
+/// Name of the virtual device, used by the evdev hook to ignore our output.
+pub const VIRTUAL_KEYBOARD_NAME: &str = "MuttonText virtual keyboard";
+
+const UINPUT_PATH: &str = "/dev/uinput";
+
+/// Time for the compositor to pick up a newly created device before it
+/// accepts events from it.
+const DEVICE_SETTLE_DELAY: Duration = Duration::from_millis(200);
+
+/// Highest evdev key code registered on the virtual device.
+const MAX_KEY_CODE: u16 = 247;
+
+// ioctl requests from `linux/uinput.h`.
+const UI_DEV_CREATE: u64 = 0x5501;
+const UI_DEV_DESTROY: u64 = 0x5502;
+const UI_DEV_SETUP: u64 = 0x405c_5503;
+const UI_SET_EVBIT: u64 = 0x4004_5564;
+const UI_SET_KEYBIT: u64 = 0x4004_5565;
+
+// Event types and key codes from `linux/input-event-codes.h`.
+const EV_SYN: u16 = 0;
+const EV_KEY: u16 = 1;
+const SYN_REPORT: u16 = 0;
+const BUS_VIRTUAL: u16 = 0x06;
+const KEY_BACKSPACE: u16 = 14;
+const KEY_TAB: u16 = 15;
+const KEY_ENTER: u16 = 28;
+const KEY_LEFTCTRL: u16 = 29;
+const KEY_U: u16 = 22;
+const KEY_SPACE: u16 = 57;
+const KEY_DELETE: u16 = 111;
+const KEY_ESC: u16 = 1;
+const KEY_LEFTALT: u16 = 56;
+const KEY_F1: u16 = 59;
+const KEY_F11: u16 = 87;
+const KEY_HOME: u16 = 102;
+const KEY_UP: u16 = 103;
+const KEY_PAGEUP: u16 = 104;
+const KEY_LEFT: u16 = 105;
+const KEY_RIGHT: u16 = 106;
+const KEY_END: u16 = 107;
+const KEY_DOWN: u16 = 108;
+const KEY_PAGEDOWN: u16 = 109;
+const KEY_INSERT: u16 = 110;
+const KEY_LEFTMETA: u16 = 125;
+
+/// `struct uinput_setup`.
+#[repr(C)]
+struct UinputSetup {
+    bustype: u16,
+    vendor: u16,
+    product: u16,
+    version: u16,
+    name: [u8; 80],
+    ff_effects_max: u32,
+}
+
+// ---------------------------------------------------------------------------
+// Typing plans
+// ---------------------------------------------------------------------------
+
+/// Converts an XKB keycode into the evdev code uinput expects.
+fn evdev_code(keycode: u32) -> u16 {
+    keycode.saturating_sub(EVDEV_OFFSET) as u16
+}
+
+/// Appends a press and release of `code` to `events`.
+fn push_tap(events: &mut Vec<InputEvent>, code: u16) {
+    events.push(InputEvent::key(code, 1));
+    events.push(InputEvent::key(code, 0));
+}
+
+/// Appends `count` taps of `code`.
+pub fn plan_taps(code: u16, count: usize) -> Vec<InputEvent> {
+    let mut events = Vec::with_capacity(count * 2);
+    for _ in 0..count {
+        push_tap(&mut events, code);
+    }
+    events
+}
+
+/// Plans the key events that type `text` on a keyboard with the layout
+/// described by `map`.
+///
+/// Characters the layout cannot produce are entered as Ctrl+Shift+U, their
+/// hex code point and Space. `\r` is dropped so `\r\n` becomes one Enter.
+pub fn plan_text(text: &str, map: &KeystrokeMap) -> Vec<InputEvent> {
+    let mut events = Vec::new();
+    for ch in text.chars() {
+        match ch {
+            '\n' => push_tap(&mut events, KEY_ENTER),
+            '\t' => push_tap(&mut events, KEY_TAB),
+            '\r' => {}
+            _ if map.get(ch).is_some() => push_char(&mut events, ch, map),
+            _ => {
+                let u = map.get('u').map_or(KEY_U, |ks| evdev_code(ks.keycode));
+                let shift = evdev_code(map.shift_keycode);
+                events.push(InputEvent::key(KEY_LEFTCTRL, 1));
+                events.push(InputEvent::key(shift, 1));
+                push_tap(&mut events, u);
+                events.push(InputEvent::key(shift, 0));
+                events.push(InputEvent::key(KEY_LEFTCTRL, 0));
+                for digit in format!("{:x}", ch as u32).chars() {
+                    if map.get(digit).is_some() {
+                        push_char(&mut events, digit, map);
+                    } else {
+                        tracing::warn!("Layout cannot type hex digit '{}' for U+{:04X}", digit, ch as u32);
+                    }
+                }
+                push_tap(&mut events, KEY_SPACE);
+            }
+        }
+    }
+    events
+}
+
+/// Returns `true` if [`plan_text`] types `ch` with a key of the layout
+/// described by `map` rather than the Unicode sequence.
+pub fn has_keystroke(ch: char, map: &KeystrokeMap) -> bool {
+    matches!(ch, '\n' | '\t' | '\r') || map.get(ch).is_some()
+}
+
+/// Appends the keystroke for `ch`, wrapped in the modifiers it needs.
+/// Does nothing if the layout has no keystroke for `ch`.
+fn push_char(events: &mut Vec<InputEvent>, ch: char, map: &KeystrokeMap) {
+    let ks = match map.get(ch) {
+        Some(ks) => ks,
+        None => return,
+    };
+    let shift = ks.shift.then(|| evdev_code(map.shift_keycode));
+    let level3 = if ks.level3 {
+        map.level3_keycode.map(evdev_code)
+    } else {
+        None
+    };
+    for &modifier in [shift, level3].iter().flatten() {
+        events.push(InputEvent::key(modifier, 1));
+    }
+    push_tap(events, evdev_code(ks.keycode));
+    for &modifier in [level3, shift].iter().flatten() {
+        events.push(InputEvent::key(modifier, 0));
+    }
+}
+
+/// Returns the evdev code of `key`. Letters are looked up in the layout
+/// by their lowercase form; `Key::Other("Insert")` is the Insert key.
+fn key_code(key: &Key, map: &KeystrokeMap) -> Option<u16> {
+    let code = match key {
+        Key::Char(c) => {
+            let lower = c.to_lowercase().next()?;
+            return map.get(lower).map(|ks| evdev_code(ks.keycode));
+        }
+        Key::Backspace => KEY_BACKSPACE,
+        Key::Enter => KEY_ENTER,
+        Key::Tab => KEY_TAB,
+        Key::Escape => KEY_ESC,
+        Key::Space => KEY_SPACE,
+        Key::Delete => KEY_DELETE,
+        Key::Left => KEY_LEFT,
+        Key::Right => KEY_RIGHT,
+        Key::Up => KEY_UP,
+        Key::Down => KEY_DOWN,
+        Key::Home => KEY_HOME,
+        Key::End => KEY_END,
+        Key::PageUp => KEY_PAGEUP,
+        Key::PageDown => KEY_PAGEDOWN,
+        Key::F(n @ 1..=10) => KEY_F1 + u16::from(*n) - 1,
+        Key::F(n @ 11..=12) => KEY_F11 + u16::from(*n) - 11,
+        Key::Other(name) if name == "Insert" => KEY_INSERT,
+        _ => return None,
+    };
+    Some(code)
+}
+
+/// Plans pressing `key` while holding `modifiers`, or `None` if the
+/// layout has no key for it.
+pub fn plan_chord(modifiers: Modifiers, key: &Key, map: &KeystrokeMap) -> Option<Vec<InputEvent>> {
+    let code = key_code(key, map)?;
+    let held: Vec<u16> = [
+        (modifiers.ctrl, KEY_LEFTCTRL),
+        (modifiers.shift, evdev_code(map.shift_keycode)),
+        (modifiers.alt, KEY_LEFTALT),
+        (modifiers.meta, KEY_LEFTMETA),
+    ]
+    .iter()
+    .filter(|(on, _)| *on)
+    .map(|&(_, code)| code)
+    .collect();
+
+    let mut events: Vec<InputEvent> = held.iter().map(|&code| InputEvent::key(code, 1)).collect();
+    push_tap(&mut events, code);
+    events.extend(held.iter().rev().map(|&code| InputEvent::key(code, 0)));
+    Some(events)
+}
+
+// ---------------------------------------------------------------------------
+// VirtualKeyboard
+// ---------------------------------------------------------------------------
+
+/// A keyboard device created through `/dev/uinput`. The device is removed
+/// when the value is dropped.
+pub struct VirtualKeyboard {
+    file: File,
+    keystrokes: KeystrokeMap,
+    /// Generation of the [`ActiveLayout`] `keystrokes` was built from.
+    layout_generation: u64,
+}
+
+/// Issues an ioctl on `file`, converting failures into `PlatformError`.
+fn ioctl(file: &File, request: u64, arg: libc::c_ulong) -> Result<(), PlatformError> {
+    // SAFETY: `file` is an open uinput descriptor and every request used in
+    // this module takes either an integer or a pointer to a live struct.
+    let ret = unsafe { libc::ioctl(file.as_raw_fd(), request as _, arg) };
+    if ret < 0 {
+        return Err(PlatformError::Internal(format!(
+            "uinput ioctl {:#x} failed: {}",
+            request,
+            io::Error::last_os_error()
+        )));
+    }
+    Ok(())
+}
+
+impl VirtualKeyboard {
+    /// Creates the virtual device. `keystrokes` describes the layout the
+    /// compositor applies to it, normally the active one.
+    pub fn new(keystrokes: KeystrokeMap) -> Result<Self, PlatformError> {
+        let file = OpenOptions::new()
+            .write(true)
+            .open(UINPUT_PATH)
+            .map_err(|e| match e.kind() {
+                io::ErrorKind::PermissionDenied => PlatformError::PermissionDenied(format!(
+                    "cannot open {}; add a udev rule granting the 'input' group access",
+                    UINPUT_PATH
+                )),
+                _ => PlatformError::Internal(format!("Failed to open {}: {}", UINPUT_PATH, e)),
+            })?;
+
+        ioctl(&file, UI_SET_EVBIT, EV_KEY.into())?;
+        for code in 1..=MAX_KEY_CODE {
+            ioctl(&file, UI_SET_KEYBIT, code.into())?;
+        }
+        let mut setup = UinputSetup {
+            bustype: BUS_VIRTUAL,
+            vendor: 0,
+            product: 0,
+            version: 1,
+            name: [0; 80],
+            ff_effects_max: 0,
+        };
+        setup.name[..VIRTUAL_KEYBOARD_NAME.len()].copy_from_slice(VIRTUAL_KEYBOARD_NAME.as_bytes());
+        ioctl(&file, UI_DEV_SETUP, &setup as *const UinputSetup as libc::c_ulong)?;
+        ioctl(&file, UI_DEV_CREATE, 0)?;
+
+        tracing::info!("Created uinput virtual keyboard");
+        thread::sleep(DEVICE_SETTLE_DELAY);
+        Ok(Self {
+            file,
+            keystrokes,
+            layout_generation: 0,
+        })
+    }
+
+    /// Rebuilds the keystroke map if `layout` changed since it was built.
+    fn follow_layout(&mut self, layout: &ActiveLayout) {
+        let generation = layout.generation();
+        if generation == self.layout_generation {
+            return;
+        }
+        match KeystrokeMap::from_layout(layout) {
+            Ok(keystrokes) => {
+                self.keystrokes = keystrokes;
+                self.layout_generation = generation;
+            }
+            Err(e) => tracing::warn!("{}; keeping the previous keymap", e),
+        }
+    }
+
+    /// Writes `events`, each followed by a sync report, pausing `delay`
+    /// after every key event.
+    pub fn emit(&mut self, events: &[InputEvent], delay: Duration) -> Result<(), PlatformError> {
+        let sync = InputEvent {
+            event_type: EV_SYN,
+            code: SYN_REPORT,
+            value: 0,
+        }
+        .to_bytes();
+        for event in events {
+            let mut record = event.to_bytes();
+            record.extend_from_slice(&sync);
+            self.file
+                .write_all(&record)
+                .map_err(|e| PlatformError::Internal(format!("uinput write failed: {}", e)))?;
+            thread::sleep(delay);
+        }
+        Ok(())
+    }
+
+    /// Types `text` through the device's layout.
+    pub fn type_text(&mut self, text: &str, delay: Duration) -> Result<(), PlatformError> {
+        let events = plan_text(text, &self.keystrokes);
+        self.emit(&events, delay)
+    }
+
+    /// Returns `true` if [`Self::type_text`] types `ch` with a key of the
+    /// device's layout.
+    pub fn can_type(&self, ch: char) -> bool {
+        has_keystroke(ch, &self.keystrokes)
+    }
+
+    /// Presses Backspace `count` times.
+    pub fn backspace(&mut self, count: usize, delay: Duration) -> Result<(), PlatformError> {
+        self.emit(&plan_taps(KEY_BACKSPACE, count), delay)
+    }
+
+    /// Presses Delete `count` times.
+    pub fn delete(&mut self, count: usize, delay: Duration) -> Result<(), PlatformError> {
+        self.emit(&plan_taps(KEY_DELETE, count), delay)
+    }
+
+    /// Presses `key` `count` times.
+    pub fn tap_key(&mut self, key: &Key, count: usize, delay: Duration) -> Result<(), PlatformError> {
+        let code = key_code(key, &self.keystrokes)
+            .ok_or_else(|| PlatformError::NotSupported(format!("No key code for key {}", key)))?;
+        self.emit(&plan_taps(code, count), delay)
+    }
+
+    /// Presses `key` while holding `modifiers`, e.g. Ctrl+V.
+    pub fn tap_chord(
+        &mut self,
+        modifiers: Modifiers,
+        key: &Key,
+        delay: Duration,
+    ) -> Result<(), PlatformError> {
+        let events = plan_chord(modifiers, key, &self.keystrokes)
+            .ok_or_else(|| PlatformError::NotSupported(format!("No key code for key {}", key)))?;
+        self.emit(&events, delay)
+    }
+}
+
+impl Drop for VirtualKeyboard {
+    fn drop(&mut self) {
+        if let Err(e) = ioctl(&self.file, UI_DEV_DESTROY, 0) {
+            tracing::warn!("{}", e);
+        }
+    }
+}
+
+/// The process-wide virtual keyboard, created on first use.
+static VIRTUAL_KEYBOARD: Mutex<Option<VirtualKeyboard>> = Mutex::new(None);
+
+/// Runs `f` with the shared virtual keyboard, creating it for the active
+/// layout on first use and updating its keymap when the layout changes.
+/// Creation is retried on the next call if it fails.
+pub fn with_virtual_keyboard<T>(
+    f: impl FnOnce(&mut VirtualKeyboard) -> Result<T, PlatformError>,
+) -> Result<T, PlatformError> {
+    let mut guard = VIRTUAL_KEYBOARD
+        .lock()
+        .map_err(|_| PlatformError::Internal("Virtual keyboard lock poisoned".into()))?;
+    let layout = active_layout();
+    if guard.is_none() {
+        let generation = layout.generation();
+        let mut keyboard = VirtualKeyboard::new(KeystrokeMap::from_layout(&layout)?)?;
+        keyboard.layout_generation = generation;
+        *guard = Some(keyboard);
+    }
+    match guard.as_mut() {
+        Some(keyboard) => {
+            keyboard.follow_layout(&layout);
+            f(keyboard)
+        }
+        None => Err(PlatformError::Internal("Virtual keyboard unavailable".into())),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::xkb::{XkbNames, XkbTranslator};
+
+    fn keystrokes(layout: &str) -> KeystrokeMap {
+        KeystrokeMap::new(&XkbNames::layout(layout, "")).expect("keymap")
+    }
+
+    /// Plays a plan back through a translator for the same layout and
+    /// returns the keys it produces.
+    fn replay(layout: &str, events: &[InputEvent]) -> Vec<Key> {
+        let mut t = XkbTranslator::new(&XkbNames::layout(layout, "")).expect("keymap");
+        let mut keys = Vec::new();
+        for event in events {
+            let keycode = u32::from(event.code) + EVDEV_OFFSET;
+            if event.value == 1 {
+                keys.extend(t.press(keycode));
+            } else {
+                t.release(keycode);
+            }
+        }
+        keys
+    }
+
+    fn chars(keys: &[Key]) -> String {
+        keys.iter()
+            .map(|k| match k {
+                Key::Char(c) => *c,
+                Key::Space => ' ',
+                Key::Enter => '\n',
+                Key::Tab => '\t',
+                _ => '?',
+            })
+            .collect()
+    }
+
+    #[test]
+    #[ignore = "needs the xkeyboard-config data files"]
+    fn test_plan_roundtrips_us() {
+        let text = "Hello, World! 42\tdone\n";
+        let plan = plan_text(text, &keystrokes("us"));
+        assert_eq!(chars(&replay("us", &plan)), text);
+    }
+
+    #[test]
+    #[ignore = "needs the xkeyboard-config data files"]
+    fn test_plan_roundtrips_german_with_altgr() {
+        let text = "Grüße an bob@example.com {x}";
+        let plan = plan_text(text, &keystrokes("de"));
+        assert_eq!(chars(&replay("de", &plan)), text);
+    }
+
+    #[test]
+    #[ignore = "needs the xkeyboard-config data files"]
+    fn test_plan_releases_every_key() {
+        let plan = plan_text("A@ß☃", &keystrokes("de"));
+        let pressed = plan.iter().filter(|e| e.value == 1).count();
+        let released = plan.iter().filter(|e| e.value == 0).count();
+        assert_eq!(pressed, released);
+    }
+
+    #[test]
+    #[ignore = "needs the xkeyboard-config data files"]
+    fn test_missing_character_uses_unicode_entry() {
+        let plan = plan_text("☃", &keystrokes("us"));
+        assert_eq!(plan[0], InputEvent::key(KEY_LEFTCTRL, 1));
+        assert_eq!(plan[1], InputEvent::key(42, 1));
+        assert_eq!(plan[2], InputEvent::key(KEY_U, 1));
+        assert_eq!(chars(&replay("us", &plan)), "U2603 ");
+    }
+
+    #[test]
+    #[ignore = "needs the xkeyboard-config data files"]
+    fn test_has_keystroke_follows_layout() {
+        let us = keystrokes("us");
+        assert!(has_keystroke('a', &us));
+        assert!(has_keystroke('\r', &us));
+        assert!(!has_keystroke('€', &us));
+        assert!(has_keystroke('€', &keystrokes("de")));
+    }
+
+    #[test]
+    #[ignore = "needs the xkeyboard-config data files"]
+    fn test_crlf_is_one_enter() {
+        let plan = plan_text("a\r\nb", &keystrokes("us"));
+        assert_eq!(chars(&replay("us", &plan)), "a\nb");
+    }
+
+    #[test]
+    fn test_plan_taps() {
+        let tap = [
+            InputEvent::key(KEY_BACKSPACE, 1),
+            InputEvent::key(KEY_BACKSPACE, 0),
+        ];
+        assert_eq!(plan_taps(KEY_BACKSPACE, 3), tap.repeat(3));
+        assert!(plan_taps(KEY_DELETE, 0).is_empty());
+    }
+
+    #[test]
+    #[ignore = "needs the xkeyboard-config data files"]
+    fn test_plan_chord_wraps_key_in_modifiers() {
+        let map = keystrokes("us");
+        let modifiers = Modifiers {
+            ctrl: true,
+            shift: true,
+            ..Default::default()
+        };
+        let plan = plan_chord(modifiers, &Key::Char('V'), &map).unwrap();
+        let v = evdev_code(map.get('v').unwrap().keycode);
+        assert_eq!(
+            plan,
+            vec![
+                InputEvent::key(KEY_LEFTCTRL, 1),
+                InputEvent::key(42, 1),
+                InputEvent::key(v, 1),
+                InputEvent::key(v, 0),
+                InputEvent::key(42, 0),
+                InputEvent::key(KEY_LEFTCTRL, 0),
+            ]
+        );
+    }
+
+    #[test]
+    #[ignore = "needs the xkeyboard-config data files"]
+    fn test_plan_chord_special_keys() {
+        let map = keystrokes("us");
+        let insert = plan_chord(Modifiers::default(), &Key::Other("Insert".into()), &map).unwrap();
+        assert_eq!(insert, plan_taps(KEY_INSERT, 1));
+        assert_eq!(replay("us", &plan_chord(Modifiers::default(), &Key::Left, &map).unwrap()), vec![Key::Left]);
+        assert!(plan_chord(Modifiers::default(), &Key::Other("Launch".into()), &map).is_none());
+    }
+
+    #[test]
+    fn test_uinput_setup_matches_kernel_layout() {
+        // `UI_DEV_SETUP` encodes the struct size (92 bytes) in the request.
+        assert_eq!(std::mem::size_of::<UinputSetup>(), 92);
+        assert_eq!((UI_DEV_SETUP >> 16) & 0x3fff, 92);
+    }
+}
@@ -0,0 +1,735 @@
+//! Focus tracking through Wayland compositor IPC.
+//!
+//! Wayland has no protocol for asking which window is focused, so each
+//! supported compositor is followed through its own interface:
+//!
+//! - **sway / i3**: the `$SWAYSOCK` / `$I3SOCK` IPC socket (`GET_TREE` for
+//!   the initial window, then subscribed `window` events).
+//! - **Hyprland**: `activewindow` events on `.socket2.sock`, with details
+//!   queried from `.socket.sock`.
+//! - **KWin**: a small KWin script that calls back over D-Bus whenever a
+//!   window is activated.
+//!
+//! Watchers run on the [`LinuxFocusDetector`](crate::platform::linux::LinuxFocusDetector)
+//! thread and report through its [`FocusState`].
+
+#![cfg(target_os = "linux")]
+
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::os::unix::io::AsRawFd;
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+
+use serde_json::Value;
+
+use crate::platform::keyboard_hook::WindowInfo;
+use crate::platform::linux::{wait_readable, FocusState, FOCUS_POLL_INTERVAL};
+
+fn invalid_data<E: std::fmt::Display>(e: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e.to_string())
+}
+
+/// Reads a non-empty string field from a JSON object.
+fn string_field(value: &Value, key: &str) -> Option<String> {
+    value
+        .get(key)
+        .and_then(Value::as_str)
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+}
+
+fn pid_field(value: &Value, key: &str) -> Option<u32> {
+    value
+        .get(key)
+        .and_then(Value::as_u64)
+        .and_then(|pid| u32::try_from(pid).ok())
+        .filter(|&pid| pid > 0)
+}
+
+// ---------------------------------------------------------------------------
+// sway / i3
+// ---------------------------------------------------------------------------
+
+/// Magic string that starts every i3 IPC message.
+const I3_IPC_MAGIC: &[u8; 6] = b"i3-ipc";
+
+/// i3 IPC message types.
+const I3_IPC_SUBSCRIBE: u32 = 2;
+const I3_IPC_GET_TREE: u32 = 4;
+
+/// Event type of `window` events (high bit marks events).
+const I3_IPC_EVENT_WINDOW: u32 = 0x8000_0003;
+
+/// Header size: magic, payload length and message type.
+const I3_IPC_HEADER_SIZE: usize = 14;
+
+/// Writes one i3 IPC message.
+pub fn write_i3_message(stream: &mut impl Write, kind: u32, payload: &[u8]) -> io::Result<()> {
+    let mut message = Vec::with_capacity(I3_IPC_HEADER_SIZE + payload.len());
+    message.extend_from_slice(I3_IPC_MAGIC);
+    message.extend_from_slice(&(payload.len() as u32).to_ne_bytes());
+    message.extend_from_slice(&kind.to_ne_bytes());
+    message.extend_from_slice(payload);
+    stream.write_all(&message)
+}
+
+/// Reads one i3 IPC message, returning its type and payload.
+pub fn read_i3_message(stream: &mut impl Read) -> io::Result<(u32, Vec<u8>)> {
+    let mut header = [0u8; I3_IPC_HEADER_SIZE];
+    stream.read_exact(&mut header)?;
+    if &header[..6] != I3_IPC_MAGIC {
+        return Err(invalid_data("bad i3 IPC magic"));
+    }
+    let len = u32::from_ne_bytes([header[6], header[7], header[8], header[9]]) as usize;
+    let kind = u32::from_ne_bytes([header[10], header[11], header[12], header[13]]);
+    let mut payload = vec![0u8; len];
+    stream.read_exact(&mut payload)?;
+    Ok((kind, payload))
+}
+
+/// Converts a sway/i3 container to `WindowInfo`.
+///
+/// Native Wayland clients carry an `app_id`; XWayland and i3 clients carry
+/// `window_properties.class` instead.
+pub fn sway_window_info(container: &Value) -> WindowInfo {
+    let app_name = string_field(container, "app_id").or_else(|| {
+        container
+            .get("window_properties")
+            .and_then(|props| string_field(props, "class"))
+    });
+    let app_name = match app_name {
+        Some(app_name) => app_name,
+        // Workspaces and outputs get focus when they are empty.
+        None => return WindowInfo::default(),
+    };
+    WindowInfo {
+        title: string_field(container, "name").unwrap_or_else(|| "Unknown".to_string()),
+        app_name,
+        process_id: pid_field(container, "pid"),
+    }
+}
+
+/// Finds the focused node in a `GET_TREE` reply.
+pub fn find_focused(node: &Value) -> Option<&Value> {
+    if node.get("focused").and_then(Value::as_bool) == Some(true) {
+        return Some(node);
+    }
+    ["nodes", "floating_nodes"]
+        .iter()
+        .filter_map(|key| node.get(*key).and_then(Value::as_array))
+        .flatten()
+        .find_map(find_focused)
+}
+
+/// Follows sway/i3 focus until `state` is shut down.
+pub(crate) fn watch_sway(socket: &Path, state: &FocusState) -> io::Result<()> {
+    let mut stream = UnixStream::connect(socket)?;
+
+    write_i3_message(&mut stream, I3_IPC_GET_TREE, b"")?;
+    let (_, tree) = read_i3_message(&mut stream)?;
+    let tree: Value = serde_json::from_slice(&tree).map_err(invalid_data)?;
+    state.switch(find_focused(&tree).map(sway_window_info).unwrap_or_default());
+
+    write_i3_message(&mut stream, I3_IPC_SUBSCRIBE, br#"["window"]"#)?;
+    let (_, reply) = read_i3_message(&mut stream)?;
+    let reply: Value = serde_json::from_slice(&reply).map_err(invalid_data)?;
+    if reply.get("success").and_then(Value::as_bool) != Some(true) {
+        return Err(invalid_data("sway refused the window subscription"));
+    }
+
+    while !state.is_shut_down() {
+        if !wait_readable(stream.as_raw_fd(), FOCUS_POLL_INTERVAL) {
+            continue;
+        }
+        let (kind, payload) = read_i3_message(&mut stream)?;
+        if kind != I3_IPC_EVENT_WINDOW {
+            continue;
+        }
+        let event: Value = serde_json::from_slice(&payload).map_err(invalid_data)?;
+        let container = &event["container"];
+        match event.get("change").and_then(Value::as_str) {
+            Some("focus") => state.switch(sway_window_info(container)),
+            Some("title") if container.get("focused").and_then(Value::as_bool) == Some(true) => {
+                state.store(sway_window_info(container));
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// Hyprland
+// ---------------------------------------------------------------------------
+
+/// Converts Hyprland's `j/activewindow` reply to `WindowInfo`. An empty
+/// object means no window is focused.
+pub fn hyprland_window_info(window: &Value) -> WindowInfo {
+    match string_field(window, "class") {
+        Some(app_name) => WindowInfo {
+            title: string_field(window, "title").unwrap_or_else(|| "Unknown".to_string()),
+            app_name,
+            process_id: pid_field(window, "pid"),
+        },
+        None => WindowInfo::default(),
+    }
+}
+
+/// Parses the `CLASS,TITLE` payload of an `activewindow` event. Used when
+/// the request socket cannot be queried; it carries no PID.
+pub fn parse_hyprland_active_window(data: &str) -> WindowInfo {
+    let (class, title) = data.split_once(',').unwrap_or((data, ""));
+    if class.is_empty() {
+        return WindowInfo::default();
+    }
+    WindowInfo {
+        title: if title.is_empty() { "Unknown".to_string() } else { title.to_string() },
+        app_name: class.to_string(),
+        process_id: None,
+    }
+}
+
+/// Queries the active window over Hyprland's request socket.
+fn query_hyprland_active_window(requests: &Path) -> io::Result<WindowInfo> {
+    let mut stream = UnixStream::connect(requests)?;
+    stream.write_all(b"j/activewindow")?;
+    let mut reply = Vec::new();
+    stream.read_to_end(&mut reply)?;
+    let window: Value = serde_json::from_slice(&reply).map_err(invalid_data)?;
+    Ok(hyprland_window_info(&window))
+}
+
+/// Follows Hyprland focus until `state` is shut down.
+pub(crate) fn watch_hyprland(requests: &Path, events: &Path, state: &FocusState) -> io::Result<()> {
+    // Subscribe before the initial query so no switch is missed in between.
+    let stream = UnixStream::connect(events)?;
+    let fd = stream.as_raw_fd();
+    let mut reader = BufReader::new(stream);
+    state.switch(query_hyprland_active_window(requests)?);
+
+    let mut line = String::new();
+    while !state.is_shut_down() {
+        if reader.buffer().is_empty() && !wait_readable(fd, FOCUS_POLL_INTERVAL) {
+            continue;
+        }
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "Hyprland closed the event socket",
+            ));
+        }
+        let (event, data) = match line.trim_end_matches('\n').split_once(">>") {
+            Some(parts) => parts,
+            None => continue,
+        };
+        match event {
+            "activewindow" => {
+                let info = query_hyprland_active_window(requests)
+                    .unwrap_or_else(|_| parse_hyprland_active_window(data));
+                state.switch(info);
+            }
+            "windowtitle" | "windowtitlev2" => {
+                if let Ok(info) = query_hyprland_active_window(requests) {
+                    state.store(info);
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// KWin
+// ---------------------------------------------------------------------------
+
+/// Object path and interface the KWin script calls.
+pub const KWIN_TRACKER_PATH: &str = "/FocusTracker";
+pub const KWIN_TRACKER_INTERFACE: &str = "org.muttontext.FocusTracker";
+
+/// Plugin name the script is loaded under, so a stale copy can be unloaded.
+const KWIN_SCRIPT_NAME: &str = "muttontext-focus";
+
+/// Receives window reports from the KWin script.
+pub struct FocusTracker {
+    state: Arc<FocusState>,
+}
+
+impl FocusTracker {
+    pub(crate) fn new(state: Arc<FocusState>) -> Self {
+        Self { state }
+    }
+
+    fn window_info(caption: String, resource_class: String, pid: &str) -> WindowInfo {
+        if resource_class.is_empty() {
+            return WindowInfo::default();
+        }
+        WindowInfo {
+            title: if caption.is_empty() { "Unknown".to_string() } else { caption },
+            app_name: resource_class,
+            process_id: pid.parse().ok().filter(|&pid| pid > 0),
+        }
+    }
+}
+
+#[zbus::interface(name = "org.muttontext.FocusTracker")]
+impl FocusTracker {
+    /// A window was activated. Arguments are strings because KWin's
+    /// `callDBus` passes script numbers as doubles.
+    fn window_activated(&self, caption: String, resource_class: String, pid: String) {
+        self.state.switch(Self::window_info(caption, resource_class, &pid));
+    }
+
+    /// The active window's caption changed.
+    fn caption_changed(&self, caption: String, resource_class: String, pid: String) {
+        self.state.store(Self::window_info(caption, resource_class, &pid));
+    }
+}
+
+/// Returns the KWin script that reports activations to `service`.
+///
+/// Handles both KWin 6 (`windowActivated`, `activeWindow`) and KWin 5
+/// (`clientActivated`, `activeClient`).
+pub fn kwin_script(service: &str) -> String {
+    format!(
+        r#"// Reports the active window to MuttonText.
+var current = null;
+
+function report(method, window) {{
+    callDBus("{service}", "{path}", "{interface}", method,
+             String(window.caption), String(window.resourceClass), String(window.pid));
+}}
+
+function captionChanged() {{
+    if (current) {{
+        report("CaptionChanged", current);
+    }}
+}}
+
+function activated(window) {{
+    if (current) {{
+        current.captionChanged.disconnect(captionChanged);
+    }}
+    current = window;
+    if (window) {{
+        window.captionChanged.connect(captionChanged);
+        report("WindowActivated", window);
+    }}
+}}
+
+(workspace.windowActivated || workspace.clientActivated).connect(activated);
+activated(workspace.activeWindow || workspace.activeClient);
+"#,
+        service = service,
+        path = KWIN_TRACKER_PATH,
+        interface = KWIN_TRACKER_INTERFACE,
+    )
+}
+
+/// Calls a method on KWin's scripting interface.
+fn call_kwin_scripting<B>(
+    connection: &zbus::blocking::Connection,
+    method: &str,
+    body: &B,
+) -> zbus::Result<zbus::Message>
+where
+    B: serde::Serialize + zbus::zvariant::DynamicType,
+{
+    connection.call_method(
+        Some("org.kde.KWin"),
+        "/Scripting",
+        Some("org.kde.kwin.Scripting"),
+        method,
+        body,
+    )
+}
+
+/// Loads the reporting script into KWin and serves its callbacks until
+/// `state` is shut down.
+pub(crate) fn watch_kwin(state: &Arc<FocusState>) -> io::Result<()> {
+    let connection = zbus::blocking::connection::Builder::session()
+        .and_then(|builder| builder.serve_at(KWIN_TRACKER_PATH, FocusTracker::new(state.clone())))
+        .and_then(|builder| builder.build())
+        .map_err(invalid_data)?;
+    let service = connection
+        .unique_name()
+        .map(|name| name.to_string())
+        .ok_or_else(|| invalid_data("session bus assigned no unique name"))?;
+
+    let dir = std::env::var_os("XDG_RUNTIME_DIR")
+        .filter(|dir| !dir.is_empty())
+        .map_or_else(std::env::temp_dir, PathBuf::from);
+    let script_path = write_kwin_script(&dir, &kwin_script(&service))?;
+
+    // A copy left by a previous run would report to a dead connection.
+    let _ = call_kwin_scripting(&connection, "unloadScript", &(KWIN_SCRIPT_NAME,));
+    let path = script_path.to_string_lossy().into_owned();
+    let loaded = call_kwin_scripting(&connection, "loadScript", &(path.as_str(), KWIN_SCRIPT_NAME))
+        .and_then(|_| call_kwin_scripting(&connection, "start", &()));
+
+    // KWin may read the file after `start` returns, so it stays until the
+    // script is unloaded.
+    if loaded.is_ok() {
+        while !state.is_shut_down() {
+            thread::sleep(FOCUS_POLL_INTERVAL);
+        }
+        let _ = call_kwin_scripting(&connection, "unloadScript", &(KWIN_SCRIPT_NAME,));
+    }
+    let _ = fs::remove_file(&script_path);
+    loaded.map(|_| ()).map_err(invalid_data)
+}
+
+/// Writes `script` to a new file in `dir` that only this user can read.
+///
+/// The name is unique to the process and the file must not exist yet, so
+/// a file or symlink another user placed there first is never written
+/// through or handed to KWin.
+fn write_kwin_script(dir: &Path, script: &str) -> io::Result<PathBuf> {
+    use std::os::unix::fs::OpenOptionsExt;
+
+    let mut last_err = None;
+    for attempt in 0..16 {
+        let path = dir.join(format!(
+            "{}-{}-{}.js",
+            KWIN_SCRIPT_NAME,
+            std::process::id(),
+            attempt
+        ));
+        match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&path)
+        {
+            Ok(mut file) => {
+                if let Err(e) = file.write_all(script.as_bytes()) {
+                    let _ = fs::remove_file(&path);
+                    return Err(e);
+                }
+                return Ok(path);
+            }
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => last_err = Some(e),
+            Err(e) => return Err(e),
+        }
+    }
+    Err(last_err.unwrap_or_else(|| io::Error::from(io::ErrorKind::AlreadyExists)))
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::keyboard_hook::FocusDetector;
+    use crate::platform::linux::{FocusBackend, LinuxFocusDetector};
+    use serde_json::json;
+    use std::os::unix::net::UnixListener;
+    use std::sync::mpsc;
+    use std::sync::Mutex;
+    use std::time::{Duration, Instant};
+
+    fn wait_for(det: &LinuxFocusDetector, pred: impl Fn(&WindowInfo) -> bool) -> WindowInfo {
+        let deadline = Instant::now() + Duration::from_secs(3);
+        loop {
+            let info = det.get_active_window_info().unwrap();
+            if pred(&info) || Instant::now() > deadline {
+                return info;
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+    }
+
+    fn recording_detector(backend: FocusBackend) -> (LinuxFocusDetector, Arc<Mutex<Vec<String>>>) {
+        let mut det = LinuxFocusDetector::with_backend(backend);
+        let switches = Arc::new(Mutex::new(Vec::new()));
+        let recorded = switches.clone();
+        det.on_focus_change(Box::new(move |info| {
+            recorded.lock().unwrap().push(info.app_name);
+        }));
+        (det, switches)
+    }
+
+    // ── sway / i3 ──
+
+    #[test]
+    fn test_i3_message_roundtrip() {
+        let mut buf = Vec::new();
+        write_i3_message(&mut buf, I3_IPC_SUBSCRIBE, br#"["window"]"#).unwrap();
+        assert_eq!(&buf[..6], b"i3-ipc");
+        let (kind, payload) = read_i3_message(&mut buf.as_slice()).unwrap();
+        assert_eq!(kind, I3_IPC_SUBSCRIBE);
+        assert_eq!(payload, br#"["window"]"#);
+
+        let bad = b"i3-ipx\0\0\0\0\0\0\0\0".to_vec();
+        assert!(read_i3_message(&mut bad.as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_sway_window_info_wayland_and_xwayland() {
+        let native = json!({"name": "notes.md", "app_id": "org.gnome.TextEditor", "pid": 4242});
+        assert_eq!(
+            sway_window_info(&native),
+            WindowInfo {
+                title: "notes.md".into(),
+                app_name: "org.gnome.TextEditor".into(),
+                process_id: Some(4242),
+            }
+        );
+
+        let xwayland = json!({
+            "name": "Steam", "app_id": null, "pid": 77,
+            "window_properties": {"class": "Steam", "instance": "steam"}
+        });
+        assert_eq!(sway_window_info(&xwayland).app_name, "Steam");
+
+        let workspace = json!({"name": "1", "type": "workspace", "focused": true});
+        assert_eq!(sway_window_info(&workspace), WindowInfo::default());
+    }
+
+    #[test]
+    fn test_find_focused_searches_floating_nodes() {
+        let tree = json!({
+            "nodes": [{"nodes": [{"app_id": "foot", "focused": false}]}],
+            "floating_nodes": [],
+        });
+        assert!(find_focused(&tree).is_none());
+
+        let tree = json!({
+            "nodes": [{
+                "nodes": [{"app_id": "foot", "focused": false}],
+                "floating_nodes": [{"app_id": "pavucontrol", "focused": true}],
+            }],
+        });
+        assert_eq!(find_focused(&tree).unwrap()["app_id"], "pavucontrol");
+    }
+
+    /// A fake sway that answers `GET_TREE` and `SUBSCRIBE`, then forwards
+    /// window events sent through the returned channel.
+    fn fake_sway(dir: &Path, tree: Value) -> (std::path::PathBuf, mpsc::Sender<Value>) {
+        let path = dir.join("sway-ipc.sock");
+        let listener = UnixListener::bind(&path).unwrap();
+        let (tx, rx) = mpsc::channel::<Value>();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let (kind, _) = read_i3_message(&mut stream).unwrap();
+            assert_eq!(kind, I3_IPC_GET_TREE);
+            write_i3_message(&mut stream, I3_IPC_GET_TREE, tree.to_string().as_bytes()).unwrap();
+            let (kind, payload) = read_i3_message(&mut stream).unwrap();
+            assert_eq!(kind, I3_IPC_SUBSCRIBE);
+            assert_eq!(payload, br#"["window"]"#);
+            write_i3_message(&mut stream, I3_IPC_SUBSCRIBE, br#"{"success":true}"#).unwrap();
+            for event in rx {
+                let payload = event.to_string();
+                if write_i3_message(&mut stream, I3_IPC_EVENT_WINDOW, payload.as_bytes()).is_err() {
+                    break;
+                }
+            }
+        });
+        (path, tx)
+    }
+
+    #[test]
+    fn test_sway_backend_follows_window_events() {
+        let dir = tempfile::tempdir().unwrap();
+        let tree = json!({"nodes": [{"app_id": "foot", "name": "~", "pid": 10, "focused": true}]});
+        let (path, events) = fake_sway(dir.path(), tree);
+
+        let (det, switches) = recording_detector(FocusBackend::Sway(path));
+        assert_eq!(wait_for(&det, |i| i.app_name == "foot").process_id, Some(10));
+
+        events
+            .send(json!({"change": "focus", "container": {
+                "app_id": "firefox", "name": "Docs", "pid": 20, "focused": true
+            }}))
+            .unwrap();
+        assert_eq!(wait_for(&det, |i| i.app_name == "firefox").title, "Docs");
+
+        // Title changes update the cache without counting as a switch.
+        events
+            .send(json!({"change": "title", "container": {
+                "app_id": "firefox", "name": "Mail", "pid": 20, "focused": true
+            }}))
+            .unwrap();
+        assert_eq!(wait_for(&det, |i| i.title == "Mail").title, "Mail");
+
+        assert_eq!(*switches.lock().unwrap(), vec!["foot".to_string(), "firefox".to_string()]);
+    }
+
+    // ── Hyprland ──
+
+    #[test]
+    fn test_hyprland_window_info() {
+        let window = json!({"class": "kitty", "title": "vim", "pid": 31337, "address": "0x1"});
+        assert_eq!(
+            hyprland_window_info(&window),
+            WindowInfo {
+                title: "vim".into(),
+                app_name: "kitty".into(),
+                process_id: Some(31337),
+            }
+        );
+        assert_eq!(hyprland_window_info(&json!({})), WindowInfo::default());
+    }
+
+    #[test]
+    fn test_parse_hyprland_event_payload() {
+        let info = parse_hyprland_active_window("kitty,vim: main.rs, line 4");
+        assert_eq!(info.app_name, "kitty");
+        assert_eq!(info.title, "vim: main.rs, line 4");
+        assert_eq!(parse_hyprland_active_window(","), WindowInfo::default());
+    }
+
+    /// A fake Hyprland: the request socket answers `j/activewindow` with the
+    /// shared window, the event socket forwards lines from the channel.
+    fn fake_hyprland(dir: &Path, window: Arc<Mutex<Value>>) -> (FocusBackend, mpsc::Sender<String>) {
+        let requests = dir.join(".socket.sock");
+        let events = dir.join(".socket2.sock");
+
+        let request_listener = UnixListener::bind(&requests).unwrap();
+        thread::spawn(move || {
+            for stream in request_listener.incoming() {
+                let mut stream = match stream {
+                    Ok(stream) => stream,
+                    Err(_) => break,
+                };
+                let mut request = [0u8; 14];
+                stream.read_exact(&mut request).unwrap();
+                assert_eq!(&request, b"j/activewindow");
+                let reply = window.lock().unwrap().to_string();
+                let _ = stream.write_all(reply.as_bytes());
+            }
+        });
+
+        let event_listener = UnixListener::bind(&events).unwrap();
+        let (tx, rx) = mpsc::channel::<String>();
+        thread::spawn(move || {
+            let (mut stream, _) = event_listener.accept().unwrap();
+            for line in rx {
+                if stream.write_all(line.as_bytes()).is_err() {
+                    break;
+                }
+            }
+        });
+
+        (FocusBackend::Hyprland { requests, events }, tx)
+    }
+
+    #[test]
+    fn test_hyprland_backend_follows_activewindow() {
+        let dir = tempfile::tempdir().unwrap();
+        let window = Arc::new(Mutex::new(json!({"class": "kitty", "title": "zsh", "pid": 5})));
+        let (backend, events) = fake_hyprland(dir.path(), window.clone());
+
+        let (det, switches) = recording_detector(backend);
+        assert_eq!(wait_for(&det, |i| i.app_name == "kitty").process_id, Some(5));
+
+        *window.lock().unwrap() = json!({"class": "firefox", "title": "News", "pid": 6});
+        events
+            .send("activewindowv2>>55aa\nactivewindow>>firefox,News\n".to_string())
+            .unwrap();
+        assert_eq!(wait_for(&det, |i| i.app_name == "firefox").process_id, Some(6));
+
+        *window.lock().unwrap() = json!({"class": "firefox", "title": "Weather", "pid": 6});
+        events.send("windowtitlev2>>55aa,Weather\n".to_string()).unwrap();
+        assert_eq!(wait_for(&det, |i| i.title == "Weather").title, "Weather");
+
+        assert_eq!(*switches.lock().unwrap(), vec!["kitty".to_string(), "firefox".to_string()]);
+    }
+
+    // ── KWin ──
+
+    #[test]
+    fn test_kwin_script_reports_to_service() {
+        let script = kwin_script(":1.42");
+        assert!(script.contains(r#"callDBus(":1.42", "/FocusTracker", "org.muttontext.FocusTracker""#));
+        assert!(script.contains("workspace.windowActivated || workspace.clientActivated"));
+        assert!(script.contains("\"WindowActivated\""));
+        assert!(script.contains("\"CaptionChanged\""));
+    }
+
+    #[test]
+    fn test_kwin_script_file_is_private_and_new() {
+        use std::os::unix::fs::{symlink, PermissionsExt};
+
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("target");
+        fs::write(&target, "keep").unwrap();
+        // Someone else claimed the first name with a symlink.
+        let first = dir
+            .path()
+            .join(format!("{}-{}-0.js", KWIN_SCRIPT_NAME, std::process::id()));
+        symlink(&target, &first).unwrap();
+
+        let path = write_kwin_script(dir.path(), "script").unwrap();
+        assert_ne!(path, first);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "script");
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        assert_eq!(fs::read_to_string(&target).unwrap(), "keep");
+    }
+
+    #[test]
+    fn test_kwin_tracker_over_dbus_socket() {
+        // The KWin script's calls, made over a peer-to-peer D-Bus socket.
+        let (server_socket, client_socket) = UnixStream::pair().unwrap();
+        let state = Arc::new(FocusState::new());
+        let switches = Arc::new(Mutex::new(Vec::new()));
+        let recorded = switches.clone();
+        state.set_callback(Arc::new(move |info: WindowInfo| {
+            recorded.lock().unwrap().push(info.app_name);
+        }));
+
+        let server_state = state.clone();
+        let server = thread::spawn(move || {
+            zbus::blocking::connection::Builder::unix_stream(server_socket)
+                .server(zbus::Guid::generate())
+                .unwrap()
+                .p2p()
+                .serve_at(KWIN_TRACKER_PATH, FocusTracker::new(server_state))
+                .unwrap()
+                .build()
+                .unwrap()
+        });
+        let client = zbus::blocking::connection::Builder::unix_stream(client_socket)
+            .p2p()
+            .build()
+            .unwrap();
+        let _server = server.join().unwrap();
+
+        let call = |method: &str, args: (&str, &str, &str)| {
+            client
+                .call_method(
+                    None::<&str>,
+                    KWIN_TRACKER_PATH,
+                    Some(KWIN_TRACKER_INTERFACE),
+                    method,
+                    &args,
+                )
+                .unwrap();
+        };
+        call("WindowActivated", ("Inbox", "thunderbird", "1234"));
+        assert_eq!(
+            state.current(),
+            WindowInfo {
+                title: "Inbox".into(),
+                app_name: "thunderbird".into(),
+                process_id: Some(1234),
+            }
+        );
+
+        call("CaptionChanged", ("Drafts", "thunderbird", "1234"));
+        assert_eq!(state.current().title, "Drafts");
+
+        call("WindowActivated", ("", "", "0"));
+        assert_eq!(state.current(), WindowInfo::default());
+        assert_eq!(
+            *switches.lock().unwrap(),
+            vec!["thunderbird".to_string(), "Unknown".to_string()]
+        );
+    }
+}
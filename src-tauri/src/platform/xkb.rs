@@ -0,0 +1,734 @@
+//! Layout-aware key translation using libxkbcommon.
+//!
+//! Physical keycodes are translated through the active XKB keymap and a
+//! running XKB state, so QWERTZ, AZERTY, Dvorak and other layouts produce
+//! the characters the user actually sees. Dead keys and `Multi_key`
+//! sequences are resolved through the locale's compose table, so typing
+//! `´` then `e` yields a single `é`.
+//!
+//! Keycodes are XKB keycodes (evdev code + 8), which is what X11 reports.
+//!
+//! The keymap names come from the X server when there is one, and from
+//! `systemd-localed` (the keymap the console and most compositors are
+//! configured with) otherwise. An [`ActiveLayout`] carries later layout
+//! changes to the threads that translate keys.
+
+#![cfg(target_os = "linux")]
+
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use xkbcommon::xkb;
+use xkbcommon::xkb::keysyms;
+
+use crate::platform::keyboard_hook::{Key, PlatformError};
+
+/// Offset between evdev scancodes and XKB keycodes.
+pub const EVDEV_OFFSET: u32 = 8;
+
+/// RMLVO names identifying an XKB keymap.
+///
+/// Empty fields fall back to libxkbcommon's defaults, which honour the
+/// `XKB_DEFAULT_*` environment variables.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct XkbNames {
+    pub rules: String,
+    pub model: String,
+    pub layout: String,
+    pub variant: String,
+    pub options: Option<String>,
+}
+
+impl XkbNames {
+    /// Names for a single layout (and optional variant) with default rules.
+    pub fn layout(layout: &str, variant: &str) -> Self {
+        Self {
+            layout: layout.to_string(),
+            variant: variant.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Queries the active keymap names: from the X server's root window
+    /// when a display is available, otherwise from `systemd-localed`.
+    ///
+    /// Falls back to defaults if neither source answers.
+    pub fn query_active() -> Self {
+        let has_display = std::env::var_os("DISPLAY").is_some_and(|d| !d.is_empty());
+        let names = if has_display {
+            crate::platform::linux::query_x11_rules_names()
+        } else {
+            None
+        };
+        match names.or_else(Self::query_localed) {
+            Some(names) => names,
+            None => {
+                tracing::debug!("No keymap source available; using default XKB keymap names");
+                Self::default()
+            }
+        }
+    }
+
+    /// Reads the system keymap from `org.freedesktop.locale1`. Pure Wayland
+    /// sessions have no X server to ask, and compositors without their own
+    /// keyboard settings use this keymap.
+    fn query_localed() -> Option<Self> {
+        let connection = zbus::blocking::Connection::system().ok()?;
+        let proxy = zbus::blocking::Proxy::new(
+            &connection,
+            "org.freedesktop.locale1",
+            "/org/freedesktop/locale1",
+            "org.freedesktop.locale1",
+        )
+        .ok()?;
+        let property = |name: &str| proxy.get_property::<String>(name).unwrap_or_default();
+        let layout = property("X11Layout");
+        if layout.is_empty() {
+            return None;
+        }
+        let options = property("X11Options");
+        Some(Self {
+            rules: String::new(),
+            model: property("X11Model"),
+            layout,
+            variant: property("X11Variant"),
+            options: Some(options).filter(|o| !o.is_empty()),
+        })
+    }
+
+    /// Parses the root window's `_XKB_RULES_NAMES` property, which holds
+    /// rules, model, layout, variant and options as NUL-terminated strings.
+    pub fn parse_rules_names(data: &[u8]) -> Option<Self> {
+        let mut fields = data
+            .split(|&b| b == 0)
+            .map(|field| String::from_utf8_lossy(field).into_owned());
+        let names = Self {
+            rules: fields.next()?,
+            model: fields.next().unwrap_or_default(),
+            layout: fields.next().unwrap_or_default(),
+            variant: fields.next().unwrap_or_default(),
+            options: fields.next().filter(|o| !o.is_empty()),
+        };
+        Some(names).filter(|names| !names.layout.is_empty())
+    }
+}
+
+/// The keymap in use on the display, shared between the thread that
+/// follows layout changes and the hook threads translating keys.
+///
+/// Every change bumps a generation counter, so translators only take the
+/// lock when something actually changed.
+pub struct ActiveLayout {
+    names: Mutex<XkbNames>,
+    group: AtomicU32,
+    generation: AtomicU64,
+}
+
+impl ActiveLayout {
+    pub fn new(names: XkbNames) -> Self {
+        Self {
+            names: Mutex::new(names),
+            group: AtomicU32::new(0),
+            generation: AtomicU64::new(0),
+        }
+    }
+
+    /// Starts from the names [`XkbNames::query_active`] reports.
+    pub fn query() -> Self {
+        Self::new(XkbNames::query_active())
+    }
+
+    pub fn names(&self) -> XkbNames {
+        self.names.lock().map(|n| n.clone()).unwrap_or_default()
+    }
+
+    /// The locked layout group, i.e. which of the keymap's layouts is
+    /// selected.
+    pub fn group(&self) -> u32 {
+        self.group.load(Ordering::SeqCst)
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::SeqCst)
+    }
+
+    /// Records new keymap names, e.g. after `setxkbmap de`.
+    pub fn set_names(&self, names: XkbNames) {
+        let mut current = match self.names.lock() {
+            Ok(current) => current,
+            Err(_) => return,
+        };
+        if *current != names {
+            tracing::info!(
+                "XKB layout changed to '{}' (variant '{}')",
+                names.layout,
+                names.variant
+            );
+            *current = names;
+            self.generation.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    /// Records a switch between the layouts of a multi-layout keymap.
+    pub fn set_group(&self, group: u32) {
+        if self.group.swap(group, Ordering::SeqCst) != group {
+            self.generation.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+}
+
+/// Re-reads the `systemd-localed` keymap into `layout` whenever localed
+/// announces a change. Blocks until the system bus connection closes.
+pub fn watch_localed(layout: &ActiveLayout) -> Result<(), PlatformError> {
+    let bus_error = |e: zbus::Error| PlatformError::Internal(format!("localed: {}", e));
+    let connection = zbus::blocking::Connection::system().map_err(bus_error)?;
+    let rule = zbus::MatchRule::builder()
+        .msg_type(zbus::message::Type::Signal)
+        .interface("org.freedesktop.DBus.Properties")
+        .and_then(|builder| builder.member("PropertiesChanged"))
+        .and_then(|builder| builder.path("/org/freedesktop/locale1"))
+        .map_err(bus_error)?
+        .build();
+    let messages = zbus::blocking::MessageIterator::for_match_rule(rule, &connection, None)
+        .map_err(bus_error)?;
+    for message in messages {
+        message.map_err(bus_error)?;
+        if let Some(names) = XkbNames::query_localed() {
+            layout.set_names(names);
+        }
+    }
+    Ok(())
+}
+
+/// Returns the locale used to pick a compose table, following the usual
+/// `LC_ALL` → `LC_CTYPE` → `LANG` precedence.
+fn compose_locale() -> OsString {
+    ["LC_ALL", "LC_CTYPE", "LANG"]
+        .iter()
+        .filter_map(std::env::var_os)
+        .find(|v| !v.is_empty())
+        .unwrap_or_else(|| OsString::from("C"))
+}
+
+/// Compiles the keymap named by `names`.
+fn compile_keymap(context: &xkb::Context, names: &XkbNames) -> Result<xkb::Keymap, PlatformError> {
+    xkb::Keymap::new_from_names(
+        context,
+        &names.rules,
+        &names.model,
+        &names.layout,
+        &names.variant,
+        names.options.clone(),
+        xkb::KEYMAP_COMPILE_NO_FLAGS,
+    )
+    .ok_or_else(|| PlatformError::Internal(format!("Failed to compile XKB keymap for {:?}", names)))
+}
+
+/// Translates XKB keycodes into [`Key`]s using a keymap, a key state that
+/// tracks modifiers, locks and the active layout group, and an optional
+/// compose state for dead keys.
+pub struct XkbTranslator {
+    context: xkb::Context,
+    state: xkb::State,
+    compose: Option<xkb::compose::State>,
+}
+
+impl XkbTranslator {
+    /// Compiles the keymap named by `names` and loads the compose table for
+    /// the current locale. A missing compose table is not an error; dead
+    /// keys are then ignored instead of composed.
+    pub fn new(names: &XkbNames) -> Result<Self, PlatformError> {
+        let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+        let keymap = compile_keymap(&context, names)?;
+        let state = xkb::State::new(&keymap);
+
+        let compose = xkb::compose::Table::new_from_locale(
+            &context,
+            &compose_locale(),
+            xkb::compose::COMPILE_NO_FLAGS,
+        )
+        .ok()
+        .map(|table| xkb::compose::State::new(&table, xkb::compose::STATE_NO_FLAGS));
+        if compose.is_none() {
+            tracing::debug!("No compose table for locale; dead keys will be ignored");
+        }
+
+        Ok(Self {
+            context,
+            state,
+            compose,
+        })
+    }
+
+    /// Builds a translator for `layout`'s current keymap and group.
+    pub fn from_layout(layout: &ActiveLayout) -> Result<Self, PlatformError> {
+        let names = layout.names();
+        tracing::info!(
+            "Using XKB layout '{}' (variant '{}')",
+            names.layout,
+            names.variant
+        );
+        let mut translator = Self::new(&names)?;
+        translator.lock_group(layout.group());
+        Ok(translator)
+    }
+
+    /// Selects one of the keymap's layouts, keeping the modifier state.
+    pub fn lock_group(&mut self, group: u32) {
+        let depressed = self.state.serialize_mods(xkb::STATE_MODS_DEPRESSED);
+        let latched = self.state.serialize_mods(xkb::STATE_MODS_LATCHED);
+        let locked = self.state.serialize_mods(xkb::STATE_MODS_LOCKED);
+        self.state.update_mask(depressed, latched, locked, 0, 0, group);
+    }
+
+    /// Replaces the compose table, e.g. with one parsed from a buffer.
+    pub fn set_compose_table(&mut self, compose_file: &str) -> Result<(), PlatformError> {
+        let locale = compose_locale();
+        let table = xkb::compose::Table::new_from_buffer(
+            &self.context,
+            compose_file,
+            &locale.to_string_lossy(),
+            xkb::compose::FORMAT_TEXT_V1,
+            xkb::compose::COMPILE_NO_FLAGS,
+        )
+        .map_err(|_| PlatformError::Internal("Failed to parse compose table".into()))?;
+        self.compose = Some(xkb::compose::State::new(&table, xkb::compose::STATE_NO_FLAGS));
+        Ok(())
+    }
+
+    /// Records a key press and returns the key it produced.
+    ///
+    /// Returns `None` for modifier keys, dead keys and keys that continue a
+    /// compose sequence; the composed character is returned with the key
+    /// that completes the sequence.
+    pub fn press(&mut self, keycode: u32) -> Option<Key> {
+        let code = xkb::Keycode::new(keycode);
+        let keysym = self.state.key_get_one_sym(code);
+        self.state.update_key(code, xkb::KeyDirection::Down);
+        self.translate(keysym)
+    }
+
+    /// Records a key release. Releases never produce characters but must be
+    /// fed so that modifier and group state stays accurate.
+    pub fn release(&mut self, keycode: u32) {
+        self.state
+            .update_key(xkb::Keycode::new(keycode), xkb::KeyDirection::Up);
+    }
+
+    fn translate(&mut self, keysym: xkb::Keysym) -> Option<Key> {
+        let raw = keysym.raw();
+        if keysym.is_modifier_key() {
+            return None;
+        }
+        if let Some(key) = special_key(raw) {
+            // Navigation and editing keys abort any pending compose sequence.
+            if let Some(compose) = self.compose.as_mut() {
+                compose.reset();
+            }
+            return Some(key);
+        }
+
+        if let Some(compose) = self.compose.as_mut() {
+            if compose.feed(keysym) == xkb::compose::FeedResult::Accepted {
+                match compose.status() {
+                    xkb::compose::Status::Composing => return None,
+                    xkb::compose::Status::Composed => {
+                        let text = compose.utf8().unwrap_or_default();
+                        compose.reset();
+                        return Some(text_to_key(&text, keysym));
+                    }
+                    xkb::compose::Status::Cancelled => {
+                        compose.reset();
+                        return None;
+                    }
+                    xkb::compose::Status::Nothing => {}
+                }
+            }
+        }
+
+        if (keysyms::KEY_dead_grave..=keysyms::KEY_dead_longsolidusoverlay).contains(&raw)
+            || raw == keysyms::KEY_Multi_key
+        {
+            return None;
+        }
+        // The keysym's own character is used rather than the state's UTF-8
+        // output, which applies the Control transformation (Ctrl+C -> U+0003)
+        // and would hide the letter from shortcut detection.
+        let text = keysym.key_char().map(String::from).unwrap_or_default();
+        Some(text_to_key(&text, keysym))
+    }
+}
+
+/// Highest XKB keycode considered when looking up keystrokes.
+const MAX_TYPING_KEYCODE: u32 = 255;
+
+/// XKB keycodes of the left Shift and right Alt keys.
+const KC_LEFT_SHIFT: u32 = 50;
+const KC_RIGHT_ALT: u32 = 108;
+
+/// A physical key plus the modifiers that must be held to type a
+/// character with it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Keystroke {
+    /// XKB keycode (evdev code + 8).
+    pub keycode: u32,
+    pub shift: bool,
+    /// Whether AltGr (`ISO_Level3_Shift`) must be held.
+    pub level3: bool,
+}
+
+/// Reverse lookup from characters to the keystrokes that type them, the
+/// inverse of [`XkbTranslator`]. Only one layout group of the keymap is
+/// considered, and the keystroke needing the fewest modifiers wins.
+#[derive(Debug, Clone)]
+pub struct KeystrokeMap {
+    keystrokes: HashMap<char, Keystroke>,
+    /// Keycode of the left Shift key.
+    pub shift_keycode: u32,
+    /// Keycode of the key producing `ISO_Level3_Shift`, if the layout has one.
+    pub level3_keycode: Option<u32>,
+}
+
+impl KeystrokeMap {
+    /// Builds the map for the first layout of the keymap named by `names`.
+    pub fn new(names: &XkbNames) -> Result<Self, PlatformError> {
+        Self::for_group(names, 0)
+    }
+
+    /// Builds the map for layout `group` of the keymap named by `names`.
+    pub fn for_group(names: &XkbNames, group: u32) -> Result<Self, PlatformError> {
+        let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+        let keymap = compile_keymap(&context, names)?;
+        let mut state = xkb::State::new(&keymap);
+        state.update_mask(0, 0, 0, 0, 0, group);
+        // Keys beyond the core X11 range are rare extended keys that
+        // virtual keyboards don't register.
+        let max_keycode = keymap.max_keycode().raw().min(MAX_TYPING_KEYCODE);
+        let keycodes: Vec<xkb::Keycode> = (keymap.min_keycode().raw()..=max_keycode)
+            .map(xkb::Keycode::new)
+            .collect();
+
+        // Prefer the conventional physical key over virtual ones such as
+        // `<LVL3>` that produce the same keysym.
+        let find_keycode = |preferred: u32, keysym: u32| {
+            let produces = |kc: xkb::Keycode| state.key_get_one_sym(kc).raw() == keysym;
+            if produces(xkb::Keycode::new(preferred)) {
+                return Some(preferred);
+            }
+            keycodes.iter().copied().find(|&kc| produces(kc)).map(|kc| kc.raw())
+        };
+        let shift_keycode = find_keycode(KC_LEFT_SHIFT, keysyms::KEY_Shift_L).unwrap_or(KC_LEFT_SHIFT);
+        let level3_keycode = find_keycode(KC_RIGHT_ALT, keysyms::KEY_ISO_Level3_Shift);
+
+        let mod_mask = |name: &str| match keymap.mod_get_index(name) {
+            xkb::MOD_INVALID => 0,
+            index => 1 << index,
+        };
+        let shift_mask = mod_mask(xkb::MOD_NAME_SHIFT);
+        let level3_mask = mod_mask(xkb::MOD_NAME_ISO_LEVEL3_SHIFT);
+
+        let mut levels = vec![(false, false), (true, false)];
+        if level3_keycode.is_some() && level3_mask != 0 {
+            levels.extend([(false, true), (true, true)]);
+        }
+
+        let mut keystrokes = HashMap::new();
+        for (shift, level3) in levels {
+            let mut depressed = 0;
+            if shift {
+                depressed |= shift_mask;
+            }
+            if level3 {
+                depressed |= level3_mask;
+            }
+            state.update_mask(depressed, 0, 0, 0, 0, group);
+            for &kc in &keycodes {
+                let keysym = state.key_get_one_sym(kc);
+                if keysym.is_modifier_key() {
+                    continue;
+                }
+                if let Some(ch) = keysym.key_char().filter(|c| !c.is_control()) {
+                    keystrokes.entry(ch).or_insert(Keystroke {
+                        keycode: kc.raw(),
+                        shift,
+                        level3,
+                    });
+                }
+            }
+        }
+
+        Ok(Self {
+            keystrokes,
+            shift_keycode,
+            level3_keycode,
+        })
+    }
+
+    /// Builds the map for `layout`'s current keymap and group.
+    pub fn from_layout(layout: &ActiveLayout) -> Result<Self, PlatformError> {
+        Self::for_group(&layout.names(), layout.group())
+    }
+
+    /// Returns the keystroke that types `ch`, if the layout has one.
+    pub fn get(&self, ch: char) -> Option<Keystroke> {
+        self.keystrokes.get(&ch).copied()
+    }
+}
+
+/// Maps keysyms for non-printing keys to their [`Key`] variants.
+fn special_key(keysym: u32) -> Option<Key> {
+    let key = match keysym {
+        keysyms::KEY_BackSpace => Key::Backspace,
+        keysyms::KEY_Return | keysyms::KEY_KP_Enter => Key::Enter,
+        keysyms::KEY_Tab | keysyms::KEY_ISO_Left_Tab | keysyms::KEY_KP_Tab => Key::Tab,
+        keysyms::KEY_Escape => Key::Escape,
+        keysyms::KEY_space | keysyms::KEY_KP_Space => Key::Space,
+        keysyms::KEY_Delete | keysyms::KEY_KP_Delete => Key::Delete,
+        keysyms::KEY_Left | keysyms::KEY_KP_Left => Key::Left,
+        keysyms::KEY_Right | keysyms::KEY_KP_Right => Key::Right,
+        keysyms::KEY_Up | keysyms::KEY_KP_Up => Key::Up,
+        keysyms::KEY_Down | keysyms::KEY_KP_Down => Key::Down,
+        keysyms::KEY_Home | keysyms::KEY_KP_Home => Key::Home,
+        keysyms::KEY_End | keysyms::KEY_KP_End => Key::End,
+        keysyms::KEY_Page_Up | keysyms::KEY_KP_Page_Up => Key::PageUp,
+        keysyms::KEY_Page_Down | keysyms::KEY_KP_Page_Down => Key::PageDown,
+        k if (keysyms::KEY_F1..=keysyms::KEY_F35).contains(&k) => {
+            Key::F((k - keysyms::KEY_F1 + 1) as u8)
+        }
+        _ => return None,
+    };
+    Some(key)
+}
+
+/// Converts the text produced by a key into a [`Key`]. Anything other than
+/// a single printable character is reported as [`Key::Other`].
+fn text_to_key(text: &str, keysym: xkb::Keysym) -> Key {
+    let mut chars = text.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if !c.is_control() => Key::Char(c),
+        _ => Key::Other(xkb::keysym_get_name(keysym)),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Tests (those compiling a keymap need the xkeyboard-config data files and
+// only run with `--ignored`)
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // XKB keycodes (evdev + 8) of the physical keys used below.
+    const KC_Q: u32 = 24;
+    const KC_Y: u32 = 29;
+    const KC_EQUAL: u32 = 21;
+    const KC_A: u32 = 38;
+    const KC_S: u32 = 39;
+    const KC_APOSTROPHE: u32 = 48;
+    const KC_LSHIFT: u32 = 50;
+    const KC_Z: u32 = 52;
+    const KC_E: u32 = 26;
+    const KC_SPACE: u32 = 65;
+    const KC_BACKSPACE: u32 = 22;
+    const KC_RALT: u32 = 108;
+
+    const COMPOSE: &str = r#"
+<dead_acute> <e> : "é" eacute
+<dead_circumflex> <a> : "â" acircumflex
+<Multi_key> <a> <e> : "æ" ae
+"#;
+
+    fn translator(layout: &str, variant: &str) -> XkbTranslator {
+        let mut t = XkbTranslator::new(&XkbNames::layout(layout, variant)).expect("keymap");
+        t.set_compose_table(COMPOSE).expect("compose table");
+        t
+    }
+
+    fn tap(t: &mut XkbTranslator, keycode: u32) -> Option<Key> {
+        let key = t.press(keycode);
+        t.release(keycode);
+        key
+    }
+
+    #[test]
+    #[ignore = "needs the xkeyboard-config data files"]
+    fn test_us_layout() {
+        let mut t = translator("us", "");
+        assert_eq!(tap(&mut t, KC_Z), Some(Key::Char('z')));
+        assert_eq!(tap(&mut t, KC_Y), Some(Key::Char('y')));
+    }
+
+    #[test]
+    #[ignore = "needs the xkeyboard-config data files"]
+    fn test_german_layout_swaps_y_and_z() {
+        let mut t = translator("de", "");
+        assert_eq!(tap(&mut t, KC_Z), Some(Key::Char('y')));
+        assert_eq!(tap(&mut t, KC_Y), Some(Key::Char('z')));
+        assert_eq!(tap(&mut t, KC_APOSTROPHE), Some(Key::Char('ä')));
+    }
+
+    #[test]
+    #[ignore = "needs the xkeyboard-config data files"]
+    fn test_french_layout() {
+        let mut t = translator("fr", "");
+        assert_eq!(tap(&mut t, KC_Q), Some(Key::Char('a')));
+        assert_eq!(tap(&mut t, KC_A), Some(Key::Char('q')));
+    }
+
+    #[test]
+    #[ignore = "needs the xkeyboard-config data files"]
+    fn test_dvorak_layout() {
+        let mut t = translator("us", "dvorak");
+        assert_eq!(tap(&mut t, KC_S), Some(Key::Char('o')));
+    }
+
+    #[test]
+    #[ignore = "needs the xkeyboard-config data files"]
+    fn test_shift_produces_uppercase() {
+        let mut t = translator("de", "");
+        assert_eq!(t.press(KC_LSHIFT), None);
+        assert_eq!(tap(&mut t, KC_APOSTROPHE), Some(Key::Char('Ä')));
+        t.release(KC_LSHIFT);
+        assert_eq!(tap(&mut t, KC_APOSTROPHE), Some(Key::Char('ä')));
+    }
+
+    #[test]
+    #[ignore = "needs the xkeyboard-config data files"]
+    fn test_dead_key_composes_with_next_key() {
+        // On the German layout the key right of ß is dead_acute.
+        let mut t = translator("de", "");
+        assert_eq!(tap(&mut t, KC_EQUAL), None);
+        assert_eq!(tap(&mut t, KC_E), Some(Key::Char('é')));
+        assert_eq!(tap(&mut t, KC_E), Some(Key::Char('e')));
+    }
+
+    #[test]
+    #[ignore = "needs the xkeyboard-config data files"]
+    fn test_multi_key_sequence() {
+        let names = XkbNames {
+            options: Some("compose:ralt".into()),
+            ..XkbNames::layout("us", "")
+        };
+        let mut t = XkbTranslator::new(&names).expect("keymap");
+        t.set_compose_table(COMPOSE).expect("compose table");
+        assert_eq!(tap(&mut t, KC_RALT), None);
+        assert_eq!(tap(&mut t, KC_A), None);
+        assert_eq!(tap(&mut t, KC_E), Some(Key::Char('æ')));
+    }
+
+    #[test]
+    #[ignore = "needs the xkeyboard-config data files"]
+    fn test_special_keys() {
+        let mut t = translator("us", "");
+        assert_eq!(tap(&mut t, KC_SPACE), Some(Key::Space));
+        assert_eq!(tap(&mut t, KC_BACKSPACE), Some(Key::Backspace));
+    }
+
+    #[test]
+    #[ignore = "needs the xkeyboard-config data files"]
+    fn test_special_key_cancels_compose() {
+        let mut t = translator("de", "");
+        assert_eq!(tap(&mut t, KC_EQUAL), None);
+        assert_eq!(tap(&mut t, KC_BACKSPACE), Some(Key::Backspace));
+        assert_eq!(tap(&mut t, KC_E), Some(Key::Char('e')));
+    }
+
+    #[test]
+    fn test_parse_rules_names() {
+        let data = b"evdev\0pc105\0de,us\0nodeadkeys,\0grp:alt_shift_toggle\0";
+        let names = XkbNames::parse_rules_names(data).expect("names");
+        assert_eq!(names.rules, "evdev");
+        assert_eq!(names.model, "pc105");
+        assert_eq!(names.layout, "de,us");
+        assert_eq!(names.variant, "nodeadkeys,");
+        assert_eq!(names.options.as_deref(), Some("grp:alt_shift_toggle"));
+
+        let names = XkbNames::parse_rules_names(b"evdev\0pc105\0us\0\0\0").expect("names");
+        assert_eq!(names.options, None);
+        assert_eq!(XkbNames::parse_rules_names(b"evdev\0pc105\0\0\0\0"), None);
+    }
+
+    #[test]
+    fn test_active_layout_counts_changes() {
+        let layout = ActiveLayout::new(XkbNames::layout("us", ""));
+        layout.set_names(XkbNames::layout("us", ""));
+        layout.set_group(0);
+        assert_eq!(layout.generation(), 0);
+
+        layout.set_names(XkbNames::layout("de", ""));
+        assert_eq!(layout.generation(), 1);
+        assert_eq!(layout.names().layout, "de");
+        layout.set_group(1);
+        assert_eq!((layout.generation(), layout.group()), (2, 1));
+    }
+
+    #[test]
+    #[ignore = "needs the xkeyboard-config data files"]
+    fn test_translator_follows_locked_group() {
+        let layout = ActiveLayout::new(XkbNames::layout("us,de", ""));
+        layout.set_group(1);
+        let mut t = XkbTranslator::from_layout(&layout).expect("keymap");
+        assert_eq!(tap(&mut t, KC_Y), Some(Key::Char('z')));
+        t.lock_group(0);
+        assert_eq!(tap(&mut t, KC_Y), Some(Key::Char('y')));
+    }
+
+    // ── Keystroke map ──
+
+    #[test]
+    #[ignore = "needs the xkeyboard-config data files"]
+    fn test_keystroke_map_us() {
+        let map = KeystrokeMap::new(&XkbNames::layout("us", "")).expect("keymap");
+        assert_eq!(
+            map.get('a'),
+            Some(Keystroke { keycode: KC_A, shift: false, level3: false })
+        );
+        assert_eq!(
+            map.get('A'),
+            Some(Keystroke { keycode: KC_A, shift: true, level3: false })
+        );
+        assert_eq!(map.shift_keycode, KC_LSHIFT);
+        assert_eq!(map.get('€'), None);
+    }
+
+    #[test]
+    #[ignore = "needs the xkeyboard-config data files"]
+    fn test_keystroke_map_german_uses_altgr() {
+        let map = KeystrokeMap::new(&XkbNames::layout("de", "")).expect("keymap");
+        assert_eq!(map.get('z').map(|k| k.keycode), Some(KC_Y));
+        assert_eq!(map.level3_keycode, Some(KC_RALT));
+        let at = map.get('@').expect("@ on de layout");
+        assert_eq!((at.keycode, at.shift, at.level3), (KC_Q, false, true));
+    }
+
+    #[test]
+    #[ignore = "needs the xkeyboard-config data files"]
+    fn test_keystroke_map_for_group() {
+        let names = XkbNames::layout("us,de", "");
+        let first = KeystrokeMap::for_group(&names, 0).expect("keymap");
+        let second = KeystrokeMap::for_group(&names, 1).expect("keymap");
+        assert_eq!(first.get('z').map(|k| k.keycode), Some(KC_Z));
+        assert_eq!(second.get('z').map(|k| k.keycode), Some(KC_Y));
+        assert_eq!(second.get('ä').map(|k| k.keycode), Some(KC_APOSTROPHE));
+    }
+
+    #[test]
+    #[ignore = "needs the xkeyboard-config data files"]
+    fn test_keystroke_map_roundtrips_through_translator() {
+        let map = KeystrokeMap::new(&XkbNames::layout("fr", "")).expect("keymap");
+        let mut t = translator("fr", "");
+        for ch in "azerty AZERTY 1234 é".chars() {
+            let ks = map.get(ch).expect("character on fr layout");
+            if ks.shift {
+                t.press(map.shift_keycode);
+            }
+            assert_eq!(tap(&mut t, ks.keycode), Some(if ch == ' ' { Key::Space } else { Key::Char(ch) }));
+            if ks.shift {
+                t.release(map.shift_keycode);
+            }
+        }
+    }
+}
@@ -0,0 +1,550 @@
+//! In-process X11 keyboard output through the XTest extension.
+//!
+//! Replaces spawning `xdotool` for every expansion. Like `xdotool type`,
+//! keys are sent as real key events, so terminals accept them, modifiers
+//! the user is still holding are released while typing and restored
+//! afterwards, and characters missing from the keymap are typed by briefly
+//! remapping a spare keycode to the character's keysym.
+//!
+//! Applications translate a key event with whatever mapping they hold when
+//! they read it, so a spare keycode must not change while events sent
+//! with its old keysym may still be unread. Several spare keycodes are used
+//! in turn, and one is only remapped or cleared after the server has
+//! processed the earlier events and clients have had a moment to read
+//! them.
+
+#![cfg(target_os = "linux")]
+
+use std::os::raw::{c_char, c_int, c_uint};
+use std::ptr;
+use std::thread;
+use std::time::Duration;
+
+use x11::keysym;
+use x11::xlib;
+use x11::xtest;
+
+use crate::platform::keyboard_hook::{Key, Modifiers, PlatformError};
+
+/// Keysym value meaning "no symbol".
+const NO_SYMBOL: xlib::KeySym = 0;
+
+/// Most spare keycodes taken over for missing keysyms.
+const MAX_SCRATCH_KEYCODES: usize = 8;
+
+/// Time given to clients to read key events sent with a scratch keycode
+/// before its mapping changes again.
+const SCRATCH_SETTLE_DELAY: Duration = Duration::from_millis(50);
+
+/// Modifier keysyms released before typing, as `xdotool --clearmodifiers`
+/// does.
+const MODIFIER_KEYSYMS: [c_uint; 9] = [
+    keysym::XK_Shift_L,
+    keysym::XK_Shift_R,
+    keysym::XK_Control_L,
+    keysym::XK_Control_R,
+    keysym::XK_Alt_L,
+    keysym::XK_Alt_R,
+    keysym::XK_Super_L,
+    keysym::XK_Super_R,
+    keysym::XK_ISO_Level3_Shift,
+];
+
+/// Returns the keysym that types `ch`, or `None` for control characters
+/// other than newline and tab.
+///
+/// Latin-1 characters map to their own code point; everything else uses
+/// the Unicode keysym range (`0x0100_0000 + code point`).
+pub fn char_to_keysym(ch: char) -> Option<xlib::KeySym> {
+    let cp = ch as u32;
+    let sym = match ch {
+        '\n' => keysym::XK_Return,
+        '\t' => keysym::XK_Tab,
+        _ if ch.is_control() => return None,
+        _ if (0x20..=0x7e).contains(&cp) || (0xa0..=0xff).contains(&cp) => cp,
+        _ => 0x0100_0000 + cp,
+    };
+    Some(xlib::KeySym::from(sym))
+}
+
+/// Returns the keysym for `key` when pressed as part of a shortcut.
+///
+/// Letters map to their lowercase keysym, so `Key::Char('V')` is the V key
+/// rather than Shift+V. `Key::Other("Insert")` names the Insert key, which
+/// has no variant of its own.
+pub fn key_to_keysym(key: &Key) -> Option<xlib::KeySym> {
+    let sym = match key {
+        Key::Char(c) => return c.to_lowercase().next().and_then(char_to_keysym),
+        Key::Backspace => keysym::XK_BackSpace,
+        Key::Enter => keysym::XK_Return,
+        Key::Tab => keysym::XK_Tab,
+        Key::Escape => keysym::XK_Escape,
+        Key::Space => keysym::XK_space,
+        Key::Delete => keysym::XK_Delete,
+        Key::Left => keysym::XK_Left,
+        Key::Right => keysym::XK_Right,
+        Key::Up => keysym::XK_Up,
+        Key::Down => keysym::XK_Down,
+        Key::Home => keysym::XK_Home,
+        Key::End => keysym::XK_End,
+        Key::PageUp => keysym::XK_Page_Up,
+        Key::PageDown => keysym::XK_Page_Down,
+        Key::F(n @ 1..=24) => keysym::XK_F1 + c_uint::from(*n) - 1,
+        Key::Other(name) if name == "Insert" => keysym::XK_Insert,
+        _ => return None,
+    };
+    Some(xlib::KeySym::from(sym))
+}
+
+/// Keysyms of the left-hand keys for the modifiers set in `modifiers`.
+fn modifier_keysyms(modifiers: Modifiers) -> Vec<c_uint> {
+    [
+        (modifiers.ctrl, keysym::XK_Control_L),
+        (modifiers.shift, keysym::XK_Shift_L),
+        (modifiers.alt, keysym::XK_Alt_L),
+        (modifiers.meta, keysym::XK_Super_L),
+    ]
+    .iter()
+    .filter(|(held, _)| *held)
+    .map(|&(_, sym)| sym)
+    .collect()
+}
+
+/// A connection to the X server for sending synthetic key events.
+///
+/// Opening one is cheap, so callers open a connection per expansion rather
+/// than sharing one across threads.
+pub struct XTestKeyboard {
+    display: *mut xlib::Display,
+    /// Keycodes with no symbols, used in turn to type keysyms the keymap
+    /// lacks, with the keysym each one currently carries.
+    scratch: Vec<(xlib::KeyCode, xlib::KeySym)>,
+    /// Index of the scratch keycode to remap next.
+    next_scratch: usize,
+}
+
+// The display connection is only ever used by the thread that owns the
+// value.
+unsafe impl Send for XTestKeyboard {}
+
+impl XTestKeyboard {
+    /// Opens the display named by `$DISPLAY` and checks that it supports
+    /// XTest.
+    pub fn open() -> Result<Self, PlatformError> {
+        // SAFETY: a null name selects `$DISPLAY`; the result is checked.
+        let display = unsafe { xlib::XOpenDisplay(ptr::null()) };
+        if display.is_null() {
+            return Err(PlatformError::NotSupported(
+                "Cannot open X display; XTest output needs X11 or XWayland".into(),
+            ));
+        }
+        let (mut event_base, mut error_base, mut major, mut minor) = (0, 0, 0, 0);
+        // SAFETY: `display` is a live connection and the out-pointers are valid.
+        let has_xtest = unsafe {
+            xtest::XTestQueryExtension(
+                display,
+                &mut event_base,
+                &mut error_base,
+                &mut major,
+                &mut minor,
+            )
+        };
+        if has_xtest == 0 {
+            // SAFETY: the connection was opened above and is not used again.
+            unsafe { xlib::XCloseDisplay(display) };
+            return Err(PlatformError::NotSupported(
+                "X server does not support the XTest extension".into(),
+            ));
+        }
+        let scratch = find_spare_keycodes(display, MAX_SCRATCH_KEYCODES)
+            .into_iter()
+            .map(|keycode| (keycode, NO_SYMBOL))
+            .collect();
+        Ok(Self {
+            display,
+            scratch,
+            next_scratch: 0,
+        })
+    }
+
+    /// Types `text`, releasing any held modifiers first.
+    pub fn type_text(&mut self, text: &str, delay: Duration) -> Result<(), PlatformError> {
+        let held = self.release_held_modifiers(delay);
+        let result = text
+            .chars()
+            .filter_map(char_to_keysym)
+            .try_for_each(|sym| self.tap_keysym(sym, delay));
+        self.restore_scratch();
+        self.press_keycodes(&held, delay);
+        result
+    }
+
+    /// Presses the key producing `sym` (e.g. `XK_BackSpace`) `count` times,
+    /// releasing any held modifiers first.
+    pub fn tap_repeated(
+        &mut self,
+        sym: c_uint,
+        count: usize,
+        delay: Duration,
+    ) -> Result<(), PlatformError> {
+        let held = self.release_held_modifiers(delay);
+        let result = (0..count).try_for_each(|_| self.tap_keysym(xlib::KeySym::from(sym), delay));
+        self.restore_scratch();
+        self.press_keycodes(&held, delay);
+        result
+    }
+
+    /// Presses `key` while holding `modifiers`, e.g. Ctrl+V. Modifiers the
+    /// user is already holding are left alone.
+    pub fn tap_chord(
+        &mut self,
+        modifiers: Modifiers,
+        key: &Key,
+        delay: Duration,
+    ) -> Result<(), PlatformError> {
+        let sym = key_to_keysym(key)
+            .ok_or_else(|| PlatformError::NotSupported(format!("No keysym for key {}", key)))?;
+        let (keycode, _) = self.keycode_for(sym)?;
+        let held: Vec<xlib::KeyCode> = modifier_keysyms(modifiers)
+            .into_iter()
+            .map(|sym| self.keysym_to_keycode(xlib::KeySym::from(sym)))
+            .filter(|&keycode| keycode != 0)
+            .collect();
+        self.press_keycodes(&held, delay);
+        self.send(keycode, true, delay);
+        self.send(keycode, false, delay);
+        for &modifier in held.iter().rev() {
+            self.send(modifier, false, delay);
+        }
+        self.restore_scratch();
+        Ok(())
+    }
+
+    /// Presses and releases the key producing `sym`, holding Shift if the
+    /// keysym sits on the key's shifted level.
+    fn tap_keysym(&mut self, sym: xlib::KeySym, delay: Duration) -> Result<(), PlatformError> {
+        let (keycode, shift) = self.keycode_for(sym)?;
+        let shift_keycode = self.keysym_to_keycode(xlib::KeySym::from(keysym::XK_Shift_L));
+        if shift {
+            self.send(shift_keycode, true, delay);
+        }
+        self.send(keycode, true, delay);
+        self.send(keycode, false, delay);
+        if shift {
+            self.send(shift_keycode, false, delay);
+        }
+        Ok(())
+    }
+
+    /// Finds a keycode that types `sym` on the first or shifted level,
+    /// remapping a scratch keycode if no key has it.
+    fn keycode_for(&mut self, sym: xlib::KeySym) -> Result<(xlib::KeyCode, bool), PlatformError> {
+        let keycode = self.keysym_to_keycode(sym);
+        if keycode != 0 {
+            for (level, shift) in [(0, false), (1, true)] {
+                // SAFETY: `self.display` is live for the lifetime of `self`.
+                if unsafe { xlib::XkbKeycodeToKeysym(self.display, keycode, 0, level) } == sym {
+                    return Ok((keycode, shift));
+                }
+            }
+        }
+
+        if let Some(&(scratch, _)) = self.scratch.iter().find(|&&(_, mapped)| mapped == sym) {
+            return Ok((scratch, false));
+        }
+        if self.scratch.is_empty() {
+            return Err(PlatformError::Internal(format!(
+                "No spare keycode to type keysym {:#x}",
+                sym
+            )));
+        }
+        let index = self.next_scratch;
+        self.next_scratch = (index + 1) % self.scratch.len();
+        let (scratch, previous) = self.scratch[index];
+        if previous != NO_SYMBOL {
+            // Events typed with the previous keysym may still be unread.
+            self.settle();
+        }
+        self.set_scratch_mapping(scratch, sym);
+        self.scratch[index].1 = sym;
+        Ok((scratch, false))
+    }
+
+    /// Whether any scratch keycode currently carries a remapped keysym.
+    fn is_remapped(&self) -> bool {
+        self.scratch.iter().any(|&(_, sym)| sym != NO_SYMBOL)
+    }
+
+    /// Waits until the server has processed every event sent so far, then
+    /// gives clients [`SCRATCH_SETTLE_DELAY`] to read them.
+    fn settle(&self) {
+        // SAFETY: `self.display` is live for the lifetime of `self`.
+        unsafe { xlib::XSync(self.display, xlib::False) };
+        thread::sleep(SCRATCH_SETTLE_DELAY);
+    }
+
+    fn keysym_to_keycode(&self, sym: xlib::KeySym) -> xlib::KeyCode {
+        // SAFETY: `self.display` is live for the lifetime of `self`.
+        unsafe { xlib::XKeysymToKeycode(self.display, sym) }
+    }
+
+    /// Maps `keycode` to `sym` on both levels and waits for the server to
+    /// apply it.
+    fn set_scratch_mapping(&self, keycode: xlib::KeyCode, sym: xlib::KeySym) {
+        let mut syms = [sym, sym];
+        // SAFETY: `syms` holds one keycode's worth (2) of keysyms.
+        unsafe {
+            xlib::XChangeKeyboardMapping(self.display, c_int::from(keycode), 2, syms.as_mut_ptr(), 1);
+            xlib::XSync(self.display, xlib::False);
+        }
+    }
+
+    /// Clears the scratch keycodes' temporary mappings, if any, once the
+    /// events typed with them have been read.
+    fn restore_scratch(&mut self) {
+        if !self.is_remapped() {
+            return;
+        }
+        self.settle();
+        for index in 0..self.scratch.len() {
+            let (scratch, sym) = self.scratch[index];
+            if sym != NO_SYMBOL {
+                self.set_scratch_mapping(scratch, NO_SYMBOL);
+                self.scratch[index].1 = NO_SYMBOL;
+            }
+        }
+        self.next_scratch = 0;
+    }
+
+    /// Sends one key event and flushes it to the server.
+    fn send(&self, keycode: xlib::KeyCode, press: bool, delay: Duration) {
+        // SAFETY: `self.display` is live for the lifetime of `self`.
+        unsafe {
+            xtest::XTestFakeKeyEvent(
+                self.display,
+                c_uint::from(keycode),
+                c_int::from(press),
+                xlib::CurrentTime,
+            );
+            xlib::XFlush(self.display);
+        }
+        thread::sleep(delay);
+    }
+
+    /// Returns true if `keycode` is currently down.
+    fn is_key_down(&self, keycode: xlib::KeyCode) -> bool {
+        let mut keys: [c_char; 32] = [0; 32];
+        // SAFETY: XQueryKeymap fills exactly 32 bytes.
+        unsafe { xlib::XQueryKeymap(self.display, keys.as_mut_ptr()) };
+        let byte = keys[usize::from(keycode / 8)] as u8;
+        byte & (1 << (keycode % 8)) != 0
+    }
+
+    /// Releases every held modifier key and returns their keycodes.
+    fn release_held_modifiers(&self, delay: Duration) -> Vec<xlib::KeyCode> {
+        let mut held: Vec<xlib::KeyCode> = MODIFIER_KEYSYMS
+            .iter()
+            .map(|&sym| self.keysym_to_keycode(xlib::KeySym::from(sym)))
+            .filter(|&keycode| keycode != 0 && self.is_key_down(keycode))
+            .collect();
+        held.dedup();
+        for &keycode in &held {
+            self.send(keycode, false, delay);
+        }
+        held
+    }
+
+    fn press_keycodes(&self, keycodes: &[xlib::KeyCode], delay: Duration) {
+        for &keycode in keycodes {
+            self.send(keycode, true, delay);
+        }
+    }
+}
+
+impl Drop for XTestKeyboard {
+    fn drop(&mut self) {
+        self.restore_scratch();
+        // SAFETY: the connection is not used after this point.
+        unsafe { xlib::XCloseDisplay(self.display) };
+    }
+}
+
+/// Returns up to `limit` keycodes that have no keysyms bound, highest
+/// first.
+fn find_spare_keycodes(display: *mut xlib::Display, limit: usize) -> Vec<xlib::KeyCode> {
+    let (mut min, mut max) = (0, 0);
+    let mut per_keycode = 0;
+    // SAFETY: `display` is live; the mapping returned by XGetKeyboardMapping
+    // holds `(max - min + 1) * per_keycode` keysyms and is freed below.
+    unsafe {
+        xlib::XDisplayKeycodes(display, &mut min, &mut max);
+        let count = max - min + 1;
+        let mapping = xlib::XGetKeyboardMapping(display, min as xlib::KeyCode, count, &mut per_keycode);
+        if mapping.is_null() {
+            return Vec::new();
+        }
+        let per = per_keycode.max(0) as usize;
+        let syms = std::slice::from_raw_parts(mapping, count.max(0) as usize * per);
+        let spare = (0..count as usize)
+            .rev()
+            .filter(|&i| syms[i * per..(i + 1) * per].iter().all(|&s| s == NO_SYMBOL))
+            .map(|i| (min as usize + i) as xlib::KeyCode)
+            .take(limit)
+            .collect();
+        xlib::XFree(mapping.cast());
+        spare
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Opens the test display. Tests that need one are ignored by default;
+    /// CI runs them with `--ignored` under `xvfb-run`.
+    fn keyboard() -> XTestKeyboard {
+        XTestKeyboard::open().expect("X display with XTest")
+    }
+
+    #[test]
+    fn test_char_to_keysym_latin1() {
+        assert_eq!(char_to_keysym('a'), Some(0x61));
+        assert_eq!(char_to_keysym(' '), Some(0x20));
+        assert_eq!(char_to_keysym('é'), Some(0xe9));
+    }
+
+    #[test]
+    fn test_char_to_keysym_unicode() {
+        assert_eq!(char_to_keysym('€'), Some(0x0100_20ac));
+        assert_eq!(char_to_keysym('☃'), Some(0x0100_2603));
+    }
+
+    #[test]
+    fn test_char_to_keysym_control_characters() {
+        assert_eq!(char_to_keysym('\n'), Some(xlib::KeySym::from(keysym::XK_Return)));
+        assert_eq!(char_to_keysym('\t'), Some(xlib::KeySym::from(keysym::XK_Tab)));
+        assert_eq!(char_to_keysym('\r'), None);
+        assert_eq!(char_to_keysym('\u{7}'), None);
+    }
+
+    #[test]
+    fn test_key_to_keysym_letters_are_lowercase() {
+        assert_eq!(key_to_keysym(&Key::Char('V')), Some(0x76));
+        assert_eq!(key_to_keysym(&Key::Char('v')), Some(0x76));
+    }
+
+    #[test]
+    fn test_key_to_keysym_special_keys() {
+        assert_eq!(key_to_keysym(&Key::Left), Some(xlib::KeySym::from(keysym::XK_Left)));
+        assert_eq!(key_to_keysym(&Key::F(5)), Some(xlib::KeySym::from(keysym::XK_F5)));
+        assert_eq!(
+            key_to_keysym(&Key::Other("Insert".into())),
+            Some(xlib::KeySym::from(keysym::XK_Insert))
+        );
+        assert_eq!(key_to_keysym(&Key::Other("Launch".into())), None);
+        assert_eq!(key_to_keysym(&Key::F(0)), None);
+    }
+
+    #[test]
+    fn test_modifier_keysyms_in_press_order() {
+        let modifiers = Modifiers {
+            ctrl: true,
+            shift: true,
+            ..Default::default()
+        };
+        assert_eq!(modifier_keysyms(modifiers), vec![keysym::XK_Control_L, keysym::XK_Shift_L]);
+        assert!(modifier_keysyms(Modifiers::default()).is_empty());
+    }
+
+    // ── Under X (Xvfb in CI) ──
+
+    #[test]
+    #[ignore = "needs an X server"]
+    fn test_keycode_for_mapped_keysyms() {
+        let mut kb = keyboard();
+        let (a, shift) = kb.keycode_for(0x61).unwrap();
+        assert_ne!(a, 0);
+        assert!(!shift);
+        let (upper_a, shift) = kb.keycode_for(0x41).unwrap();
+        assert_eq!(upper_a, a);
+        assert!(shift);
+        assert!(!kb.is_remapped());
+    }
+
+    #[test]
+    #[ignore = "needs an X server"]
+    fn test_missing_keysym_uses_scratch_keycode() {
+        let mut kb = keyboard();
+        let snowman = char_to_keysym('☃').unwrap();
+        let (keycode, shift) = kb.keycode_for(snowman).unwrap();
+        assert_eq!(Some(keycode), kb.scratch.first().map(|&(scratch, _)| scratch));
+        assert!(!shift);
+        // SAFETY: the display is live for the lifetime of `kb`.
+        let mapped = unsafe { xlib::XkbKeycodeToKeysym(kb.display, keycode, 0, 0) };
+        assert_eq!(mapped, snowman);
+
+        kb.restore_scratch();
+        let restored = unsafe { xlib::XkbKeycodeToKeysym(kb.display, keycode, 0, 0) };
+        assert_eq!(restored, NO_SYMBOL);
+    }
+
+    #[test]
+    #[ignore = "needs an X server"]
+    fn test_missing_keysyms_rotate_through_scratch_keycodes() {
+        let mut kb = keyboard();
+        assert!(kb.scratch.len() > 1, "Xvfb leaves many keycodes unbound");
+        let snowman = char_to_keysym('☃').unwrap();
+        let euro = char_to_keysym('€').unwrap();
+        let (first, _) = kb.keycode_for(snowman).unwrap();
+        let (second, _) = kb.keycode_for(euro).unwrap();
+        assert_ne!(first, second);
+        // The first keycode keeps its keysym for events not yet read.
+        let mapped = unsafe { xlib::XkbKeycodeToKeysym(kb.display, first, 0, 0) };
+        assert_eq!(mapped, snowman);
+        assert_eq!(kb.keycode_for(snowman).unwrap(), (first, false));
+        kb.restore_scratch();
+        assert!(!kb.is_remapped());
+    }
+
+    #[test]
+    #[ignore = "needs an X server"]
+    fn test_type_text_leaves_no_keys_down() {
+        let mut kb = keyboard();
+        kb.type_text("Hi ☃\n", Duration::from_millis(1)).unwrap();
+        let shift = kb.keysym_to_keycode(xlib::KeySym::from(keysym::XK_Shift_L));
+        let h = kb.keysym_to_keycode(0x68);
+        assert!(!kb.is_key_down(shift));
+        assert!(!kb.is_key_down(h));
+        assert!(!kb.is_remapped());
+    }
+
+    #[test]
+    #[ignore = "needs an X server"]
+    fn test_held_modifier_restored_after_typing() {
+        let mut kb = keyboard();
+        let ctrl = kb.keysym_to_keycode(xlib::KeySym::from(keysym::XK_Control_L));
+        kb.send(ctrl, true, Duration::ZERO);
+        assert!(kb.is_key_down(ctrl));
+
+        kb.tap_repeated(keysym::XK_BackSpace, 2, Duration::from_millis(1)).unwrap();
+        assert!(kb.is_key_down(ctrl));
+        kb.send(ctrl, false, Duration::ZERO);
+    }
+
+    #[test]
+    #[ignore = "needs an X server"]
+    fn test_tap_chord_releases_modifiers() {
+        let mut kb = keyboard();
+        let modifiers = Modifiers {
+            ctrl: true,
+            ..Default::default()
+        };
+        kb.tap_chord(modifiers, &Key::Char('x'), Duration::from_millis(1)).unwrap();
+        let ctrl = kb.keysym_to_keycode(xlib::KeySym::from(keysym::XK_Control_L));
+        assert!(!kb.is_key_down(ctrl));
+    }
+}
@@ -0,0 +1,92 @@
+//! Grapheme-cluster helpers for typed text.
+//!
+//! Applications delete one grapheme cluster per Backspace and move the
+//! cursor one cluster per arrow key, so keyword and snippet lengths that
+//! turn into key presses are counted in clusters, not bytes or chars: an
+//! `é` typed as `e` plus a combining accent, or a ZWJ emoji sequence, is a
+//! single key press to remove.
+
+use unicode_segmentation::{GraphemeCursor, UnicodeSegmentation};
+
+/// Number of extended grapheme clusters in `text`.
+pub fn grapheme_count(text: &str) -> usize {
+    text.graphemes(true).count()
+}
+
+/// The last `count` grapheme clusters of `text`, or all of it if it has
+/// fewer.
+pub fn tail_graphemes(text: &str, count: usize) -> &str {
+    if count == 0 {
+        return "";
+    }
+    let start = text
+        .grapheme_indices(true)
+        .rev()
+        .nth(count - 1)
+        .map_or(0, |(idx, _)| idx);
+    &text[start..]
+}
+
+/// Byte offset at which the last grapheme cluster of `text` starts, or
+/// `None` if `text` is empty.
+pub fn last_grapheme_start(text: &str) -> Option<usize> {
+    text.grapheme_indices(true).next_back().map(|(idx, _)| idx)
+}
+
+/// Whether byte offset `idx` of `text` falls between two grapheme clusters
+/// (or at either end).
+pub fn is_grapheme_boundary(text: &str, idx: usize) -> bool {
+    if !text.is_char_boundary(idx) {
+        return false;
+    }
+    GraphemeCursor::new(idx, text.len(), true)
+        .is_boundary(text, 0)
+        .unwrap_or(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const E_ACUTE: &str = "e\u{301}";
+    const TECHNOLOGIST: &str = "\u{1F469}\u{200D}\u{1F4BB}";
+
+    #[test]
+    fn test_grapheme_count() {
+        assert_eq!(grapheme_count(""), 0);
+        assert_eq!(grapheme_count("abc"), 3);
+        assert_eq!(grapheme_count(&format!("caf{}", E_ACUTE)), 4);
+        assert_eq!(grapheme_count(TECHNOLOGIST), 1);
+        assert_eq!(grapheme_count("\u{1F1EB}\u{1F1F7}"), 1);
+    }
+
+    #[test]
+    fn test_tail_graphemes() {
+        let text = format!("a {}{}", TECHNOLOGIST, E_ACUTE);
+        assert_eq!(tail_graphemes(&text, 0), "");
+        assert_eq!(tail_graphemes(&text, 1), E_ACUTE);
+        assert_eq!(tail_graphemes(&text, 2), format!("{}{}", TECHNOLOGIST, E_ACUTE));
+        assert_eq!(tail_graphemes(&text, 10), text);
+    }
+
+    #[test]
+    fn test_last_grapheme_start() {
+        assert_eq!(last_grapheme_start(""), None);
+        assert_eq!(last_grapheme_start("ab"), Some(1));
+        assert_eq!(last_grapheme_start(&format!("x{}", TECHNOLOGIST)), Some(1));
+    }
+
+    #[test]
+    fn test_is_grapheme_boundary() {
+        let text = format!("x{}", TECHNOLOGIST);
+        assert!(is_grapheme_boundary(&text, 0));
+        assert!(is_grapheme_boundary(&text, 1));
+        assert!(is_grapheme_boundary(&text, text.len()));
+        // Inside the ZWJ sequence, at a char boundary and inside a char.
+        assert!(!is_grapheme_boundary(&text, 1 + '\u{1F469}'.len_utf8()));
+        assert!(!is_grapheme_boundary(&text, 2));
+
+        let text = format!("caf{}", E_ACUTE);
+        assert!(!is_grapheme_boundary(&text, 4));
+    }
+}
@@ -1,6 +1,8 @@
 // Shared utilities
 
+pub mod graphemes;
 pub mod memory;
+pub mod rich_text;
 
 pub use memory::{PooledBuffer, clear_caches};
 
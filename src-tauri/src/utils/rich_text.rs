@@ -0,0 +1,651 @@
+//! Rendering of rich-text snippets.
+//!
+//! HTML and Markdown snippets are pasted as `text/html` with a plain-text
+//! alternative, and typed as that plain text by the keystroke methods. This
+//! module converts the small subset of Markdown that snippets need to HTML,
+//! and HTML to readable plain text.
+
+use crate::models::SnippetFormat;
+
+/// A snippet ready to insert.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenderedSnippet {
+    /// Plain text, typed by keystroke methods and offered as `text/plain`.
+    pub text: String,
+    /// HTML offered alongside the text on the clipboard, for rich formats.
+    pub html: Option<String>,
+}
+
+/// Renders `snippet`, written in `format`, for insertion.
+pub fn render_snippet(snippet: &str, format: SnippetFormat) -> RenderedSnippet {
+    match format {
+        SnippetFormat::Plain => RenderedSnippet {
+            text: snippet.to_string(),
+            html: None,
+        },
+        SnippetFormat::Html => RenderedSnippet {
+            text: html_to_text(snippet),
+            html: Some(snippet.to_string()),
+        },
+        SnippetFormat::Markdown => {
+            let html = markdown_to_html(snippet);
+            RenderedSnippet {
+                text: html_to_text(&html),
+                html: Some(html),
+            }
+        }
+        // The snippet is an asset name, not text to insert.
+        SnippetFormat::Image => RenderedSnippet {
+            text: String::new(),
+            html: None,
+        },
+    }
+}
+
+/// Escapes `&`, `<`, `>` and quotes for use in HTML text and attributes.
+pub fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+// ---------------------------------------------------------------------------
+// Markdown → HTML
+// ---------------------------------------------------------------------------
+
+/// A block being collected by [`markdown_to_html`].
+enum Block {
+    Paragraph(Vec<String>),
+    List { ordered: bool, items: Vec<String> },
+}
+
+/// Converts Markdown to HTML.
+///
+/// Supports paragraphs, `#` headings, `-`/`*`/`+` and numbered lists,
+/// `**bold**`, `*italic*`, `` `code` ``, `[links](url)` and `<url>`
+/// autolinks. Unlike CommonMark, a single line break inside a paragraph is
+/// kept as `<br>`, since snippets such as signatures are laid out by line.
+pub fn markdown_to_html(markdown: &str) -> String {
+    let mut html: Vec<String> = Vec::new();
+    let mut block: Option<Block> = None;
+
+    for line in markdown.lines() {
+        let line = line.trim_end();
+        let trimmed = line.trim_start();
+
+        if trimmed.is_empty() {
+            flush_block(&mut html, block.take());
+            continue;
+        }
+        if let Some((level, text)) = heading(trimmed) {
+            flush_block(&mut html, block.take());
+            html.push(format!("<h{0}>{1}</h{0}>", level, inline_html(text)));
+            continue;
+        }
+        if let Some((ordered, text)) = list_item(trimmed) {
+            match &mut block {
+                Some(Block::List { ordered: o, items }) if *o == ordered => {
+                    items.push(text.to_string());
+                }
+                _ => {
+                    flush_block(&mut html, block.take());
+                    block = Some(Block::List {
+                        ordered,
+                        items: vec![text.to_string()],
+                    });
+                }
+            }
+            continue;
+        }
+        match &mut block {
+            // An indented line continues the previous list item.
+            Some(Block::List { items, .. }) if line.len() > trimmed.len() => {
+                if let Some(last) = items.last_mut() {
+                    last.push(' ');
+                    last.push_str(trimmed);
+                }
+            }
+            Some(Block::Paragraph(lines)) => lines.push(trimmed.to_string()),
+            _ => {
+                flush_block(&mut html, block.take());
+                block = Some(Block::Paragraph(vec![trimmed.to_string()]));
+            }
+        }
+    }
+    flush_block(&mut html, block);
+    html.join("\n")
+}
+
+fn flush_block(html: &mut Vec<String>, block: Option<Block>) {
+    match block {
+        Some(Block::Paragraph(lines)) => {
+            let lines: Vec<String> = lines.iter().map(|l| inline_html(l)).collect();
+            html.push(format!("<p>{}</p>", lines.join("<br>\n")));
+        }
+        Some(Block::List { ordered, items }) => {
+            let tag = if ordered { "ol" } else { "ul" };
+            let items: String = items
+                .iter()
+                .map(|item| format!("<li>{}</li>\n", inline_html(item)))
+                .collect();
+            html.push(format!("<{0}>\n{1}</{0}>", tag, items));
+        }
+        None => {}
+    }
+}
+
+/// `# Title` → `(1, "Title")`.
+fn heading(line: &str) -> Option<(usize, &str)> {
+    let level = line.chars().take_while(|&c| c == '#').count();
+    if !(1..=6).contains(&level) {
+        return None;
+    }
+    let rest = &line[level..];
+    if rest.is_empty() {
+        return Some((level, ""));
+    }
+    rest.strip_prefix(' ').map(|text| (level, text.trim()))
+}
+
+/// `- item` → `(false, "item")`, `2. item` → `(true, "item")`.
+fn list_item(line: &str) -> Option<(bool, &str)> {
+    for marker in ["- ", "* ", "+ "] {
+        if let Some(text) = line.strip_prefix(marker) {
+            return Some((false, text.trim_start()));
+        }
+    }
+    let digits = line.chars().take_while(char::is_ascii_digit).count();
+    if digits == 0 || digits > 9 {
+        return None;
+    }
+    let rest = &line[digits..];
+    rest.strip_prefix(". ")
+        .or_else(|| rest.strip_prefix(") "))
+        .map(|text| (true, text.trim_start()))
+}
+
+/// Converts inline Markdown in `text` to HTML, escaping everything else.
+fn inline_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut prev: Option<char> = None;
+    let mut rest = text;
+
+    while let Some(c) = rest.chars().next() {
+        let after = &rest[c.len_utf8()..];
+
+        // Backslash escapes a punctuation character.
+        if c == '\\' {
+            if let Some(next) = after.chars().next().filter(char::is_ascii_punctuation) {
+                out.push_str(&escape_html(&next.to_string()));
+                prev = Some(next);
+                rest = &after[next.len_utf8()..];
+                continue;
+            }
+        }
+
+        if c == '`' {
+            if let Some(end) = after.find('`') {
+                out.push_str("<code>");
+                out.push_str(&escape_html(&after[..end]));
+                out.push_str("</code>");
+                prev = Some('`');
+                rest = &after[end + 1..];
+                continue;
+            }
+        }
+
+        if c == '*' || c == '_' {
+            let strong = after.starts_with(c);
+            let delim = if strong { &rest[..2] } else { &rest[..1] };
+            let body = &rest[delim.len()..];
+            // `_` only emphasizes at word boundaries, so snake_case survives.
+            let opens = c == '*' || !prev.is_some_and(char::is_alphanumeric);
+            if opens && !body.starts_with(char::is_whitespace) {
+                if let Some(end) = find_closing(body, delim) {
+                    let closes = c == '*'
+                        || !body[end + delim.len()..]
+                            .starts_with(char::is_alphanumeric);
+                    if end > 0 && closes {
+                        let tag = if strong { "strong" } else { "em" };
+                        out.push_str(&format!("<{0}>{1}</{0}>", tag, inline_html(&body[..end])));
+                        prev = Some(c);
+                        rest = &body[end + delim.len()..];
+                        continue;
+                    }
+                }
+            }
+        }
+
+        if c == '[' {
+            if let Some((label, url, consumed)) = link(after) {
+                out.push_str(&format!(
+                    "<a href=\"{}\">{}</a>",
+                    escape_html(url),
+                    inline_html(label)
+                ));
+                prev = Some(')');
+                rest = &after[consumed..];
+                continue;
+            }
+        }
+
+        if c == '<' {
+            if let Some(end) = after.find('>') {
+                let url = &after[..end];
+                let is_url = ["http://", "https://", "mailto:"]
+                    .iter()
+                    .any(|scheme| url.starts_with(scheme))
+                    && !url.contains(char::is_whitespace);
+                if is_url {
+                    let label = url.strip_prefix("mailto:").unwrap_or(url);
+                    out.push_str(&format!(
+                        "<a href=\"{}\">{}</a>",
+                        escape_html(url),
+                        escape_html(label)
+                    ));
+                    prev = Some('>');
+                    rest = &after[end + 1..];
+                    continue;
+                }
+            }
+        }
+
+        out.push_str(&escape_html(&c.to_string()));
+        prev = Some(c);
+        rest = after;
+    }
+    out
+}
+
+/// Byte offset of the closing `delim` in `body`, not preceded by whitespace.
+fn find_closing(body: &str, delim: &str) -> Option<usize> {
+    let mut from = 0;
+    while let Some(pos) = body[from..].find(delim) {
+        let end = from + pos;
+        let before_ws = body[..end].ends_with(char::is_whitespace);
+        // `**` inside `*…*` belongs to a nested strong span.
+        let doubled = delim.len() == 1 && body[end + 1..].starts_with(delim);
+        if end > 0 && !before_ws && !doubled {
+            return Some(end);
+        }
+        from = end + if doubled { 2 } else { delim.len() };
+    }
+    None
+}
+
+/// Parses `label](url)` after an opening `[`, returning the label, the URL
+/// and the bytes consumed.
+fn link(after: &str) -> Option<(&str, &str, usize)> {
+    let close = after.find("](")?;
+    let label = &after[..close];
+    let url_start = close + 2;
+    let url_len = after[url_start..].find(')')?;
+    let url = after[url_start..url_start + url_len].trim();
+    if label.is_empty() || url.is_empty() || url.contains(char::is_whitespace) {
+        return None;
+    }
+    Some((label, url, url_start + url_len + 1))
+}
+
+// ---------------------------------------------------------------------------
+// HTML → plain text
+// ---------------------------------------------------------------------------
+
+/// Converts HTML to plain text.
+///
+/// Block elements start new lines, paragraphs and headings are separated by
+/// a blank line, list items get `- ` or `1. ` markers, and links whose text
+/// differs from their target are followed by the URL in parentheses.
+/// Whitespace is collapsed as a browser would, except inside `<pre>`.
+pub fn html_to_text(html: &str) -> String {
+    let mut out = String::with_capacity(html.len());
+    // One entry per open list: `None` for `<ul>`, the next number for `<ol>`.
+    let mut lists: Vec<Option<usize>> = Vec::new();
+    // Open links: the href and where their text starts in `out`.
+    let mut links: Vec<(String, usize)> = Vec::new();
+    let mut pre_depth = 0usize;
+    let mut rest = html;
+
+    while !rest.is_empty() {
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+            continue;
+        }
+        if rest.starts_with('<') {
+            let end = match rest.find('>') {
+                Some(end) => end,
+                None => break,
+            };
+            let tag = &rest[1..end];
+            rest = &rest[end + 1..];
+
+            let closing = tag.starts_with('/');
+            let name: String = tag
+                .trim_start_matches('/')
+                .chars()
+                .take_while(char::is_ascii_alphanumeric)
+                .collect::<String>()
+                .to_ascii_lowercase();
+
+            match (name.as_str(), closing) {
+                ("script", false) | ("style", false) => {
+                    let close = format!("</{}", name);
+                    rest = find_ascii_ci(rest, &close)
+                        .and_then(|start| rest[start..].find('>').map(|e| &rest[start + e + 1..]))
+                        .unwrap_or("");
+                }
+                ("br", _) => {
+                    trim_trailing_spaces(&mut out);
+                    out.push('\n');
+                }
+                ("p", _) | ("h1", _) | ("h2", _) | ("h3", _) | ("h4", _) | ("h5", _)
+                | ("h6", _) | ("blockquote", _) | ("table", _) => break_line(&mut out, true),
+                ("pre", false) => {
+                    break_line(&mut out, true);
+                    pre_depth += 1;
+                }
+                ("pre", true) => {
+                    pre_depth = pre_depth.saturating_sub(1);
+                    break_line(&mut out, true);
+                }
+                ("ul", false) => {
+                    break_line(&mut out, lists.is_empty());
+                    lists.push(None);
+                }
+                ("ol", false) => {
+                    break_line(&mut out, lists.is_empty());
+                    lists.push(Some(1));
+                }
+                ("ul", true) | ("ol", true) => {
+                    lists.pop();
+                    break_line(&mut out, lists.is_empty());
+                }
+                ("li", false) => {
+                    break_line(&mut out, false);
+                    let depth = lists.len().saturating_sub(1);
+                    out.push_str(&"  ".repeat(depth));
+                    match lists.last_mut() {
+                        Some(Some(n)) => {
+                            out.push_str(&format!("{}. ", n));
+                            *n += 1;
+                        }
+                        _ => out.push_str("- "),
+                    }
+                }
+                ("div", _) | ("tr", _) | ("li", true) => break_line(&mut out, false),
+                ("td", false) | ("th", false) if !out.is_empty() && !out.ends_with('\n') => {
+                    out.push('\t');
+                }
+                ("a", false) => links.push((attribute(tag, "href").unwrap_or_default(), out.len())),
+                ("a", true) => {
+                    if let Some((href, start)) = links.pop() {
+                        // Line breaks inside the link may have trimmed `out`
+                        // back past where its text started.
+                        let mut start = start.min(out.len());
+                        while !out.is_char_boundary(start) {
+                            start -= 1;
+                        }
+                        let label = out[start..].trim().to_string();
+                        let target = href.strip_prefix("mailto:").unwrap_or(&href);
+                        if !target.is_empty() && label != target && !href.starts_with('#') {
+                            out.push_str(&format!(" ({})", target));
+                        }
+                    }
+                }
+                _ => {}
+            }
+            continue;
+        }
+
+        let end = rest.find('<').unwrap_or(rest.len());
+        let text = decode_entities(&rest[..end]);
+        rest = &rest[end..];
+        if pre_depth > 0 {
+            out.push_str(&text);
+            continue;
+        }
+        for c in text.chars() {
+            if c == '\u{a0}' {
+                out.push(' ');
+            } else if c.is_whitespace() {
+                if !out.is_empty() && !out.ends_with([' ', '\n', '\t']) {
+                    out.push(' ');
+                }
+            } else {
+                out.push(c);
+            }
+        }
+    }
+
+    let lines: Vec<&str> = out.lines().map(str::trim_end).collect();
+    let mut text = lines.join("\n");
+    while text.contains("\n\n\n") {
+        text = text.replace("\n\n\n", "\n\n");
+    }
+    text.trim_matches('\n').to_string()
+}
+
+/// Ends the current line, leaving a blank line after it when `blank`.
+fn break_line(out: &mut String, blank: bool) {
+    trim_trailing_spaces(out);
+    if out.is_empty() {
+        return;
+    }
+    let wanted = if blank { 2 } else { 1 };
+    let have = out.chars().rev().take_while(|&c| c == '\n').count();
+    for _ in have..wanted {
+        out.push('\n');
+    }
+}
+
+fn trim_trailing_spaces(out: &mut String) {
+    let len = out.trim_end_matches([' ', '\t']).len();
+    out.truncate(len);
+}
+
+/// Position of `needle` (ASCII) in `haystack`, ignoring ASCII case.
+fn find_ascii_ci(haystack: &str, needle: &str) -> Option<usize> {
+    haystack
+        .as_bytes()
+        .windows(needle.len())
+        .position(|window| window.eq_ignore_ascii_case(needle.as_bytes()))
+}
+
+/// The value of attribute `name` in the inside of a start tag.
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    let mut rest = tag;
+    while let Some(pos) = find_ascii_ci(rest, name) {
+        let preceded_by_space = rest[..pos].ends_with(char::is_whitespace);
+        let after = rest[pos + name.len()..].trim_start();
+        if preceded_by_space {
+            if let Some(value) = after.strip_prefix('=') {
+                let value = value.trim_start();
+                let raw = match value.chars().next() {
+                    Some(q @ ('"' | '\'')) => {
+                        let inner = &value[1..];
+                        &inner[..inner.find(q).unwrap_or(inner.len())]
+                    }
+                    _ => value
+                        .split(|c: char| c.is_whitespace() || c == '/')
+                        .next()
+                        .unwrap_or(""),
+                };
+                return Some(decode_entities(raw));
+            }
+        }
+        rest = &rest[pos + name.len()..];
+    }
+    None
+}
+
+/// Decodes named and numeric character references.
+fn decode_entities(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let decoded = rest[1..].find(';').filter(|&end| end <= 10).and_then(|end| {
+            let entity = &rest[1..1 + end];
+            let c = match entity {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "nbsp" => Some('\u{a0}'),
+                _ => entity
+                    .strip_prefix("#x")
+                    .or_else(|| entity.strip_prefix("#X"))
+                    .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                    .or_else(|| entity.strip_prefix('#').and_then(|dec| dec.parse().ok()))
+                    .and_then(char::from_u32),
+            };
+            c.map(|c| (c, end + 2))
+        });
+        match decoded {
+            Some((c, len)) => {
+                out.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // ── Markdown ─────────────────────────────────────────────────
+
+    #[test]
+    fn test_markdown_inline_styles() {
+        assert_eq!(
+            markdown_to_html("**Jane** *Doe* `id` [site](https://example.com)"),
+            "<p><strong>Jane</strong> <em>Doe</em> <code>id</code> \
+             <a href=\"https://example.com\">site</a></p>"
+        );
+    }
+
+    #[test]
+    fn test_markdown_keeps_line_breaks_and_paragraphs() {
+        assert_eq!(
+            markdown_to_html("Best regards,\nJane\n\nACME Corp"),
+            "<p>Best regards,<br>\nJane</p>\n<p>ACME Corp</p>"
+        );
+    }
+
+    #[test]
+    fn test_markdown_lists_and_headings() {
+        assert_eq!(
+            markdown_to_html("## Agenda\n- one\n- two\n  continued\n1. first\n2. second"),
+            "<h2>Agenda</h2>\n<ul>\n<li>one</li>\n<li>two continued</li>\n</ul>\n\
+             <ol>\n<li>first</li>\n<li>second</li>\n</ol>"
+        );
+    }
+
+    #[test]
+    fn test_markdown_escapes_html_and_leaves_snake_case() {
+        assert_eq!(
+            markdown_to_html("a < b & my_var_name \\*not em\\*"),
+            "<p>a &lt; b &amp; my_var_name *not em*</p>"
+        );
+    }
+
+    #[test]
+    fn test_markdown_autolink_and_unclosed_markers() {
+        assert_eq!(
+            markdown_to_html("<mailto:jane@example.com> 2 * 3 **open"),
+            "<p><a href=\"mailto:jane@example.com\">jane@example.com</a> 2 * 3 **open</p>"
+        );
+    }
+
+    // ── HTML to text ─────────────────────────────────────────────
+
+    #[test]
+    fn test_html_to_text_blocks_and_breaks() {
+        assert_eq!(
+            html_to_text("<p>Best   regards,<br>\n<b>Jane</b></p><p>ACME&nbsp;Corp</p>"),
+            "Best regards,\nJane\n\nACME Corp"
+        );
+    }
+
+    #[test]
+    fn test_html_to_text_lists() {
+        assert_eq!(
+            html_to_text("<ul><li>one</li><li>two</li></ul><ol><li>a</li><li>b</li></ol>"),
+            "- one\n- two\n\n1. a\n2. b"
+        );
+    }
+
+    #[test]
+    fn test_html_to_text_links() {
+        assert_eq!(
+            html_to_text(
+                "<a href=\"https://example.com\">site</a> \
+                 <a href='https://example.com'>https://example.com</a> \
+                 <a href=\"mailto:jane@example.com\">jane@example.com</a>"
+            ),
+            "site (https://example.com) https://example.com jane@example.com"
+        );
+    }
+
+    #[test]
+    fn test_html_to_text_link_trimmed_by_line_break() {
+        // The paragraph break trims the space the link text started after.
+        assert_eq!(html_to_text("&nbsp;<a href=\"x\"><p></a>"), " (x)");
+        assert_eq!(html_to_text("&nbsp;<a href=\"x\"><p>é</a>"), "é (x)");
+    }
+
+    #[test]
+    fn test_html_to_text_skips_style_comments_and_decodes_entities() {
+        assert_eq!(
+            html_to_text("<style>p { color: red }</style><!-- note --><p>&lt;tag&gt; &#169; &#x1F600;</p>"),
+            "<tag> \u{a9} \u{1F600}"
+        );
+    }
+
+    #[test]
+    fn test_html_to_text_keeps_pre_whitespace() {
+        assert_eq!(html_to_text("<pre>a  b\n  c</pre>"), "a  b\n  c");
+    }
+
+    // ── Rendering ────────────────────────────────────────────────
+
+    #[test]
+    fn test_render_snippet_formats() {
+        let plain = render_snippet("**as is**", SnippetFormat::Plain);
+        assert_eq!(plain.text, "**as is**");
+        assert_eq!(plain.html, None);
+
+        let html = render_snippet("<b>Jane</b>", SnippetFormat::Html);
+        assert_eq!(html.text, "Jane");
+        assert_eq!(html.html.as_deref(), Some("<b>Jane</b>"));
+
+        let md = render_snippet("**Jane**\n[site](https://x.io)", SnippetFormat::Markdown);
+        assert_eq!(md.text, "Jane\nsite (https://x.io)");
+        assert_eq!(
+            md.html.as_deref(),
+            Some("<p><strong>Jane</strong><br>\n<a href=\"https://x.io\">site</a></p>")
+        );
+
+        let image = render_snippet("logo.png", SnippetFormat::Image);
+        assert_eq!(image.text, "");
+        assert_eq!(image.html, None);
+    }
+}
@@ -4,7 +4,7 @@ import { ContentArea } from "./components/common/ContentArea";
 import { AccessibilityBanner } from "./components/common/AccessibilityBanner";
 import { ComboList } from "./components/combo/ComboList";
 import { PreferencesDialog } from "./components/preferences/PreferencesDialog";
-import { ImportDialog, ExportDialog, BackupManager } from "./components/data";
+import { ImportDialog, ExportDialog, BackupManager, AutocorrectManager } from "./components/data";
 import { usePreferencesStore } from "./stores/preferencesStore";
 import { useComboStore } from "./stores/comboStore";
 import { useTheme } from "./hooks/useTheme";
@@ -15,6 +15,7 @@ function App() {
   const [importOpen, setImportOpen] = useState(false);
   const [exportOpen, setExportOpen] = useState(false);
   const [backupsOpen, setBackupsOpen] = useState(false);
+  const [autocorrectOpen, setAutocorrectOpen] = useState(false);
   const { preferences, loadPreferences } = usePreferencesStore();
   const { combos, toggleCombo: toggleSingleCombo } = useComboStore();
   useTheme(preferences?.theme);
@@ -67,6 +68,7 @@ function App() {
       onOpenImport={() => setImportOpen(true)}
       onOpenExport={() => setExportOpen(true)}
       onOpenBackups={() => setBackupsOpen(true)}
+      onOpenAutocorrect={() => setAutocorrectOpen(true)}
       onNewCombo={handleNewCombo}
       onNewGroup={handleNewGroup}
       onEnableAll={handleEnableAll}
@@ -100,6 +102,11 @@ function App() {
         isOpen={backupsOpen}
         onClose={() => setBackupsOpen(false)}
       />
+
+      <AutocorrectManager
+        isOpen={autocorrectOpen}
+        onClose={() => setAutocorrectOpen(false)}
+      />
     </MainLayout>
   );
 }
@@ -7,6 +7,7 @@ import * as Select from "@radix-ui/react-select";
 import { X, ChevronDown, Check } from "lucide-react";
 import { useGroupStore } from "../../stores/groupStore";
 import { createComboSchema } from "../../lib/schemas";
+import { getImageAsset, storeImageAsset } from "../../lib/tauri";
 import { InsertVariableMenu } from "./InsertVariableMenu";
 import { SnippetEditor } from "./SnippetEditor";
 import type { Combo, CreateComboInput } from "../../lib/types";
@@ -25,6 +26,8 @@ export function ComboEditor({ open, combo, onSave, onCancel }: ComboEditorProps)
   const { groups } = useGroupStore();
   const snippetRef = useRef<HTMLTextAreaElement>(null);
   const [submitting, setSubmitting] = useState(false);
+  const [imagePreview, setImagePreview] = useState<string | null>(null);
+  const [imageError, setImageError] = useState("");
 
   const {
     register,
@@ -43,6 +46,9 @@ export function ComboEditor({ open, combo, onSave, onCancel }: ComboEditorProps)
       groupId: groups[0]?.id || "",
       matchingMode: "strict",
       caseSensitive: false,
+      propagateCase: false,
+      snippetFormat: "plain",
+      excludeFromHistory: true,
       enabled: true,
     },
   });
@@ -59,6 +65,9 @@ export function ComboEditor({ open, combo, onSave, onCancel }: ComboEditorProps)
           groupId: combo.groupId,
           matchingMode: combo.matchingMode,
           caseSensitive: combo.caseSensitive,
+          propagateCase: combo.propagateCase,
+          snippetFormat: combo.snippetFormat,
+          excludeFromHistory: combo.excludeFromHistory,
           enabled: combo.enabled,
         });
       } else {
@@ -70,12 +79,51 @@ export function ComboEditor({ open, combo, onSave, onCancel }: ComboEditorProps)
           groupId: groups[0]?.id || "",
           matchingMode: "strict",
           caseSensitive: false,
+          propagateCase: false,
+          snippetFormat: "plain",
+          excludeFromHistory: true,
           enabled: true,
         });
       }
     }
   }, [open, combo, reset, groups]);
 
+  const snippetFormat = watch("snippetFormat");
+  const snippetValue = watch("snippet");
+
+  // Load a preview of the stored image for image combos
+  useEffect(() => {
+    if (!open || snippetFormat !== "image" || !snippetValue) {
+      setImagePreview(null);
+      return;
+    }
+    let cancelled = false;
+    getImageAsset(snippetValue)
+      .then((url) => {
+        if (!cancelled) setImagePreview(url);
+      })
+      .catch(() => {
+        if (!cancelled) setImagePreview(null);
+      });
+    return () => {
+      cancelled = true;
+    };
+  }, [open, snippetFormat, snippetValue]);
+
+  const handleImageChange = async (file: File | undefined) => {
+    if (!file) return;
+    setImageError("");
+    try {
+      const data = new Uint8Array(await file.arrayBuffer());
+      const name = await storeImageAsset(data);
+      setValue("snippet", name, { shouldValidate: true });
+    } catch (err) {
+      const message =
+        err && typeof err === "object" && "message" in err ? String(err.message) : String(err);
+      setImageError(message);
+    }
+  };
+
   const onSubmit = async (data: ComboFormData) => {
     setSubmitting(true);
     try {
@@ -172,7 +220,7 @@ export function ComboEditor({ open, combo, onSave, onCancel }: ComboEditorProps)
               type="text"
               {...register("keyword")}
               className="w-full px-3 py-2 border border-gray-300 dark:border-gray-600 bg-white dark:bg-gray-700 text-gray-900 dark:text-gray-100 rounded-lg focus:outline-none focus:ring-2 focus:ring-blue-500 font-mono"
-              placeholder="e.g., sig (no spaces)"
+              placeholder="e.g., sig or on my way"
             />
             {errors.keyword && (
               <p className="text-red-500 text-sm mt-1">{errors.keyword.message}</p>
@@ -180,25 +228,100 @@ export function ComboEditor({ open, combo, onSave, onCancel }: ComboEditorProps)
           </div>
 
           {/* Snippet */}
+          {snippetFormat === "image" ? (
+            <div>
+              <label htmlFor="snippet-image" className="block text-sm font-medium mb-1 text-gray-900 dark:text-gray-100">
+                Image *
+              </label>
+              {imagePreview && (
+                <img
+                  src={imagePreview}
+                  alt="Snippet image"
+                  className="max-h-40 mb-2 border border-gray-200 dark:border-gray-600 rounded"
+                />
+              )}
+              <input
+                id="snippet-image"
+                type="file"
+                accept="image/png"
+                onChange={(e) => handleImageChange(e.target.files?.[0])}
+                className="block w-full text-sm text-gray-700 dark:text-gray-300"
+              />
+              <p className="text-xs text-gray-500 dark:text-gray-400 mt-1">
+                PNG up to 5 MB and 4096×4096 pixels. The image is pasted through the clipboard.
+              </p>
+              {imageError && <p className="text-red-500 text-sm mt-1">{imageError}</p>}
+              {errors.snippet && !imageError && (
+                <p className="text-red-500 text-sm mt-1">Choose an image</p>
+              )}
+            </div>
+          ) : (
+            <div>
+              <div className="flex items-center justify-between mb-1">
+                <label htmlFor="snippet" className="block text-sm font-medium text-gray-900 dark:text-gray-100">
+                  Snippet *
+                </label>
+                <InsertVariableMenu onInsert={insertVariable} />
+              </div>
+              <SnippetEditor
+                id="snippet"
+                ref={snippetRef}
+                value={watch("snippet")}
+                onChange={(value) => setValue("snippet", value)}
+                placeholder="The text to expand..."
+                rows={6}
+                className="w-full"
+              />
+              {errors.snippet && (
+                <p className="text-red-500 text-sm mt-1">{errors.snippet.message}</p>
+              )}
+            </div>
+          )}
+
+          {/* Snippet Format */}
           <div>
-            <div className="flex items-center justify-between mb-1">
-              <label htmlFor="snippet" className="block text-sm font-medium text-gray-900 dark:text-gray-100">
-                Snippet *
+            <label className="block text-sm font-medium mb-2 text-gray-900 dark:text-gray-100">Snippet Format</label>
+            <div className="flex flex-wrap gap-4">
+              <label className="flex items-center gap-2">
+                <input
+                  type="radio"
+                  {...register("snippetFormat")}
+                  value="plain"
+                  className="w-4 h-4"
+                />
+                <span className="text-sm text-gray-700 dark:text-gray-300">Plain text</span>
+              </label>
+              <label className="flex items-center gap-2">
+                <input
+                  type="radio"
+                  {...register("snippetFormat")}
+                  value="html"
+                  className="w-4 h-4"
+                />
+                <span className="text-sm text-gray-700 dark:text-gray-300">HTML</span>
+              </label>
+              <label className="flex items-center gap-2">
+                <input
+                  type="radio"
+                  {...register("snippetFormat")}
+                  value="markdown"
+                  className="w-4 h-4"
+                />
+                <span className="text-sm text-gray-700 dark:text-gray-300">Markdown</span>
+              </label>
+              <label className="flex items-center gap-2">
+                <input
+                  type="radio"
+                  {...register("snippetFormat")}
+                  value="image"
+                  className="w-4 h-4"
+                />
+                <span className="text-sm text-gray-700 dark:text-gray-300">Image</span>
               </label>
-              <InsertVariableMenu onInsert={insertVariable} />
             </div>
-            <SnippetEditor
-              id="snippet"
-              ref={snippetRef}
-              value={watch("snippet")}
-              onChange={(value) => setValue("snippet", value)}
-              placeholder="The text to expand..."
-              rows={6}
-              className="w-full"
-            />
-            {errors.snippet && (
-              <p className="text-red-500 text-sm mt-1">{errors.snippet.message}</p>
-            )}
+            <p className="text-xs text-gray-500 dark:text-gray-400 mt-1">
+              Rich formats are pasted as formatted text where supported, with a plain-text fallback.
+            </p>
           </div>
 
           {/* Group */}
@@ -276,6 +399,22 @@ export function ComboEditor({ open, combo, onSave, onCancel }: ComboEditorProps)
               />
               <span className="text-sm text-gray-700 dark:text-gray-300">Case Sensitive</span>
             </label>
+            <label className="flex items-center gap-2">
+              <input
+                type="checkbox"
+                {...register("propagateCase")}
+                className="w-4 h-4"
+              />
+              <span className="text-sm text-gray-700 dark:text-gray-300">Match Typed Capitalization</span>
+            </label>
+            <label className="flex items-center gap-2">
+              <input
+                type="checkbox"
+                {...register("excludeFromHistory")}
+                className="w-4 h-4"
+              />
+              <span className="text-sm text-gray-700 dark:text-gray-300">Hide From Clipboard History</span>
+            </label>
             <label className="flex items-center gap-2">
               <input type="checkbox" {...register("enabled")} className="w-4 h-4" />
               <span className="text-sm text-gray-700 dark:text-gray-300">Enabled</span>
@@ -1,5 +1,6 @@
 import React from "react";
 import { MenuBar } from "./MenuBar";
+import { StatusBar } from "./StatusBar";
 import { Sidebar } from "../group/Sidebar";
 
 interface MainLayoutProps {
@@ -8,6 +9,7 @@ interface MainLayoutProps {
   onOpenImport?: () => void;
   onOpenExport?: () => void;
   onOpenBackups?: () => void;
+  onOpenAutocorrect?: () => void;
   onNewCombo?: () => void;
   onNewGroup?: () => void;
   onEnableAll?: () => void;
@@ -21,11 +23,11 @@ const SIDEBAR_DEFAULT_WIDTH = 250;
 const SIDEBAR_WIDTH_KEY = "muttontext-sidebar-width";
 
 /**
- * Main application layout shell with menu bar, sidebar, and content area.
- * Uses CSS Grid for layout: menu bar at top, sidebar on left, content on right.
+ * Main application layout shell with menu bar, sidebar, content area, and status bar.
+ * Uses CSS Grid for layout: menu bar at top, sidebar on left, content on right, status bar at bottom.
  * Sidebar is resizable with min/max width constraints and persists size in localStorage.
  */
-export const MainLayout: React.FC<MainLayoutProps> = ({ children, onOpenPreferences, onOpenImport, onOpenExport, onOpenBackups, onNewCombo, onNewGroup, onEnableAll, onDisableAll, onCheckForUpdates }) => {
+export const MainLayout: React.FC<MainLayoutProps> = ({ children, onOpenPreferences, onOpenImport, onOpenExport, onOpenBackups, onOpenAutocorrect, onNewCombo, onNewGroup, onEnableAll, onDisableAll, onCheckForUpdates }) => {
   const [sidebarWidth, setSidebarWidth] = React.useState(() => {
     const saved = localStorage.getItem(SIDEBAR_WIDTH_KEY);
     if (saved) {
@@ -71,7 +73,7 @@ export const MainLayout: React.FC<MainLayoutProps> = ({ children, onOpenPreferen
 
   return (
     <div className="flex h-screen flex-col bg-white dark:bg-gray-900 text-gray-900 dark:text-gray-100">
-      <MenuBar onOpenPreferences={onOpenPreferences} onOpenImport={onOpenImport} onOpenExport={onOpenExport} onOpenBackups={onOpenBackups} onNewCombo={onNewCombo} onNewGroup={onNewGroup} onEnableAll={onEnableAll} onDisableAll={onDisableAll} onCheckForUpdates={onCheckForUpdates} />
+      <MenuBar onOpenPreferences={onOpenPreferences} onOpenImport={onOpenImport} onOpenExport={onOpenExport} onOpenBackups={onOpenBackups} onOpenAutocorrect={onOpenAutocorrect} onNewCombo={onNewCombo} onNewGroup={onNewGroup} onEnableAll={onEnableAll} onDisableAll={onDisableAll} onCheckForUpdates={onCheckForUpdates} />
 
       {/* Main content area with resizable sidebar */}
       <div className="flex flex-1 overflow-hidden">
@@ -96,6 +98,8 @@ export const MainLayout: React.FC<MainLayoutProps> = ({ children, onOpenPreferen
           {children}
         </div>
       </div>
+
+      <StatusBar />
     </div>
   );
 };
@@ -15,6 +15,7 @@ interface MenuBarProps {
   onOpenImport?: () => void;
   onOpenExport?: () => void;
   onOpenBackups?: () => void;
+  onOpenAutocorrect?: () => void;
   onNewCombo?: () => void;
   onNewGroup?: () => void;
   onEnableAll?: () => void;
@@ -26,7 +27,7 @@ interface MenuBarProps {
  * Menu bar component with File, Edit, Combos, Groups, and Help menus.
  * Uses Radix UI DropdownMenu for accessible menu implementation.
  */
-export const MenuBar: React.FC<MenuBarProps> = ({ onOpenPreferences, onOpenImport, onOpenExport, onOpenBackups, onNewCombo, onNewGroup, onEnableAll, onDisableAll, onCheckForUpdates }) => {
+export const MenuBar: React.FC<MenuBarProps> = ({ onOpenPreferences, onOpenImport, onOpenExport, onOpenBackups, onOpenAutocorrect, onNewCombo, onNewGroup, onEnableAll, onDisableAll, onCheckForUpdates }) => {
   const [aboutOpen, setAboutOpen] = useState(false);
 
   return (
@@ -81,6 +82,12 @@ export const MenuBar: React.FC<MenuBarProps> = ({ onOpenPreferences, onOpenImpor
             >
               Backups...
             </DropdownMenu.Item>
+            <DropdownMenu.Item
+              className="cursor-pointer px-3 py-2 text-sm outline-none hover:bg-gray-100 dark:hover:bg-gray-700 dark:text-gray-100"
+              onSelect={() => onOpenAutocorrect?.()}
+            >
+              Autocorrect...
+            </DropdownMenu.Item>
             <DropdownMenu.Separator className="my-1 h-px bg-gray-200 dark:bg-gray-600" />
             <DropdownMenu.Item
               className="cursor-pointer px-3 py-2 text-sm outline-none hover:bg-gray-100 dark:hover:bg-gray-700 dark:text-gray-100"
@@ -1,9 +1,39 @@
-// StatusBar - Shows combo statistics at bottom of main layout
-import { useMemo } from "react";
+// StatusBar - Shows expansion state and combo statistics at bottom of main layout
+import { useEffect, useMemo, useState } from "react";
 import { useComboStore } from "../../stores/comboStore";
+import { getTrayState, onTrayStateChanged } from "@/lib/tauri";
+import type { TrayState } from "@/lib/types";
+
+const TRAY_STATE_LABELS: Record<TrayState, string> = {
+  active: "Active",
+  paused: "Paused",
+  excludedApp: "Disabled (excluded app)",
+  passwordField: "Paused (password field)",
+};
 
 export function StatusBar() {
   const { combos } = useComboStore();
+  const [trayState, setTrayState] = useState<TrayState | null>(null);
+
+  // Mirror the tray state, which the backend changes on its own when a
+  // password field gains or loses focus
+  useEffect(() => {
+    let unlisten: (() => void) | undefined;
+    let cancelled = false;
+    onTrayStateChanged(setTrayState)
+      .then((fn) => {
+        if (cancelled) fn();
+        else unlisten = fn;
+      })
+      .catch((err) => console.error("Failed to listen for tray state:", err));
+    getTrayState()
+      .then(setTrayState)
+      .catch((err) => console.error("Failed to load tray state:", err));
+    return () => {
+      cancelled = true;
+      unlisten?.();
+    };
+  }, []);
 
   // Find most recently used combo
   const lastUsedInfo = useMemo(() => {
@@ -45,9 +75,22 @@ export function StatusBar() {
       role="contentinfo"
       className="border-t border-gray-200 dark:border-gray-700 px-4 py-2 bg-gray-50 dark:bg-gray-800 text-sm text-gray-600 dark:text-gray-400 flex items-center justify-between"
     >
-      <div>
-        <span className="font-medium">{combos.length}</span>{" "}
-        {combos.length === 1 ? "combo" : "combos"}
+      <div className="flex items-center gap-4">
+        {trayState && (
+          <span className="flex items-center gap-1.5" data-testid="expansion-state">
+            <span
+              aria-hidden="true"
+              className={`h-2 w-2 rounded-full ${
+                trayState === "active" ? "bg-green-500" : "bg-yellow-500"
+              }`}
+            />
+            {TRAY_STATE_LABELS[trayState]}
+          </span>
+        )}
+        <span>
+          <span className="font-medium">{combos.length}</span>{" "}
+          {combos.length === 1 ? "combo" : "combos"}
+        </span>
       </div>
 
       {lastUsedInfo && (
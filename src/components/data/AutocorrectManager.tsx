@@ -0,0 +1,306 @@
+import React, { useState, useEffect, useCallback, useRef } from "react";
+import {
+  XIcon,
+  UploadIcon,
+  TrashIcon,
+  AlertTriangleIcon,
+  CheckCircleIcon,
+} from "lucide-react";
+import type { AutocorrectDictionarySummary, AutocorrectImportResult } from "@/lib/types";
+import {
+  getAutocorrectDictionaries,
+  importAutocorrectList,
+  toggleAutocorrectDictionary,
+  deleteAutocorrectDictionary,
+} from "@/lib/tauri";
+
+interface AutocorrectManagerProps {
+  isOpen: boolean;
+  onClose: () => void;
+}
+
+const MAX_FILE_SIZE = 10 * 1024 * 1024; // 10 MB
+
+/** The file name without its extension, as a default dictionary name. */
+function baseName(fileName: string): string {
+  const dot = fileName.lastIndexOf(".");
+  return dot > 0 ? fileName.slice(0, dot) : fileName;
+}
+
+export const AutocorrectManager: React.FC<AutocorrectManagerProps> = ({ isOpen, onClose }) => {
+  const [dictionaries, setDictionaries] = useState<AutocorrectDictionarySummary[]>([]);
+  const [loading, setLoading] = useState(false);
+  const [error, setError] = useState("");
+  const [fileContent, setFileContent] = useState("");
+  const [fileName, setFileName] = useState("");
+  const [name, setName] = useState("");
+  const [importing, setImporting] = useState(false);
+  const [result, setResult] = useState<AutocorrectImportResult | null>(null);
+  const [confirmDelete, setConfirmDelete] = useState<string | null>(null);
+  const fileInputRef = useRef<HTMLInputElement>(null);
+
+  const loadDictionaries = useCallback(async () => {
+    setLoading(true);
+    setError("");
+    try {
+      const list = await getAutocorrectDictionaries();
+      setDictionaries(list);
+    } catch (err) {
+      setError(err instanceof Error ? err.message : String(err));
+    } finally {
+      setLoading(false);
+    }
+  }, []);
+
+  useEffect(() => {
+    if (isOpen) {
+      loadDictionaries();
+    }
+  }, [isOpen, loadDictionaries]);
+
+  const handleClose = useCallback(() => {
+    setFileContent("");
+    setFileName("");
+    setName("");
+    setResult(null);
+    setConfirmDelete(null);
+    setError("");
+    onClose();
+  }, [onClose]);
+
+  const handleFileSelect = useCallback((e: React.ChangeEvent<HTMLInputElement>) => {
+    const file = e.target.files?.[0];
+    e.target.value = "";
+    if (!file) return;
+    if (file.size > MAX_FILE_SIZE) {
+      setError("File exceeds 10 MB size limit");
+      return;
+    }
+    setError("");
+    setResult(null);
+    const reader = new FileReader();
+    reader.onload = () => {
+      setFileContent(reader.result as string);
+      setFileName(file.name);
+      setName((current) => current || baseName(file.name));
+    };
+    reader.readAsText(file);
+  }, []);
+
+  const handleImport = useCallback(async () => {
+    if (!fileContent || !name.trim()) return;
+    setImporting(true);
+    setError("");
+    try {
+      const r = await importAutocorrectList(fileContent, name.trim());
+      setResult(r);
+      setFileContent("");
+      setFileName("");
+      setName("");
+      await loadDictionaries();
+    } catch (err) {
+      setError(err instanceof Error ? err.message : String(err));
+    } finally {
+      setImporting(false);
+    }
+  }, [fileContent, name, loadDictionaries]);
+
+  const handleToggle = useCallback(async (id: string) => {
+    setError("");
+    try {
+      const enabled = await toggleAutocorrectDictionary(id);
+      setDictionaries((list) => list.map((d) => (d.id === id ? { ...d, enabled } : d)));
+    } catch (err) {
+      setError(err instanceof Error ? err.message : String(err));
+    }
+  }, []);
+
+  const handleDelete = useCallback(
+    async (id: string) => {
+      setError("");
+      setConfirmDelete(null);
+      try {
+        await deleteAutocorrectDictionary(id);
+        await loadDictionaries();
+      } catch (err) {
+        setError(err instanceof Error ? err.message : String(err));
+      }
+    },
+    [loadDictionaries]
+  );
+
+  if (!isOpen) return null;
+
+  return (
+    <div
+      className="fixed inset-0 z-50 flex items-center justify-center bg-black/50"
+      role="dialog"
+      aria-modal="true"
+      aria-label="Autocorrect Dictionaries"
+    >
+      <div className="flex h-[520px] w-[520px] flex-col rounded-lg bg-white dark:bg-gray-800 shadow-xl">
+        {/* Header */}
+        <div className="flex items-center justify-between border-b dark:border-gray-700 px-6 py-4">
+          <h2 className="text-lg font-semibold text-gray-900 dark:text-gray-100">Autocorrect Dictionaries</h2>
+          <button
+            onClick={handleClose}
+            className="rounded p-1 text-gray-400 dark:text-gray-500 hover:bg-gray-100 dark:hover:bg-gray-700 hover:text-gray-600 dark:hover:text-gray-300"
+            aria-label="Close"
+          >
+            <XIcon size={18} />
+          </button>
+        </div>
+
+        {/* Import */}
+        <div className="space-y-2 border-b dark:border-gray-700 px-6 py-3">
+          <p className="text-xs text-gray-500 dark:text-gray-400">
+            Import a typo list: one <code>typo&lt;Tab&gt;correction</code> per line, or
+            Wikipedia's <code>typo-&gt;correction</code> format.
+          </p>
+          <div className="flex items-center gap-2">
+            <button
+              onClick={() => fileInputRef.current?.click()}
+              className="flex shrink-0 items-center gap-1.5 rounded border border-gray-300 dark:border-gray-600 px-3 py-1.5 text-sm text-gray-600 dark:text-gray-300 hover:bg-gray-50 dark:hover:bg-gray-700"
+            >
+              <UploadIcon size={14} />
+              Choose File
+            </button>
+            <input
+              ref={fileInputRef}
+              type="file"
+              accept=".tsv,.txt,.csv"
+              onChange={handleFileSelect}
+              className="hidden"
+              aria-label="Typo list file"
+            />
+            <span className="truncate text-xs text-gray-500 dark:text-gray-400">
+              {fileName || "No file chosen"}
+            </span>
+          </div>
+          <div className="flex items-center gap-2">
+            <input
+              type="text"
+              value={name}
+              onChange={(e) => setName(e.target.value)}
+              placeholder="Dictionary name"
+              aria-label="Dictionary name"
+              className="flex-1 rounded border border-gray-300 dark:border-gray-600 bg-white dark:bg-gray-700 text-gray-900 dark:text-gray-100 px-3 py-1.5 text-sm focus:border-blue-500 focus:outline-none"
+            />
+            <button
+              onClick={handleImport}
+              disabled={!fileContent || !name.trim() || importing}
+              className="rounded bg-blue-600 px-3 py-1.5 text-sm text-white hover:bg-blue-700 disabled:opacity-50"
+            >
+              {importing ? "Importing..." : "Import"}
+            </button>
+          </div>
+          {result && (
+            <div className="flex items-start gap-2 rounded border border-green-200 dark:border-green-800 bg-green-50 dark:bg-green-900/30 p-2">
+              <CheckCircleIcon size={14} className="mt-0.5 shrink-0 text-green-600" />
+              <div className="text-xs text-green-800 dark:text-green-300">
+                <p>
+                  Imported {result.importedCount} corrections into "{result.dictionary.name}"
+                  {result.skippedCount > 0 && `, skipped ${result.skippedCount}`}.
+                </p>
+                {result.errors.slice(0, 5).map((e, i) => (
+                  <p key={i} className="text-gray-600 dark:text-gray-400">{e}</p>
+                ))}
+                {result.errors.length > 5 && (
+                  <p className="text-gray-600 dark:text-gray-400">
+                    ...and {result.errors.length - 5} more
+                  </p>
+                )}
+              </div>
+            </div>
+          )}
+        </div>
+
+        {/* Body */}
+        <div className="flex-1 overflow-y-auto p-4">
+          {loading && (
+            <p className="text-center text-sm text-gray-500 dark:text-gray-400">Loading dictionaries...</p>
+          )}
+
+          {!loading && dictionaries.length === 0 && (
+            <p className="text-center text-sm text-gray-500 dark:text-gray-400">No dictionaries imported.</p>
+          )}
+
+          {!loading && dictionaries.length > 0 && (
+            <div className="space-y-2">
+              {dictionaries.map((dictionary) => (
+                <div
+                  key={dictionary.id}
+                  className="flex items-center justify-between rounded border dark:border-gray-700 p-3"
+                >
+                  <label className="flex min-w-0 flex-1 items-center gap-3">
+                    <input
+                      type="checkbox"
+                      checked={dictionary.enabled}
+                      onChange={() => handleToggle(dictionary.id)}
+                      className="h-4 w-4 rounded border-gray-300 text-blue-600"
+                    />
+                    <div className="min-w-0">
+                      <p className="truncate text-sm font-medium text-gray-900 dark:text-gray-100">
+                        {dictionary.name}
+                      </p>
+                      <p className="text-xs text-gray-500 dark:text-gray-400">
+                        {dictionary.entryCount} corrections
+                      </p>
+                    </div>
+                  </label>
+                  <button
+                    onClick={() => setConfirmDelete(dictionary.id)}
+                    className="rounded p-1.5 text-gray-400 hover:bg-red-50 dark:hover:bg-red-900/30 hover:text-red-600 dark:hover:text-red-400"
+                    title="Delete"
+                  >
+                    <TrashIcon size={14} />
+                  </button>
+                </div>
+              ))}
+            </div>
+          )}
+
+          {error && (
+            <div className="mt-3 rounded border border-red-200 dark:border-red-800 bg-red-50 dark:bg-red-900/30 p-2">
+              <p className="text-xs text-red-700 dark:text-red-400">{error}</p>
+            </div>
+          )}
+        </div>
+
+        {/* Confirmation overlay */}
+        {confirmDelete && (
+          <div className="border-t dark:border-gray-700 bg-yellow-50 dark:bg-yellow-900/20 px-6 py-3">
+            <div className="flex items-center gap-2">
+              <AlertTriangleIcon size={16} className="text-yellow-600" />
+              <p className="flex-1 text-sm text-yellow-800 dark:text-yellow-200">
+                Delete this dictionary? This cannot be undone.
+              </p>
+              <button
+                onClick={() => setConfirmDelete(null)}
+                className="rounded border border-gray-300 dark:border-gray-600 px-3 py-1 text-xs text-gray-600 dark:text-gray-300 hover:bg-gray-50 dark:hover:bg-gray-700"
+              >
+                Cancel
+              </button>
+              <button
+                onClick={() => handleDelete(confirmDelete)}
+                className="rounded bg-red-600 px-3 py-1 text-xs text-white hover:bg-red-700"
+              >
+                Delete
+              </button>
+            </div>
+          </div>
+        )}
+
+        {/* Footer */}
+        <div className="flex justify-end border-t dark:border-gray-700 px-6 py-3">
+          <button
+            onClick={handleClose}
+            className="rounded border border-gray-300 dark:border-gray-600 px-4 py-1.5 text-sm text-gray-600 dark:text-gray-300 hover:bg-gray-50 dark:hover:bg-gray-700"
+          >
+            Close
+          </button>
+        </div>
+      </div>
+    </div>
+  );
+};
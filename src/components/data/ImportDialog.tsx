@@ -62,14 +62,16 @@ export const ImportDialog: React.FC<ImportDialogProps> = ({
     }
   }, []);
 
-  const MAX_FILE_SIZE = 10 * 1024 * 1024; // 10 MB
+  // Native exports may embed up to 16 images of 5 MB each; the backend
+  // holds other formats to 10 MB.
+  const MAX_FILE_SIZE = 120 * 1024 * 1024; // 120 MB
 
   const handleFileSelect = useCallback(
     (e: React.ChangeEvent<HTMLInputElement>) => {
       const file = e.target.files?.[0];
       if (!file) return;
       if (file.size > MAX_FILE_SIZE) {
-        setError("File exceeds 10 MB size limit");
+        setError("File exceeds 120 MB size limit");
         return;
       }
       const reader = new FileReader();
@@ -88,7 +90,7 @@ export const ImportDialog: React.FC<ImportDialogProps> = ({
       const file = e.dataTransfer.files[0];
       if (!file) return;
       if (file.size > MAX_FILE_SIZE) {
-        setError("File exceeds 10 MB size limit");
+        setError("File exceeds 120 MB size limit");
         return;
       }
       const reader = new FileReader();
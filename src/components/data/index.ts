@@ -1,3 +1,4 @@
 export { ImportDialog } from "./ImportDialog";
 export { ExportDialog } from "./ExportDialog";
 export { BackupManager } from "./BackupManager";
+export { AutocorrectManager } from "./AutocorrectManager";
@@ -1,13 +1,38 @@
 import React, { useState } from "react";
-import type { Preferences } from "@/lib/types";
+import type { PasteMethod, PasteRule, Preferences, TimingProfile } from "@/lib/types";
 
 interface AdvancedTabProps {
   preferences: Preferences;
   onChange: (prefs: Preferences) => void;
 }
 
+const EMPTY_RULE: PasteRule = {
+  field: "windowClass",
+  pattern: "",
+  pasteMethod: "clipboard",
+  pasteChord: "",
+};
+
+/** The engine's global delays, which a new profile starts from. */
+const DEFAULT_TIMING = {
+  keyDelayMs: 5,
+  pasteRestoreDelayMs: 200,
+  preDeletionDelayMs: 20,
+};
+
+const TIMING_FIELDS: { key: keyof typeof DEFAULT_TIMING; label: string }[] = [
+  { key: "keyDelayMs", label: "Key delay" },
+  { key: "pasteRestoreDelayMs", label: "Clipboard restore delay" },
+  { key: "preDeletionDelayMs", label: "Pre-deletion delay" },
+];
+
+const inputClass =
+  "rounded border border-gray-300 dark:border-gray-600 bg-white dark:bg-gray-700 text-gray-900 dark:text-gray-100 px-3 py-2 text-sm focus:border-blue-500 focus:outline-none";
+
 export const AdvancedTab: React.FC<AdvancedTabProps> = ({ preferences, onChange }) => {
   const [newApp, setNewApp] = useState("");
+  const [newRule, setNewRule] = useState<PasteRule>(EMPTY_RULE);
+  const [newTimingClass, setNewTimingClass] = useState("");
 
   const update = (partial: Partial<Preferences>) => {
     onChange({ ...preferences, ...partial });
@@ -25,6 +50,47 @@ export const AdvancedTab: React.FC<AdvancedTabProps> = ({ preferences, onChange
     update({ excludedApps: preferences.excludedApps.filter((a) => a !== app) });
   };
 
+  const handleAddRule = () => {
+    const rule = {
+      ...newRule,
+      pattern: newRule.pattern.trim(),
+      pasteChord: newRule.pasteChord.trim(),
+    };
+    if (rule.pattern) {
+      update({ pasteRules: [...preferences.pasteRules, rule] });
+      setNewRule(EMPTY_RULE);
+    }
+  };
+
+  const handleRemoveRule = (index: number) => {
+    update({ pasteRules: preferences.pasteRules.filter((_, i) => i !== index) });
+  };
+
+  const handleAddTimingProfile = () => {
+    const windowClass = newTimingClass.trim();
+    const exists = preferences.timingProfiles.some(
+      (p) => p.windowClass.toLowerCase() === windowClass.toLowerCase()
+    );
+    if (windowClass && !exists) {
+      const profile: TimingProfile = { windowClass, ...DEFAULT_TIMING, learned: false };
+      update({ timingProfiles: [...preferences.timingProfiles, profile] });
+      setNewTimingClass("");
+    }
+  };
+
+  // Editing a learned profile makes it manual, so the engine leaves it alone.
+  const handleTimingChange = (index: number, key: keyof typeof DEFAULT_TIMING, value: number) => {
+    update({
+      timingProfiles: preferences.timingProfiles.map((p, i) =>
+        i === index ? { ...p, [key]: Math.max(0, value), learned: false } : p
+      ),
+    });
+  };
+
+  const handleRemoveTimingProfile = (index: number) => {
+    update({ timingProfiles: preferences.timingProfiles.filter((_, i) => i !== index) });
+  };
+
   const handleKeyDown = (e: React.KeyboardEvent) => {
     if (e.key === "Enter") {
       e.preventDefault();
@@ -42,17 +108,19 @@ export const AdvancedTab: React.FC<AdvancedTabProps> = ({ preferences, onChange
           <select
             value={preferences.pasteMethod}
             onChange={(e) =>
-              update({ pasteMethod: e.target.value as "clipboard" | "simulateKeystrokes" | "xdotoolType" })
+              update({ pasteMethod: e.target.value as PasteMethod })
             }
             className="block w-full rounded border border-gray-300 dark:border-gray-600 bg-white dark:bg-gray-700 text-gray-900 dark:text-gray-100 px-3 py-2 text-sm focus:border-blue-500 focus:outline-none"
           >
             <option value="simulateKeystrokes">Simulate keystrokes (recommended)</option>
             <option value="clipboard">Clipboard (Cmd+V / Ctrl+V)</option>
             <option value="xdotoolType">AppleScript paste (System Events)</option>
+            <option value="uinput">Virtual keyboard (Linux, Wayland)</option>
           </select>
           <p className="text-xs text-gray-500 dark:text-gray-400">
             How expanded text is inserted. Simulate keystrokes types each character individually.
             Clipboard copies the snippet and pastes via Cmd+V. AppleScript uses System Events as an alternative paste mechanism.
+            Virtual keyboard types through /dev/uinput and works on native Wayland.
           </p>
         </div>
 
@@ -101,6 +169,166 @@ export const AdvancedTab: React.FC<AdvancedTabProps> = ({ preferences, onChange
             <p className="text-xs italic text-gray-400">No excluded applications</p>
           )}
         </div>
+
+        <div className="space-y-2">
+          <label className="block text-sm font-medium text-gray-700 dark:text-gray-300">Per-application paste rules</label>
+          <p className="text-xs text-gray-500 dark:text-gray-400">
+            Use a different paste method or shortcut in matching windows, e.g. Ctrl+Shift+V in terminals.
+            The first rule whose pattern appears in the window class or title wins.
+          </p>
+
+          <div className="flex flex-wrap gap-2">
+            <select
+              aria-label="Match on"
+              value={newRule.field}
+              onChange={(e) =>
+                setNewRule({ ...newRule, field: e.target.value as PasteRule["field"] })
+              }
+              className={inputClass}
+            >
+              <option value="windowClass">Window class</option>
+              <option value="windowTitle">Window title</option>
+            </select>
+            <input
+              type="text"
+              value={newRule.pattern}
+              onChange={(e) => setNewRule({ ...newRule, pattern: e.target.value })}
+              placeholder="Pattern (e.g. terminal)"
+              className={`flex-1 ${inputClass}`}
+            />
+            <select
+              aria-label="Rule paste method"
+              value={newRule.pasteMethod}
+              onChange={(e) =>
+                setNewRule({ ...newRule, pasteMethod: e.target.value as PasteMethod })
+              }
+              className={inputClass}
+            >
+              <option value="simulateKeystrokes">Simulate keystrokes</option>
+              <option value="clipboard">Clipboard</option>
+              <option value="xdotoolType">XTest / AppleScript</option>
+              <option value="uinput">Virtual keyboard</option>
+            </select>
+            <input
+              type="text"
+              value={newRule.pasteChord}
+              onChange={(e) => setNewRule({ ...newRule, pasteChord: e.target.value })}
+              placeholder="Shortcut (optional)"
+              className={`w-40 ${inputClass}`}
+            />
+            <button
+              onClick={handleAddRule}
+              disabled={!newRule.pattern.trim()}
+              className="rounded bg-blue-600 px-3 py-2 text-sm text-white hover:bg-blue-700 disabled:opacity-50"
+            >
+              Add
+            </button>
+          </div>
+
+          {preferences.pasteRules.length > 0 ? (
+            <ul className="space-y-1">
+              {preferences.pasteRules.map((rule, index) => (
+                <li
+                  key={`${rule.field}:${rule.pattern}:${index}`}
+                  className="flex items-center justify-between rounded border border-gray-200 dark:border-gray-600 bg-gray-50 dark:bg-gray-900 px-3 py-1.5 text-sm"
+                >
+                  <span>
+                    {rule.field === "windowClass" ? "Class" : "Title"} contains "{rule.pattern}" → {rule.pasteMethod}
+                    {rule.pasteChord && ` (${rule.pasteChord})`}
+                  </span>
+                  <button
+                    onClick={() => handleRemoveRule(index)}
+                    className="text-red-500 hover:text-red-700"
+                  >
+                    Remove
+                  </button>
+                </li>
+              ))}
+            </ul>
+          ) : (
+            <p className="text-xs italic text-gray-400">No paste rules</p>
+          )}
+        </div>
+
+        <div className="space-y-2">
+          <label className="block text-sm font-medium text-gray-700 dark:text-gray-300">Per-application timing</label>
+          <p className="text-xs text-gray-500 dark:text-gray-400">
+            Delays used instead of the defaults in windows of one class, in milliseconds. Raise them for
+            applications that drop characters or paste the old clipboard; lower them for fast ones.
+          </p>
+
+          <label className="flex items-center gap-3">
+            <input
+              type="checkbox"
+              checked={preferences.adaptiveTiming}
+              onChange={(e) => update({ adaptiveTiming: e.target.checked })}
+              className="h-4 w-4 rounded border-gray-300 text-blue-600"
+            />
+            <div>
+              <span className="text-sm font-medium text-gray-700 dark:text-gray-300">Learn timing for slow applications</span>
+              <p className="text-xs text-gray-500 dark:text-gray-400">
+                Raise the delays for an application when its input lags behind an expansion. Profiles you edit are never changed.
+              </p>
+            </div>
+          </label>
+
+          <div className="flex gap-2">
+            <input
+              type="text"
+              value={newTimingClass}
+              onChange={(e) => setNewTimingClass(e.target.value)}
+              placeholder="Window class (e.g. firefox)"
+              className={`flex-1 ${inputClass}`}
+            />
+            <button
+              onClick={handleAddTimingProfile}
+              disabled={!newTimingClass.trim()}
+              className="rounded bg-blue-600 px-3 py-2 text-sm text-white hover:bg-blue-700 disabled:opacity-50"
+            >
+              Add
+            </button>
+          </div>
+
+          {preferences.timingProfiles.length > 0 ? (
+            <ul className="space-y-1">
+              {preferences.timingProfiles.map((profile, index) => (
+                <li
+                  key={profile.windowClass}
+                  className="flex flex-wrap items-center gap-2 rounded border border-gray-200 dark:border-gray-600 bg-gray-50 dark:bg-gray-900 px-3 py-1.5 text-sm"
+                >
+                  <span className="flex-1">
+                    {profile.windowClass}
+                    {profile.learned && (
+                      <span className="ml-2 rounded bg-blue-100 dark:bg-blue-900 px-1.5 py-0.5 text-xs text-blue-700 dark:text-blue-300">
+                        Learned
+                      </span>
+                    )}
+                  </span>
+                  {TIMING_FIELDS.map(({ key, label }) => (
+                    <input
+                      key={key}
+                      type="number"
+                      min={0}
+                      aria-label={`${label} for ${profile.windowClass}`}
+                      title={label}
+                      value={profile[key]}
+                      onChange={(e) => handleTimingChange(index, key, Number(e.target.value))}
+                      className={`w-20 ${inputClass}`}
+                    />
+                  ))}
+                  <button
+                    onClick={() => handleRemoveTimingProfile(index)}
+                    className="text-red-500 hover:text-red-700"
+                  >
+                    Remove
+                  </button>
+                </li>
+              ))}
+            </ul>
+          ) : (
+            <p className="text-xs italic text-gray-400">No timing profiles</p>
+          )}
+        </div>
       </div>
     </div>
   );
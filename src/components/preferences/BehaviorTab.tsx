@@ -42,6 +42,38 @@ export const BehaviorTab: React.FC<BehaviorTabProps> = ({ preferences, onChange
           </div>
         </label>
 
+        <label className="flex items-center gap-3">
+          <input
+            type="checkbox"
+            checked={preferences.undoWithBackspace}
+            onChange={(e) => update({ undoWithBackspace: e.target.checked })}
+            className="h-4 w-4 rounded border-gray-300 text-blue-600"
+          />
+          <div>
+            <span className="text-sm font-medium text-gray-700 dark:text-gray-300">Undo expansion with Backspace</span>
+            <p className="text-xs text-gray-500 dark:text-gray-400">Pressing Backspace right after an expansion restores the typed keyword</p>
+          </div>
+        </label>
+
+        {preferences.undoWithBackspace && (
+          <div className="space-y-2">
+            <label className="block text-sm font-medium text-gray-700">
+              Undo window (milliseconds)
+            </label>
+            <input
+              type="number"
+              min={100}
+              max={60000}
+              step={100}
+              value={preferences.undoWindowMs}
+              onChange={(e) =>
+                update({ undoWindowMs: parseInt(e.target.value, 10) || 3000 })
+              }
+              className="block w-32 rounded border border-gray-300 dark:border-gray-600 bg-white dark:bg-gray-700 text-gray-900 dark:text-gray-100 px-3 py-2 text-sm focus:border-blue-500 focus:outline-none"
+            />
+          </div>
+        )}
+
         <div className="space-y-2">
           <label className="block text-sm font-medium text-gray-700">
             Default matching mode
@@ -6,10 +6,36 @@ import { z } from "zod";
  */
 export const matchingModeSchema = z.enum(["strict", "loose"]);
 
+/**
+ * Snippet format enum
+ */
+export const snippetFormatSchema = z.enum(["plain", "html", "markdown", "image"]);
+
 /**
  * Paste method enum
  */
-export const pasteMethodSchema = z.enum(["clipboard", "simulateKeystrokes", "xdotoolType"]);
+export const pasteMethodSchema = z.enum(["clipboard", "simulateKeystrokes", "xdotoolType", "uinput"]);
+
+/**
+ * Per-application paste rule schema
+ */
+export const pasteRuleSchema = z.object({
+  field: z.enum(["windowClass", "windowTitle"]),
+  pattern: z.string().trim().min(1),
+  pasteMethod: pasteMethodSchema,
+  pasteChord: z.string(),
+});
+
+/**
+ * Per-application timing profile schema
+ */
+export const timingProfileSchema = z.object({
+  windowClass: z.string().trim().min(1),
+  keyDelayMs: z.number().int().min(0).max(1000),
+  pasteRestoreDelayMs: z.number().int().min(0).max(5000),
+  preDeletionDelayMs: z.number().int().min(0).max(1000),
+  learned: z.boolean(),
+});
 
 /**
  * Theme enum
@@ -28,6 +54,9 @@ export const comboSchema = z.object({
   groupId: z.string().uuid(),
   matchingMode: matchingModeSchema,
   caseSensitive: z.boolean(),
+  propagateCase: z.boolean(),
+  snippetFormat: snippetFormatSchema,
+  excludeFromHistory: z.boolean(),
   enabled: z.boolean(),
   useCount: z.number().int().min(0),
   lastUsed: z.string().nullable(),
@@ -76,11 +105,18 @@ export const preferencesSchema = z.object({
   maxBackups: z.number().int().min(0),
   autoCheckUpdates: z.boolean(),
   excludedApps: z.array(z.string()),
+  pasteRules: z.array(pasteRuleSchema).max(100),
+  undoWithBackspace: z.boolean(),
+  undoWindowMs: z.number().int().min(1).max(60000),
+  recordKeystrokeTrace: z.boolean(),
+  timingProfiles: z.array(timingProfileSchema).max(100),
+  adaptiveTiming: z.boolean(),
 });
 
 /**
  * Create combo schema with validation rules
- * - Keyword must have no spaces
+ * - Keyword may be a multi-word phrase with single spaces between words,
+ *   but cannot start or end with whitespace or contain tabs or line breaks
  * - Keyword must be at least 2 characters
  * - Snippet cannot be empty or only whitespace
  */
@@ -90,8 +126,8 @@ export const createComboSchema = z.object({
   keyword: z
     .string()
     .min(2, "Keyword must be at least 2 characters")
-    .refine((val) => !val.includes(" "), {
-      message: "Keyword cannot contain spaces",
+    .refine((val) => val === val.trim() && !/[\t\r\n]| {2}/.test(val), {
+      message: "Keyword cannot start or end with spaces, repeat spaces or contain line breaks",
     }),
   snippet: z
     .string()
@@ -102,6 +138,9 @@ export const createComboSchema = z.object({
   groupId: z.string().uuid(),
   matchingMode: matchingModeSchema,
   caseSensitive: z.boolean(),
+  propagateCase: z.boolean(),
+  snippetFormat: snippetFormatSchema,
+  excludeFromHistory: z.boolean(),
   enabled: z.boolean(),
 });
 
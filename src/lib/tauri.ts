@@ -1,5 +1,6 @@
 // Tauri IPC wrapper functions for invoking Rust backend commands
 import { invoke } from "@tauri-apps/api/core";
+import { listen, type UnlistenFn } from "@tauri-apps/api/event";
 import { openUrl as _openUrl } from "@tauri-apps/plugin-opener";
 import type {
   Combo,
@@ -12,6 +13,8 @@ import type {
   TrayState,
   ImportResult,
   ImportPreview,
+  AutocorrectDictionarySummary,
+  AutocorrectImportResult,
   BackupInfo,
   VersionInfo,
 } from "./types";
@@ -46,6 +49,9 @@ export async function createCombo(input: CreateComboInput): Promise<Combo> {
     groupId: input.groupId,
     matchingMode: input.matchingMode,
     caseSensitive: input.caseSensitive,
+    propagateCase: input.propagateCase,
+    snippetFormat: input.snippetFormat,
+    excludeFromHistory: input.excludeFromHistory,
     enabled: input.enabled,
   });
 }
@@ -57,7 +63,7 @@ export async function updateCombo(
   id: string,
   input: UpdateComboInput
 ): Promise<Combo> {
-  return invoke("update_combo", { id, ...input });
+  return invoke("update_combo", { id, patch: input });
 }
 
 /**
@@ -146,6 +152,42 @@ export async function toggleGroup(id: string): Promise<boolean> {
   return invoke("toggle_group", { id });
 }
 
+// ========================================
+// Autocorrect Operations
+// ========================================
+
+/**
+ * Get all autocorrect dictionaries (metadata only)
+ */
+export async function getAutocorrectDictionaries(): Promise<AutocorrectDictionarySummary[]> {
+  return invoke("get_autocorrect_dictionaries");
+}
+
+/**
+ * Import a typo list (`teh<TAB>the` or `teh->the` per line) as a new dictionary
+ */
+export async function importAutocorrectList(
+  content: string,
+  name: string
+): Promise<AutocorrectImportResult> {
+  return invoke("import_autocorrect_list", { content, name });
+}
+
+/**
+ * Toggle a dictionary's enabled state
+ * Returns the new enabled state
+ */
+export async function toggleAutocorrectDictionary(id: string): Promise<boolean> {
+  return invoke("toggle_autocorrect_dictionary", { id });
+}
+
+/**
+ * Delete an autocorrect dictionary
+ */
+export async function deleteAutocorrectDictionary(id: string): Promise<void> {
+  return invoke("delete_autocorrect_dictionary", { id });
+}
+
 // ========================================
 // Picker Operations
 // ========================================
@@ -207,6 +249,13 @@ export async function getTrayState(): Promise<TrayState> {
   return invoke("get_tray_state");
 }
 
+/** Calls `handler` whenever the backend changes the tray state itself. */
+export async function onTrayStateChanged(
+  handler: (state: TrayState) => void,
+): Promise<UnlistenFn> {
+  return listen<TrayState>("tray-state-changed", (event) => handler(event.payload));
+}
+
 export async function setTrayEnabled(enabled: boolean): Promise<void> {
   return invoke("set_tray_enabled", { enabled });
 }
@@ -231,6 +280,24 @@ export async function exportCombos(format: string): Promise<string> {
   return invoke("export_combos", { format });
 }
 
+// ========================================
+// Asset Operations
+// ========================================
+
+/**
+ * Store a PNG for an image combo, returning the asset name to use as its snippet
+ */
+export async function storeImageAsset(data: Uint8Array): Promise<string> {
+  return invoke("store_image_asset", { data: Array.from(data) });
+}
+
+/**
+ * Get a stored image as a data: URL for previews
+ */
+export async function getImageAsset(name: string): Promise<string> {
+  return invoke("get_image_asset", { name });
+}
+
 // ========================================
 // Backup Operations
 // ========================================
@@ -8,13 +8,48 @@
  */
 export type MatchingMode = "strict" | "loose";
 
+/**
+ * How a combo's snippet text is interpreted
+ * - plain: Inserted exactly as written
+ * - html: Pasted as rich text, with a plain-text fallback
+ * - markdown: Rendered to HTML, then pasted like an html snippet
+ * - image: The snippet is the name of a stored PNG, pasted as an image
+ */
+export type SnippetFormat = "plain" | "html" | "markdown" | "image";
+
 /**
  * Method for pasting snippets
  * - clipboard: Use system clipboard (faster, more reliable)
  * - simulateKeystrokes: Type out the snippet (for apps that block clipboard)
- * - xdotoolType: Use xdotool type command (best for terminals on Linux)
+ * - xdotoolType: Type via XTest (best for terminals on Linux; AppleScript on macOS)
+ * - uinput: Type through a virtual keyboard (Linux, works on native Wayland)
+ */
+export type PasteMethod = "clipboard" | "simulateKeystrokes" | "xdotoolType" | "uinput";
+
+/**
+ * Per-application paste override. `pattern` is matched case-insensitively
+ * against the focused window's class (WM_CLASS / app id) or title.
+ */
+export interface PasteRule {
+  field: "windowClass" | "windowTitle";
+  pattern: string;
+  pasteMethod: PasteMethod;
+  /** Paste shortcut such as "Ctrl+Shift+V"; empty uses the default */
+  pasteChord: string;
+}
+
+/**
+ * Timing used instead of the global delays in windows of one class
+ * (matched case-insensitively). Learned profiles are created and adjusted
+ * by the engine; it never changes the others.
  */
-export type PasteMethod = "clipboard" | "simulateKeystrokes" | "xdotoolType";
+export interface TimingProfile {
+  windowClass: string;
+  keyDelayMs: number;
+  pasteRestoreDelayMs: number;
+  preDeletionDelayMs: number;
+  learned: boolean;
+}
 
 /**
  * Application theme
@@ -37,7 +72,7 @@ export interface Combo {
   /** Optional description */
   description: string;
 
-  /** Trigger keyword (no spaces allowed) */
+  /** Trigger keyword (a single word or a phrase such as "on my way") */
   keyword: string;
 
   /** The snippet text to expand (can contain variables) */
@@ -52,6 +87,15 @@ export interface Combo {
   /** Whether keyword matching is case-sensitive */
   caseSensitive: boolean;
 
+  /** Mirror the typed keyword's capitalization onto the snippet */
+  propagateCase: boolean;
+
+  /** How the snippet text is interpreted when expanded */
+  snippetFormat: SnippetFormat;
+
+  /** Ask clipboard managers not to record the snippet when it is pasted */
+  excludeFromHistory: boolean;
+
   /** Whether this combo is enabled */
   enabled: boolean;
 
@@ -156,6 +200,24 @@ export interface Preferences {
 
   /** List of application names to exclude from expansion */
   excludedApps: string[];
+
+  /** Per-application paste overrides; the first matching rule wins */
+  pasteRules: PasteRule[];
+
+  /** Pressing Backspace right after an expansion restores the keyword */
+  undoWithBackspace: boolean;
+
+  /** How long after an expansion the undo Backspace is honoured (in milliseconds) */
+  undoWindowMs: number;
+
+  /** Debug: record keystrokes, clicks and focus changes to a trace file in the logs directory */
+  recordKeystrokeTrace: boolean;
+
+  /** Per-application timing; at most one profile per window class */
+  timingProfiles: TimingProfile[];
+
+  /** Let the engine learn timing profiles for slow applications */
+  adaptiveTiming: boolean;
 }
 
 /**
@@ -190,7 +252,7 @@ export type UpdateGroupInput = Partial<
 // ========================================
 
 /** System tray state */
-export type TrayState = "active" | "paused" | "excludedApp";
+export type TrayState = "active" | "paused" | "excludedApp" | "passwordField";
 
 /** System tray menu item */
 export interface TrayMenuItem {
@@ -243,6 +305,28 @@ export interface ImportResult {
   errors: string[];
 }
 
+// ========================================
+// Autocorrect Types
+// ========================================
+
+/** An autocorrect dictionary (entries are not sent to the frontend) */
+export interface AutocorrectDictionarySummary {
+  id: string;
+  name: string;
+  enabled: boolean;
+  entryCount: number;
+  createdAt: string;
+  modifiedAt: string;
+}
+
+/** Result of importing a TSV or Wikipedia-style typo list */
+export interface AutocorrectImportResult {
+  dictionary: AutocorrectDictionarySummary;
+  importedCount: number;
+  skippedCount: number;
+  errors: string[];
+}
+
 // ========================================
 // Update Types
 // ========================================
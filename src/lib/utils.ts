@@ -70,6 +70,12 @@ export function createDefaultPreferences(): Preferences {
     maxBackups: 10,
     autoCheckUpdates: true,
     excludedApps: [],
+    pasteRules: [],
+    undoWithBackspace: false,
+    undoWindowMs: 3000,
+    recordKeystrokeTrace: false,
+    timingProfiles: [],
+    adaptiveTiming: true,
   };
 }
 
@@ -34,6 +34,12 @@ const DEFAULT_PREFERENCES: Preferences = {
   maxBackups: 10,
   autoCheckUpdates: true,
   excludedApps: [],
+  pasteRules: [],
+  undoWithBackspace: false,
+  undoWindowMs: 3000,
+  recordKeystrokeTrace: false,
+  timingProfiles: [],
+  adaptiveTiming: true,
 };
 
 export const usePreferencesStore = create<PreferencesState>((set, get) => ({